@@ -0,0 +1,198 @@
+use chrono::Utc;
+use cqrs_es::Aggregate;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use domain::dispenses::{
+    aggregate::DrugItem, Command, Dispense, DispenseStatus, Event, Priority, Services,
+};
+
+const DISPENSE_ID: &str = "01HZX3V5N8K2Q4R6T8W0Y2A4C6";
+
+/// Samples per benchmark, enough to catch small regressions on the hot path
+const SAMPLE_SIZE: usize = 500;
+
+fn drug(drug_id: &str, name: &str, quantity: u32) -> DrugItem {
+    DrugItem::builder()
+        .drug_id(drug_id)
+        .name(name)
+        .quantity(quantity)
+        .build()
+        .expect("valid drug")
+}
+
+/// Events of a dispense from start to completion
+fn lifecycle() -> Vec<Event> {
+    let now = Utc::now();
+    vec![
+        Event::DispenseStarted {
+            id: DISPENSE_ID.to_string(),
+            created_at: now,
+            status: DispenseStatus::Pending,
+            original_dispense_id: None,
+            pharmacy_branch_id: None,
+            priority: Priority::Routine,
+            notes: None,
+            expires_at: None,
+            actor_id: None,
+        },
+        Event::PrescriptionUploaded {
+            id: DISPENSE_ID.to_string(),
+            prescription_id: "rx-1".to_string(),
+            url: "s3://prescriptions/rx-1.jpg".to_string(),
+            updated_at: now,
+            expires_at: None,
+            file_size_bytes: None,
+            actor_id: None,
+        },
+        Event::PrescriptionAnalyzed {
+            id: DISPENSE_ID.to_string(),
+            analysis_data: r#"{"confidence_score":0.95}"#.to_string(),
+            analysis_version: 1,
+            model_id: "bench".to_string(),
+            quality_score: 0.95,
+            prescription_page_count: Some(1),
+            updated_at: now,
+            actor_id: None,
+        },
+        Event::PatientAdded {
+            id: DISPENSE_ID.to_string(),
+            patient_id: "patient-1".to_string(),
+            patient_name: "Jane Doe".to_string(),
+            updated_at: now,
+            actor_id: None,
+        },
+        Event::PrescriberSet {
+            id: DISPENSE_ID.to_string(),
+            prescriber_id: None,
+            license_number: "A123456".to_string(),
+            state: "CA".to_string(),
+            prescriber_name: Some("Dr. Smith".to_string()),
+            updated_at: now,
+            actor_id: None,
+        },
+        Event::DrugsAdded {
+            id: DISPENSE_ID.to_string(),
+            drugs: vec![
+                drug("amoxicillin-500", "Amoxicillin 500mg", 30),
+                drug("ibuprofen-200", "Ibuprofen 200mg", 20),
+            ],
+            updated_at: now,
+            actor_id: None,
+        },
+        Event::DispenseCompleted {
+            id: DISPENSE_ID.to_string(),
+            updated_at: now,
+            actor_id: None,
+        },
+    ]
+}
+
+/// Ready dispense with patient, prescriber and drugs
+fn ready_dispense() -> Dispense {
+    let mut events = lifecycle();
+    events.pop();
+
+    let mut dispense = Dispense::default();
+    for event in events {
+        dispense.apply(event);
+    }
+    dispense
+}
+
+fn bench_handle_start_dispense(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let services = Services::default();
+    let dispense = Dispense::default();
+
+    let mut group = c.benchmark_group("handle");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("start_dispense", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let command = Command::StartDispense {
+                id: DISPENSE_ID.to_string(),
+                original_dispense_id: None,
+                pharmacy_branch_id: None,
+                priority: Priority::Routine,
+                notes: None,
+            };
+            black_box(dispense.handle(black_box(command), &services).await)
+        })
+    });
+    group.finish();
+}
+
+fn bench_handle_complete_dispense(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let services = Services::default();
+    let dispense = ready_dispense();
+
+    let mut group = c.benchmark_group("handle");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("complete_dispense", |b| {
+        b.to_async(&runtime).iter(|| async {
+            black_box(
+                dispense
+                    .handle(black_box(Command::CompleteDispense), &services)
+                    .await,
+            )
+        })
+    });
+    group.finish();
+}
+
+fn bench_apply_full_lifecycle(c: &mut Criterion) {
+    let events = lifecycle();
+
+    let mut group = c.benchmark_group("apply");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("full_lifecycle", |b| {
+        b.iter(|| {
+            let mut dispense = Dispense::default();
+            for event in black_box(events.clone()) {
+                dispense.apply(event);
+            }
+            black_box(dispense)
+        })
+    });
+    group.finish();
+}
+
+fn bench_clone_aggregate(c: &mut Criterion) {
+    let mut dispense = ready_dispense();
+    dispense.apply(Event::DrugsAdded {
+        id: DISPENSE_ID.to_string(),
+        drugs: (0..100)
+            .map(|i| drug(&format!("drug-{}", i), &format!("Drug {}", i), 10))
+            .collect(),
+        updated_at: Utc::now(),
+        actor_id: None,
+    });
+
+    let mut group = c.benchmark_group("clone");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("aggregate_100_drugs", |b| {
+        b.iter(|| black_box(black_box(&dispense).clone()))
+    });
+    group.finish();
+}
+
+fn bench_validate_can_complete(c: &mut Criterion) {
+    let dispense = ready_dispense();
+
+    let mut group = c.benchmark_group("validate");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("can_complete", |b| {
+        b.iter(|| black_box(black_box(&dispense).validate_can_complete()))
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(SAMPLE_SIZE);
+    targets = bench_handle_start_dispense,
+        bench_handle_complete_dispense,
+        bench_apply_full_lifecycle,
+        bench_clone_aggregate,
+        bench_validate_can_complete
+}
+criterion_main!(benches);
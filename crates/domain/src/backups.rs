@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Export of the event log to S3, keyed by `backup_id`
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BackupJob {
+    pub backup_id: String,
+    pub bucket: String,
+    /// `backups/{date}/event-log-{timestamp}.jsonl.gz`
+    pub key: String,
+    pub status: BackupStatus,
+    pub events_exported: usize,
+    /// Compressed size, known once the upload completes
+    pub size_bytes: Option<u64>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl BackupJob {
+    pub fn new(backup_id: &str, bucket: &str, started_at: DateTime<Utc>) -> Self {
+        Self {
+            backup_id: backup_id.to_string(),
+            bucket: bucket.to_string(),
+            key: format!(
+                "backups/{}/event-log-{}.jsonl.gz",
+                started_at.format("%Y-%m-%d"),
+                started_at.format("%Y%m%dT%H%M%SZ")
+            ),
+            status: BackupStatus::Running,
+            events_exported: 0,
+            size_bytes: None,
+            started_at,
+            completed_at: None,
+            error: None,
+        }
+    }
+}
+
+pub struct BackupJobStore {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl BackupJobStore {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// Table of the loaded `Config`
+    pub fn from_env(client: aws_sdk_dynamodb::Client) -> Self {
+        Self::new(client, &Config::current().backup_jobs_table)
+    }
+
+    pub async fn save(&self, job: &BackupJob) -> Result<(), PersistenceError> {
+        let item =
+            serde_dynamo::to_item(job).map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// All backups, most recent first
+    pub async fn list(&self) -> Result<Vec<BackupJob>, PersistenceError> {
+        let mut jobs: Vec<BackupJob> = Vec::new();
+        let mut start_key = None;
+
+        loop {
+            let output = self
+                .client
+                .scan()
+                .table_name(&self.table)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+            for item in output.items.unwrap_or_default() {
+                jobs.push(
+                    serde_dynamo::from_item(item)
+                        .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))?,
+                );
+            }
+
+            start_key = output.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        Ok(jobs)
+    }
+}
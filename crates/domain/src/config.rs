@@ -0,0 +1,186 @@
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::errors::Error;
+
+/// How long the secret is used before being read again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long a stale configuration is still served while the secret cannot be read
+/// (e.g. mid-rotation)
+const ROTATION_GRACE: Duration = Duration::from_secs(30);
+
+static CACHE: OnceCell<RwLock<Cached>> = OnceCell::new();
+
+struct Cached {
+    config: Config,
+    loaded_at: Instant,
+}
+
+/// DynamoDB table names
+///
+/// Read from the `dispensary/{env}/config` secret when `SECRETS_ARN` is set, missing keys
+/// falling back to the `DYNAMODB_*_TABLE` variables and their defaults.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default = "Config::from_env")]
+pub struct Config {
+    pub event_log_table: String,
+    pub snapshots_table: String,
+    /// Dispenses view
+    pub view_table: String,
+    pub prescribers_view_table: String,
+    pub timeline_index_table: String,
+    pub patient_history_table: String,
+    pub patient_last_dispense_table: String,
+    pub export_locks_table: String,
+    pub analysis_jobs_table: String,
+    pub replay_checkpoints_table: String,
+    pub backup_jobs_table: String,
+    pub migrations_table: String,
+    pub formulary_table: String,
+    pub ocr_cache_table: String,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            event_log_table: table_var("DYNAMODB_EVENT_LOG_TABLE", "dispensary-event-log"),
+            snapshots_table: table_var(
+                "DYNAMODB_EVENT_SNAPSHOTS_TABLE",
+                "dispensary-event-snapshots",
+            ),
+            view_table: table_var("DYNAMODB_DISPENSES_VIEW_TABLE", "dispensary-dispenses-view"),
+            prescribers_view_table: table_var(
+                "DYNAMODB_PRESCRIBERS_VIEW_TABLE",
+                "dispensary-prescribers-view",
+            ),
+            timeline_index_table: table_var(
+                "DYNAMODB_TIMELINE_INDEX_TABLE",
+                "dispensary-timeline-index",
+            ),
+            patient_history_table: table_var(
+                "DYNAMODB_PATIENT_HISTORY_TABLE",
+                "dispensary-patient-history",
+            ),
+            patient_last_dispense_table: table_var(
+                "DYNAMODB_PATIENT_LAST_DISPENSE_TABLE",
+                "dispensary-patient-last-dispense",
+            ),
+            export_locks_table: table_var("DYNAMODB_EXPORT_LOCKS_TABLE", "dispensary-export-locks"),
+            analysis_jobs_table: table_var(
+                "DYNAMODB_ANALYSIS_JOBS_TABLE",
+                "dispensary-analysis-jobs",
+            ),
+            replay_checkpoints_table: table_var(
+                "DYNAMODB_REPLAY_CHECKPOINTS_TABLE",
+                "dispensary-replay-checkpoints",
+            ),
+            backup_jobs_table: table_var("DYNAMODB_BACKUP_JOBS_TABLE", "dispensary-backup-jobs"),
+            migrations_table: table_var("DYNAMODB_MIGRATIONS_TABLE", "dispensary-migrations"),
+            formulary_table: table_var("DYNAMODB_FORMULARY_TABLE", "dispensary-formulary"),
+            ocr_cache_table: table_var("DYNAMODB_OCR_CACHE_TABLE", "dispensary-ocr-cache"),
+        }
+    }
+
+    /// Last configuration loaded by `ConfigLoader`, the environment before the first load
+    pub fn current() -> Self {
+        match CACHE.get() {
+            Some(cache) => cache
+                .read()
+                .map(|cached| cached.config.clone())
+                .unwrap_or_else(|_| Self::from_env()),
+            None => Self::from_env(),
+        }
+    }
+}
+
+fn table_var(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or(default.to_string())
+}
+
+/// Loads the table names from Secrets Manager, or the environment without `SECRETS_ARN`
+///
+/// Lambdas load once at startup, before the `cqrs::init` functions read `Config::current()`.
+pub struct ConfigLoader {
+    client: aws_sdk_secretsmanager::Client,
+    secret_id: Option<String>,
+}
+
+impl ConfigLoader {
+    pub fn new(client: aws_sdk_secretsmanager::Client, secret_id: Option<String>) -> Self {
+        Self { client, secret_id }
+    }
+
+    pub fn from_env(client: aws_sdk_secretsmanager::Client) -> Self {
+        Self::new(
+            client,
+            env::var("SECRETS_ARN").ok().filter(|arn| !arn.is_empty()),
+        )
+    }
+
+    pub async fn load(&self) -> Result<Config, Error> {
+        let Some(secret_id) = &self.secret_id else {
+            return Ok(store(Config::from_env()));
+        };
+
+        let cached = CACHE.get().and_then(|cache| {
+            let cached = cache.read().ok()?;
+            Some((cached.config.clone(), cached.loaded_at.elapsed()))
+        });
+        if let Some((config, age)) = &cached {
+            if *age < REFRESH_INTERVAL {
+                return Ok(config.clone());
+            }
+        }
+
+        match self.fetch(secret_id).await {
+            Ok(config) => Ok(store(config)),
+            Err(e) => match cached {
+                Some((config, age)) if age < REFRESH_INTERVAL + ROTATION_GRACE => {
+                    tracing::warn!("Using cached configuration, {}", e);
+                    Ok(config)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    async fn fetch(&self, secret_id: &str) -> Result<Config, Error> {
+        let output = self
+            .client
+            .get_secret_value()
+            .secret_id(secret_id)
+            .send()
+            .await
+            .map_err(|e| Error::Validation {
+                message: format!("Cannot read secret {}: {}", secret_id, e),
+            })?;
+
+        let secret = output.secret_string().ok_or(Error::Validation {
+            message: format!("Secret {} has no string value", secret_id),
+        })?;
+
+        serde_json::from_str(secret).map_err(|e| Error::Validation {
+            message: format!("Invalid configuration in secret {}: {}", secret_id, e),
+        })
+    }
+}
+
+fn store(config: Config) -> Config {
+    let cache = CACHE.get_or_init(|| {
+        RwLock::new(Cached {
+            config: config.clone(),
+            loaded_at: Instant::now(),
+        })
+    });
+    if let Ok(mut cached) = cache.write() {
+        cached.config = config.clone();
+        cached.loaded_at = Instant::now();
+    }
+    config
+}
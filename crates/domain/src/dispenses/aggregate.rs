@@ -2,16 +2,39 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use cqrs_es::Aggregate;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    ops::{Deref, DerefMut},
+    time::Instant,
+};
 
-use crate::errors::Error;
+use crate::{errors::Error, metrics};
 
-use super::{Command, Event};
+use super::{
+    analysis::AnalysisResult,
+    inputs::{DispenseId, DrugId, PatientId},
+    name_validator,
+    services::{DispenseConfig, SchedulerService},
+    timeline::DEFAULT_PHARMACY_ID,
+    Command, Event, Services,
+};
+
+/// Status transitions kept in `DispenseData::status_history`
+pub const MAX_STATUS_HISTORY: usize = 10;
+
+/// Uploads kept in `DispenseData::prescription_history`
+pub const MAX_PRESCRIPTION_HISTORY: usize = 5;
+
+/// Analyses scoring below this need a pharmacist review before completion
+pub const PHARMACIST_REVIEW_CONFIDENCE: f32 = 0.9;
 
 /// Dispense workflow status
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DispenseStatus {
     /// Initial state - user started dispense
+    #[default]
     Pending,
     /// Prescription uploaded, waiting for analysis
     Analyzing,
@@ -23,47 +46,374 @@ pub enum DispenseStatus {
     Cancelled,
 }
 
-/// Dispense aggregate
+/// How urgently the dispense must be prepared
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Routine,
+    Urgent,
+    /// Immediately
+    Stat,
+}
+
+/// Dispense aggregate: its data, and the count of events applied
+///
+/// Data fields are read and written through `Deref` (`dispense.status`). Both serialize flat,
+/// as snapshots did before the split.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Dispense {
-    pub id: String,
+    #[serde(flatten)]
+    pub data: DispenseData,
+
+    /// Events applied, the `aggregate_id_sequence` of the last one
+    #[serde(default)]
+    pub aggregate_version: usize,
+}
+
+impl Deref for Dispense {
+    type Target = DispenseData;
+
+    fn deref(&self) -> &DispenseData {
+        &self.data
+    }
+}
+
+impl DerefMut for Dispense {
+    fn deref_mut(&mut self) -> &mut DispenseData {
+        &mut self.data
+    }
+}
+
+/// Business data of a dispense, as rebuilt from its events (also held by the view)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DispenseData {
+    pub id: DispenseId,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub status: DispenseStatus,
+    /// Dispense this one was split from
+    #[serde(default)]
+    pub original_dispense_id: Option<String>,
+    #[serde(default)]
+    pub pharmacy_branch_id: Option<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Automatic cancellation deadline, when an expiry is scheduled
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Actor of the last event carrying one, from the `actor_id` command metadata
+    #[serde(default)]
+    pub updated_by: Option<String>,
     
     // Prescription data
     pub prescription_id: Option<String>,
     pub prescription_url: Option<String>,
+    /// Size of the prescription file, for providers billing by size
+    #[serde(default)]
+    pub prescription_file_size_bytes: Option<u64>,
+    #[serde(default)]
+    pub prescription_uploaded_at: Option<DateTime<Utc>>,
+    /// Last `MAX_PRESCRIPTION_HISTORY` uploads, oldest first, the current one last
+    #[serde(default)]
+    pub prescription_history: VecDeque<PrescriptionRecord>,
+    /// JPEG preview of the prescription (first page of a PDF)
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
     pub prescription_analyzed: bool,
+    pub analysis_data: Option<String>, // JSON
+    #[serde(default)]
+    pub analysis_version: u32,
+    pub analysis_model: Option<String>,
+    pub prescription_page_count: Option<u32>,
+    #[serde(default)]
+    pub requires_multi_page_review: bool,
+    /// Low-confidence analysis or controlled drugs
+    #[serde(default)]
+    pub requires_pharmacist_review: bool,
+    #[serde(default)]
+    pub pharmacist_review_completed: bool,
+    #[serde(default)]
+    pub pharmacist_reviewed_by: Option<String>,
+    #[serde(default)]
+    pub pharmacist_review_notes: Option<String>,
     
     // Patient data
-    pub patient_id: Option<String>,
+    pub patient_id: Option<PatientId>,
     pub patient_name: Option<String>,
     
+    // Prescriber data
+    pub prescriber_id: Option<String>,
+    pub prescriber_license_number: Option<String>,
+    pub prescriber_state: Option<String>,
+    pub prescriber_name: Option<String>,
+    
     // Drugs data
     pub drugs: Vec<DrugItem>,
+    #[serde(default)]
+    pub drug_interaction_warnings: Vec<DrugInteraction>,
+    /// Drug changes since the dispense started (only those applied since the ledger exists for
+    /// older dispenses)
+    #[serde(default)]
+    pub drug_ledger: DrugLedger,
+    
+    // Fulfillment data
+    pub fulfillment_method: Option<FulfillmentMethod>,
+    pub carrier: Option<String>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
     
+    #[serde(default)]
+    pub cancellation_reason: Option<String>,
     pub deleted: bool,
+
+    /// Last `MAX_STATUS_HISTORY` status changes, oldest first
+    #[serde(default)]
+    pub status_history: VecDeque<StatusTransition>,
+}
+
+/// Uploaded prescription, `replaced_at` set once another one is uploaded
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PrescriptionRecord {
+    pub prescription_id: String,
+    pub url: String,
+    pub uploaded_at: DateTime<Utc>,
+    pub replaced_at: Option<DateTime<Utc>>,
+}
+
+/// Status change, with the variant of the event causing it (`PrescriptionUploaded`)
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct StatusTransition {
+    pub from: DispenseStatus,
+    pub to: DispenseStatus,
+    pub at: DateTime<Utc>,
+    pub triggered_by_event: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct DrugItem {
-    pub drug_id: String,
+    pub drug_id: DrugId,
     pub name: String,
     pub quantity: u32,
+    /// Controlled substance (requires an authorized prescriber)
+    #[serde(default)]
+    pub controlled: bool,
 }
 
-impl Default for DispenseStatus {
-    fn default() -> Self {
-        Self::Pending
+impl DrugItem {
+    pub fn builder() -> DrugItemBuilder {
+        DrugItemBuilder::default()
     }
 }
 
-pub const AGGREGATE_TYPE: &str = "Dispense";
+/// `DrugItem` with its fields set one by one, not controlled unless stated
+#[derive(Clone, Debug, Default)]
+pub struct DrugItemBuilder {
+    drug_id: Option<String>,
+    name: Option<String>,
+    quantity: Option<u32>,
+    controlled: bool,
+}
+
+impl DrugItemBuilder {
+    pub fn drug_id(mut self, drug_id: impl Into<String>) -> Self {
+        self.drug_id = Some(drug_id.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    pub fn controlled(mut self, controlled: bool) -> Self {
+        self.controlled = controlled;
+        self
+    }
+
+    /// Fails on a missing field
+    pub fn build(self) -> Result<DrugItem, &'static str> {
+        Ok(DrugItem {
+            drug_id: DrugId::new(self.drug_id.ok_or("drug_id is required")?),
+            name: self.name.ok_or("name is required")?,
+            quantity: self.quantity.ok_or("quantity is required")?,
+            controlled: self.controlled,
+        })
+    }
+}
+
+/// Change of the drug quantities of a dispense
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerOperation {
+    Added,
+    Updated,
+    Removed,
+    /// Part of the quantity dispensed, the rest owed to the patient
+    PartiallyFilled,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DrugLedgerEntry {
+    pub drug_id: String,
+    pub name: String,
+    pub quantity_delta: i32,
+    /// Quantity of the drug once the change applied, 0 once removed
+    pub total_after: u32,
+    pub operation: LedgerOperation,
+    pub at: DateTime<Utc>,
+}
+
+/// Every drug quantity change of a dispense, oldest first, for compliance reporting
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DrugLedger {
+    pub entries: Vec<DrugLedgerEntry>,
+}
+
+impl DrugLedger {
+    /// Entries turning the `before` drug list into `after`: drugs added or with a new quantity,
+    /// in `after` order, then the drugs removed
+    pub fn record(&mut self, before: &[DrugItem], after: &[DrugItem], at: DateTime<Utc>) {
+        for drug in after {
+            let previous = before
+                .iter()
+                .find(|previous| previous.drug_id == drug.drug_id);
+            let (quantity_delta, operation) = match previous {
+                None => (drug.quantity as i32, LedgerOperation::Added),
+                Some(previous) if previous.quantity != drug.quantity => (
+                    drug.quantity as i32 - previous.quantity as i32,
+                    LedgerOperation::Updated,
+                ),
+                Some(_) => continue,
+            };
+            self.entries.push(DrugLedgerEntry {
+                drug_id: drug.drug_id.to_string(),
+                name: drug.name.clone(),
+                quantity_delta,
+                total_after: drug.quantity,
+                operation,
+                at,
+            });
+        }
+
+        for drug in before
+            .iter()
+            .filter(|drug| !after.iter().any(|current| current.drug_id == drug.drug_id))
+        {
+            self.entries.push(DrugLedgerEntry {
+                drug_id: drug.drug_id.to_string(),
+                name: drug.name.clone(),
+                quantity_delta: -(drug.quantity as i32),
+                total_after: 0,
+                operation: LedgerOperation::Removed,
+                at,
+            });
+        }
+    }
+}
+
+/// Severity of a drug-drug interaction
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum InteractionSeverity {
+    Minor,
+    Moderate,
+    Major,
+    /// Drugs must not be dispensed together
+    Contraindicated,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DrugInteraction {
+    pub drug_a: String,
+    pub drug_b: String,
+    pub severity: InteractionSeverity,
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Address {
+    pub street: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+}
+
+/// How the dispensed drugs reach the patient
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type")]
+pub enum FulfillmentMethod {
+    InStore,
+    MailOrder {
+        address: Address,
+        tracking_number: Option<String>,
+    },
+}
+
+impl fmt::Display for DispenseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pending => "Pending",
+            Self::Analyzing => "Analyzing Prescription",
+            Self::Ready => "Ready for Dispensing",
+            Self::Complete => "Dispensed",
+            Self::Cancelled => "Cancelled",
+        })
+    }
+}
+
+/// Commands sent by the analysis pipeline rather than users, left to their handlers in every
+/// status and not listed by `DispenseStatus::allowed_commands`
+pub const SYSTEM_COMMANDS: &[&str] = &[
+    "StartDispense",
+    "SetPrescriptionThumbnail",
+    "AnalyzePrescription",
+];
+
+impl DispenseStatus {
+    /// User commands accepted in this status, named as by `Command::command_type`
+    ///
+    /// Lets clients render the available actions; `Dispense::handle` rejects the others.
+    pub fn allowed_commands(&self) -> &'static [&'static str] {
+        match self {
+            Self::Pending => &["UploadPrescription", "MergeDispense", "CancelDispense"],
+            // Waiting for the analyzer
+            Self::Analyzing => &["CancelDispense"],
+            Self::Ready => &[
+                "ResetAnalysis",
+                "AddPatient",
+                "SetPrescriber",
+                "AddDrugs",
+                "ApproveMultiPagePrescription",
+                "MarkPharmacistReviewed",
+                "SetFulfillmentMethod",
+                "RecordShipment",
+                "RecordDelivery",
+                "SplitDispense",
+                "MergeDispense",
+                "CompleteDispense",
+                "CancelDispense",
+            ],
+            // Mail-order dispenses can still be shipped and delivered
+            Self::Complete => &["RecordShipment", "RecordDelivery"],
+            Self::Cancelled => &[],
+        }
+    }
+}
 
-#[derive(Clone, Default)]
-pub struct Services {}
+pub const AGGREGATE_TYPE: &str = "Dispense";
 
+// `Aggregate` is declared with `#[async_trait]` by cqrs_es 0.4, so the impl must be too: native
+// async fn in traits (Rust 1.75) would not match its boxed futures.
+// TODO: remove when cqrs_es declares its traits with native async fn
 #[async_trait]
 impl Aggregate for Dispense {
     type Command = Command;
@@ -75,133 +425,810 @@ impl Aggregate for Dispense {
         AGGREGATE_TYPE.to_string()
     }
 
+    #[tracing::instrument(
+        skip(self, command, services),
+        fields(
+            aggregate_id = %self.id,
+            status = %self.status,
+            command_type = command.command_type()
+        )
+    )]
     async fn handle(
         &self,
         command: Self::Command,
-        _services: &Self::Services,
+        services: &Self::Services,
     ) -> Result<Vec<Self::Event>, Self::Error> {
+        let command_type = command.command_type();
+        let started = Instant::now();
+        let result = self.handle_command(command, services).await;
+
+        let outcome = match &result {
+            Ok(events) => {
+                tracing::info!(events_generated = events.len());
+                "success"
+            }
+            Err(Error::Validation { .. }) => "validation_error",
+            Err(_) => "other_error",
+        };
+        metrics::record_command_duration(command_type, outcome, started.elapsed());
+
+        result
+    }
+
+    fn apply(&mut self, event: Self::Event) {
+        self.aggregate_version += 1;
+        self.data.apply(event);
+    }
+}
+
+impl DispenseData {
+    // Without a wildcard arm, a new `Event` variant does not compile until it is applied here;
+    // an arm leaving the state unchanged is caught by `every_event_variant_changes_the_state`
+    #[deny(unreachable_patterns, clippy::wildcard_enum_match_arm)]
+    pub fn apply(&mut self, event: Event) {
+        let from = self.status.clone();
+        let triggered_by_event = event.variant_name().to_string();
+        // Events without an actor (e.g. from the analyzer) keep the last known one
+        if let Some(actor_id) = event.actor_id() {
+            self.updated_by = Some(actor_id.to_string());
+        }
+
+        match event {
+            Event::DispenseStarted {
+                id,
+                created_at,
+                status,
+                original_dispense_id,
+                pharmacy_branch_id,
+                priority,
+                notes,
+                expires_at,
+                actor_id: _,
+            } => {
+                self.id = DispenseId::new(id);
+                self.created_at = created_at;
+                self.updated_at = created_at;
+                self.status = status;
+                self.original_dispense_id = original_dispense_id;
+                self.pharmacy_branch_id = pharmacy_branch_id;
+                self.priority = priority;
+                self.notes = notes;
+                self.expires_at = expires_at;
+            }
+
+            Event::PrescriptionUploaded {
+                prescription_id,
+                url,
+                updated_at,
+                expires_at,
+                file_size_bytes,
+                ..
+            } => {
+                // A re-upload (e.g. after a failed quality check) replaces the prescription
+                if let Some(current) = self.prescription_history.back_mut() {
+                    current.replaced_at.get_or_insert(updated_at);
+                }
+                self.prescription_history.push_back(PrescriptionRecord {
+                    prescription_id: prescription_id.clone(),
+                    url: url.clone(),
+                    uploaded_at: updated_at,
+                    replaced_at: None,
+                });
+                if self.prescription_history.len() > MAX_PRESCRIPTION_HISTORY {
+                    self.prescription_history.pop_front();
+                }
+                self.prescription_id = Some(prescription_id);
+                self.prescription_url = Some(url);
+                self.prescription_file_size_bytes = file_size_bytes;
+                self.prescription_uploaded_at = Some(updated_at);
+                self.expires_at = expires_at;
+                self.status = DispenseStatus::Analyzing;
+                self.updated_at = updated_at;
+            }
+
+            Event::PrescriptionThumbnailSet { thumbnail_url, updated_at, .. } => {
+                self.thumbnail_url = Some(thumbnail_url);
+                self.updated_at = updated_at;
+            }
+
+            Event::PrescriptionAnalyzed {
+                analysis_data,
+                analysis_version,
+                model_id,
+                quality_score,
+                prescription_page_count,
+                updated_at,
+                ..
+            } => {
+                self.requires_pharmacist_review =
+                    quality_score < PHARMACIST_REVIEW_CONFIDENCE || self.has_controlled_drugs();
+                self.prescription_page_count = prescription_page_count;
+                self.prescription_analyzed = true;
+                self.analysis_data = Some(analysis_data);
+                self.analysis_version = analysis_version;
+                self.analysis_model = Some(model_id);
+                self.status = DispenseStatus::Ready;
+                self.updated_at = updated_at;
+            }
+
+            // Keeps `analysis_version` so the next analysis increments it
+            Event::AnalysisReset { updated_at, .. } => {
+                self.prescription_analyzed = false;
+                self.analysis_data = None;
+                self.prescription_page_count = None;
+                self.requires_multi_page_review = false;
+                // The next analysis is reviewed again
+                self.requires_pharmacist_review = self.has_controlled_drugs();
+                self.pharmacist_review_completed = false;
+                self.status = DispenseStatus::Analyzing;
+                self.updated_at = updated_at;
+            }
+
+            Event::MultiPagePrescriptionDetected { updated_at, .. } => {
+                self.requires_multi_page_review = true;
+                self.updated_at = updated_at;
+            }
+
+            Event::MultiPagePrescriptionApproved { updated_at, .. } => {
+                self.requires_multi_page_review = false;
+                self.updated_at = updated_at;
+            }
+
+            Event::PharmacistReviewCompleted { reviewed_by, notes, reviewed_at, .. } => {
+                self.pharmacist_review_completed = true;
+                self.pharmacist_reviewed_by = Some(reviewed_by);
+                self.pharmacist_review_notes = notes;
+                self.updated_at = reviewed_at;
+            }
+
+            // The prescription must be uploaded again
+            Event::PrescriptionQualityFailed { updated_at, .. } => {
+                self.status = DispenseStatus::Pending;
+                self.updated_at = updated_at;
+            }
+
+            Event::PatientAdded { patient_id, patient_name, updated_at, .. } => {
+                self.patient_id = Some(PatientId::new(patient_id));
+                self.patient_name = Some(patient_name);
+                self.updated_at = updated_at;
+            }
+
+            Event::PrescriberSet {
+                prescriber_id,
+                license_number,
+                state,
+                prescriber_name,
+                updated_at,
+                ..
+            } => {
+                self.prescriber_id = prescriber_id;
+                self.prescriber_license_number = Some(license_number);
+                self.prescriber_state = Some(state);
+                self.prescriber_name = prescriber_name;
+                self.updated_at = updated_at;
+            }
+
+            Event::DrugsAdded { drugs, updated_at, .. } => {
+                self.drug_ledger.record(&self.drugs, &drugs, updated_at);
+                self.drugs = drugs;
+                self.requires_pharmacist_review |= self.has_controlled_drugs();
+                self.updated_at = updated_at;
+            }
+
+            Event::DrugInteractionWarning { interactions, updated_at, .. } => {
+                self.drug_interaction_warnings = interactions;
+                self.updated_at = updated_at;
+            }
+
+            Event::FulfillmentMethodSet { method, updated_at, .. } => {
+                self.fulfillment_method = Some(method);
+                self.updated_at = updated_at;
+            }
+
+            Event::DispenseShipped { tracking_number, shipped_at, carrier, updated_at, .. } => {
+                if let Some(FulfillmentMethod::MailOrder { tracking_number: current, .. }) =
+                    &mut self.fulfillment_method
+                {
+                    *current = Some(tracking_number);
+                }
+                self.carrier = Some(carrier);
+                self.shipped_at = Some(shipped_at);
+                self.updated_at = updated_at;
+            }
+
+            Event::DispenseDelivered { delivered_at, updated_at, .. } => {
+                self.delivered_at = Some(delivered_at);
+                self.updated_at = updated_at;
+            }
+
+            Event::DispenseSplit { moved_drug_ids, split_at, .. } => {
+                let before = self.drugs.clone();
+                self.drugs.retain(|drug| {
+                    !moved_drug_ids
+                        .iter()
+                        .any(|drug_id| drug.drug_id == *drug_id)
+                });
+                self.drug_ledger.record(&before, &self.drugs, split_at);
+                self.updated_at = split_at;
+            }
+
+            Event::DispenseMerged { additional_drugs, updated_at, .. } => {
+                let before = self.drugs.clone();
+                self.drugs.extend(additional_drugs);
+                self.drug_ledger.record(&before, &self.drugs, updated_at);
+                self.requires_pharmacist_review |= self.has_controlled_drugs();
+                self.updated_at = updated_at;
+            }
+
+            Event::DispenseCompleted { updated_at, .. } => {
+                self.status = DispenseStatus::Complete;
+                self.expires_at = None;
+                self.updated_at = updated_at;
+            }
+
+            Event::DispenseCancelled { reason, updated_at, .. } => {
+                self.status = DispenseStatus::Cancelled;
+                self.cancellation_reason = reason;
+                self.expires_at = None;
+                self.updated_at = updated_at;
+            }
+        }
+
+        if self.status != from {
+            self.status_history.push_back(StatusTransition {
+                from,
+                to: self.status.clone(),
+                at: self.updated_at,
+                triggered_by_event,
+            });
+            if self.status_history.len() > MAX_STATUS_HISTORY {
+                self.status_history.pop_front();
+            }
+        }
+    }
+
+    /// Ready with a patient, drugs and an analyzed prescription, no review pending
+    pub fn is_complete_ready(&self) -> bool {
+        self.completion_blockers().is_empty()
+    }
+
+    /// Conditions preventing completion, empty once the dispense is ready
+    pub fn completion_blockers(&self) -> Vec<&'static str> {
+        let mut blockers = Vec::new();
+        if self.status != DispenseStatus::Ready {
+            blockers.push("wrong status");
+        }
+        if self.patient_id.is_none() {
+            blockers.push("missing patient");
+        }
+        if self.drugs.is_empty() {
+            blockers.push("no drugs added");
+        }
+        if !self.prescription_analyzed {
+            blockers.push("prescription not analyzed");
+        }
+        if self.requires_multi_page_review {
+            blockers.push("multi-page review pending");
+        }
+        if self.requires_pharmacist_review && !self.pharmacist_review_completed {
+            blockers.push("pharmacist review pending");
+        }
+        blockers
+    }
+
+    /// Workflow progress from 0 to 100, for progress bars
+    ///
+    /// Pending 0 (20 once a prescription was uploaded), analyzing 40, ready 50 plus 15 each for
+    /// the patient and the drugs, complete 100 and cancelled 0.
+    pub fn completion_percentage(&self) -> u8 {
+        match self.status {
+            DispenseStatus::Pending if self.prescription_id.is_some() => 20,
+            DispenseStatus::Pending => 0,
+            DispenseStatus::Analyzing => 40,
+            DispenseStatus::Ready => {
+                let mut percentage = 50;
+                if self.patient_id.is_some() {
+                    percentage += 15;
+                }
+                if !self.drugs.is_empty() {
+                    percentage += 15;
+                }
+                percentage
+            }
+            DispenseStatus::Complete => 100,
+            DispenseStatus::Cancelled => 0,
+        }
+    }
+
+    /// Actions left before the dispense can be completed, empty once completed or cancelled
+    pub fn next_steps(&self) -> Vec<&'static str> {
+        match self.status {
+            DispenseStatus::Pending => vec!["Upload prescription"],
+            DispenseStatus::Analyzing => vec!["Wait for prescription analysis"],
+            DispenseStatus::Ready => {
+                let mut steps = Vec::new();
+                if self.patient_id.is_none() {
+                    steps.push("Add patient");
+                }
+                if self.drugs.is_empty() {
+                    steps.push("Add drugs");
+                }
+                if self.requires_multi_page_review {
+                    steps.push("Approve multi-page prescription");
+                }
+                if self.requires_pharmacist_review && !self.pharmacist_review_completed {
+                    steps.push("Complete pharmacist review");
+                }
+                steps.push("Complete dispense");
+                steps
+            }
+            DispenseStatus::Complete | DispenseStatus::Cancelled => Vec::new(),
+        }
+    }
+
+    pub fn has_controlled_drugs(&self) -> bool {
+        self.drugs.iter().any(|drug| drug.controlled)
+    }
+
+    /// `completion_blockers` as a validation error listing them all
+    pub fn validate_can_complete(&self) -> Result<(), Error> {
+        let blockers = self.completion_blockers();
+        if !blockers.is_empty() {
+            return Err(Error::Validation {
+                message: format!("Cannot complete dispense: {}", blockers.join(", ")),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Dispense {
+    /// `Aggregate::handle` without its span and metrics, for commands that are not executed
+    /// (dry runs)
+    pub(crate) async fn handle_command(
+        &self,
+        command: Command,
+        services: &Services,
+    ) -> Result<Vec<Event>, Error> {
+        self.validate_event_count(&command, &services.config)?;
+        self.validate_command_allowed(&command)?;
+
         match command {
-            Command::StartDispense { id } => {
+            Command::StartDispense {
+                id,
+                original_dispense_id,
+                pharmacy_branch_id,
+                priority,
+                notes,
+            } => {
                 self.validate_new()?;
                 let now = Utc::now();
+                let expires_at = Self::schedule_expiry(&id, now, services).await;
                 
                 Ok(vec![Event::DispenseStarted {
                     id,
                     created_at: now,
                     status: DispenseStatus::Pending,
+                    original_dispense_id,
+                    pharmacy_branch_id,
+                    priority,
+                    notes,
+                    expires_at,
+                    actor_id: None,
                 }])
             }
 
-            Command::UploadPrescription { prescription_id, url } => {
+            Command::UploadPrescription { prescription_id, url, file_size_bytes } => {
                 self.validate_existing()?;
+                let now = Utc::now();
+                // The upload extends the deadline
+                self.cancel_expiry(services).await;
+                let expires_at = Self::schedule_expiry(&self.id, now, services).await;
                 
                 Ok(vec![Event::PrescriptionUploaded {
-                    id: self.id.clone(),
+                    id: self.id.to_string(),
                     prescription_id,
                     url,
+                    updated_at: now,
+                    expires_at,
+                    file_size_bytes,
+                    actor_id: None,
+                }])
+            }
+
+            Command::SetPrescriptionThumbnail { thumbnail_url } => {
+                self.validate_existing()?;
+                if self.prescription_url.is_none() {
+                    return Err(Error::Validation {
+                        message: "Cannot set a thumbnail without prescription".to_string(),
+                    });
+                }
+
+                Ok(vec![Event::PrescriptionThumbnailSet {
+                    id: self.id.to_string(),
+                    thumbnail_url,
                     updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
 
-            Command::AnalyzePrescription { analysis_data } => {
+            Command::AnalyzePrescription { analysis_data, model_id, page_count } => {
                 self.validate_existing()?;
-                
-                Ok(vec![Event::PrescriptionAnalyzed {
-                    id: self.id.clone(),
+                self.validate_analyzing()?;
+                let quality_score = Self::parse_quality_score(&analysis_data)?;
+
+                // Failed checks are recorded with the raw score for audit
+                if services.quality.check(quality_score).is_err() {
+                    return Ok(vec![Event::PrescriptionQualityFailed {
+                        id: self.id.to_string(),
+                        quality_score,
+                        min_score: services.quality.min_score,
+                        updated_at: Utc::now(),
+                        actor_id: None,
+                    }]);
+                }
+
+                let now = Utc::now();
+
+                // Each re-analysis of a replaced prescription bumps the version
+                let mut events = vec![Event::PrescriptionAnalyzed {
+                    id: self.id.to_string(),
                     analysis_data,
+                    analysis_version: self.analysis_version + 1,
+                    model_id,
+                    quality_score,
+                    prescription_page_count: page_count,
+                    updated_at: now,
+                    actor_id: None,
+                }];
+                if let Some(page_count) = page_count.filter(|&pages| pages > 1) {
+                    events.push(Event::MultiPagePrescriptionDetected {
+                        id: self.id.to_string(),
+                        page_count,
+                        updated_at: now,
+                        actor_id: None,
+                    });
+                }
+
+                Ok(events)
+            }
+
+            Command::ResetAnalysis => {
+                self.validate_existing()?;
+                if matches!(self.status, DispenseStatus::Complete | DispenseStatus::Cancelled) {
+                    return Err(Error::Validation {
+                        message: "Cannot reset analysis of a finished dispense".to_string(),
+                    });
+                }
+                if self.prescription_url.is_none() {
+                    return Err(Error::Validation {
+                        message: "Cannot reset analysis without prescription".to_string(),
+                    });
+                }
+
+                Ok(vec![Event::AnalysisReset {
+                    id: self.id.to_string(),
                     updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
 
             Command::AddPatient { patient_id, name } => {
                 self.validate_existing()?;
+                self.validate_prescription_uploaded("patient")?;
+                // Adding the same patient again is a retry
+                match self.patient_id.as_deref() {
+                    Some(current) if current == patient_id => return Ok(vec![]),
+                    Some(_) => {
+                        return Err(Error::Uniqueness { field: "patient_id".to_string() })
+                    }
+                    None => {}
+                }
+                Self::validate_patient_name(&name)?;
+                services.dispensing.validate_patient_exists(&patient_id).await?;
                 
                 Ok(vec![Event::PatientAdded {
-                    id: self.id.clone(),
+                    id: self.id.to_string(),
                     patient_id,
                     patient_name: name,
                     updated_at: Utc::now(),
+                    actor_id: None,
+                }])
+            }
+
+            Command::SetPrescriber { prescriber_id, license_number, state } => {
+                self.validate_existing()?;
+                self.validate_controlled_prescriber(prescriber_id.as_deref(), &self.drugs, services)
+                    .await?;
+                let prescriber = services
+                    .dispensing
+                    .validate_prescriber_license(&license_number, &state)
+                    .await
+                    .map_err(|e| match e {
+                        // An unavailable validation API is reported as such, not as a bad license
+                        Error::Validation { message } => Error::Validation {
+                            message: format!("Invalid prescriber license: {}", message),
+                        },
+                        e => e,
+                    })?;
+                
+                Ok(vec![Event::PrescriberSet {
+                    id: self.id.to_string(),
+                    prescriber_id,
+                    license_number: prescriber.license_number,
+                    state: prescriber.state,
+                    prescriber_name: prescriber.name,
+                    updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
 
             Command::AddDrugs { drugs } => {
                 self.validate_existing()?;
+                self.validate_prescription_uploaded("drugs")?;
+                validate_drug_ids(&drugs)?;
+                // The list replaces the current drugs
+                services.config.check_drug_count(drugs.len())?;
+                for drug in &drugs {
+                    services.config.check_drug_quantity(drug.quantity)?;
+                }
+                self.validate_prescriber_of_added_drugs(&drugs, services).await?;
+                self.validate_drugs_available(&drugs, services).await?;
+                self.validate_drugs_on_formulary(&drugs, services).await?;
                 
                 Ok(vec![Event::DrugsAdded {
-                    id: self.id.clone(),
+                    id: self.id.to_string(),
                     drugs,
                     updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
 
-            Command::CompleteDispense => {
+            Command::SetFulfillmentMethod { method } => {
                 self.validate_existing()?;
-                self.validate_can_complete()?;
+                self.validate_ready()?;
                 
-                Ok(vec![Event::DispenseCompleted {
-                    id: self.id.clone(),
+                Ok(vec![Event::FulfillmentMethodSet {
+                    id: self.id.to_string(),
+                    method,
                     updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
 
-            Command::CancelDispense => {
+            Command::RecordShipment { tracking_number, shipped_at, carrier } => {
                 self.validate_existing()?;
+                self.validate_can_ship()?;
                 
-                Ok(vec![Event::DispenseCancelled {
-                    id: self.id.clone(),
+                Ok(vec![Event::DispenseShipped {
+                    id: self.id.to_string(),
+                    tracking_number,
+                    shipped_at,
+                    carrier,
                     updated_at: Utc::now(),
+                    actor_id: None,
                 }])
             }
-        }
-    }
 
-    fn apply(&mut self, event: Self::Event) {
-        match event {
-            Event::DispenseStarted { id, created_at, status } => {
-                self.id = id;
-                self.created_at = created_at;
-                self.updated_at = created_at;
-                self.status = status;
+            Command::RecordDelivery { delivered_at } => {
+                self.validate_existing()?;
+                self.validate_can_deliver()?;
+                
+                Ok(vec![Event::DispenseDelivered {
+                    id: self.id.to_string(),
+                    delivered_at,
+                    updated_at: Utc::now(),
+                    actor_id: None,
+                }])
             }
 
-            Event::PrescriptionUploaded { prescription_id, url, updated_at, .. } => {
-                self.prescription_id = Some(prescription_id);
-                self.prescription_url = Some(url);
-                self.status = DispenseStatus::Analyzing;
-                self.updated_at = updated_at;
+            Command::CompleteDispense => {
+                self.validate_existing()?;
+                tracing::debug_span!("validate_complete")
+                    .in_scope(|| self.validate_can_complete())?;
+                let interactions = services.dispensing.check_drug_interactions(&self.drugs).await?;
+                self.validate_no_contraindications(&interactions)?;
+                self.cancel_expiry(services).await;
+                let now = Utc::now();
+                
+                let mut events = Vec::new();
+                let major: Vec<DrugInteraction> = interactions
+                    .into_iter()
+                    .filter(|interaction| interaction.severity == InteractionSeverity::Major)
+                    .collect();
+                if !major.is_empty() {
+                    events.push(Event::DrugInteractionWarning {
+                        id: self.id.to_string(),
+                        interactions: major,
+                        updated_at: now,
+                        actor_id: None,
+                    });
+                }
+                events.push(Event::DispenseCompleted {
+                    id: self.id.to_string(),
+                    updated_at: now,
+                    actor_id: None,
+                });
+                
+                Ok(events)
             }
 
-            Event::PrescriptionAnalyzed { updated_at, .. } => {
-                self.prescription_analyzed = true;
-                self.status = DispenseStatus::Ready;
-                self.updated_at = updated_at;
-            }
+            Command::ApproveMultiPagePrescription { approved_by } => {
+                self.validate_existing()?;
+                if !self.requires_multi_page_review {
+                    return Err(Error::Validation {
+                        message: "Prescription does not require multi-page review".to_string(),
+                    });
+                }
 
-            Event::PatientAdded { patient_id, patient_name, updated_at, .. } => {
-                self.patient_id = Some(patient_id);
-                self.patient_name = Some(patient_name);
-                self.updated_at = updated_at;
+                Ok(vec![Event::MultiPagePrescriptionApproved {
+                    id: self.id.to_string(),
+                    approved_by,
+                    updated_at: Utc::now(),
+                    actor_id: None,
+                }])
             }
 
-            Event::DrugsAdded { drugs, updated_at, .. } => {
-                self.drugs = drugs;
-                self.updated_at = updated_at;
+            Command::MarkPharmacistReviewed { reviewed_by, notes } => {
+                self.validate_existing()?;
+                if !self.requires_pharmacist_review {
+                    return Err(Error::Validation {
+                        message: "Prescription does not require pharmacist review".to_string(),
+                    });
+                }
+                if self.pharmacist_review_completed {
+                    return Err(Error::Uniqueness { field: "pharmacist_review".to_string() });
+                }
+
+                Ok(vec![Event::PharmacistReviewCompleted {
+                    id: self.id.to_string(),
+                    reviewed_by,
+                    notes,
+                    reviewed_at: Utc::now(),
+                    actor_id: None,
+                }])
             }
 
-            Event::DispenseCompleted { updated_at, .. } => {
-                self.status = DispenseStatus::Complete;
-                self.updated_at = updated_at;
+            Command::SplitDispense { new_dispense_id, drugs_to_split } => {
+                self.validate_existing()?;
+                self.validate_can_split(&drugs_to_split)?;
+
+                let (moved, retained): (Vec<&DrugItem>, Vec<&DrugItem>) =
+                    self.drugs.iter().partition(|drug| {
+                        drugs_to_split
+                            .iter()
+                            .any(|drug_id| drug.drug_id == *drug_id)
+                    });
+
+                Ok(vec![Event::DispenseSplit {
+                    id: self.id.to_string(),
+                    new_dispense_id,
+                    moved_drug_ids: moved
+                        .into_iter()
+                        .map(|drug| drug.drug_id.to_string())
+                        .collect(),
+                    retained_drug_ids: retained
+                        .into_iter()
+                        .map(|drug| drug.drug_id.to_string())
+                        .collect(),
+                    split_at: Utc::now(),
+                    actor_id: None,
+                }])
             }
 
-            Event::DispenseCancelled { updated_at, .. } => {
-                self.status = DispenseStatus::Cancelled;
-                self.updated_at = updated_at;
+            Command::MergeDispense { source_dispense_id, merged_drugs } => {
+                self.validate_existing()?;
+                self.validate_can_merge(&source_dispense_id)?;
+                // Same rules as `AddDrugs` on the combined list; a drug in both dispenses is
+                // rejected rather than listed twice
+                let combined: Vec<DrugItem> =
+                    self.drugs.iter().chain(&merged_drugs).cloned().collect();
+                validate_drug_ids(&combined)?;
+                services.config.check_drug_count(combined.len())?;
+                for drug in &merged_drugs {
+                    services.config.check_drug_quantity(drug.quantity)?;
+                }
+                self.validate_prescriber_of_added_drugs(&merged_drugs, services).await?;
+                self.validate_drugs_available(&merged_drugs, services).await?;
+                self.validate_drugs_on_formulary(&merged_drugs, services).await?;
+
+                Ok(vec![Event::DispenseMerged {
+                    id: self.id.to_string(),
+                    source_dispense_id,
+                    additional_drugs: merged_drugs,
+                    updated_at: Utc::now(),
+                    actor_id: None,
+                }])
+            }
+
+            Command::CancelDispense { reason } => {
+                self.validate_existing()?;
+                self.cancel_expiry(services).await;
+                
+                Ok(vec![Event::DispenseCancelled {
+                    id: self.id.to_string(),
+                    reason,
+                    updated_at: Utc::now(),
+                    actor_id: None,
+                }])
             }
         }
     }
-}
 
-impl Dispense {
+    /// Expiry deadline of the dispense, `None` when no scheduler is configured or scheduling
+    /// failed: the dispense then stays open until cancelled by hand
+    async fn schedule_expiry(
+        dispense_id: &str,
+        from: DateTime<Utc>,
+        services: &Services,
+    ) -> Option<DateTime<Utc>> {
+        let scheduler = services.scheduler.as_ref()?;
+        let expires_at = scheduler.expires_at(from);
+
+        match scheduler.schedule_expiry(dispense_id, expires_at).await {
+            Ok(_) => Some(expires_at),
+            Err(e) => {
+                tracing::warn!("Expiry of dispense {} not scheduled: {}", dispense_id, e);
+                None
+            }
+        }
+    }
+
+    /// Delete the pending expiry, a leftover schedule only fails to cancel a finished dispense
+    async fn cancel_expiry(&self, services: &Services) {
+        let (Some(scheduler), Some(_)) = (&services.scheduler, self.expires_at) else {
+            return;
+        };
+
+        let schedule_name = SchedulerService::schedule_name(&self.id);
+        if let Err(e) = scheduler.cancel_expiry(&schedule_name).await {
+            tracing::warn!("Expiry schedule {} not deleted: {}", schedule_name, e);
+        }
+    }
+
+    /// Past `max_events_before_compaction` events, only the cancellation is accepted
+    fn validate_event_count(
+        &self,
+        command: &Command,
+        config: &DispenseConfig,
+    ) -> Result<(), Error> {
+        let event_count = self.aggregate_version;
+        if event_count >= config.max_events_before_compaction {
+            if matches!(command, Command::CancelDispense { .. }) {
+                return Ok(());
+            }
+            return Err(Error::Validation {
+                message: "Dispense aggregate has too many events; contact support".to_string(),
+            });
+        }
+        if event_count >= config.compaction_warning_events() {
+            tracing::warn!(
+                event_count,
+                max_events = config.max_events_before_compaction,
+                "CompactionWarning: dispense {} is nearing its event limit",
+                self.id
+            );
+        }
+        Ok(())
+    }
+
+    /// New and deleted dispenses are left to `validate_new` and `validate_existing`
+    fn validate_command_allowed(&self, command: &Command) -> Result<(), Error> {
+        let command_type = command.command_type();
+        if self.id.is_empty() || self.deleted || SYSTEM_COMMANDS.contains(&command_type) {
+            return Ok(());
+        }
+        if !self.status.allowed_commands().contains(&command_type) {
+            return Err(Error::Validation {
+                message: format!(
+                    "{} is not allowed in status {:?}",
+                    command_type, self.status
+                ),
+            });
+        }
+        Ok(())
+    }
+
     fn validate_new(&self) -> Result<(), Error> {
         if !self.id.is_empty() {
             return Err(Error::Uniqueness { field: "id".to_string() });
@@ -219,17 +1246,1382 @@ impl Dispense {
         Ok(())
     }
 
-    fn validate_can_complete(&self) -> Result<(), Error> {
-        if self.patient_id.is_none() {
+    /// Keeps the workflow linear: an analysis alone (without upload) does not unlock the
+    /// patient and drug steps
+    fn validate_prescription_uploaded(&self, step: &str) -> Result<(), Error> {
+        if self.prescription_id.is_none() {
             return Err(Error::Validation {
-                message: "Cannot complete dispense without patient".to_string(),
+                message: format!("Cannot add {} before uploading prescription", step),
             });
         }
-        if self.drugs.is_empty() {
+        Ok(())
+    }
+
+    fn validate_patient_name(name: &str) -> Result<(), Error> {
+        name_validator::validate_patient_name(name).map_err(|message| Error::Validation {
+            message: message.to_string(),
+        })
+    }
+
+    /// Analyses only apply to an uploaded (or reset) prescription: a late S3 event must not
+    /// overwrite the analysis of a ready, completed or cancelled dispense
+    fn validate_analyzing(&self) -> Result<(), Error> {
+        if self.status != DispenseStatus::Analyzing {
+            return Err(Error::InvalidStateTransition {
+                from: format!("{:?}", self.status),
+                to: format!("{:?}", DispenseStatus::Ready),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_ready(&self) -> Result<(), Error> {
+        if self.status != DispenseStatus::Ready {
+            return Err(Error::Validation {
+                message: "Fulfillment method requires a ready dispense".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_can_split(&self, drugs_to_split: &[String]) -> Result<(), Error> {
+        if self.status != DispenseStatus::Ready {
+            return Err(Error::Validation {
+                message: "Only ready dispenses can be split".to_string(),
+            });
+        }
+        if drugs_to_split.is_empty() {
+            return Err(Error::Validation {
+                message: "No drugs to split".to_string(),
+            });
+        }
+        if let Some(drug_id) = drugs_to_split
+            .iter()
+            .find(|drug_id| !self.drugs.iter().any(|drug| drug.drug_id == **drug_id))
+        {
+            return Err(Error::Validation {
+                message: format!("Drug {} is not part of the dispense", drug_id),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_can_merge(&self, source_dispense_id: &str) -> Result<(), Error> {
+        if !matches!(self.status, DispenseStatus::Pending | DispenseStatus::Ready) {
+            return Err(Error::Validation {
+                message: "Only pending or ready dispenses can be merged".to_string(),
+            });
+        }
+        if self.id == source_dispense_id {
+            return Err(Error::Validation {
+                message: "Cannot merge a dispense into itself".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_can_ship(&self) -> Result<(), Error> {
+        if !matches!(self.fulfillment_method, Some(FulfillmentMethod::MailOrder { .. })) {
+            return Err(Error::Validation {
+                message: "Only mail-order dispenses can be shipped".to_string(),
+            });
+        }
+        if self.shipped_at.is_some() {
+            return Err(Error::Uniqueness { field: "shipped_at".to_string() });
+        }
+        Ok(())
+    }
+
+    fn validate_can_deliver(&self) -> Result<(), Error> {
+        if self.shipped_at.is_none() {
+            return Err(Error::Validation {
+                message: "Cannot record delivery before shipment".to_string(),
+            });
+        }
+        if self.delivered_at.is_some() {
+            return Err(Error::Uniqueness { field: "delivered_at".to_string() });
+        }
+        Ok(())
+    }
+
+    fn validate_no_contraindications(&self, interactions: &[DrugInteraction]) -> Result<(), Error> {
+        if let Some(interaction) = interactions
+            .iter()
+            .find(|interaction| interaction.severity == InteractionSeverity::Contraindicated)
+        {
             return Err(Error::Validation {
-                message: "Cannot complete dispense without drugs".to_string(),
+                message: format!(
+                    "Contraindicated drugs {} and {}: {}",
+                    interaction.drug_a, interaction.drug_b, interaction.description
+                ),
             });
         }
         Ok(())
     }
+
+    /// Model confidence from the analysis, a missing score fails the quality check
+    fn parse_quality_score(analysis_data: &str) -> Result<f32, Error> {
+        let analysis: AnalysisResult =
+            serde_json::from_str(analysis_data).map_err(|e| Error::Validation {
+                message: format!("Invalid analysis data: {}", e),
+            })?;
+        Ok(analysis.confidence_score.unwrap_or(0.0))
+    }
+
+    /// Prescriber authorized for controlled substances when `drugs` include one
+    async fn validate_controlled_prescriber(
+        &self,
+        prescriber_id: Option<&str>,
+        drugs: &[DrugItem],
+        services: &Services,
+    ) -> Result<(), Error> {
+        if !drugs.iter().any(|drug| drug.controlled) {
+            return Ok(());
+        }
+        match prescriber_id {
+            Some(prescriber_id) => {
+                services
+                    .dispensing
+                    .authorize_controlled_prescription(prescriber_id)
+                    .await
+            }
+            None => Err(Error::Forbidden),
+        }
+    }
+
+    /// `SetPrescriber` checked the drugs of its time, drugs added after it are checked here
+    async fn validate_prescriber_of_added_drugs(
+        &self,
+        drugs: &[DrugItem],
+        services: &Services,
+    ) -> Result<(), Error> {
+        if self.prescriber_license_number.is_none() {
+            return Ok(());
+        }
+        self.validate_controlled_prescriber(self.prescriber_id.as_deref(), drugs, services)
+            .await
+    }
+
+    async fn validate_drugs_available(
+        &self,
+        drugs: &[DrugItem],
+        services: &Services,
+    ) -> Result<(), Error> {
+        for drug in drugs {
+            let available = services
+                .dispensing
+                .check_drug_availability(&drug.drug_id, drug.quantity)
+                .await?;
+            if !available {
+                return Err(Error::Validation {
+                    message: format!(
+                        "Drug {} is not available in quantity {}",
+                        drug.drug_id, drug.quantity
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Drugs dispensed by the branch, or the default pharmacy without one
+    async fn validate_drugs_on_formulary(
+        &self,
+        drugs: &[DrugItem],
+        services: &Services,
+    ) -> Result<(), Error> {
+        let pharmacy_id = self
+            .pharmacy_branch_id
+            .as_deref()
+            .unwrap_or(DEFAULT_PHARMACY_ID);
+
+        for drug in drugs {
+            let on_formulary = services
+                .dispensing
+                .is_drug_on_formulary(pharmacy_id, &drug.drug_id)
+                .await?;
+            if !on_formulary {
+                return Err(Error::Validation {
+                    message: format!(
+                        "Drug {} ({}) is not on formulary for pharmacy {}",
+                        drug.name, drug.drug_id, pharmacy_id
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `apply` replaces the drug list, so each `drug_id` must be set and appear once
+fn validate_drug_ids(drugs: &[DrugItem]) -> Result<(), Error> {
+    let mut drug_ids = HashSet::new();
+    for drug in drugs {
+        if drug.drug_id.is_empty() {
+            return Err(Error::Validation {
+                message: "drug_id must not be empty".to_string(),
+            });
+        }
+        if !drug_ids.insert(drug.drug_id.as_str()) {
+            return Err(Error::Validation {
+                message: format!("Duplicate drug_id: {}", drug.drug_id),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Fixtures for aggregate command handler tests
+#[cfg(any(test, feature = "mocks"))]
+impl DrugItem {
+    /// `drug-{n}` named `Drug {n}`, quantity 1
+    pub fn test_item(n: usize) -> DrugItem {
+        DrugItem {
+            drug_id: DrugId::new(format!("drug-{}", n)),
+            name: format!("Drug {}", n),
+            quantity: 1,
+            controlled: false,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "mocks"))]
+impl Dispense {
+    /// Dispense in `status` with the data that status implies: an uploaded prescription from
+    /// `Analyzing`, an analysis, a patient and one drug from `Ready`
+    pub fn test_dispense(status: DispenseStatus) -> Dispense {
+        let now = Utc::now();
+        let mut dispense = Dispense {
+            data: DispenseData {
+                id: DispenseId::new("test-dispense"),
+                created_at: now,
+                updated_at: now,
+                status: status.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        if status != DispenseStatus::Pending {
+            dispense.prescription_id = Some("test-prescription".to_string());
+            dispense.prescription_url =
+                Some("s3://prescriptions/test-dispense/prescription.jpg".to_string());
+        }
+        if matches!(status, DispenseStatus::Ready | DispenseStatus::Complete) {
+            dispense.prescription_analyzed = true;
+            dispense.analysis_data = Some(r#"{"confidence_score":0.95}"#.to_string());
+            dispense.analysis_version = 1;
+            dispense.patient_id = Some(PatientId::new("test-patient"));
+            dispense.patient_name = Some("Test Patient".to_string());
+            dispense.drugs = vec![DrugItem::test_item(1)];
+        }
+
+        dispense
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::eq;
+
+    use super::*;
+    use crate::dispenses::{
+        services::{
+            MockServices, DEFAULT_MAX_DRUGS_PER_DISPENSE, DEFAULT_MAX_DRUG_QUANTITY,
+            DEFAULT_MAX_EVENTS_BEFORE_COMPACTION,
+        },
+        testing::{CommandOutcome, DispenseTestHarness},
+    };
+
+    fn harness(status: DispenseStatus, mock: MockServices) -> DispenseTestHarness {
+        DispenseTestHarness::given_dispense(Dispense::test_dispense(status))
+            .with_services(Services::new(mock))
+    }
+
+    /// Ready dispense the patient is still to be added to
+    fn ready_without_patient(mock: MockServices) -> DispenseTestHarness {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.patient_id = None;
+        dispense.patient_name = None;
+
+        DispenseTestHarness::given_dispense(dispense).with_services(Services::new(mock))
+    }
+
+    /// Dispense handled with services that must not be called
+    fn harness_of(dispense: Dispense) -> DispenseTestHarness {
+        DispenseTestHarness::given_dispense(dispense)
+            .with_services(Services::new(MockServices::new()))
+    }
+
+    fn start_dispense() -> Command {
+        Command::StartDispense {
+            id: "test-dispense".to_string(),
+            original_dispense_id: None,
+            pharmacy_branch_id: None,
+            priority: Priority::Routine,
+            notes: None,
+        }
+    }
+
+    fn upload_prescription() -> Command {
+        Command::UploadPrescription {
+            prescription_id: "test-prescription".to_string(),
+            url: "s3://prescriptions/test-dispense/prescription.jpg".to_string(),
+            file_size_bytes: Some(1024),
+        }
+    }
+
+    fn analyze_prescription(confidence_score: f32, page_count: u32) -> Command {
+        Command::AnalyzePrescription {
+            analysis_data: format!(r#"{{"confidence_score":{}}}"#, confidence_score),
+            model_id: "test-model".to_string(),
+            page_count: Some(page_count),
+        }
+    }
+
+    fn add_patient(patient_id: &str) -> Command {
+        Command::AddPatient {
+            patient_id: patient_id.to_string(),
+            name: "Jane Doe".to_string(),
+        }
+    }
+
+    fn add_drugs(drugs: Vec<DrugItem>) -> Command {
+        Command::AddDrugs { drugs }
+    }
+
+    fn started() -> DispenseTestHarness {
+        DispenseTestHarness::given(vec![]).when_and_apply(start_dispense())
+    }
+
+    /// Started, uploaded and analyzed with a high confidence single page
+    fn analyzed() -> DispenseTestHarness {
+        started()
+            .when_and_apply(upload_prescription())
+            .when_and_apply(analyze_prescription(0.95, 1))
+    }
+
+    #[test]
+    fn start_new_dispense() {
+        DispenseTestHarness::given(vec![])
+            .when(start_dispense())
+            .then_event_types(&["Dispense:Started"]);
+    }
+
+    #[test]
+    fn start_dispense_rejects_existing_dispense() {
+        started()
+            .when(start_dispense())
+            .then_error(Error::Uniqueness {
+                field: "id".to_string(),
+            });
+    }
+
+    #[test]
+    fn command_on_missing_dispense_is_not_found() {
+        DispenseTestHarness::given(vec![])
+            .when(upload_prescription())
+            .then_error(Error::NotFound {
+                entity: AGGREGATE_TYPE.to_string(),
+            });
+    }
+
+    #[test]
+    fn command_on_deleted_dispense_is_forbidden() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.deleted = true;
+
+        DispenseTestHarness::given_dispense(dispense)
+            .when(Command::CancelDispense { reason: None })
+            .then_error(Error::Forbidden);
+    }
+
+    #[test]
+    fn upload_prescription_starts_analysis() {
+        let harness = started().when_and_apply(upload_prescription());
+
+        assert_eq!(harness.dispense().status, DispenseStatus::Analyzing);
+        assert_eq!(
+            harness.dispense().prescription_id.as_deref(),
+            Some("test-prescription")
+        );
+    }
+
+    #[test]
+    fn analyze_prescription_makes_dispense_ready() {
+        let harness = analyzed();
+
+        assert_eq!(harness.dispense().status, DispenseStatus::Ready);
+        assert!(harness.dispense().prescription_analyzed);
+        assert_eq!(harness.dispense().analysis_version, 1);
+    }
+
+    #[test]
+    fn analyze_prescription_before_upload_is_invalid_transition() {
+        started()
+            .when(analyze_prescription(0.95, 1))
+            .then_error(Error::InvalidStateTransition {
+                from: "Pending".to_string(),
+                to: "Ready".to_string(),
+            });
+    }
+
+    #[test]
+    fn analyze_prescription_of_completed_dispense_is_invalid_transition() {
+        analyzed()
+            .when_and_apply(add_patient("patient-1"))
+            .when_and_apply(add_drugs(vec![DrugItem::test_item(1)]))
+            .when_and_apply(Command::CompleteDispense)
+            .when(analyze_prescription(0.95, 1))
+            .then_error(Error::InvalidStateTransition {
+                from: "Complete".to_string(),
+                to: "Ready".to_string(),
+            });
+    }
+
+    #[test]
+    fn low_quality_analysis_returns_dispense_to_pending() {
+        let uploaded = started().when_and_apply(upload_prescription());
+        uploaded
+            .when(analyze_prescription(0.1, 1))
+            .then_event_types(&["Dispense:PrescriptionQualityFailed"]);
+
+        let harness = uploaded.when_and_apply(analyze_prescription(0.1, 1));
+        assert_eq!(harness.dispense().status, DispenseStatus::Pending);
+    }
+
+    #[test]
+    fn multi_page_prescription_needs_approval() {
+        let harness = started()
+            .when_and_apply(upload_prescription())
+            .when_and_apply(analyze_prescription(0.95, 3))
+            .when_and_apply(add_patient("patient-1"))
+            .when_and_apply(add_drugs(vec![DrugItem::test_item(1)]));
+        harness
+            .when(Command::CompleteDispense)
+            .then_error(Error::Validation {
+                message: "Cannot complete dispense: multi-page review pending".to_string(),
+            });
+
+        harness
+            .when_and_apply(Command::ApproveMultiPagePrescription {
+                approved_by: "pharmacist-1".to_string(),
+            })
+            .when(Command::CompleteDispense)
+            .then_event_types(&["Dispense:Completed"]);
+    }
+
+    #[test]
+    fn add_patient_requires_uploaded_prescription() {
+        started()
+            .when(add_patient("patient-1"))
+            .then_error(Error::Validation {
+                message: "AddPatient is not allowed in status Pending".to_string(),
+            });
+    }
+
+    #[test]
+    fn complete_dispense_without_drugs_is_rejected() {
+        analyzed()
+            .when_and_apply(add_patient("patient-1"))
+            .when(Command::CompleteDispense)
+            .then_error(Error::Validation {
+                message: "Cannot complete dispense: no drugs added".to_string(),
+            });
+    }
+
+    #[test]
+    fn full_workflow_completes_dispense() {
+        let harness = analyzed()
+            .when_and_apply(add_patient("patient-1"))
+            .when_and_apply(add_drugs(vec![DrugItem::test_item(1)]))
+            .when_and_apply(Command::CompleteDispense);
+
+        assert_eq!(harness.dispense().status, DispenseStatus::Complete);
+        assert_eq!(harness.dispense().aggregate_version, 6);
+        assert!(harness.dispense().expires_at.is_none());
+    }
+
+    #[test]
+    fn cancel_dispense() {
+        let harness = analyzed().when_and_apply(Command::CancelDispense {
+            reason: Some("Patient request".to_string()),
+        });
+
+        assert_eq!(harness.dispense().status, DispenseStatus::Cancelled);
+        assert_eq!(
+            harness.dispense().cancellation_reason.as_deref(),
+            Some("Patient request")
+        );
+    }
+
+    #[test]
+    fn fulfillment_method_requires_ready_dispense() {
+        started()
+            .when(Command::SetFulfillmentMethod {
+                method: FulfillmentMethod::InStore,
+            })
+            .then_error(Error::Validation {
+                message: "SetFulfillmentMethod is not allowed in status Pending".to_string(),
+            });
+    }
+
+    #[test]
+    fn in_store_dispense_cannot_be_shipped() {
+        analyzed()
+            .when_and_apply(Command::SetFulfillmentMethod {
+                method: FulfillmentMethod::InStore,
+            })
+            .when(Command::RecordShipment {
+                tracking_number: "1Z999".to_string(),
+                shipped_at: Utc::now(),
+                carrier: "UPS".to_string(),
+            })
+            .then_error(Error::Validation {
+                message: "Only mail-order dispenses can be shipped".to_string(),
+            });
+    }
+
+    #[test]
+    fn mail_order_dispense_is_shipped_then_delivered() {
+        let harness = analyzed()
+            .when_and_apply(Command::SetFulfillmentMethod {
+                method: FulfillmentMethod::MailOrder {
+                    address: Address {
+                        street: "1 Main St".to_string(),
+                        city: "Springfield".to_string(),
+                        state: "CA".to_string(),
+                        zip: "90001".to_string(),
+                        country: "US".to_string(),
+                    },
+                    tracking_number: None,
+                },
+            })
+            .when_and_apply(Command::RecordShipment {
+                tracking_number: "1Z999".to_string(),
+                shipped_at: Utc::now(),
+                carrier: "UPS".to_string(),
+            });
+        harness
+            .when(Command::RecordShipment {
+                tracking_number: "1Z999".to_string(),
+                shipped_at: Utc::now(),
+                carrier: "UPS".to_string(),
+            })
+            .then_error(Error::Uniqueness {
+                field: "shipped_at".to_string(),
+            });
+
+        let harness = harness.when_and_apply(Command::RecordDelivery {
+            delivered_at: Utc::now(),
+        });
+        assert!(harness.dispense().delivered_at.is_some());
+    }
+
+    #[test]
+    fn add_patient_validates_patient_exists() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_patient_exists()
+            .with(eq("patient-1"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let outcome = ready_without_patient(mock).when(add_patient("patient-1"));
+
+        assert!(matches!(
+            outcome.result().as_deref(),
+            Ok([Event::PatientAdded { patient_id, .. }]) if patient_id == "patient-1"
+        ));
+    }
+
+    #[test]
+    fn add_patient_rejects_unknown_patient() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_patient_exists()
+            .times(1)
+            .returning(|_| {
+                Err(Error::NotFound {
+                    entity: "patient".to_string(),
+                })
+            });
+
+        ready_without_patient(mock)
+            .when(add_patient("patient-1"))
+            .then_error(Error::NotFound {
+                entity: "patient".to_string(),
+            });
+    }
+
+    #[test]
+    fn add_patient_retry_skips_validation() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_patient_exists().never();
+
+        harness(DispenseStatus::Ready, mock)
+            .when(Command::AddPatient {
+                patient_id: "test-patient".to_string(),
+                name: "Test Patient".to_string(),
+            })
+            .then_events(vec![]);
+    }
+
+    #[test]
+    fn add_patient_rejects_another_patient() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_patient_exists().never();
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_patient("other-patient"))
+            .then_error(Error::Uniqueness {
+                field: "patient_id".to_string(),
+            });
+    }
+
+    #[test]
+    fn add_drugs_checks_availability_of_each_drug() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability()
+            .with(eq("drug-1"), eq(1))
+            .times(1)
+            .returning(|_, _| Ok(true));
+        mock.expect_check_drug_availability()
+            .with(eq("drug-2"), eq(1))
+            .times(1)
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .times(2)
+            .returning(|_, _| Ok(true));
+        let drugs = vec![DrugItem::test_item(1), DrugItem::test_item(2)];
+
+        let outcome = harness(DispenseStatus::Ready, mock).when(add_drugs(drugs.clone()));
+
+        assert!(matches!(
+            outcome.result().as_deref(),
+            Ok([Event::DrugsAdded { drugs: added, .. }]) if *added == drugs
+        ));
+    }
+
+    #[test]
+    fn add_drugs_rejects_unavailable_drug() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability()
+            .times(1)
+            .returning(|_, _| Ok(false));
+        mock.expect_is_drug_on_formulary().never();
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_drugs(vec![DrugItem::test_item(1)]))
+            .then_error(Error::Validation {
+                message: "Drug drug-1 is not available in quantity 1".to_string(),
+            });
+    }
+
+    #[test]
+    fn add_drugs_rejects_duplicate_drug_id() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability().never();
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_drugs(vec![DrugItem::test_item(1), DrugItem::test_item(1)]))
+            .then_error(Error::Validation {
+                message: "Duplicate drug_id: drug-1".to_string(),
+            });
+    }
+
+    #[test]
+    fn add_drugs_rejects_empty_drug_id() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability().never();
+        let drug = DrugItem {
+            drug_id: DrugId::new(""),
+            ..DrugItem::test_item(1)
+        };
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_drugs(vec![drug]))
+            .then_error(Error::Validation {
+                message: "drug_id must not be empty".to_string(),
+            });
+    }
+
+    /// Ready dispense given one drug of `quantity`, with every drug available and on formulary
+    fn add_drug_of_quantity(quantity: u32) -> CommandOutcome {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability()
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .returning(|_, _| Ok(true));
+        let drug = DrugItem {
+            quantity,
+            ..DrugItem::test_item(1)
+        };
+
+        harness(DispenseStatus::Ready, mock).when(add_drugs(vec![drug]))
+    }
+
+    #[test]
+    fn add_drugs_rejects_zero_quantity() {
+        add_drug_of_quantity(0).then_error(Error::Validation {
+            message: "Drug quantity must be at least 1".to_string(),
+        });
+    }
+
+    #[test]
+    fn add_drugs_accepts_quantity_of_1() {
+        add_drug_of_quantity(1).then_event_types(&["Dispense:DrugsAdded"]);
+    }
+
+    #[test]
+    fn add_drugs_accepts_maximum_quantity() {
+        add_drug_of_quantity(DEFAULT_MAX_DRUG_QUANTITY).then_event_types(&["Dispense:DrugsAdded"]);
+    }
+
+    #[test]
+    fn add_drugs_rejects_quantity_over_maximum() {
+        for quantity in [DEFAULT_MAX_DRUG_QUANTITY + 1, u32::MAX] {
+            add_drug_of_quantity(quantity).then_error(Error::Validation {
+                message: "Drug quantity exceeds maximum of 9999".to_string(),
+            });
+        }
+    }
+
+    /// Drugs numbered from 1, every drug available and on formulary
+    fn drugs_and_services(count: usize) -> (Vec<DrugItem>, MockServices) {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability()
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .returning(|_, _| Ok(true));
+
+        ((1..=count).map(DrugItem::test_item).collect(), mock)
+    }
+
+    #[test]
+    fn add_drugs_accepts_maximum_drug_count() {
+        let (drugs, mock) = drugs_and_services(DEFAULT_MAX_DRUGS_PER_DISPENSE);
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_drugs(drugs))
+            .then_event_types(&["Dispense:DrugsAdded"]);
+    }
+
+    #[test]
+    fn add_drugs_rejects_drug_count_over_maximum() {
+        let (drugs, mock) = drugs_and_services(DEFAULT_MAX_DRUGS_PER_DISPENSE + 1);
+
+        harness(DispenseStatus::Ready, mock)
+            .when(add_drugs(drugs))
+            .then_error(Error::Validation {
+                message: "Maximum 20 drugs per dispense, the dispense would have 21".to_string(),
+            });
+    }
+
+    #[test]
+    fn merge_dispense_rejects_one_drug_over_maximum_count() {
+        let (drugs, mock) = drugs_and_services(DEFAULT_MAX_DRUGS_PER_DISPENSE);
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.drugs = drugs;
+
+        DispenseTestHarness::given_dispense(dispense)
+            .with_services(Services::new(mock))
+            .when(Command::MergeDispense {
+                source_dispense_id: "source-dispense".to_string(),
+                merged_drugs: vec![DrugItem::test_item(DEFAULT_MAX_DRUGS_PER_DISPENSE + 1)],
+            })
+            .then_error(Error::Validation {
+                message: "Maximum 20 drugs per dispense, the dispense would have 21".to_string(),
+            });
+    }
+
+    /// Ready dispense with `event_count` events
+    fn harness_with_events(event_count: usize) -> DispenseTestHarness {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.aggregate_version = event_count;
+
+        harness_of(dispense)
+    }
+
+    #[test]
+    fn commands_are_accepted_below_the_event_limit() {
+        harness_with_events(DEFAULT_MAX_EVENTS_BEFORE_COMPACTION - 1)
+            .when(Command::ResetAnalysis)
+            .then_event_types(&["Dispense:AnalysisReset"]);
+    }
+
+    #[test]
+    fn commands_are_rejected_at_the_event_limit() {
+        for event_count in [DEFAULT_MAX_EVENTS_BEFORE_COMPACTION, usize::MAX] {
+            harness_with_events(event_count)
+                .when(Command::ResetAnalysis)
+                .then_error(Error::Validation {
+                    message: "Dispense aggregate has too many events; contact support".to_string(),
+                });
+        }
+    }
+
+    #[test]
+    fn cancellation_is_accepted_past_the_event_limit() {
+        harness_with_events(DEFAULT_MAX_EVENTS_BEFORE_COMPACTION)
+            .when(Command::CancelDispense { reason: None })
+            .then_event_types(&["Dispense:Cancelled"]);
+    }
+
+    #[test]
+    fn reset_analysis_rejects_finished_dispense() {
+        for status in [DispenseStatus::Complete, DispenseStatus::Cancelled] {
+            harness(status.clone(), MockServices::new())
+                .when(Command::ResetAnalysis)
+                .then_error(Error::Validation {
+                    message: format!("ResetAnalysis is not allowed in status {:?}", status),
+                });
+        }
+    }
+
+    #[test]
+    fn reset_analysis_of_ready_dispense() {
+        let harness = harness(DispenseStatus::Ready, MockServices::new());
+        harness
+            .when(Command::ResetAnalysis)
+            .then_event_types(&["Dispense:AnalysisReset"]);
+
+        let harness = harness.when_and_apply(Command::ResetAnalysis);
+        assert_eq!(harness.dispense().status, DispenseStatus::Analyzing);
+        assert!(!harness.dispense().prescription_analyzed);
+    }
+
+    #[test]
+    fn merge_dispense_rejects_drug_in_both_dispenses() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability().never();
+
+        harness(DispenseStatus::Ready, mock)
+            .when(Command::MergeDispense {
+                source_dispense_id: "source-dispense".to_string(),
+                merged_drugs: vec![DrugItem::test_item(1)],
+            })
+            .then_error(Error::Validation {
+                message: "Duplicate drug_id: drug-1".to_string(),
+            });
+    }
+
+    #[test]
+    fn merge_dispense_checks_merged_drugs() {
+        let mut mock = MockServices::new();
+        mock.expect_check_drug_availability()
+            .with(eq("drug-2"), eq(1))
+            .times(1)
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .with(eq(DEFAULT_PHARMACY_ID), eq("drug-2"))
+            .times(1)
+            .returning(|_, _| Ok(false));
+
+        let outcome = harness(DispenseStatus::Ready, mock).when(Command::MergeDispense {
+            source_dispense_id: "source-dispense".to_string(),
+            merged_drugs: vec![DrugItem::test_item(2)],
+        });
+
+        assert!(matches!(outcome.result(), Err(Error::Validation { .. })));
+    }
+
+    fn set_prescriber() -> Command {
+        Command::SetPrescriber {
+            prescriber_id: None,
+            license_number: "CA123456".to_string(),
+            state: "CA".to_string(),
+        }
+    }
+
+    #[test]
+    fn set_prescriber_rejects_invalid_license() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_prescriber_license()
+            .with(eq("CA123456"), eq("CA"))
+            .times(1)
+            .returning(|license_number, _| {
+                Err(Error::Validation {
+                    message: format!("Prescriber license {} rejected", license_number),
+                })
+            });
+
+        harness(DispenseStatus::Ready, mock)
+            .when(set_prescriber())
+            .then_error(Error::Validation {
+                message: "Invalid prescriber license: Prescriber license CA123456 rejected"
+                    .to_string(),
+            });
+    }
+
+    #[test]
+    fn set_prescriber_reports_validation_outage() {
+        let mut mock = MockServices::new();
+        mock.expect_validate_prescriber_license()
+            .times(1)
+            .returning(|_, _| {
+                Err(Error::ExternalService {
+                    service: "Prescriber validation API".to_string(),
+                    message: "Prescriber validation unavailable".to_string(),
+                })
+            });
+
+        let outcome = harness(DispenseStatus::Ready, mock).when(set_prescriber());
+
+        assert!(matches!(
+            outcome.result(),
+            Err(Error::ExternalService { .. })
+        ));
+    }
+
+    /// Ready dispense given the `PrescriberSet` of `prescriber-1`, `authorized` or not to
+    /// prescribe controlled substances, with every drug available and on formulary
+    fn prescriber_set(authorized: bool) -> DispenseTestHarness {
+        let mut mock = MockServices::new();
+        mock.expect_authorize_controlled_prescription()
+            .with(eq("prescriber-1"))
+            .times(1)
+            .returning(move |_| if authorized { Ok(()) } else { Err(Error::Forbidden) });
+        mock.expect_check_drug_availability()
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .returning(|_, _| Ok(true));
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.apply(Event::PrescriberSet {
+            id: "test-dispense".to_string(),
+            prescriber_id: Some("prescriber-1".to_string()),
+            license_number: "CA123456".to_string(),
+            state: "CA".to_string(),
+            prescriber_name: None,
+            updated_at: Utc::now(),
+            actor_id: None,
+        });
+
+        DispenseTestHarness::given_dispense(dispense).with_services(Services::new(mock))
+    }
+
+    fn controlled_item(n: usize) -> DrugItem {
+        DrugItem {
+            controlled: true,
+            ..DrugItem::test_item(n)
+        }
+    }
+
+    #[test]
+    fn add_controlled_drug_after_prescriber_rejects_unauthorized_prescriber() {
+        prescriber_set(false)
+            .when(add_drugs(vec![controlled_item(1)]))
+            .then_error(Error::Forbidden);
+    }
+
+    #[test]
+    fn add_controlled_drug_after_authorized_prescriber() {
+        prescriber_set(true)
+            .when(add_drugs(vec![controlled_item(1)]))
+            .then_event_types(&["Dispense:DrugsAdded"]);
+    }
+
+    #[test]
+    fn merge_controlled_drug_after_prescriber_rejects_unauthorized_prescriber() {
+        prescriber_set(false)
+            .when(Command::MergeDispense {
+                source_dispense_id: "source-dispense".to_string(),
+                merged_drugs: vec![controlled_item(2)],
+            })
+            .then_error(Error::Forbidden);
+    }
+
+    #[test]
+    fn add_uncontrolled_drug_after_prescriber_skips_authorization() {
+        let mut mock = MockServices::new();
+        mock.expect_authorize_controlled_prescription().never();
+        mock.expect_check_drug_availability()
+            .returning(|_, _| Ok(true));
+        mock.expect_is_drug_on_formulary()
+            .returning(|_, _| Ok(true));
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.prescriber_id = Some("prescriber-1".to_string());
+        dispense.prescriber_license_number = Some("CA123456".to_string());
+
+        DispenseTestHarness::given_dispense(dispense)
+            .with_services(Services::new(mock))
+            .when(add_drugs(vec![DrugItem::test_item(2)]))
+            .then_event_types(&["Dispense:DrugsAdded"]);
+    }
+
+    #[test]
+    fn completion_blockers_of_every_combination() {
+        for status in all_statuses() {
+            for mask in 0..8 {
+                let (patient, drugs, analyzed) = (mask & 1 != 0, mask & 2 != 0, mask & 4 != 0);
+                let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+                dispense.status = status.clone();
+                if !patient {
+                    dispense.patient_id = None;
+                }
+                if !drugs {
+                    dispense.drugs.clear();
+                }
+                dispense.prescription_analyzed = analyzed;
+
+                let mut expected = Vec::new();
+                if status != DispenseStatus::Ready {
+                    expected.push("wrong status");
+                }
+                if !patient {
+                    expected.push("missing patient");
+                }
+                if !drugs {
+                    expected.push("no drugs added");
+                }
+                if !analyzed {
+                    expected.push("prescription not analyzed");
+                }
+
+                assert_eq!(dispense.completion_blockers(), expected);
+                assert_eq!(dispense.is_complete_ready(), expected.is_empty());
+                assert_eq!(
+                    dispense.validate_can_complete().is_ok(),
+                    expected.is_empty()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn completion_blockers_include_pending_reviews() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.requires_multi_page_review = true;
+        dispense.requires_pharmacist_review = true;
+
+        assert_eq!(
+            dispense.completion_blockers(),
+            ["multi-page review pending", "pharmacist review pending"]
+        );
+
+        dispense.requires_multi_page_review = false;
+        dispense.pharmacist_review_completed = true;
+        assert!(dispense.is_complete_ready());
+    }
+
+    #[test]
+    fn complete_dispense_rejects_cancelled_dispense_with_patient_and_drugs() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.status = DispenseStatus::Cancelled;
+
+        harness_of(dispense)
+            .when(Command::CompleteDispense)
+            .then_error(Error::Validation {
+                message: "CompleteDispense is not allowed in status Cancelled".to_string(),
+            });
+    }
+
+    #[test]
+    fn complete_dispense_rejects_analyzing_dispense_with_patient_and_drugs() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Analyzing);
+        dispense.patient_id = Some(PatientId::new("test-patient"));
+        dispense.drugs = vec![DrugItem::test_item(1)];
+
+        harness_of(dispense)
+            .when(Command::CompleteDispense)
+            .then_error(Error::Validation {
+                message: "CompleteDispense is not allowed in status Analyzing".to_string(),
+            });
+    }
+
+    /// `Dispense::test_dispense` at a fixed time, for snapshots
+    fn snapshot_dispense(status: DispenseStatus) -> Dispense {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut dispense = Dispense::test_dispense(status);
+        dispense.created_at = at;
+        dispense.updated_at = at;
+        dispense.aggregate_version = 5;
+
+        dispense
+    }
+
+    fn all_statuses() -> [DispenseStatus; 5] {
+        [
+            DispenseStatus::Pending,
+            DispenseStatus::Analyzing,
+            DispenseStatus::Ready,
+            DispenseStatus::Complete,
+            DispenseStatus::Cancelled,
+        ]
+    }
+
+    #[test]
+    fn dispense_clone_is_equal() {
+        for status in all_statuses() {
+            let dispense = snapshot_dispense(status);
+
+            assert_eq!(dispense.clone(), dispense);
+        }
+    }
+
+    #[test]
+    fn dispense_field_change_is_not_equal() {
+        let dispense = snapshot_dispense(DispenseStatus::Ready);
+
+        let mut other = dispense.clone();
+        other.status = DispenseStatus::Complete;
+        assert_ne!(other, dispense);
+
+        let mut other = dispense.clone();
+        other.drugs.push(DrugItem::test_item(2));
+        assert_ne!(other, dispense);
+
+        let mut other = dispense.clone();
+        other.aggregate_version += 1;
+        assert_ne!(other, dispense);
+    }
+
+    #[test]
+    fn dispense_json_round_trip() {
+        for status in all_statuses() {
+            let dispense = snapshot_dispense(status);
+            let json = serde_json::to_string(&dispense).unwrap();
+
+            assert_eq!(serde_json::from_str::<Dispense>(&json).unwrap(), dispense);
+        }
+    }
+
+    #[test]
+    fn dispense_json_is_stable() {
+        insta::assert_json_snapshot!(snapshot_dispense(DispenseStatus::Ready));
+    }
+
+    #[test]
+    fn status_history_keeps_the_last_transitions() {
+        let mut data = Dispense::test_dispense(DispenseStatus::Ready).data;
+        let at = Utc::now();
+
+        // Ready -> Analyzing -> Ready ..., 12 transitions
+        for i in 0..12 {
+            let event = if i % 2 == 0 {
+                Event::AnalysisReset {
+                    id: "test-dispense".to_string(),
+                    updated_at: at + chrono::Duration::seconds(i),
+                    actor_id: None,
+                }
+            } else {
+                Event::PrescriptionAnalyzed {
+                    id: "test-dispense".to_string(),
+                    analysis_data: r#"{"confidence_score":0.95}"#.to_string(),
+                    analysis_version: 1,
+                    model_id: "test-model".to_string(),
+                    quality_score: 0.95,
+                    prescription_page_count: Some(1),
+                    updated_at: at + chrono::Duration::seconds(i),
+                    actor_id: None,
+                }
+            };
+            data.apply(event);
+        }
+
+        assert_eq!(data.status_history.len(), MAX_STATUS_HISTORY);
+        // The first two transitions were dropped
+        let oldest = data.status_history.front().unwrap();
+        assert_eq!(oldest.at, at + chrono::Duration::seconds(2));
+        assert_eq!(oldest.from, DispenseStatus::Ready);
+        assert_eq!(oldest.to, DispenseStatus::Analyzing);
+        assert_eq!(oldest.triggered_by_event, "AnalysisReset");
+        let newest = data.status_history.back().unwrap();
+        assert_eq!(newest.at, at + chrono::Duration::seconds(11));
+        assert_eq!(newest.triggered_by_event, "PrescriptionAnalyzed");
+    }
+
+    #[test]
+    fn drug_item_field_change_is_not_equal() {
+        let drug = DrugItem::test_item(1);
+        assert_eq!(drug.clone(), drug);
+
+        let changes: [fn(&mut DrugItem); 4] = [
+            |drug| drug.drug_id = DrugId::new("drug-2"),
+            |drug| drug.name = "Drug 2".to_string(),
+            |drug| drug.quantity += 1,
+            |drug| drug.controlled = true,
+        ];
+        for change in changes {
+            let mut other = drug.clone();
+            change(&mut other);
+
+            assert_ne!(other, drug);
+        }
+    }
+
+    #[test]
+    fn drug_item_json_round_trip() {
+        let drug = DrugItem {
+            controlled: true,
+            ..DrugItem::test_item(1)
+        };
+        let json = serde_json::to_string(&drug).unwrap();
+
+        assert_eq!(serde_json::from_str::<DrugItem>(&json).unwrap(), drug);
+        insta::assert_json_snapshot!(drug);
+    }
+
+    #[test]
+    fn drug_item_controlled_defaults_to_false() {
+        let drug: DrugItem =
+            serde_json::from_str(r#"{"drug_id":"drug-1","name":"Drug 1","quantity":1}"#).unwrap();
+
+        assert_eq!(drug, DrugItem::test_item(1));
+    }
+
+    #[test]
+    fn dispense_status_json_round_trip() {
+        for status in all_statuses() {
+            let json = serde_json::to_string(&status).unwrap();
+
+            assert_eq!(status.clone(), status);
+            assert_eq!(
+                serde_json::from_str::<DispenseStatus>(&json).unwrap(),
+                status
+            );
+        }
+        insta::assert_json_snapshot!(all_statuses());
+    }
+
+    #[test]
+    fn drug_item_builder_builds_item() {
+        let drug = DrugItem::builder()
+            .drug_id("drug-1")
+            .name("Drug 1")
+            .quantity(1)
+            .build();
+
+        assert_eq!(drug, Ok(DrugItem::test_item(1)));
+    }
+
+    #[test]
+    fn drug_item_builder_sets_controlled() {
+        let drug = DrugItem::builder()
+            .drug_id("drug-1")
+            .name("Drug 1")
+            .quantity(1)
+            .controlled(true)
+            .build()
+            .unwrap();
+
+        assert!(drug.controlled);
+    }
+
+    #[test]
+    fn drug_item_builder_requires_every_field() {
+        assert_eq!(
+            DrugItem::builder().name("Drug 1").quantity(1).build(),
+            Err("drug_id is required")
+        );
+        assert_eq!(
+            DrugItem::builder().drug_id("drug-1").quantity(1).build(),
+            Err("name is required")
+        );
+        assert_eq!(
+            DrugItem::builder().drug_id("drug-1").name("Drug 1").build(),
+            Err("quantity is required")
+        );
+    }
+
+    #[test]
+    fn test_item_is_numbered() {
+        let drug = DrugItem::test_item(7);
+
+        assert_eq!(drug.drug_id, "drug-7");
+        assert_eq!(drug.name, "Drug 7");
+        assert_eq!(drug.quantity, 1);
+        assert!(!drug.controlled);
+    }
+
+    #[test]
+    fn test_dispense_has_the_data_of_its_status() {
+        let pending = Dispense::test_dispense(DispenseStatus::Pending);
+        assert_eq!(pending.status, DispenseStatus::Pending);
+        assert!(pending.prescription_url.is_none());
+
+        let analyzing = Dispense::test_dispense(DispenseStatus::Analyzing);
+        assert!(analyzing.prescription_url.is_some());
+        assert!(!analyzing.prescription_analyzed);
+        assert!(analyzing.drugs.is_empty());
+
+        for status in [DispenseStatus::Ready, DispenseStatus::Complete] {
+            let dispense = Dispense::test_dispense(status.clone());
+
+            assert_eq!(dispense.status, status);
+            assert!(dispense.prescription_analyzed);
+            assert_eq!(dispense.patient_id.as_deref(), Some("test-patient"));
+            assert_eq!(dispense.drugs, vec![DrugItem::test_item(1)]);
+        }
+    }
+
+    /// Ready dispense with or without its patient and drugs
+    fn ready_with(patient: bool, drugs: bool) -> Dispense {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        if !patient {
+            dispense.patient_id = None;
+            dispense.patient_name = None;
+        }
+        if !drugs {
+            dispense.drugs.clear();
+        }
+        dispense
+    }
+
+    #[test]
+    fn completion_percentage_of_pending_without_prescription_is_0() {
+        let dispense = Dispense::test_dispense(DispenseStatus::Pending);
+
+        assert_eq!(dispense.completion_percentage(), 0);
+    }
+
+    #[test]
+    fn completion_percentage_of_pending_with_prescription_is_20() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Pending);
+        dispense.prescription_id = Some("test-prescription".to_string());
+
+        assert_eq!(dispense.completion_percentage(), 20);
+    }
+
+    #[test]
+    fn completion_percentage_of_analyzing_is_40() {
+        let dispense = Dispense::test_dispense(DispenseStatus::Analyzing);
+
+        assert_eq!(dispense.completion_percentage(), 40);
+    }
+
+    #[test]
+    fn completion_percentage_of_ready_without_patient_nor_drugs_is_50() {
+        assert_eq!(ready_with(false, false).completion_percentage(), 50);
+    }
+
+    #[test]
+    fn completion_percentage_of_ready_with_patient_only_is_65() {
+        assert_eq!(ready_with(true, false).completion_percentage(), 65);
+    }
+
+    #[test]
+    fn completion_percentage_of_ready_with_drugs_only_is_65() {
+        assert_eq!(ready_with(false, true).completion_percentage(), 65);
+    }
+
+    #[test]
+    fn completion_percentage_of_ready_with_patient_and_drugs_is_80() {
+        assert_eq!(ready_with(true, true).completion_percentage(), 80);
+    }
+
+    #[test]
+    fn completion_percentage_of_complete_is_100() {
+        let dispense = Dispense::test_dispense(DispenseStatus::Complete);
+
+        assert_eq!(dispense.completion_percentage(), 100);
+    }
+
+    #[test]
+    fn completion_percentage_of_cancelled_is_0() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Cancelled);
+        dispense.prescription_id = Some("test-prescription".to_string());
+        dispense.patient_id = Some(PatientId::new("test-patient"));
+        dispense.drugs = vec![DrugItem::test_item(1)];
+
+        assert_eq!(dispense.completion_percentage(), 0);
+    }
 }
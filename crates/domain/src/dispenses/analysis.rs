@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use super::View;
+
+/// Structured prescription analysis, parsed from `analysis_data`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnalysisResult {
+    pub patient_name: Option<String>,
+    pub date_of_birth: Option<String>,
+    pub prescriber_name: Option<String>,
+    #[serde(default)]
+    pub medications: Vec<ExtractedMedication>,
+    pub confidence_score: Option<f32>,
+    pub analyzed_at: Option<String>,
+}
+
+/// Medication extracted from a prescription
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExtractedMedication {
+    pub name: String,
+    pub dosage: Option<String>,
+    pub quantity: Option<u32>,
+}
+
+/// Parse the analysis stored on a dispense.
+///
+/// Returns `None` when the prescription has not been analyzed yet.
+pub fn parse_analysis(view: &View) -> Option<Result<AnalysisResult, serde_json::Error>> {
+    let dispense = &view.dispense_data;
+    if !dispense.prescription_analyzed {
+        return None;
+    }
+    let data = dispense.analysis_data.as_deref().unwrap_or_default();
+    Some(serde_json::from_str(data))
+}
@@ -0,0 +1,130 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use serde::{Deserialize, Serialize};
+
+/// SQS message asking the analyzer to (re-)analyze a prescription
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AnalysisJobMessage {
+    pub job_id: String,
+    pub dispense_id: String,
+    /// Model to use instead of `ANALYSIS_MODEL_ID`
+    pub model_id: Option<String>,
+    pub triggered_by: String,
+    pub triggered_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisJobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Tracked analysis job, keyed by `job_id`
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AnalysisJob {
+    pub job_id: String,
+    pub dispense_id: String,
+    pub model_id: Option<String>,
+    pub status: AnalysisJobStatus,
+    pub triggered_by: String,
+    pub triggered_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+impl AnalysisJob {
+    pub fn queued(message: &AnalysisJobMessage) -> Self {
+        Self {
+            job_id: message.job_id.clone(),
+            dispense_id: message.dispense_id.clone(),
+            model_id: message.model_id.clone(),
+            status: AnalysisJobStatus::Queued,
+            triggered_by: message.triggered_by.clone(),
+            triggered_at: message.triggered_at,
+            updated_at: message.triggered_at,
+            error: None,
+        }
+    }
+}
+
+pub struct AnalysisJobStore {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl AnalysisJobStore {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    pub async fn create(&self, job: &AnalysisJob) -> Result<(), PersistenceError> {
+        let item =
+            serde_dynamo::to_item(job).map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, job_id: &str) -> Result<Option<AnalysisJob>, PersistenceError> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("job_id", AttributeValue::S(job_id.to_string()))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        output
+            .item
+            .map(serde_dynamo::from_item)
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    pub async fn set_status(
+        &self,
+        job_id: &str,
+        status: AnalysisJobStatus,
+        error: Option<String>,
+    ) -> Result<(), PersistenceError> {
+        let status = serde_dynamo::to_attribute_value(status)
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+        let updated_at = serde_dynamo::to_attribute_value(Utc::now())
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+        let error = match error {
+            Some(error) => AttributeValue::S(error),
+            None => AttributeValue::Null(true),
+        };
+
+        self.client
+            .update_item()
+            .table_name(&self.table)
+            .key("job_id", AttributeValue::S(job_id.to_string()))
+            .update_expression("SET #status = :status, #error = :error, updated_at = :updated_at")
+            .expression_attribute_names("#status", "status")
+            .expression_attribute_names("#error", "error")
+            .expression_attribute_values(":status", status)
+            .expression_attribute_values(":error", error)
+            .expression_attribute_values(":updated_at", updated_at)
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
@@ -1,38 +1,309 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use super::aggregate::DrugItem;
+use super::aggregate::{DrugItem, FulfillmentMethod, Priority};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Command {
     /// Start a new dispense workflow
     StartDispense {
         id: String,
+        /// Set when the dispense is split from another one
+        original_dispense_id: Option<String>,
+        pharmacy_branch_id: Option<String>,
+        priority: Priority,
+        notes: Option<String>,
     },
 
     /// Upload prescription document
     UploadPrescription {
         prescription_id: String,
         url: String,
+        /// Size of the uploaded file, when known
+        file_size_bytes: Option<u64>,
+    },
+
+    /// Preview image of the prescription (set by the analyzer)
+    SetPrescriptionThumbnail {
+        thumbnail_url: String,
     },
 
     /// Analyze prescription (triggered by projector)
     AnalyzePrescription {
         analysis_data: String, // JSON
+        model_id: String,
+        page_count: Option<u32>,
     },
 
+    /// Discard the current analysis before re-analyzing the prescription
+    ResetAnalysis,
+
     /// Add patient information
     AddPatient {
         patient_id: String,
         name: String,
     },
 
+    /// Set the prescriber (license validated against the state database,
+    /// controlled substance authorization checked for controlled drugs)
+    SetPrescriber {
+        prescriber_id: Option<String>,
+        license_number: String,
+        state: String,
+    },
+
     /// Add drugs to dispense
     AddDrugs {
         drugs: Vec<DrugItem>,
     },
 
+    /// Choose in-store pickup or mail order (dispense must be ready)
+    SetFulfillmentMethod {
+        method: FulfillmentMethod,
+    },
+
+    /// Record that a mail-order dispense was shipped
+    RecordShipment {
+        tracking_number: String,
+        shipped_at: DateTime<Utc>,
+        carrier: String,
+    },
+
+    /// Record that a shipped dispense was delivered
+    RecordDelivery {
+        delivered_at: DateTime<Utc>,
+    },
+
     /// Mark dispense as complete
     CompleteDispense,
 
+    /// Pharmacist acknowledgment of a multi-page prescription
+    ApproveMultiPagePrescription { approved_by: String },
+
+    /// Pharmacist review of a flagged prescription (low-confidence analysis or controlled drugs)
+    MarkPharmacistReviewed {
+        reviewed_by: String,
+        notes: Option<String>,
+    },
+
+    /// Move drugs (by `drug_id`) to a new dispense (dispense must be ready)
+    SplitDispense {
+        new_dispense_id: String,
+        drugs_to_split: Vec<String>,
+    },
+
+    /// Take over the drugs of another pending or ready dispense for the same patient
+    MergeDispense {
+        source_dispense_id: String,
+        merged_drugs: Vec<DrugItem>,
+    },
+
     /// Cancel the dispense
-    CancelDispense,
+    CancelDispense {
+        reason: Option<String>,
+    },
+}
+
+impl Command {
+    /// Variant name, used as a metric label
+    pub fn command_type(&self) -> &'static str {
+        match self {
+            Command::StartDispense { .. } => "StartDispense",
+            Command::UploadPrescription { .. } => "UploadPrescription",
+            Command::SetPrescriptionThumbnail { .. } => "SetPrescriptionThumbnail",
+            Command::AnalyzePrescription { .. } => "AnalyzePrescription",
+            Command::ResetAnalysis => "ResetAnalysis",
+            Command::AddPatient { .. } => "AddPatient",
+            Command::SetPrescriber { .. } => "SetPrescriber",
+            Command::AddDrugs { .. } => "AddDrugs",
+            Command::SetFulfillmentMethod { .. } => "SetFulfillmentMethod",
+            Command::RecordShipment { .. } => "RecordShipment",
+            Command::RecordDelivery { .. } => "RecordDelivery",
+            Command::CompleteDispense => "CompleteDispense",
+            Command::ApproveMultiPagePrescription { .. } => "ApproveMultiPagePrescription",
+            Command::MarkPharmacistReviewed { .. } => "MarkPharmacistReviewed",
+            Command::SplitDispense { .. } => "SplitDispense",
+            Command::MergeDispense { .. } => "MergeDispense",
+            Command::CancelDispense { .. } => "CancelDispense",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        dispenses::{
+            aggregate::{Address, Dispense, DispenseStatus, SYSTEM_COMMANDS},
+            testing::{field_changes, DispenseTestHarness},
+        },
+        Error,
+    };
+
+    /// One command of each variant
+    fn all_commands() -> Vec<Command> {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+
+        vec![
+            Command::StartDispense {
+                id: "test-dispense".to_string(),
+                original_dispense_id: Some("original-dispense".to_string()),
+                pharmacy_branch_id: Some("pharmacy-1".to_string()),
+                priority: Priority::Urgent,
+                notes: Some("Call before pickup".to_string()),
+            },
+            Command::UploadPrescription {
+                prescription_id: "test-prescription".to_string(),
+                url: "s3://prescriptions/test-dispense/prescription.jpg".to_string(),
+                file_size_bytes: Some(1024),
+            },
+            Command::SetPrescriptionThumbnail {
+                thumbnail_url: "s3://prescriptions/test-dispense/thumbnail.jpg".to_string(),
+            },
+            Command::AnalyzePrescription {
+                analysis_data: r#"{"confidence_score":0.95}"#.to_string(),
+                model_id: "test-model".to_string(),
+                page_count: Some(2),
+            },
+            Command::ResetAnalysis,
+            Command::AddPatient {
+                patient_id: "test-patient".to_string(),
+                name: "Test Patient".to_string(),
+            },
+            Command::SetPrescriber {
+                prescriber_id: Some("test-prescriber".to_string()),
+                license_number: "CA123456".to_string(),
+                state: "CA".to_string(),
+            },
+            Command::AddDrugs {
+                drugs: vec![DrugItem::test_item(1), DrugItem::test_item(2)],
+            },
+            Command::SetFulfillmentMethod {
+                method: FulfillmentMethod::MailOrder {
+                    address: Address {
+                        street: "1 Main St".to_string(),
+                        city: "Springfield".to_string(),
+                        state: "CA".to_string(),
+                        zip: "90001".to_string(),
+                        country: "US".to_string(),
+                    },
+                    tracking_number: None,
+                },
+            },
+            Command::RecordShipment {
+                tracking_number: "1Z999".to_string(),
+                shipped_at: at,
+                carrier: "UPS".to_string(),
+            },
+            Command::RecordDelivery { delivered_at: at },
+            Command::CompleteDispense,
+            Command::ApproveMultiPagePrescription {
+                approved_by: "pharmacist-1".to_string(),
+            },
+            Command::MarkPharmacistReviewed {
+                reviewed_by: "pharmacist-1".to_string(),
+                notes: Some("Dosage confirmed".to_string()),
+            },
+            Command::SplitDispense {
+                new_dispense_id: "new-dispense".to_string(),
+                drugs_to_split: vec!["drug-2".to_string()],
+            },
+            Command::MergeDispense {
+                source_dispense_id: "source-dispense".to_string(),
+                merged_drugs: vec![DrugItem::test_item(3)],
+            },
+            Command::CancelDispense {
+                reason: Some("Patient request".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn all_commands_covers_every_variant() {
+        let command_types: HashSet<&str> =
+            all_commands().iter().map(Command::command_type).collect();
+
+        assert_eq!(command_types.len(), all_commands().len());
+        assert_eq!(command_types.len(), 17);
+    }
+
+    #[test]
+    fn handle_accepts_exactly_the_allowed_commands_of_each_status() {
+        let statuses = [
+            DispenseStatus::Pending,
+            DispenseStatus::Analyzing,
+            DispenseStatus::Ready,
+            DispenseStatus::Complete,
+            DispenseStatus::Cancelled,
+        ];
+
+        for status in statuses {
+            for command in all_commands() {
+                let command_type = command.command_type();
+                if SYSTEM_COMMANDS.contains(&command_type) {
+                    continue;
+                }
+                let rejected = Error::Validation {
+                    message: format!("{} is not allowed in status {:?}", command_type, status),
+                };
+
+                let outcome =
+                    DispenseTestHarness::given_dispense(Dispense::test_dispense(status.clone()))
+                        .when(command);
+                let result = outcome.result();
+
+                // Allowed commands may still fail on their own rules, never on the status
+                let allowed = status.allowed_commands().contains(&command_type);
+                assert_eq!(
+                    !matches!(&result, Err(e) if e.to_string() == rejected.to_string()),
+                    allowed,
+                    "{} in status {:?}: {:?}",
+                    command_type,
+                    status,
+                    result
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clone_is_equal() {
+        for command in all_commands() {
+            assert_eq!(command.clone(), command);
+        }
+    }
+
+    #[test]
+    fn variants_differ() {
+        let commands = all_commands();
+        for (i, command) in commands.iter().enumerate() {
+            for other in &commands[i + 1..] {
+                assert_ne!(command, other);
+            }
+        }
+    }
+
+    #[test]
+    fn field_change_is_not_equal() {
+        for command in all_commands() {
+            for (field, changed) in field_changes(&command) {
+                assert_ne!(changed, command, "{}", field);
+            }
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for command in all_commands() {
+            let json = serde_json::to_string(&command).unwrap();
+
+            assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn json_is_stable() {
+        insta::assert_json_snapshot!(all_commands());
+    }
 }
@@ -1,36 +1,752 @@
-use std::{env, sync::Arc};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    env,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+use aws_sdk_dynamodb::{
+    config::{BehaviorVersion, Credentials, Region},
+    error::{ProvideErrorMetadata, SdkError},
+    operation::{query::QueryError, transact_write_items::TransactWriteItemsError},
+};
 use cqrs_es::{
-    persist::{PersistedEventStore, ViewRepository},
-    CqrsFramework,
+    persist::ViewRepository, Aggregate, AggregateContext, AggregateError, CqrsFramework, EventStore,
+};
+use ulid::Ulid;
+use super::{
+    analysis_jobs::AnalysisJobStore, export::ExportLock, services::DefaultServices,
+    snapshots::SnapshotInspector, timeline::DEFAULT_PHARMACY_ID,
+    transactional_view::TransactionalViewRepository, Command, Dispense, DispenseEventStore, Event,
+    FormularyService, PatientHistoryQuery, PatientLastDispenseStore, Priority, Query,
+    SchedulerService, Services, SnapshotStrategy, TimelineIndexQuery, View, ViewScanner,
+    AGGREGATE_TYPE,
 };
-use dynamo_es::{DynamoEventRepository, DynamoViewRepository};
-use super::{Dispense, Query, Services, View};
+use crate::{
+    config::Config,
+    prescribers::{self, PrescriberService},
+    view_cache::CachingViewRepository,
+    Error,
+};
+
+pub type DispenseCqrs = RetryingCqrsFramework<Dispense, DispenseEventStore>;
+
+/// Framework and dispenses view repository returned by `init_local`
+pub type LocalDispenseCqrs = (
+    Arc<DispenseCqrs>,
+    Arc<Box<dyn ViewRepository<View, Dispense>>>,
+);
+
+/// Retries of a command failing on a transient event store error
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Executions of the command, the first one included
+    pub max_attempts: usize,
+    /// Backoff before the first retry, doubled on each following one
+    pub base_delay_ms: u64,
+    /// Wait a random time between half and all of the backoff
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 50,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Single execution
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The default policy with `CQRS_RETRY_ENABLED=true`, no retry otherwise
+    pub fn from_env() -> Self {
+        match env::var("CQRS_RETRY_ENABLED").as_deref() {
+            Ok("true") => Self::default(),
+            _ => Self::none(),
+        }
+    }
+
+    /// Backoff before the retry following attempt `attempt` (from 1)
+    fn delay(&self, attempt: usize) -> Duration {
+        let delay_ms = self.base_delay_ms << (attempt - 1).min(16);
+        if !self.jitter || delay_ms < 2 {
+            return Duration::from_millis(delay_ms);
+        }
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(delay_ms / 2 + random % (delay_ms / 2 + 1))
+    }
+}
+
+/// `CqrsFramework` executing the commands again on transient event store errors (e.g. DynamoDB
+/// throttling), rejections by the aggregate and conflicts being returned at once
+///
+/// Each attempt handles the command again, repeating the calls of `Dispense::handle` to its
+/// services: these must be idempotent, like the expiry schedule of a dispense (moved to the new
+/// deadline when it exists) and its cancellation.
+pub struct RetryingCqrsFramework<A, ES>
+where
+    A: Aggregate,
+    ES: EventStore<A>,
+{
+    inner: CqrsFramework<A, ES>,
+    policy: RetryPolicy,
+}
+
+impl<A, ES> RetryingCqrsFramework<A, ES>
+where
+    A: Aggregate,
+    A::Command: Clone,
+    ES: EventStore<A>,
+{
+    pub fn new(inner: CqrsFramework<A, ES>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub async fn execute(
+        &self,
+        aggregate_id: &str,
+        command: A::Command,
+    ) -> Result<(), AggregateError<A::Error>> {
+        self.execute_with_metadata(aggregate_id, command, HashMap::new())
+            .await
+    }
 
-pub fn init(
+    /// `CqrsFramework::execute_with_metadata`, the retries counted in the `retry_attempts` span
+    /// field
+    #[tracing::instrument(skip(self, command, metadata), fields(retry_attempts = 0))]
+    pub async fn execute_with_metadata(
+        &self,
+        aggregate_id: &str,
+        command: A::Command,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), AggregateError<A::Error>> {
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .inner
+                .execute_with_metadata(aggregate_id, command.clone(), metadata.clone())
+                .await;
+            match result {
+                Err(error) if attempt < self.policy.max_attempts && is_transient(&error) => {
+                    tracing::warn!("Attempt {} of the command failed: {}", attempt, error);
+                    tokio::time::sleep(self.policy.delay(attempt)).await;
+                    tracing::Span::current().record("retry_attempts", attempt);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// DynamoDB error codes of a request rejected for its rate
+const THROTTLING_ERROR_CODES: [&str; 3] = [
+    "ThrottlingException",
+    "ProvisionedThroughputExceededException",
+    "RequestLimitExceeded",
+];
+
+/// Cancellation reasons of a transaction rejected for its rate
+const THROTTLING_CANCELLATION_CODES: [&str; 2] =
+    ["ThrottlingError", "ProvisionedThroughputExceeded"];
+
+/// Connection errors, and the SDK errors of throttled or undelivered requests, which
+/// `dynamo-es` reports as unexpected errors: `Query` when loading, `TransactWriteItems` when
+/// committing
+///
+/// A commit timing out may have been written, and its command executed again would append its
+/// events twice or be rejected by the new state, so only a load timeout is retried.
+fn is_transient<E: std::error::Error>(error: &AggregateError<E>) -> bool {
+    match error {
+        AggregateError::DatabaseConnectionError(_) => true,
+        AggregateError::UnexpectedError(error) => {
+            if let Some(error) = error.downcast_ref::<SdkError<QueryError>>() {
+                matches!(error, SdkError::TimeoutError(_)) || is_throttled(error)
+            } else if let Some(error) = error.downcast_ref::<SdkError<TransactWriteItemsError>>() {
+                match error.as_service_error() {
+                    Some(TransactWriteItemsError::TransactionCanceledException(cancellation)) => {
+                        cancellation.cancellation_reasons().iter().any(|reason| {
+                            reason
+                                .code()
+                                .is_some_and(|code| THROTTLING_CANCELLATION_CODES.contains(&code))
+                        })
+                    }
+                    _ => is_throttled(error),
+                }
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Throttled, or not sent
+fn is_throttled<E: ProvideErrorMetadata>(error: &SdkError<E>) -> bool {
+    match error {
+        SdkError::DispatchFailure(_) => true,
+        error => error
+            .code()
+            .is_some_and(|code| THROTTLING_ERROR_CODES.contains(&code)),
+    }
+}
+
+/// Snapshot strategy, the queries dispatched for every committed event, the optional expiry
+/// scheduler and the retry policy of the commands
+pub struct DispenseCqrsConfig {
+    pub snapshot_strategy: SnapshotStrategy,
+    pub queries: Vec<Box<dyn cqrs_es::Query<Dispense>>>,
+    pub scheduler: Option<SchedulerService>,
+    pub retry_policy: RetryPolicy,
+}
+
+impl Default for DispenseCqrsConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_strategy: SnapshotStrategy::default(),
+            queries: Vec::new(),
+            scheduler: None,
+            retry_policy: RetryPolicy::from_env(),
+        }
+    }
+}
+
+/// Full framework (event store, queries and services) for Lambdas executing commands,
+/// see `init_read_only` for those only reading dispenses
+pub fn init(client: aws_sdk_dynamodb::Client, config: DispenseCqrsConfig) -> Arc<DispenseCqrs> {
+    let store = DispenseEventStore::new(client.clone(), config.snapshot_strategy);
+    let services = match config.scheduler {
+        Some(scheduler) => services(client).with_scheduler(scheduler),
+        None => services(client),
+    };
+
+    Arc::new(RetryingCqrsFramework::new(
+        CqrsFramework::new(store, config.queries, services),
+        config.retry_policy,
+    ))
+}
+
+fn services(client: aws_sdk_dynamodb::Client) -> Services {
+    let mut dispensing = DefaultServices::default().with_prescribers(PrescriberService::new(
+        prescribers::cqrs::init_repo(client.clone()),
+    ));
+    if let Some(formulary) = FormularyService::from_env(client) {
+        dispensing = dispensing.with_formulary(formulary);
+    }
+
+    Services::new(dispensing)
+}
+
+/// Start a dispense under a new ULID, returned once `DispenseStarted` is committed
+///
+/// `pharmacy_id` is added to the metadata (and a `command_id` when missing); the default pharmacy
+/// starts a dispense without `pharmacy_branch_id`.
+pub async fn create_dispense_with_id<ES: EventStore<Dispense>>(
+    cqrs: &RetryingCqrsFramework<Dispense, ES>,
+    pharmacy_id: &str,
+    priority: Priority,
+    notes: Option<String>,
+    mut command_metadata: HashMap<String, String>,
+) -> Result<String, AggregateError<Error>> {
+    let aggregate_id = Ulid::new().to_string();
+
+    command_metadata
+        .entry("command_id".to_string())
+        .or_insert_with(|| Ulid::new().to_string());
+    command_metadata.insert("pharmacy_id".to_string(), pharmacy_id.to_string());
+
+    let command = Command::StartDispense {
+        id: aggregate_id.clone(),
+        original_dispense_id: None,
+        pharmacy_branch_id: (pharmacy_id != DEFAULT_PHARMACY_ID).then(|| pharmacy_id.to_string()),
+        priority,
+        notes,
+    };
+
+    cqrs.execute_with_metadata(&aggregate_id, command, command_metadata)
+        .await?;
+
+    Ok(aggregate_id)
+}
+
+/// Handles commands against the stored aggregate without committing the events, nor
+/// scheduling expiries, nor recording command metrics
+pub struct DispenseDryRun {
+    store: DispenseEventStore,
+    services: Services,
+}
+
+impl DispenseDryRun {
+    /// Events the command would emit, neither applied nor stored
+    pub async fn handle(
+        &self,
+        aggregate_id: &str,
+        command: Command,
+    ) -> Result<Vec<Event>, AggregateError<Error>> {
+        let context = self.store.load_aggregate(aggregate_id).await?;
+
+        context
+            .aggregate()
+            .handle_command(command, &self.services)
+            .await
+            .map_err(AggregateError::UserError)
+    }
+
+    /// Events of the commands handled in order, each on the aggregate with the events of the
+    /// previous ones applied, e.g. to check the commands building a new dispense
+    pub async fn handle_all(
+        &self,
+        aggregate_id: &str,
+        commands: Vec<Command>,
+    ) -> Result<Vec<Event>, AggregateError<Error>> {
+        let context = self.store.load_aggregate(aggregate_id).await?;
+        let mut aggregate = context.aggregate().clone();
+
+        let mut events = Vec::new();
+        for command in commands {
+            let emitted = aggregate
+                .handle_command(command, &self.services)
+                .await
+                .map_err(AggregateError::UserError)?;
+            for event in &emitted {
+                aggregate.apply(event.clone());
+            }
+            events.extend(emitted);
+        }
+
+        Ok(events)
+    }
+}
+
+/// Chains the projections to register before building the framework
+pub struct DispenseCqrsBuilder {
     client: aws_sdk_dynamodb::Client,
+    config: DispenseCqrsConfig,
+}
+
+impl DispenseCqrsBuilder {
+    pub fn new(client: aws_sdk_dynamodb::Client) -> Self {
+        Self {
+            client,
+            config: DispenseCqrsConfig::default(),
+        }
+    }
+
+    pub fn snapshot_strategy(mut self, snapshot_strategy: SnapshotStrategy) -> Self {
+        self.config.snapshot_strategy = snapshot_strategy;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = retry_policy;
+        self
+    }
+
+    /// Dispenses view, with its DynamoDB TTL attribute and rebuilt from the event log once
+    /// expired
+    pub fn with_view_query(self, repo: Arc<Box<dyn ViewRepository<View, Dispense>>>) -> Self {
+        let query = Query::new(repo)
+            .with_ttl(self.client.clone(), &dispenses_view_table())
+            .with_event_store(DispenseEventStore::new(
+                self.client.clone(),
+                SnapshotStrategy::default(),
+            ));
+        self.with_query(query)
+    }
+
+    /// Per-pharmacy timeline index
+    pub fn with_timeline_query(self) -> Self {
+        let query = TimelineIndexQuery::new(self.client.clone(), &timeline_index_table());
+        self.with_query(query)
+    }
+
+    /// Per-patient history, registered after the view query it reads from
+    pub fn with_patient_history_query(
+        self,
+        repo: Arc<Box<dyn ViewRepository<View, Dispense>>>,
+    ) -> Self {
+        let query = PatientHistoryQuery::new(self.client.clone(), &patient_history_table(), repo);
+        self.with_query(query)
+    }
+
+    /// Schedule the expiry of started dispenses, see `SchedulerService::from_env`
+    pub fn with_scheduler(mut self, scheduler: Option<SchedulerService>) -> Self {
+        self.config.scheduler = scheduler;
+        self
+    }
+
+    /// Any other projection
+    pub fn with_query(mut self, query: impl cqrs_es::Query<Dispense> + 'static) -> Self {
+        self.config.queries.push(Box::new(query));
+        self
+    }
+
+    pub fn build(self) -> Arc<DispenseCqrs> {
+        init(self.client, self.config)
+    }
+
+    /// Registered queries without the framework, used to replay stored events
+    pub fn into_queries(self) -> Vec<Box<dyn cqrs_es::Query<Dispense>>> {
+        self.config.queries
+    }
+}
+
+/// Dispenses view without write capability
+pub struct ReadOnlyDispenseRepo {
     repo: Arc<Box<dyn ViewRepository<View, Dispense>>>,
-) -> Arc<CqrsFramework<Dispense, PersistedEventStore<DynamoEventRepository, Dispense>>> {
-    let event_log_table = env::var("DYNAMODB_EVENT_LOG_TABLE")
-        .unwrap_or("dispensary-event-log".to_string());
-
-    let event_snapshots_table = env::var("DYNAMODB_EVENT_SNAPSHOTS_TABLE")
-        .unwrap_or("dispensary-event-snapshots".to_string());
-
-    let store: PersistedEventStore<DynamoEventRepository, Dispense> =
-        PersistedEventStore::new_snapshot_store(
-            DynamoEventRepository::new(client)
-                .with_tables(&event_log_table, &event_snapshots_table),
-            5,
-        );
+}
 
-    let query = Box::new(Query::new(repo));
+impl ReadOnlyDispenseRepo {
+    pub async fn get(&self, id: &str) -> Result<Option<View>, AggregateError<Error>> {
+        Ok(self.repo.load(id).await?)
+    }
 
-    Arc::new(CqrsFramework::new(store, vec![query], Services::default()))
+    /// Like `get`, with `Error::NotFound` for an unknown dispense
+    pub async fn get_required(&self, id: &str) -> Result<View, AggregateError<Error>> {
+        self.get(id)
+            .await?
+            .ok_or(AggregateError::UserError(Error::NotFound {
+                entity: AGGREGATE_TYPE.to_string(),
+            }))
+    }
+}
+
+/// Dispenses view only, for query-only Lambdas (e.g. reporting): no event store, queries or
+/// services are set up, so nothing can be written
+pub fn init_read_only(client: aws_sdk_dynamodb::Client) -> ReadOnlyDispenseRepo {
+    ReadOnlyDispenseRepo {
+        repo: init_repo(client),
+    }
 }
 
 pub fn init_repo(client: aws_sdk_dynamodb::Client) -> Arc<Box<dyn ViewRepository<View, Dispense>>> {
-    let view_table = env::var("DYNAMODB_DISPENSES_VIEW_TABLE")
-        .unwrap_or("dispensary-dispenses-view".to_string());
+    Arc::new(Box::new(TransactionalViewRepository::new(
+        client,
+        &dispenses_view_table(),
+    )))
+}
+
+/// Like `init_repo`, cached in memory when `VIEW_CACHE_ENABLED` is set, see
+/// `CachingViewRepository`
+pub fn init_cached_repo(
+    client: aws_sdk_dynamodb::Client,
+) -> Arc<Box<dyn ViewRepository<View, Dispense>>> {
+    Arc::new(CachingViewRepository::from_env(Box::new(
+        TransactionalViewRepository::new(client, &dispenses_view_table()),
+    )))
+}
+
+/// Framework and dispenses view on a local DynamoDB (e.g. DynamoDB Local), for development and
+/// integration tests without AWS
+///
+/// The tables must exist under the configured names, see `Config`.
+pub fn init_local(endpoint_url: &str) -> LocalDispenseCqrs {
+    let client = local_client(endpoint_url);
+    let repo = init_repo(client.clone());
+    let cqrs = DispenseCqrsBuilder::new(client)
+        .with_view_query(repo.clone())
+        .build();
+
+    (cqrs, repo)
+}
+
+/// DynamoDB client of a local endpoint, with static credentials it does not check
+pub fn local_client(endpoint_url: &str) -> aws_sdk_dynamodb::Client {
+    let config = aws_sdk_dynamodb::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("local", "local", None, None, "local"))
+        .endpoint_url(endpoint_url)
+        .build();
+
+    aws_sdk_dynamodb::Client::from_conf(config)
+}
+
+pub fn init_view_scanner(client: aws_sdk_dynamodb::Client) -> Arc<ViewScanner> {
+    Arc::new(ViewScanner::new(client, &dispenses_view_table()))
+}
+
+pub fn init_timeline(client: aws_sdk_dynamodb::Client) -> Arc<TimelineIndexQuery> {
+    Arc::new(TimelineIndexQuery::new(client, &timeline_index_table()))
+}
+
+pub fn init_patient_history(client: aws_sdk_dynamodb::Client) -> Arc<PatientHistoryQuery> {
+    Arc::new(PatientHistoryQuery::new(
+        client.clone(),
+        &patient_history_table(),
+        init_repo(client),
+    ))
+}
+
+pub fn init_patient_last_dispense(
+    client: aws_sdk_dynamodb::Client,
+) -> Arc<PatientLastDispenseStore> {
+    Arc::new(PatientLastDispenseStore::new(
+        client,
+        &Config::current().patient_last_dispense_table,
+    ))
+}
+
+pub fn init_export_lock(client: aws_sdk_dynamodb::Client) -> Arc<ExportLock> {
+    Arc::new(ExportLock::new(client, &Config::current().export_locks_table))
+}
+
+pub fn init_analysis_jobs(client: aws_sdk_dynamodb::Client) -> Arc<AnalysisJobStore> {
+    Arc::new(AnalysisJobStore::new(client, &Config::current().analysis_jobs_table))
+}
+
+pub fn init_dry_run(client: aws_sdk_dynamodb::Client) -> Arc<DispenseDryRun> {
+    Arc::new(DispenseDryRun {
+        store: DispenseEventStore::new(client.clone(), SnapshotStrategy::default()),
+        services: services(client),
+    })
+}
+
+/// Event store read for the current version of a dispense, see
+/// `DispenseEventStore::current_version`
+pub fn init_event_store(client: aws_sdk_dynamodb::Client) -> Arc<DispenseEventStore> {
+    Arc::new(DispenseEventStore::new(client, SnapshotStrategy::default()))
+}
+
+pub fn init_snapshot_inspector(client: aws_sdk_dynamodb::Client) -> Arc<SnapshotInspector> {
+    Arc::new(SnapshotInspector::new(client, &event_snapshots_table()))
+}
+
+fn event_snapshots_table() -> String {
+    Config::current().snapshots_table
+}
+
+fn dispenses_view_table() -> String {
+    Config::current().view_table
+}
+
+fn timeline_index_table() -> String {
+    Config::current().timeline_index_table
+}
+
+fn patient_history_table() -> String {
+    Config::current().patient_history_table
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use cqrs_es::{
+        mem_store::{MemStore, MemStoreAggregateContext},
+        EventEnvelope,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::dispenses::services::MockServices;
+
+    /// In-memory store whose first `failures` commits fail on a lost connection
+    struct FlakyStore {
+        inner: MemStore<Dispense>,
+        failures: usize,
+        commits: Arc<AtomicUsize>,
+    }
+
+    impl FlakyStore {
+        fn new(failures: usize, commits: Arc<AtomicUsize>) -> Self {
+            Self {
+                inner: MemStore::default(),
+                failures,
+                commits,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl EventStore<Dispense> for FlakyStore {
+        type AC = MemStoreAggregateContext<Dispense>;
+
+        async fn load_events(
+            &self,
+            aggregate_id: &str,
+        ) -> Result<Vec<EventEnvelope<Dispense>>, AggregateError<Error>> {
+            self.inner.load_events(aggregate_id).await
+        }
+
+        async fn load_aggregate(
+            &self,
+            aggregate_id: &str,
+        ) -> Result<Self::AC, AggregateError<Error>> {
+            self.inner.load_aggregate(aggregate_id).await
+        }
+
+        async fn commit(
+            &self,
+            events: Vec<Event>,
+            context: Self::AC,
+            metadata: HashMap<String, String>,
+        ) -> Result<Vec<EventEnvelope<Dispense>>, AggregateError<Error>> {
+            if self.commits.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(AggregateError::DatabaseConnectionError(
+                    "connection reset".into(),
+                ));
+            }
+            self.inner.commit(events, context, metadata).await
+        }
+    }
+
+    fn framework(store: FlakyStore) -> RetryingCqrsFramework<Dispense, FlakyStore> {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 1,
+            jitter: false,
+        };
+        let services = Services::new(MockServices::new());
+
+        RetryingCqrsFramework::new(CqrsFramework::new(store, vec![], services), policy)
+    }
+
+    fn start_dispense() -> Command {
+        Command::StartDispense {
+            id: "test-dispense".to_string(),
+            original_dispense_id: None,
+            pharmacy_branch_id: None,
+            priority: Priority::default(),
+            notes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn command_retried_after_transient_failures() {
+        let commits = Arc::new(AtomicUsize::new(0));
+        let cqrs = framework(FlakyStore::new(2, commits.clone()));
+
+        cqrs.execute("test-dispense", start_dispense())
+            .await
+            .unwrap();
+
+        assert_eq!(commits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn command_fails_after_max_attempts() {
+        let commits = Arc::new(AtomicUsize::new(0));
+        let cqrs = framework(FlakyStore::new(3, commits.clone()));
+
+        let result = cqrs.execute("test-dispense", start_dispense()).await;
+
+        assert!(matches!(
+            result,
+            Err(AggregateError::DatabaseConnectionError(_))
+        ));
+        assert_eq!(commits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn rejected_command_not_retried() {
+        let cqrs = framework(FlakyStore::new(0, Arc::default()));
+
+        let result = cqrs.execute("test-dispense", Command::ResetAnalysis).await;
+
+        assert!(matches!(
+            result,
+            Err(AggregateError::UserError(Error::NotFound { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn created_dispense_is_stored_under_the_returned_id() {
+        let store = MemStore::<Dispense>::default();
+        let cqrs = RetryingCqrsFramework::new(
+            CqrsFramework::new(store.clone(), vec![], Services::new(MockServices::new())),
+            RetryPolicy::default(),
+        );
+
+        let id = create_dispense_with_id(
+            &cqrs,
+            "pharmacy-1",
+            Priority::Urgent,
+            None,
+            HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let events = store.load_events(&id).await.unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [EventEnvelope {
+                aggregate_id,
+                payload: Event::DispenseStarted { id: started_id, pharmacy_branch_id, .. },
+                metadata,
+                ..
+            }] if *aggregate_id == id
+                && *started_id == id
+                && pharmacy_branch_id.as_deref() == Some("pharmacy-1")
+                && metadata.get("pharmacy_id").map(String::as_str) == Some("pharmacy-1")
+        ));
+
+        let duplicate = Command::StartDispense {
+            id: id.clone(),
+            original_dispense_id: None,
+            pharmacy_branch_id: None,
+            priority: Priority::default(),
+            notes: None,
+        };
+        assert!(matches!(
+            cqrs.execute(&id, duplicate).await,
+            Err(AggregateError::UserError(Error::Uniqueness { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn created_dispenses_have_distinct_ids() {
+        let cqrs = framework(FlakyStore::new(0, Arc::default()));
+        let create = || {
+            create_dispense_with_id(
+                &cqrs,
+                DEFAULT_PHARMACY_ID,
+                Priority::Routine,
+                None,
+                HashMap::new(),
+            )
+        };
+
+        let first = create().await.unwrap();
+        let second = create().await.unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn load_timeout_is_transient() {
+        let error: AggregateError<Error> =
+            AggregateError::UnexpectedError(Box::new(SdkError::<QueryError>::timeout_error(
+                "timed out",
+            )));
+
+        assert!(is_transient(&error));
+    }
+
+    #[test]
+    fn commit_timeout_is_not_transient() {
+        let error: AggregateError<Error> = AggregateError::UnexpectedError(Box::new(
+            SdkError::<TransactWriteItemsError>::timeout_error("timed out"),
+        ));
+
+        assert!(!is_transient(&error));
+    }
+
+    #[test]
+    fn sdk_construction_failure_is_not_transient() {
+        let error: AggregateError<Error> = AggregateError::UnexpectedError(Box::new(
+            SdkError::<TransactWriteItemsError>::construction_failure("missing table"),
+        ));
 
-    Arc::new(Box::new(DynamoViewRepository::new(&view_table, client)))
+        assert!(!is_transient(&error));
+    }
 }
@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{EventStoreAggregateContext, PersistedEventStore},
+    AggregateError, EventEnvelope, EventStore,
+};
+use dynamo_es::DynamoEventRepository;
+use std::collections::HashMap;
+
+use super::{upcasters, Dispense, Event};
+use crate::{config::Config, Error};
+
+/// Default number of events between aggregate snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 5;
+
+/// When the dispense event store writes aggregate snapshots
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SnapshotStrategy {
+    /// Once `n` events were committed since the last snapshot
+    EveryN(usize),
+    /// Only with the events reaching a terminal status (completed or cancelled), after which
+    /// the aggregate no longer changes
+    OnCompletion,
+    /// Every `every_n` events, and with the terminal events when `on_terminal` is set
+    Hybrid { every_n: usize, on_terminal: bool },
+}
+
+impl Default for SnapshotStrategy {
+    fn default() -> Self {
+        Self::Hybrid {
+            every_n: DEFAULT_SNAPSHOT_INTERVAL,
+            on_terminal: true,
+        }
+    }
+}
+
+impl SnapshotStrategy {
+    fn every_n(&self) -> Option<usize> {
+        match self {
+            Self::EveryN(every_n) | Self::Hybrid { every_n, .. } => Some(*every_n),
+            Self::OnCompletion => None,
+        }
+    }
+
+    fn on_terminal(&self) -> bool {
+        match self {
+            Self::EveryN(_) => false,
+            Self::OnCompletion => true,
+            Self::Hybrid { on_terminal, .. } => *on_terminal,
+        }
+    }
+}
+
+/// Dispense event store snapshotting per `SnapshotStrategy`
+///
+/// `PersistedEventStore` only snapshots every n events, so commits are routed between two
+/// stores over the same tables: one following the strategy interval (never snapshotting without
+/// one), and one snapshotting on every commit, taking the commits of terminal events. Aggregates
+/// are loaded from their latest snapshot.
+pub struct DispenseEventStore {
+    interval: PersistedEventStore<DynamoEventRepository, Dispense>,
+    every_commit: PersistedEventStore<DynamoEventRepository, Dispense>,
+    on_terminal: bool,
+}
+
+impl DispenseEventStore {
+    pub fn new(client: aws_sdk_dynamodb::Client, strategy: SnapshotStrategy) -> Self {
+        let interval = match strategy.every_n() {
+            Some(every_n) => {
+                PersistedEventStore::new_snapshot_store(repository(client.clone()), every_n)
+            }
+            None => PersistedEventStore::new_event_store(repository(client.clone())),
+        };
+        let every_commit = PersistedEventStore::new_snapshot_store(repository(client), 1);
+
+        Self {
+            interval: interval.with_upcasters(upcasters::all()),
+            every_commit: every_commit.with_upcasters(upcasters::all()),
+            on_terminal: strategy.on_terminal(),
+        }
+    }
+}
+
+impl DispenseEventStore {
+    /// Sequence number of the last committed event of the dispense, 0 for an unknown one
+    ///
+    /// Read from the event store rather than the view, which may be cached or lag behind.
+    pub async fn current_version(
+        &self,
+        aggregate_id: &str,
+    ) -> Result<usize, AggregateError<Error>> {
+        Ok(self.load_aggregate(aggregate_id).await?.current_sequence)
+    }
+}
+
+fn repository(client: aws_sdk_dynamodb::Client) -> DynamoEventRepository {
+    let config = Config::current();
+    DynamoEventRepository::new(client).with_tables(&config.event_log_table, &config.snapshots_table)
+}
+
+/// Set the `actor_id` command metadata on the events: commands are handled without their
+/// metadata, so the actor is recorded here
+fn stamp_actor(events: &mut [Event], metadata: &HashMap<String, String>) {
+    let actor_id = metadata.get("actor_id");
+    for event in events.iter_mut() {
+        event.set_actor_id(actor_id.cloned());
+    }
+}
+
+fn is_terminal(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::DispenseCompleted { .. } | Event::DispenseCancelled { .. }
+    )
+}
+
+#[async_trait]
+impl EventStore<Dispense> for DispenseEventStore {
+    type AC = EventStoreAggregateContext<Dispense>;
+
+    async fn load_events(
+        &self,
+        aggregate_id: &str,
+    ) -> Result<Vec<EventEnvelope<Dispense>>, AggregateError<Error>> {
+        self.every_commit.load_events(aggregate_id).await
+    }
+
+    async fn load_aggregate(&self, aggregate_id: &str) -> Result<Self::AC, AggregateError<Error>> {
+        self.every_commit.load_aggregate(aggregate_id).await
+    }
+
+    async fn commit(
+        &self,
+        mut events: Vec<Event>,
+        context: Self::AC,
+        metadata: HashMap<String, String>,
+    ) -> Result<Vec<EventEnvelope<Dispense>>, AggregateError<Error>> {
+        stamp_actor(&mut events, &metadata);
+
+        if self.on_terminal && events.iter().any(is_terminal) {
+            self.every_commit.commit(events, context, metadata).await
+        } else {
+            self.interval.commit(events, context, metadata).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use cqrs_es::Aggregate;
+
+    use super::*;
+    use crate::dispenses::{Dispense, DispenseStatus};
+
+    fn cancelled() -> Event {
+        Event::DispenseCancelled {
+            id: "test-dispense".to_string(),
+            reason: None,
+            updated_at: Utc::now(),
+            actor_id: None,
+        }
+    }
+
+    #[test]
+    fn actor_metadata_is_stamped_and_applied() {
+        let metadata = HashMap::from([
+            ("command_id".to_string(), "command-1".to_string()),
+            ("actor_id".to_string(), "user-1".to_string()),
+        ]);
+        let mut events = vec![cancelled()];
+
+        stamp_actor(&mut events, &metadata);
+
+        assert_eq!(events[0].actor_id(), Some("user-1"));
+
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        for event in events {
+            dispense.apply(event);
+        }
+        assert_eq!(dispense.updated_by.as_deref(), Some("user-1"));
+    }
+
+    #[test]
+    fn event_without_actor_keeps_last_actor() {
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.updated_by = Some("user-1".to_string());
+        let mut events = vec![cancelled()];
+
+        stamp_actor(&mut events, &HashMap::new());
+        for event in events {
+            dispense.apply(event);
+        }
+
+        assert_eq!(dispense.updated_by.as_deref(), Some("user-1"));
+    }
+}
@@ -1,15 +1,40 @@
 use chrono::{DateTime, Utc};
-use cqrs_es::DomainEvent;
+use cqrs_es::{persist::SerializedEvent, DomainEvent};
 use serde::{Deserialize, Serialize};
-use super::aggregate::{DispenseStatus, DrugItem};
+use super::aggregate::{
+    DispenseStatus, DrugInteraction, DrugItem, FulfillmentMethod, Priority, AGGREGATE_TYPE,
+};
+use super::upcasters;
 
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+/// `PrescriptionAnalyzed` gained `analysis_version` and `model_id` in 1.1,
+/// `quality_score` in 1.2
+pub const PRESCRIPTION_ANALYZED_VERSION: &str = "1.2";
+
+/// Version of the event types never changed since their introduction
+const DEFAULT_EVENT_VERSION: &str = "1.0";
+
+/// `actor_id` is stamped by `DispenseEventStore` from the `actor_id` command metadata,
+/// commands being handled without it
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
 pub enum Event {
     DispenseStarted {
         id: String,
         created_at: DateTime<Utc>,
         status: DispenseStatus,
+        #[serde(default)]
+        original_dispense_id: Option<String>,
+        #[serde(default)]
+        pharmacy_branch_id: Option<String>,
+        #[serde(default)]
+        priority: Priority,
+        #[serde(default)]
+        notes: Option<String>,
+        /// Scheduled automatic cancellation
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     PrescriptionUploaded {
@@ -17,12 +42,76 @@ pub enum Event {
         prescription_id: String,
         url: String,
         updated_at: DateTime<Utc>,
+        /// Deadline extended by the upload
+        #[serde(default)]
+        expires_at: Option<DateTime<Utc>>,
+        #[serde(default)]
+        file_size_bytes: Option<u64>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    PrescriptionThumbnailSet {
+        id: String,
+        thumbnail_url: String,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     PrescriptionAnalyzed {
         id: String,
         analysis_data: String, // JSON with extracted info
+        analysis_version: u32,
+        model_id: String,
+        quality_score: f32, // 0.0–1.0
+        prescription_page_count: Option<u32>,
         updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    /// Multi-page prescriptions need pharmacist review before completion
+    MultiPagePrescriptionDetected {
+        id: String,
+        page_count: u32,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    MultiPagePrescriptionApproved {
+        id: String,
+        approved_by: String,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    PharmacistReviewCompleted {
+        id: String,
+        reviewed_by: String,
+        notes: Option<String>,
+        reviewed_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    AnalysisReset {
+        id: String,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    /// Analysis rejected for a quality score below the threshold
+    PrescriptionQualityFailed {
+        id: String,
+        quality_score: f32,
+        min_score: f32,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     PatientAdded {
@@ -30,22 +119,98 @@ pub enum Event {
         patient_id: String,
         patient_name: String,
         updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    PrescriberSet {
+        id: String,
+        prescriber_id: Option<String>,
+        license_number: String,
+        state: String,
+        prescriber_name: Option<String>,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     DrugsAdded {
         id: String,
         drugs: Vec<DrugItem>,
         updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    FulfillmentMethodSet {
+        id: String,
+        method: FulfillmentMethod,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    DispenseShipped {
+        id: String,
+        tracking_number: String,
+        shipped_at: DateTime<Utc>,
+        carrier: String,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    DispenseDelivered {
+        id: String,
+        delivered_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    DrugInteractionWarning {
+        id: String,
+        interactions: Vec<DrugInteraction>,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    /// Drugs moved to the dispense `new_dispense_id`
+    DispenseSplit {
+        id: String,
+        new_dispense_id: String,
+        moved_drug_ids: Vec<String>,
+        retained_drug_ids: Vec<String>,
+        split_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
+    },
+
+    /// Drugs of the cancelled dispense `source_dispense_id` added to this one
+    DispenseMerged {
+        id: String,
+        source_dispense_id: String,
+        additional_drugs: Vec<DrugItem>,
+        updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     DispenseCompleted {
         id: String,
         updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 
     DispenseCancelled {
         id: String,
+        #[serde(default)]
+        reason: Option<String>,
         updated_at: DateTime<Utc>,
+        #[serde(default)]
+        actor_id: Option<String>,
     },
 }
 
@@ -54,15 +219,529 @@ impl DomainEvent for Event {
         match self {
             Event::DispenseStarted { .. } => "Dispense:Started".to_string(),
             Event::PrescriptionUploaded { .. } => "Dispense:PrescriptionUploaded".to_string(),
+            Event::PrescriptionThumbnailSet { .. } => {
+                "Dispense:PrescriptionThumbnailSet".to_string()
+            }
             Event::PrescriptionAnalyzed { .. } => "Dispense:PrescriptionAnalyzed".to_string(),
+            Event::PharmacistReviewCompleted { .. } => {
+                "Dispense:PharmacistReviewCompleted".to_string()
+            }
+            Event::AnalysisReset { .. } => "Dispense:AnalysisReset".to_string(),
+            Event::MultiPagePrescriptionDetected { .. } => {
+                "Dispense:MultiPagePrescriptionDetected".to_string()
+            }
+            Event::MultiPagePrescriptionApproved { .. } => {
+                "Dispense:MultiPagePrescriptionApproved".to_string()
+            }
+            Event::PrescriptionQualityFailed { .. } => {
+                "Dispense:PrescriptionQualityFailed".to_string()
+            }
             Event::PatientAdded { .. } => "Dispense:PatientAdded".to_string(),
+            Event::PrescriberSet { .. } => "Dispense:PrescriberSet".to_string(),
             Event::DrugsAdded { .. } => "Dispense:DrugsAdded".to_string(),
+            Event::FulfillmentMethodSet { .. } => "Dispense:FulfillmentMethodSet".to_string(),
+            Event::DispenseShipped { .. } => "Dispense:Shipped".to_string(),
+            Event::DispenseDelivered { .. } => "Dispense:Delivered".to_string(),
+            Event::DrugInteractionWarning { .. } => "Dispense:DrugInteractionWarning".to_string(),
+            Event::DispenseSplit { .. } => "Dispense:Split".to_string(),
+            Event::DispenseMerged { .. } => "Dispense:Merged".to_string(),
             Event::DispenseCompleted { .. } => "Dispense:Completed".to_string(),
             Event::DispenseCancelled { .. } => "Dispense:Cancelled".to_string(),
         }
     }
 
     fn event_version(&self) -> String {
-        "1.0".to_string()
+        Event::event_version_for(&self.event_type())
+            .unwrap_or(DEFAULT_EVENT_VERSION)
+            .to_string()
+    }
+}
+
+impl Event {
+    /// Current version of an event type, `None` for types this enum does not produce
+    pub fn event_version_for(event_type: &str) -> Option<&'static str> {
+        match event_type {
+            "Dispense:PrescriptionAnalyzed" => Some(PRESCRIPTION_ANALYZED_VERSION),
+            "Dispense:Started"
+            | "Dispense:PrescriptionUploaded"
+            | "Dispense:PrescriptionThumbnailSet"
+            | "Dispense:AnalysisReset"
+            | "Dispense:MultiPagePrescriptionDetected"
+            | "Dispense:MultiPagePrescriptionApproved"
+            | "Dispense:PharmacistReviewCompleted"
+            | "Dispense:PrescriptionQualityFailed"
+            | "Dispense:PatientAdded"
+            | "Dispense:PrescriberSet"
+            | "Dispense:DrugsAdded"
+            | "Dispense:FulfillmentMethodSet"
+            | "Dispense:Shipped"
+            | "Dispense:Delivered"
+            | "Dispense:DrugInteractionWarning"
+            | "Dispense:Split"
+            | "Dispense:Merged"
+            | "Dispense:Completed"
+            | "Dispense:Cancelled" => Some(DEFAULT_EVENT_VERSION),
+            _ => None,
+        }
+    }
+
+    /// Name of the variant (`PrescriptionUploaded`), the event type without its
+    /// `Dispense:` prefix and shortening
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            Event::DispenseStarted { .. } => "DispenseStarted",
+            Event::PrescriptionUploaded { .. } => "PrescriptionUploaded",
+            Event::PrescriptionThumbnailSet { .. } => "PrescriptionThumbnailSet",
+            Event::PrescriptionAnalyzed { .. } => "PrescriptionAnalyzed",
+            Event::AnalysisReset { .. } => "AnalysisReset",
+            Event::MultiPagePrescriptionDetected { .. } => "MultiPagePrescriptionDetected",
+            Event::MultiPagePrescriptionApproved { .. } => "MultiPagePrescriptionApproved",
+            Event::PharmacistReviewCompleted { .. } => "PharmacistReviewCompleted",
+            Event::PrescriptionQualityFailed { .. } => "PrescriptionQualityFailed",
+            Event::PatientAdded { .. } => "PatientAdded",
+            Event::PrescriberSet { .. } => "PrescriberSet",
+            Event::DrugsAdded { .. } => "DrugsAdded",
+            Event::FulfillmentMethodSet { .. } => "FulfillmentMethodSet",
+            Event::DispenseShipped { .. } => "DispenseShipped",
+            Event::DispenseDelivered { .. } => "DispenseDelivered",
+            Event::DrugInteractionWarning { .. } => "DrugInteractionWarning",
+            Event::DispenseSplit { .. } => "DispenseSplit",
+            Event::DispenseMerged { .. } => "DispenseMerged",
+            Event::DispenseCompleted { .. } => "DispenseCompleted",
+            Event::DispenseCancelled { .. } => "DispenseCancelled",
+        }
+    }
+
+    /// Kinesis partition key of the event, its dispense ID
+    ///
+    /// Kinesis orders records per partition key, so the events of a dispense are consumed in
+    /// order while different dispenses spread across shards.
+    pub fn kinesis_partition_key(&self) -> String {
+        match self {
+            Event::DispenseStarted { id, .. }
+            | Event::PrescriptionUploaded { id, .. }
+            | Event::PrescriptionThumbnailSet { id, .. }
+            | Event::PrescriptionAnalyzed { id, .. }
+            | Event::AnalysisReset { id, .. }
+            | Event::MultiPagePrescriptionDetected { id, .. }
+            | Event::MultiPagePrescriptionApproved { id, .. }
+            | Event::PharmacistReviewCompleted { id, .. }
+            | Event::PrescriptionQualityFailed { id, .. }
+            | Event::PatientAdded { id, .. }
+            | Event::PrescriberSet { id, .. }
+            | Event::DrugsAdded { id, .. }
+            | Event::FulfillmentMethodSet { id, .. }
+            | Event::DispenseShipped { id, .. }
+            | Event::DispenseDelivered { id, .. }
+            | Event::DrugInteractionWarning { id, .. }
+            | Event::DispenseSplit { id, .. }
+            | Event::DispenseMerged { id, .. }
+            | Event::DispenseCompleted { id, .. }
+            | Event::DispenseCancelled { id, .. } => id.clone(),
+        }
+    }
+
+    /// User whose command produced the event, when known
+    pub fn actor_id(&self) -> Option<&str> {
+        match self {
+            Event::DispenseStarted { actor_id, .. }
+            | Event::PrescriptionUploaded { actor_id, .. }
+            | Event::PrescriptionThumbnailSet { actor_id, .. }
+            | Event::PrescriptionAnalyzed { actor_id, .. }
+            | Event::AnalysisReset { actor_id, .. }
+            | Event::MultiPagePrescriptionDetected { actor_id, .. }
+            | Event::MultiPagePrescriptionApproved { actor_id, .. }
+            | Event::PharmacistReviewCompleted { actor_id, .. }
+            | Event::PrescriptionQualityFailed { actor_id, .. }
+            | Event::PatientAdded { actor_id, .. }
+            | Event::PrescriberSet { actor_id, .. }
+            | Event::DrugsAdded { actor_id, .. }
+            | Event::FulfillmentMethodSet { actor_id, .. }
+            | Event::DispenseShipped { actor_id, .. }
+            | Event::DispenseDelivered { actor_id, .. }
+            | Event::DrugInteractionWarning { actor_id, .. }
+            | Event::DispenseSplit { actor_id, .. }
+            | Event::DispenseMerged { actor_id, .. }
+            | Event::DispenseCompleted { actor_id, .. }
+            | Event::DispenseCancelled { actor_id, .. } => actor_id.as_deref(),
+        }
+    }
+
+    pub(crate) fn set_actor_id(&mut self, actor: Option<String>) {
+        match self {
+            Event::DispenseStarted { actor_id, .. }
+            | Event::PrescriptionUploaded { actor_id, .. }
+            | Event::PrescriptionThumbnailSet { actor_id, .. }
+            | Event::PrescriptionAnalyzed { actor_id, .. }
+            | Event::AnalysisReset { actor_id, .. }
+            | Event::MultiPagePrescriptionDetected { actor_id, .. }
+            | Event::MultiPagePrescriptionApproved { actor_id, .. }
+            | Event::PharmacistReviewCompleted { actor_id, .. }
+            | Event::PrescriptionQualityFailed { actor_id, .. }
+            | Event::PatientAdded { actor_id, .. }
+            | Event::PrescriberSet { actor_id, .. }
+            | Event::DrugsAdded { actor_id, .. }
+            | Event::FulfillmentMethodSet { actor_id, .. }
+            | Event::DispenseShipped { actor_id, .. }
+            | Event::DispenseDelivered { actor_id, .. }
+            | Event::DrugInteractionWarning { actor_id, .. }
+            | Event::DispenseSplit { actor_id, .. }
+            | Event::DispenseMerged { actor_id, .. }
+            | Event::DispenseCompleted { actor_id, .. }
+            | Event::DispenseCancelled { actor_id, .. } => *actor_id = actor,
+        }
+    }
+}
+
+/// Typed dispense event back from the shared wrapper (e.g. read from Kinesis), upcast like
+/// the events loaded by `DispenseEventStore`
+impl TryFrom<&crate::DomainEvent> for Event {
+    type Error = String;
+
+    fn try_from(domain_event: &crate::DomainEvent) -> Result<Self, Self::Error> {
+        if domain_event.entity != AGGREGATE_TYPE {
+            return Err(format!("Not a dispense event: {}", domain_event.entity));
+        }
+
+        let invalid = |e: serde_json::Error| {
+            format!(
+                "Unknown or invalid event {}: {}",
+                domain_event.event_type, e
+            )
+        };
+        let payload = serde_json::from_str(&domain_event.payload).map_err(invalid)?;
+
+        let mut serialized = SerializedEvent::new(
+            domain_event.id.clone(),
+            domain_event.sequence,
+            domain_event.entity.clone(),
+            domain_event.event_type.clone(),
+            domain_event.event_version.clone(),
+            payload,
+            serde_json::Value::Null,
+        );
+        for upcaster in upcasters::all() {
+            if upcaster.can_upcast(&serialized.event_type, &serialized.event_version) {
+                serialized = upcaster.upcast(serialized);
+            }
+        }
+
+        let event: Event = serde_json::from_value(serialized.payload).map_err(invalid)?;
+        if event.event_type() != domain_event.event_type {
+            return Err(format!(
+                "Event type {} does not match payload {}",
+                domain_event.event_type,
+                event.event_type()
+            ));
+        }
+
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::dispenses::{
+        aggregate::{DispenseData, DrugInteraction, InteractionSeverity},
+        testing::field_changes,
+    };
+
+    fn domain_event(
+        event_type: &str,
+        event_version: &str,
+        payload: serde_json::Value,
+    ) -> crate::DomainEvent {
+        crate::DomainEvent {
+            id: "test-dispense".to_string(),
+            entity: AGGREGATE_TYPE.to_string(),
+            sequence: 3,
+            event_type: event_type.to_string(),
+            event_version: event_version.to_string(),
+            payload: payload.to_string(),
+            metadata: "{}".to_string(),
+        }
+    }
+
+    /// One event of each variant, in workflow order
+    fn all_events() -> Vec<Event> {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let id = || "test-dispense".to_string();
+        let actor_id = Some("user-1".to_string());
+
+        vec![
+            Event::DispenseStarted {
+                id: id(),
+                created_at: at,
+                status: DispenseStatus::Pending,
+                original_dispense_id: None,
+                pharmacy_branch_id: Some("pharmacy-1".to_string()),
+                priority: Priority::Stat,
+                notes: Some("Call before pickup".to_string()),
+                expires_at: Some(at),
+                actor_id: actor_id.clone(),
+            },
+            Event::PrescriptionUploaded {
+                id: id(),
+                prescription_id: "test-prescription".to_string(),
+                url: "s3://prescriptions/test-dispense/prescription.jpg".to_string(),
+                updated_at: at,
+                expires_at: Some(at),
+                file_size_bytes: Some(1024),
+                actor_id: actor_id.clone(),
+            },
+            Event::PrescriptionThumbnailSet {
+                id: id(),
+                thumbnail_url: "s3://prescriptions/test-dispense/thumbnail.jpg".to_string(),
+                updated_at: at,
+                actor_id: None,
+            },
+            Event::PrescriptionAnalyzed {
+                id: id(),
+                analysis_data: r#"{"confidence_score":0.95}"#.to_string(),
+                analysis_version: 1,
+                model_id: "test-model".to_string(),
+                quality_score: 0.95,
+                prescription_page_count: Some(1),
+                updated_at: at,
+                actor_id: None,
+            },
+            Event::MultiPagePrescriptionDetected {
+                id: id(),
+                page_count: 3,
+                updated_at: at,
+                actor_id: None,
+            },
+            Event::MultiPagePrescriptionApproved {
+                id: id(),
+                approved_by: "pharmacist-1".to_string(),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::PharmacistReviewCompleted {
+                id: id(),
+                reviewed_by: "pharmacist-1".to_string(),
+                notes: Some("Dosage confirmed".to_string()),
+                reviewed_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::AnalysisReset {
+                id: id(),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::PrescriptionQualityFailed {
+                id: id(),
+                quality_score: 0.25,
+                min_score: 0.5,
+                updated_at: at,
+                actor_id: None,
+            },
+            Event::PatientAdded {
+                id: id(),
+                patient_id: "test-patient".to_string(),
+                patient_name: "Test Patient".to_string(),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::PrescriberSet {
+                id: id(),
+                prescriber_id: Some("test-prescriber".to_string()),
+                license_number: "CA123456".to_string(),
+                state: "CA".to_string(),
+                prescriber_name: Some("Dr. Test".to_string()),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DrugsAdded {
+                id: id(),
+                drugs: vec![DrugItem::test_item(1), DrugItem::test_item(2)],
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::FulfillmentMethodSet {
+                id: id(),
+                method: FulfillmentMethod::InStore,
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DispenseShipped {
+                id: id(),
+                tracking_number: "1Z999".to_string(),
+                shipped_at: at,
+                carrier: "UPS".to_string(),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DispenseDelivered {
+                id: id(),
+                delivered_at: at,
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DrugInteractionWarning {
+                id: id(),
+                interactions: vec![DrugInteraction {
+                    drug_a: "drug-1".to_string(),
+                    drug_b: "drug-2".to_string(),
+                    severity: InteractionSeverity::Major,
+                    description: "Increased bleeding risk".to_string(),
+                }],
+                updated_at: at,
+                actor_id: None,
+            },
+            Event::DispenseSplit {
+                id: id(),
+                new_dispense_id: "new-dispense".to_string(),
+                moved_drug_ids: vec!["drug-2".to_string()],
+                retained_drug_ids: vec!["drug-1".to_string()],
+                split_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DispenseMerged {
+                id: id(),
+                source_dispense_id: "source-dispense".to_string(),
+                additional_drugs: vec![DrugItem::test_item(3)],
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DispenseCompleted {
+                id: id(),
+                updated_at: at,
+                actor_id: actor_id.clone(),
+            },
+            Event::DispenseCancelled {
+                id: id(),
+                reason: Some("Patient request".to_string()),
+                updated_at: at,
+                actor_id,
+            },
+        ]
+    }
+
+    #[test]
+    fn all_events_covers_every_variant() {
+        let event_types: HashSet<String> = all_events().iter().map(Event::event_type).collect();
+
+        assert_eq!(event_types.len(), all_events().len());
+        assert_eq!(event_types.len(), 20);
+    }
+
+    #[test]
+    fn variant_name_is_the_serialized_variant() {
+        for event in all_events() {
+            let json = serde_json::to_value(&event).unwrap();
+
+            assert_eq!(json["type"], event.variant_name());
+        }
+    }
+
+    #[test]
+    fn partition_key_is_the_id_field() {
+        for event in all_events() {
+            let json = serde_json::to_value(&event).unwrap();
+
+            assert_eq!(
+                json["id"],
+                event.kinesis_partition_key(),
+                "{}",
+                event.event_type()
+            );
+        }
+    }
+
+    #[test]
+    fn every_event_variant_changes_the_state() {
+        // Already at the events' time and actor, so only the variant's own arm can change it
+        let mut state = DispenseData {
+            updated_at: "2024-01-01T00:00:00Z".parse().unwrap(),
+            updated_by: Some("user-1".to_string()),
+            ..Default::default()
+        };
+
+        // In workflow order, each event applied on top of the previous ones
+        for event in all_events() {
+            let event_type = event.event_type();
+            let before = state.clone();
+            state.apply(event);
+
+            assert_ne!(state, before, "{} leaves the state unchanged", event_type);
+        }
+    }
+
+    #[test]
+    fn every_event_type_has_a_version() {
+        for event in all_events() {
+            let event_type = event.event_type();
+
+            assert_eq!(
+                Event::event_version_for(&event_type),
+                Some(event.event_version().as_str()),
+                "{} has no version",
+                event_type
+            );
+        }
+        assert_eq!(
+            Event::event_version_for("Dispense:PrescriptionAnalyzed"),
+            Some(PRESCRIPTION_ANALYZED_VERSION)
+        );
+        assert_eq!(Event::event_version_for("Dispense:Unknown"), None);
+    }
+
+    #[test]
+    fn clone_is_equal() {
+        for event in all_events() {
+            assert_eq!(event.clone(), event);
+        }
+    }
+
+    #[test]
+    fn field_change_is_not_equal() {
+        for event in all_events() {
+            for (field, changed) in field_changes(&event) {
+                assert_ne!(changed, event, "{} {}", event.event_type(), field);
+            }
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for event in all_events() {
+            let json = serde_json::to_string(&event).unwrap();
+
+            assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn json_is_stable() {
+        insta::assert_json_snapshot!(all_events());
+    }
+
+    #[test]
+    fn domain_event_payload_is_upcast() {
+        let domain_event = domain_event(
+            "Dispense:PrescriptionAnalyzed",
+            "1.0",
+            json!({
+                "type": "PrescriptionAnalyzed",
+                "id": "test-dispense",
+                "analysis_data": "{}",
+                "prescription_page_count": null,
+                "updated_at": "2024-01-01T00:00:00Z",
+            }),
+        );
+
+        let event = Event::try_from(&domain_event).unwrap();
+
+        assert!(matches!(
+            event,
+            Event::PrescriptionAnalyzed { analysis_version: 1, model_id, quality_score, .. }
+                if model_id == "unknown" && quality_score == 1.0
+        ));
     }
 }
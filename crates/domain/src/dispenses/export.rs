@@ -0,0 +1,128 @@
+use super::{DispenseStatus, View};
+use aws_sdk_dynamodb::{operation::put_item::PutItemError, types::AttributeValue};
+use chrono::Utc;
+use cqrs_es::persist::PersistenceError;
+
+/// How long an export lock is held before DynamoDB TTL releases it
+pub const EXPORT_LOCK_TTL_SECONDS: i64 = 300;
+
+const CSV_HEADER: [&str; 9] = [
+    "dispense_id",
+    "pharmacy_id",
+    "status",
+    "patient_name",
+    "pharmacist_id",
+    "created_at",
+    "completed_at",
+    "total_drugs",
+    "total_cost_cents",
+];
+
+/// Writes dispense views as a CSV report for a single pharmacy
+///
+/// `pharmacist_id` and `total_cost_cents` are not tracked yet and are left empty.
+pub struct CsvExporter {
+    pharmacy_id: String,
+}
+
+impl CsvExporter {
+    pub fn new(pharmacy_id: &str) -> Self {
+        Self {
+            pharmacy_id: pharmacy_id.to_string(),
+        }
+    }
+
+    pub fn export(&self, views: &[View]) -> String {
+        let mut writer = csv::Writer::from_writer(vec![]);
+
+        writer
+            .write_record(CSV_HEADER)
+            .expect("writing CSV to memory cannot fail");
+
+        for view in views {
+            let dispense = &view.dispense_data;
+            let completed_at = match dispense.status {
+                DispenseStatus::Complete => dispense.updated_at.to_rfc3339(),
+                _ => String::new(),
+            };
+            let status = serde_json::to_value(&dispense.status)
+                .ok()
+                .and_then(|status| status.as_str().map(str::to_string))
+                .unwrap_or_default();
+
+            writer
+                .write_record([
+                    view.id.as_str(),
+                    self.pharmacy_id.as_str(),
+                    status.as_str(),
+                    dispense.patient_name.as_deref().unwrap_or_default(),
+                    "",
+                    dispense.created_at.to_rfc3339().as_str(),
+                    completed_at.as_str(),
+                    dispense.drugs.len().to_string().as_str(),
+                    "",
+                ])
+                .expect("writing CSV to memory cannot fail");
+        }
+
+        let data = writer
+            .into_inner()
+            .expect("writing CSV to memory cannot fail");
+        String::from_utf8(data).expect("CSV records are built from UTF-8 strings")
+    }
+}
+
+/// Prevents concurrent exports using an expiring item in the export locks table
+pub struct ExportLock {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl ExportLock {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// Take the lock, returning `false` if another export holds it
+    pub async fn acquire(&self, lock_id: &str) -> Result<bool, PersistenceError> {
+        let now = Utc::now().timestamp();
+
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .item("lock_id", AttributeValue::S(lock_id.to_string()))
+            .item(
+                "expires_at",
+                AttributeValue::N((now + EXPORT_LOCK_TTL_SECONDS).to_string()),
+            )
+            // TTL deletion is lazy, so an expired lock may still be present
+            .condition_expression("attribute_not_exists(lock_id) OR expires_at < :now")
+            .expression_attribute_values(":now", AttributeValue::N(now.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) => match err.into_service_error() {
+                PutItemError::ConditionalCheckFailedException(_) => Ok(false),
+                err => Err(PersistenceError::UnknownError(Box::new(err))),
+            },
+        }
+    }
+
+    pub async fn release(&self, lock_id: &str) -> Result<(), PersistenceError> {
+        self.client
+            .delete_item()
+            .table_name(&self.table)
+            .key("lock_id", AttributeValue::S(lock_id.to_string()))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use super::{DispenseStatus, View};
+use serde::{Deserialize, Serialize};
+
+/// Code system used for internal drug identifiers
+pub const DRUG_CODE_SYSTEM: &str = "urn:dispensary:drug-id";
+
+/// FHIR R4 `MedicationDispense` resource
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FhirMedicationDispense {
+    pub resource_type: String,
+    pub id: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<FhirReference>,
+    pub medication_codeable_concept: FhirCodeableConcept,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<FhirQuantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when_handed_over: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FhirReference {
+    pub reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FhirCodeableConcept {
+    pub coding: Vec<FhirCoding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FhirCoding {
+    pub system: String,
+    pub code: String,
+    pub display: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FhirQuantity {
+    pub value: u64,
+    pub unit: String,
+}
+
+/// FHIR `MedicationDispense.status` code for a dispense status
+pub fn fhir_status(status: &DispenseStatus) -> &'static str {
+    match status {
+        DispenseStatus::Pending | DispenseStatus::Analyzing | DispenseStatus::Ready => {
+            "in-progress"
+        }
+        DispenseStatus::Complete => "completed",
+        DispenseStatus::Cancelled => "stopped",
+    }
+}
+
+/// Convert a dispense view to a FHIR R4 `MedicationDispense` resource
+pub fn to_medication_dispense(view: &View) -> FhirMedicationDispense {
+    let dispense = &view.dispense_data;
+
+    let subject = dispense
+        .patient_id
+        .as_ref()
+        .map(|patient_id| FhirReference {
+            reference: format!("Patient/{}", patient_id),
+            display: dispense.patient_name.clone(),
+        });
+
+    let coding = dispense
+        .drugs
+        .iter()
+        .map(|drug| FhirCoding {
+            system: DRUG_CODE_SYSTEM.to_string(),
+            code: drug.drug_id.to_string(),
+            display: drug.name.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let text = if dispense.drugs.is_empty() {
+        None
+    } else {
+        Some(
+            dispense
+                .drugs
+                .iter()
+                .map(|drug| drug.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    };
+
+    let quantity = if dispense.drugs.is_empty() {
+        None
+    } else {
+        Some(FhirQuantity {
+            value: dispense.drugs.iter().map(|drug| drug.quantity as u64).sum(),
+            unit: "unit".to_string(),
+        })
+    };
+
+    let when_handed_over = match dispense.status {
+        DispenseStatus::Complete => Some(dispense.updated_at.to_rfc3339()),
+        _ => None,
+    };
+
+    FhirMedicationDispense {
+        resource_type: "MedicationDispense".to_string(),
+        id: view.id.to_string(),
+        status: fhir_status(&dispense.status).to_string(),
+        subject,
+        medication_codeable_concept: FhirCodeableConcept { coding, text },
+        quantity,
+        when_handed_over,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::dispenses::{aggregate::DrugItem, Dispense};
+
+    /// Elements of the FHIR R4 `MedicationDispense` resource this export writes
+    const ELEMENTS: [&str; 7] = [
+        "resourceType",
+        "id",
+        "status",
+        "subject",
+        "medicationCodeableConcept",
+        "quantity",
+        "whenHandedOver",
+    ];
+
+    /// `MedicationDispense.status` value set (required binding)
+    const STATUS_CODES: [&str; 9] = [
+        "preparation",
+        "in-progress",
+        "cancelled",
+        "on-hold",
+        "completed",
+        "entered-in-error",
+        "stopped",
+        "declined",
+        "unknown",
+    ];
+
+    fn view_of(status: DispenseStatus) -> View {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut dispense = Dispense::test_dispense(status);
+        dispense.created_at = at;
+        dispense.updated_at = at;
+        dispense.drugs = vec![DrugItem::test_item(1), DrugItem::test_item(2)];
+
+        View {
+            dispense_data: dispense.data,
+            ..View::default()
+        }
+    }
+
+    #[test]
+    fn completed_dispense_matches_the_fhir_schema() {
+        let json = serde_json::to_value(to_medication_dispense(&view_of(DispenseStatus::Complete)))
+            .unwrap();
+        let resource = json.as_object().unwrap();
+
+        assert!(resource.keys().all(|key| ELEMENTS.contains(&key.as_str())));
+        assert_eq!(resource["resourceType"], "MedicationDispense");
+        assert!(STATUS_CODES.contains(&resource["status"].as_str().unwrap()));
+        assert!(resource["subject"]["reference"]
+            .as_str()
+            .unwrap()
+            .starts_with("Patient/"));
+        let coding = resource["medicationCodeableConcept"]["coding"]
+            .as_array()
+            .unwrap();
+        assert!(!coding.is_empty());
+        for code in coding {
+            for element in ["system", "code", "display"] {
+                assert!(code[element].is_string(), "coding.{}", element);
+            }
+        }
+        assert!(matches!(&resource["quantity"]["value"], Value::Number(value) if value.is_u64()));
+        assert!(DateTime::parse_from_rfc3339(resource["whenHandedOver"].as_str().unwrap()).is_ok());
+        insta::assert_json_snapshot!(json);
+    }
+
+    #[test]
+    fn status_maps_to_the_fhir_status() {
+        for (status, expected) in [
+            (DispenseStatus::Pending, "in-progress"),
+            (DispenseStatus::Analyzing, "in-progress"),
+            (DispenseStatus::Ready, "in-progress"),
+            (DispenseStatus::Complete, "completed"),
+            (DispenseStatus::Cancelled, "stopped"),
+        ] {
+            assert_eq!(to_medication_dispense(&view_of(status)).status, expected);
+        }
+    }
+
+    #[test]
+    fn dispense_without_patient_nor_drugs_has_no_subject_nor_quantity() {
+        let mut view = view_of(DispenseStatus::Pending);
+        view.dispense_data.drugs.clear();
+
+        let json = serde_json::to_value(to_medication_dispense(&view)).unwrap();
+
+        assert!(json.get("subject").is_none());
+        assert!(json.get("quantity").is_none());
+        assert!(json.get("whenHandedOver").is_none());
+        assert_eq!(
+            json["medicationCodeableConcept"]["coding"],
+            Value::Array(vec![])
+        );
+    }
+}
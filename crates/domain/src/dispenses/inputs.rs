@@ -1,9 +1,157 @@
-use super::aggregate::DrugItem;
-use serde::{Deserialize, Serialize};
+use super::aggregate::{DrugItem, FulfillmentMethod, Priority};
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{fmt, ops::Deref};
+use ulid::Ulid;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use crate::errors::Error;
+
+/// String ID newtype, (de)serialized as the plain string and read as `&str` through `Deref`
+///
+/// `TryFrom<String>` rejects empty IDs; `Default` is the empty ID of an aggregate not yet created.
+macro_rules! string_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            /// ID read from an event, validated when its command was accepted
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = Error;
+
+            fn try_from(id: String) -> Result<Self, Self::Error> {
+                if id.trim().is_empty() {
+                    return Err(Error::Validation {
+                        message: format!("{} must not be empty", stringify!($name)),
+                    });
+                }
+                Ok(Self(id))
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl NonEmptyIds for $name {
+            fn check_non_empty(&self) -> Result<(), Error> {
+                Self::try_from(self.0.clone()).map(|_| ())
+            }
+        }
+    };
+}
+
+/// IDs of an API input, which must not be empty
+trait NonEmptyIds {
+    fn check_non_empty(&self) -> Result<(), Error>;
+}
+
+impl<T: NonEmptyIds> NonEmptyIds for Option<T> {
+    fn check_non_empty(&self) -> Result<(), Error> {
+        self.as_ref().map_or(Ok(()), T::check_non_empty)
+    }
+}
+
+impl<T: NonEmptyIds> NonEmptyIds for Vec<T> {
+    fn check_non_empty(&self) -> Result<(), Error> {
+        self.iter().try_for_each(T::check_non_empty)
+    }
+}
+
+fn non_empty<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + NonEmptyIds,
+{
+    let ids = T::deserialize(deserializer)?;
+    ids.check_non_empty().map_err(de::Error::custom)?;
+    Ok(ids)
+}
+
+string_id!(
+    /// Patient ID, not interchangeable with the other IDs:
+    ///
+    /// ```
+    /// use domain::dispenses::inputs::PatientId;
+    ///
+    /// fn add_patient(_patient_id: &PatientId) {}
+    /// add_patient(&PatientId::new("patient-1"));
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use domain::dispenses::inputs::{DrugId, PatientId};
+    ///
+    /// fn add_patient(_patient_id: &PatientId) {}
+    /// add_patient(&DrugId::new("drug-1"));
+    /// ```
+    PatientId
+);
+string_id!(DrugId);
+string_id!(PrescriberId);
+string_id!(
+    /// Dispense aggregate ID, a ULID for the dispenses created by the API
+    DispenseId
+);
+
+impl From<Ulid> for DispenseId {
+    fn from(id: Ulid) -> Self {
+        Self(id.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct StartDispenseInput {
-    // Empty for now, can add metadata later
+    pub pharmacy_branch_id: Option<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    pub notes: Option<String>,
+    /// EHR system reference, unique per pharmacy
+    pub external_reference_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -14,11 +162,95 @@ pub struct UploadPrescriptionInput {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AddPatientInput {
-    pub patient_id: String,
+    #[serde(deserialize_with = "non_empty")]
+    pub patient_id: PatientId,
     pub name: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetPrescriberInput {
+    #[serde(default, deserialize_with = "non_empty")]
+    pub prescriber_id: Option<PrescriberId>,
+    pub license_number: String,
+    pub state: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AddDrugsInput {
     pub drugs: Vec<DrugItem>,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SplitDispenseInput {
+    /// `drug_id`s moved to the new dispense
+    #[serde(deserialize_with = "non_empty")]
+    pub drugs_to_split: Vec<DrugId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MergeDispenseInput {
+    /// Dispense cancelled and whose drugs are added to the target
+    #[serde(deserialize_with = "non_empty")]
+    pub source_dispense_id: DispenseId,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PharmacistReviewInput {
+    pub notes: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidateCommandInput {
+    /// Serialized `Command`, e.g. `"CompleteDispense"`
+    pub command: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetFulfillmentMethodInput {
+    pub method: FulfillmentMethod,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordShipmentInput {
+    pub tracking_number: String,
+    pub shipped_at: DateTime<Utc>,
+    pub carrier: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordDeliveryInput {
+    pub delivered_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_serialize_as_plain_strings() {
+        let drug = DrugItem::test_item(1);
+
+        let json = serde_json::to_value(&drug).unwrap();
+
+        assert_eq!(json["drug_id"], "drug-1");
+        assert_eq!(serde_json::from_value::<DrugItem>(json).unwrap(), drug);
+    }
+
+    #[test]
+    fn input_rejects_empty_ids() {
+        let patient = serde_json::json!({ "patient_id": " ", "name": "Jane Doe" });
+        let split = serde_json::json!({ "drugs_to_split": ["drug-1", ""] });
+
+        assert!(serde_json::from_value::<AddPatientInput>(patient).is_err());
+        assert!(serde_json::from_value::<SplitDispenseInput>(split).is_err());
+    }
+
+    #[test]
+    fn input_accepts_missing_optional_id() {
+        let prescriber = serde_json::json!({ "license_number": "CA123456", "state": "CA" });
+
+        let input = serde_json::from_value::<SetPrescriberInput>(prescriber).unwrap();
+
+        assert_eq!(input.prescriber_id, None);
+    }
+}
@@ -10,13 +10,70 @@ pub mod events;
 /// Input DTOs
 pub mod inputs;
 
+/// Patient name rules
+pub mod name_validator;
+
 /// View (read model)
 pub mod view;
 
+/// Optimistically locked view writes
+pub mod transactional_view;
+
+/// Timeline index (read model sorted by creation date)
+pub mod timeline;
+
+/// Patient dispense history (read model keyed by patient)
+pub mod patient_history;
+
+/// Last dispense of each patient (read model keyed by patient)
+pub mod patient_last_dispense;
+
+/// CSV export
+pub mod export;
+
+/// Prescription analysis results
+pub mod analysis;
+
+/// Asynchronous re-analysis jobs
+pub mod analysis_jobs;
+
+/// Cached OCR results of prescription files
+pub mod ocr_cache;
+
+/// FHIR R4 export
+pub mod fhir;
+
+/// Snapshot inspection (admin)
+pub mod snapshots;
+
+/// Event upcasters for older stored payloads
+pub mod upcasters;
+
+/// Event store and its snapshot strategy
+pub mod event_store;
+
+/// External services
+pub mod services;
+
 /// CQRS setup
 pub mod cqrs;
 
-pub use aggregate::{Dispense, DispenseStatus, Services, AGGREGATE_TYPE};
+/// Given-When-Then harness of the aggregate
+#[cfg(any(test, feature = "mocks"))]
+pub mod testing;
+
+pub use aggregate::{
+    Address, Dispense, DispenseData, DispenseStatus, DrugInteraction, DrugLedger,
+    DrugLedgerEntry, FulfillmentMethod, InteractionSeverity, LedgerOperation, PrescriptionRecord,
+    Priority, StatusTransition, AGGREGATE_TYPE,
+};
 pub use commands::Command;
+pub use event_store::{DispenseEventStore, SnapshotStrategy};
 pub use events::Event;
-pub use view::{Query, View};
+pub use services::{
+    DispenseConfig, DispensingServices, FormularyService, SchedulerService, Services,
+};
+pub use patient_history::{PatientDispenseHistoryView, PatientHistoryQuery};
+pub use patient_last_dispense::{PatientLastDispenseStore, PatientLastDispenseView};
+pub use timeline::{DispenseTimelineView, TimelineIndexQuery};
+pub use view::{ComputedFields, DispenseSummary, DispenseSummaryEnvelope, Query, View, ViewScanner};
@@ -0,0 +1,95 @@
+/// Longest patient name accepted, in characters
+pub const MAX_PATIENT_NAME_LENGTH: usize = 200;
+
+/// Rejects blank, overlong and purely numeric names (an ID typed in the name field)
+pub fn validate_patient_name(name: &str) -> Result<(), &'static str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Patient name cannot be empty");
+    }
+    if name.chars().count() > MAX_PATIENT_NAME_LENGTH {
+        return Err("Patient name exceeds 200 characters");
+    }
+    if name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err("Patient name appears invalid");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_name_is_rejected() {
+        assert_eq!(
+            validate_patient_name(""),
+            Err("Patient name cannot be empty")
+        );
+    }
+
+    #[test]
+    fn whitespace_only_name_is_rejected() {
+        assert_eq!(
+            validate_patient_name(" \t\n "),
+            Err("Patient name cannot be empty")
+        );
+    }
+
+    #[test]
+    fn name_of_maximum_length_is_accepted() {
+        let name = "a".repeat(MAX_PATIENT_NAME_LENGTH);
+
+        assert_eq!(validate_patient_name(&name), Ok(()));
+    }
+
+    #[test]
+    fn name_over_maximum_length_is_rejected() {
+        let name = "a".repeat(MAX_PATIENT_NAME_LENGTH + 1);
+
+        assert_eq!(
+            validate_patient_name(&name),
+            Err("Patient name exceeds 200 characters")
+        );
+    }
+
+    #[test]
+    fn numeric_name_is_rejected() {
+        for name in ["12345", "123 456"] {
+            assert_eq!(
+                validate_patient_name(name),
+                Err("Patient name appears invalid")
+            );
+        }
+    }
+
+    #[test]
+    fn name_with_digits_is_accepted() {
+        assert_eq!(validate_patient_name("John Smith 3rd"), Ok(()));
+    }
+
+    #[test]
+    fn multibyte_names_are_accepted() {
+        for name in ["José Müller", "李小龍", "Zoë 🙂"] {
+            assert_eq!(validate_patient_name(name), Ok(()));
+        }
+    }
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        // 2 bytes each: 400 bytes, 200 characters
+        let name = "é".repeat(MAX_PATIENT_NAME_LENGTH);
+        assert!(name.len() > MAX_PATIENT_NAME_LENGTH);
+        assert_eq!(validate_patient_name(&name), Ok(()));
+
+        let name = "é".repeat(MAX_PATIENT_NAME_LENGTH + 1);
+        assert_eq!(
+            validate_patient_name(&name),
+            Err("Patient name exceeds 200 characters")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::{config::Config, errors::Error};
+
+/// How long an OCR result is reused
+pub const OCR_CACHE_TTL_DAYS: i64 = 7;
+
+/// OCR result of a prescription file, keyed by `prescription_url`
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct OcrCacheEntry {
+    /// `s3://{bucket}/{key}` of the file (a page for multi-page prescriptions)
+    pub prescription_url: String,
+    /// S3 ETag of the file analyzed, a file uploaded again under the same key having another
+    #[serde(default)]
+    pub etag: Option<String>,
+    pub textract_raw_text: Option<String>,
+    /// Analysis of the file (JSON)
+    pub extracted_fields: String,
+    pub model_id: String,
+    pub analyzed_at: DateTime<Utc>,
+    /// Unix timestamp at which DynamoDB TTL removes the entry
+    pub ttl: u64,
+}
+
+impl OcrCacheEntry {
+    pub fn new(
+        prescription_url: &str,
+        etag: Option<String>,
+        textract_raw_text: Option<String>,
+        extracted_fields: String,
+        model_id: &str,
+        analyzed_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            prescription_url: prescription_url.to_string(),
+            etag,
+            textract_raw_text,
+            extracted_fields,
+            model_id: model_id.to_string(),
+            analyzed_at,
+            ttl: (analyzed_at + Duration::days(OCR_CACHE_TTL_DAYS)).timestamp() as u64,
+        }
+    }
+
+    /// Whether the entry is the analysis of this version of the file by `model_id`
+    ///
+    /// Entries without ETag, written before it was recorded, are analyzed again.
+    pub fn is_analysis_of(&self, etag: Option<&str>, model_id: &str) -> bool {
+        self.etag.is_some() && self.etag.as_deref() == etag && self.model_id == model_id
+    }
+}
+
+/// Store of OCR results, `OcrCacheService` outside of tests
+#[cfg_attr(any(test, feature = "mocks"), mockall::automock)]
+#[async_trait]
+pub trait OcrCache: Send + Sync {
+    /// Entry of the file, `None` when missing or past its TTL but not yet removed
+    async fn get(&self, prescription_url: &str) -> Result<Option<OcrCacheEntry>, Error>;
+
+    async fn put(&self, entry: &OcrCacheEntry) -> Result<(), Error>;
+}
+
+/// OCR results kept so a retried analysis does not run Textract on the same file again
+#[derive(Clone, Debug)]
+pub struct OcrCacheService {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl OcrCacheService {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// `None` unless `OCR_CACHE_ENABLED=true`
+    pub fn from_env(client: aws_sdk_dynamodb::Client) -> Option<Self> {
+        let enabled = env::var("OCR_CACHE_ENABLED")
+            .map(|enabled| enabled == "true")
+            .unwrap_or(false);
+
+        enabled.then(|| Self::new(client, &Config::current().ocr_cache_table))
+    }
+}
+
+#[async_trait]
+impl OcrCache for OcrCacheService {
+    async fn get(&self, prescription_url: &str) -> Result<Option<OcrCacheEntry>, Error> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(
+                "prescription_url",
+                AttributeValue::S(prescription_url.to_string()),
+            )
+            .send()
+            .await
+            .map_err(|e| Error::ExternalService {
+                service: "DynamoDB".to_string(),
+                message: format!("OCR cache unavailable: {}", e),
+            })?;
+
+        let Some(item) = output.item else {
+            return Ok(None);
+        };
+        let entry: OcrCacheEntry = serde_dynamo::from_item(item).map_err(|e| Error::Internal {
+            message: format!("Invalid OCR cache entry for {}", prescription_url),
+            source: Some(Box::new(e)),
+        })?;
+
+        // DynamoDB removes expired items up to a few days late
+        Ok((entry.ttl > Utc::now().timestamp() as u64).then_some(entry))
+    }
+
+    async fn put(&self, entry: &OcrCacheEntry) -> Result<(), Error> {
+        let item = serde_dynamo::to_item(entry).map_err(|e| Error::Internal {
+            message: format!(
+                "Cannot serialize OCR cache entry for {}",
+                entry.prescription_url
+            ),
+            source: Some(Box::new(e)),
+        })?;
+
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(|e| Error::ExternalService {
+                service: "DynamoDB".to_string(),
+                message: format!("OCR cache unavailable: {}", e),
+            })?;
+
+        Ok(())
+    }
+}
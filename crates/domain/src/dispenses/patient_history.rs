@@ -0,0 +1,264 @@
+use super::{Dispense, DispenseStatus, View};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use cqrs_es::{
+    persist::{PersistenceError, ViewRepository},
+    EventEnvelope,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+
+/// Dispense of a patient, as listed in their history
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PatientDispenseSummary {
+    pub dispense_id: String,
+    pub status: DispenseStatus,
+    pub created_at: DateTime<Utc>,
+    /// Drug names
+    pub drugs: Vec<String>,
+}
+
+/// Page of the dispenses of a patient, ordered by dispense ID (ULIDs, so by creation)
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PatientDispenseHistoryView {
+    pub patient_id: String,
+    pub dispenses: Vec<PatientDispenseSummary>,
+    /// Dispense ID to pass as `cursor` for the next page, absent on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Maintains the patient history table, partitioned by `patient_id` and sorted by `dispense_id`
+///
+/// Summaries are read from the dispenses view, so this query is registered after it.
+pub struct PatientHistoryQuery {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+    repo: Arc<Box<dyn ViewRepository<View, Dispense>>>,
+}
+
+impl PatientHistoryQuery {
+    pub fn new(
+        client: aws_sdk_dynamodb::Client,
+        table: &str,
+        repo: Arc<Box<dyn ViewRepository<View, Dispense>>>,
+    ) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+            repo,
+        }
+    }
+
+    /// Page of the dispenses of a patient, starting after the `cursor` dispense
+    pub async fn list(
+        &self,
+        patient_id: &str,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<PatientDispenseHistoryView, PersistenceError> {
+        let start_key = cursor.map(|dispense_id| {
+            HashMap::from([
+                (
+                    "patient_id".to_string(),
+                    AttributeValue::S(patient_id.to_string()),
+                ),
+                (
+                    "dispense_id".to_string(),
+                    AttributeValue::S(dispense_id.to_string()),
+                ),
+            ])
+        });
+
+        let output = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .key_condition_expression("patient_id = :patient_id")
+            .expression_attribute_values(":patient_id", AttributeValue::S(patient_id.to_string()))
+            .set_exclusive_start_key(start_key)
+            .limit(limit)
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        history_page(
+            patient_id,
+            output.items.unwrap_or_default(),
+            output.last_evaluated_key,
+        )
+    }
+
+    async fn update(&self, dispense_id: &str) -> Result<(), PersistenceError> {
+        let Some(view) = self.repo.load(dispense_id).await? else {
+            return Ok(());
+        };
+        let Some(item) = history_item(dispense_id, &view)? else {
+            return Ok(());
+        };
+
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
+
+/// History item of a dispense, none until its patient is added (`PatientAdded`)
+fn history_item(
+    dispense_id: &str,
+    view: &View,
+) -> Result<Option<HashMap<String, AttributeValue>>, PersistenceError> {
+    let Some(patient_id) = view.patient_id.clone() else {
+        return Ok(None);
+    };
+
+    let summary = PatientDispenseSummary {
+        dispense_id: dispense_id.to_string(),
+        status: view.status.clone(),
+        created_at: view.created_at,
+        drugs: view.drugs.iter().map(|drug| drug.name.clone()).collect(),
+    };
+
+    let mut item: HashMap<String, AttributeValue> =
+        serde_dynamo::to_item(&summary).map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+    item.insert(
+        "patient_id".to_string(),
+        AttributeValue::S(patient_id.into()),
+    );
+    Ok(Some(item))
+}
+
+/// Page of the items a history query returned, in their sort key order
+fn history_page(
+    patient_id: &str,
+    items: Vec<HashMap<String, AttributeValue>>,
+    last_evaluated_key: Option<HashMap<String, AttributeValue>>,
+) -> Result<PatientDispenseHistoryView, PersistenceError> {
+    let dispenses = serde_dynamo::from_items(items)
+        .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))?;
+    let next_cursor = last_evaluated_key
+        .and_then(|key| key.get("dispense_id").cloned())
+        .and_then(|dispense_id| dispense_id.as_s().ok().cloned());
+
+    Ok(PatientDispenseHistoryView {
+        patient_id: patient_id.to_string(),
+        dispenses,
+        next_cursor,
+    })
+}
+
+#[async_trait]
+impl cqrs_es::Query<Dispense> for PatientHistoryQuery {
+    async fn dispatch(&self, dispense_id: &str, _events: &[EventEnvelope<Dispense>]) {
+        if let Err(err) = self.update(dispense_id).await {
+            eprintln!("PatientHistoryQuery error for {}: {}", dispense_id, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispenses::{aggregate::DrugItem, inputs::PatientId};
+
+    fn view(status: DispenseStatus, patient_id: Option<&str>, drug_count: usize) -> View {
+        let mut dispense = Dispense::test_dispense(status);
+        dispense.patient_id = patient_id.map(PatientId::new);
+        dispense.drugs = (1..=drug_count).map(DrugItem::test_item).collect();
+
+        View {
+            dispense_data: dispense.data,
+            ..View::default()
+        }
+    }
+
+    /// Items of the patient in sort key order, as a query of the table returns them
+    fn query(
+        patient_id: &str,
+        mut items: Vec<HashMap<String, AttributeValue>>,
+    ) -> Vec<HashMap<String, AttributeValue>> {
+        items.retain(|item| item["patient_id"].as_s().unwrap() == patient_id);
+        items.sort_by_key(|item| item["dispense_id"].as_s().unwrap().clone());
+        items
+    }
+
+    #[test]
+    fn history_lists_every_dispense_of_the_patient() {
+        let views = [
+            (
+                "01J0000000000000000000000B",
+                view(DispenseStatus::Complete, Some("patient-1"), 2),
+            ),
+            (
+                "01J0000000000000000000000C",
+                view(DispenseStatus::Ready, Some("patient-2"), 1),
+            ),
+            (
+                "01J0000000000000000000000A",
+                view(DispenseStatus::Ready, Some("patient-1"), 1),
+            ),
+            (
+                "01J0000000000000000000000D",
+                view(DispenseStatus::Analyzing, None, 0),
+            ),
+        ];
+        let items: Vec<_> = views
+            .iter()
+            .filter_map(|(dispense_id, view)| history_item(dispense_id, view).unwrap())
+            .collect();
+        assert_eq!(items.len(), 3);
+
+        let history = history_page("patient-1", query("patient-1", items), None).unwrap();
+
+        assert_eq!(history.patient_id, "patient-1");
+        assert_eq!(
+            history
+                .dispenses
+                .iter()
+                .map(|dispense| (dispense.dispense_id.as_str(), &dispense.status))
+                .collect::<Vec<_>>(),
+            [
+                ("01J0000000000000000000000A", &DispenseStatus::Ready),
+                ("01J0000000000000000000000B", &DispenseStatus::Complete),
+            ]
+        );
+        assert_eq!(history.dispenses[0].drugs, ["Drug 1"]);
+        assert_eq!(history.dispenses[1].drugs, ["Drug 1", "Drug 2"]);
+        assert_eq!(
+            history
+                .dispenses
+                .iter()
+                .map(|dispense| dispense.drugs.len())
+                .sum::<usize>(),
+            3
+        );
+        assert_eq!(history.next_cursor, None);
+    }
+
+    #[test]
+    fn next_cursor_is_the_last_dispense_evaluated() {
+        let item = history_item(
+            "01J0000000000000000000000A",
+            &view(DispenseStatus::Ready, Some("patient-1"), 1),
+        )
+        .unwrap()
+        .unwrap();
+        let last_evaluated_key = HashMap::from([
+            ("patient_id".to_string(), item["patient_id"].clone()),
+            ("dispense_id".to_string(), item["dispense_id"].clone()),
+        ]);
+
+        let history = history_page("patient-1", vec![item], Some(last_evaluated_key)).unwrap();
+
+        assert_eq!(
+            history.next_cursor.as_deref(),
+            Some("01J0000000000000000000000A")
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use super::DispenseStatus;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use serde::{Deserialize, Serialize};
+
+use crate::serde_utils::dynamo_datetime;
+
+/// Most recent dispense of a patient, for the patient dashboard
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PatientLastDispenseView {
+    pub patient_id: String,
+    pub last_dispense_id: String,
+    pub last_dispense_status: DispenseStatus,
+    /// Time of the event last applied
+    #[serde(with = "dynamo_datetime")]
+    pub last_updated: DateTime<Utc>,
+}
+
+/// Maintains the patient last dispense table, keyed by `patient_id`
+///
+/// Written by `projector-views` from the stream, where events of different dispenses can arrive
+/// out of order: writes are conditional on `last_updated`, so an older event never overwrites
+/// newer state.
+pub struct PatientLastDispenseStore {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl PatientLastDispenseStore {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    pub async fn get(
+        &self,
+        patient_id: &str,
+    ) -> Result<Option<PatientLastDispenseView>, PersistenceError> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("patient_id", AttributeValue::S(patient_id.to_string()))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        output
+            .item
+            .map(serde_dynamo::from_item)
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    /// `PatientAdded`: the dispense becomes the last one of the patient
+    ///
+    /// Returns `false` when newer state was kept.
+    pub async fn set_last_dispense(
+        &self,
+        view: &PatientLastDispenseView,
+    ) -> Result<bool, PersistenceError> {
+        self.put(view, false).await
+    }
+
+    /// `DispenseCompleted` or `DispenseCancelled`: status of the last dispense, ignored when the
+    /// patient has a more recent dispense
+    ///
+    /// Returns `false` when newer state was kept.
+    pub async fn set_last_dispense_status(
+        &self,
+        view: &PatientLastDispenseView,
+    ) -> Result<bool, PersistenceError> {
+        self.put(view, true).await
+    }
+
+    async fn put(
+        &self,
+        view: &PatientLastDispenseView,
+        same_dispense: bool,
+    ) -> Result<bool, PersistenceError> {
+        let item =
+            serde_dynamo::to_item(view).map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+        let new_time = AttributeValue::S(dynamo_datetime::format(&view.last_updated));
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .expression_attribute_values(":new_time", new_time);
+        let request = if same_dispense {
+            request
+                .condition_expression(
+                    "last_dispense_id = :dispense_id AND last_updated < :new_time",
+                )
+                .expression_attribute_values(
+                    ":dispense_id",
+                    AttributeValue::S(view.last_dispense_id.clone()),
+                )
+        } else {
+            request.condition_expression(
+                "attribute_not_exists(patient_id) OR last_updated < :new_time",
+            )
+        };
+
+        match request.send().await {
+            Ok(_) => Ok(true),
+            Err(err) => match err.into_service_error() {
+                err if err.is_conditional_check_failed_exception() => Ok(false),
+                err => Err(PersistenceError::UnknownError(Box::new(err))),
+            },
+        }
+    }
+}
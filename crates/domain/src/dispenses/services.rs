@@ -0,0 +1,712 @@
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_scheduler::types::{
+    ActionAfterCompletion, FlexibleTimeWindow, FlexibleTimeWindowMode, Target,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+};
+
+use super::aggregate::{DrugInteraction, DrugItem};
+use crate::{config::Config, errors::Error, prescribers::PrescriberService};
+
+/// Prescriber details returned by license validation
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PrescriberInfo {
+    pub license_number: String,
+    pub state: String,
+    pub name: Option<String>,
+}
+
+/// External service calls made while handling dispense commands
+#[cfg_attr(any(test, feature = "mocks"), mockall::automock)]
+#[async_trait]
+pub trait DispensingServices: Send + Sync {
+    /// Check that the patient is known to the patient registry
+    async fn validate_patient_exists(&self, patient_id: &str) -> Result<(), Error>;
+
+    /// Check that the requested quantity of a drug is in stock
+    async fn check_drug_availability(&self, drug_id: &str, quantity: u32) -> Result<bool, Error>;
+
+    /// Check a prescriber license against the state license database
+    async fn validate_prescriber_license(
+        &self,
+        license_number: &str,
+        state: &str,
+    ) -> Result<PrescriberInfo, Error>;
+
+    /// Check that a prescriber may prescribe controlled substances
+    async fn authorize_controlled_prescription(&self, prescriber_id: &str) -> Result<(), Error>;
+
+    /// Look up drug-drug interactions between the dispensed drugs
+    async fn check_drug_interactions(
+        &self,
+        drugs: &[DrugItem],
+    ) -> Result<Vec<DrugInteraction>, Error>;
+
+    /// Check that the pharmacy may dispense the drug
+    async fn is_drug_on_formulary(&self, pharmacy_id: &str, drug_id: &str) -> Result<bool, Error>;
+}
+
+/// Mocked services for aggregate command handler tests
+#[cfg(any(test, feature = "mocks"))]
+pub type MockServices = MockDispensingServices;
+
+/// `Error::ExternalService` name of the prescriber validation API
+const PRESCRIBER_VALIDATION_SERVICE: &str = "Prescriber validation API";
+
+/// Validates prescriber licenses, caching results for the Lambda lifetime
+///
+/// Calls `PRESCRIBER_VALIDATION_API_URL` when set; otherwise only the license
+/// format (two uppercase letters followed by six digits) is checked.
+#[derive(Debug, Default)]
+pub struct PrescriberLicenseValidator {
+    api_url: Option<String>,
+    http: reqwest::Client,
+    /// Keyed by license number and state, a number being only unique within its state
+    cache: Mutex<HashMap<(String, String), PrescriberInfo>>,
+}
+
+impl PrescriberLicenseValidator {
+    pub fn new(api_url: Option<String>) -> Self {
+        Self {
+            api_url,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            env::var("PRESCRIBER_VALIDATION_API_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+        )
+    }
+
+    pub async fn validate(
+        &self,
+        license_number: &str,
+        state: &str,
+    ) -> Result<PrescriberInfo, Error> {
+        let key = (license_number.to_string(), state.to_string());
+        if let Some(info) = self.cache.lock().unwrap().get(&key) {
+            return Ok(info.clone());
+        }
+
+        let info = match &self.api_url {
+            Some(api_url) => self.validate_remote(api_url, license_number, state).await?,
+            None => Self::validate_format(license_number, state)?,
+        };
+
+        self.cache.lock().unwrap().insert(key, info.clone());
+
+        Ok(info)
+    }
+
+    async fn validate_remote(
+        &self,
+        api_url: &str,
+        license_number: &str,
+        state: &str,
+    ) -> Result<PrescriberInfo, Error> {
+        let response = self
+            .http
+            .get(api_url)
+            .query(&[("license_number", license_number), ("state", state)])
+            .send()
+            .await
+            .map_err(|e| Error::ExternalService {
+                service: PRESCRIBER_VALIDATION_SERVICE.to_string(),
+                message: format!("Prescriber validation unavailable: {}", e),
+            })?;
+
+        // Only a client error is an answer about the license, the others are outages
+        let status = response.status();
+        if status.is_client_error() {
+            return Err(Error::Validation {
+                message: format!("Prescriber license {} rejected", license_number),
+            });
+        }
+        if !status.is_success() {
+            return Err(Error::ExternalService {
+                service: PRESCRIBER_VALIDATION_SERVICE.to_string(),
+                message: format!("Prescriber validation failed with status {}", status),
+            });
+        }
+
+        response.json().await.map_err(|e| Error::ExternalService {
+            service: PRESCRIBER_VALIDATION_SERVICE.to_string(),
+            message: format!("Invalid prescriber validation response: {}", e),
+        })
+    }
+
+    fn validate_format(license_number: &str, state: &str) -> Result<PrescriberInfo, Error> {
+        let bytes = license_number.as_bytes();
+        let valid = bytes.len() == 8
+            && bytes[..2].iter().all(u8::is_ascii_uppercase)
+            && bytes[2..].iter().all(u8::is_ascii_digit);
+
+        if !valid {
+            return Err(Error::Validation {
+                message: format!("Malformed prescriber license {}", license_number),
+            });
+        }
+
+        Ok(PrescriberInfo {
+            license_number: license_number.to_string(),
+            state: state.to_string(),
+            name: None,
+        })
+    }
+}
+
+/// `Error::ExternalService` name of the drug interaction API
+const DRUG_INTERACTION_SERVICE: &str = "Drug interaction API";
+
+/// Checks drug-drug interactions against `DRUG_INTERACTION_API_URL`
+///
+/// Reports no interactions unless `DRUG_INTERACTION_CHECK_ENABLED=true`.
+#[derive(Debug, Default)]
+pub struct DrugInteractionChecker {
+    api_url: Option<String>,
+    http: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct DrugInteractionRequest<'a> {
+    drug_ids: Vec<&'a str>,
+}
+
+impl DrugInteractionChecker {
+    pub fn new(api_url: Option<String>) -> Self {
+        Self {
+            api_url,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let enabled = env::var("DRUG_INTERACTION_CHECK_ENABLED")
+            .map(|enabled| enabled == "true")
+            .unwrap_or(false);
+
+        Self::new(
+            env::var("DRUG_INTERACTION_API_URL")
+                .ok()
+                .filter(|url| enabled && !url.is_empty()),
+        )
+    }
+
+    pub async fn check(&self, drugs: &[DrugItem]) -> Result<Vec<DrugInteraction>, Error> {
+        let Some(api_url) = &self.api_url else {
+            return Ok(vec![]);
+        };
+        if drugs.len() < 2 {
+            return Ok(vec![]);
+        }
+
+        let request = DrugInteractionRequest {
+            drug_ids: drugs.iter().map(|drug| drug.drug_id.as_str()).collect(),
+        };
+
+        let response = self
+            .http
+            .post(api_url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::ExternalService {
+                service: DRUG_INTERACTION_SERVICE.to_string(),
+                message: format!("Drug interaction check unavailable: {}", e),
+            })?;
+
+        response.json().await.map_err(|e| Error::ExternalService {
+            service: DRUG_INTERACTION_SERVICE.to_string(),
+            message: format!("Invalid drug interaction response: {}", e),
+        })
+    }
+}
+
+/// Drugs each pharmacy may dispense, one item per drug in the formulary table (`pharmacy_id`
+/// partition key, `drug_id` sort key)
+#[derive(Clone, Debug)]
+pub struct FormularyService {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl FormularyService {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// `None` unless `FORMULARY_VALIDATION_ENABLED=true`
+    pub fn from_env(client: aws_sdk_dynamodb::Client) -> Option<Self> {
+        let enabled = env::var("FORMULARY_VALIDATION_ENABLED")
+            .map(|enabled| enabled == "true")
+            .unwrap_or(false);
+
+        enabled.then(|| Self::new(client, &Config::current().formulary_table))
+    }
+}
+
+#[cfg_attr(any(test, feature = "mocks"), mockall::automock)]
+impl FormularyService {
+    pub async fn is_on_formulary(&self, pharmacy_id: &str, drug_id: &str) -> Result<bool, Error> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("pharmacy_id", AttributeValue::S(pharmacy_id.to_string()))
+            .key("drug_id", AttributeValue::S(drug_id.to_string()))
+            .projection_expression("drug_id")
+            .send()
+            .await
+            .map_err(|e| Error::ExternalService {
+                service: "DynamoDB".to_string(),
+                message: format!("Formulary unavailable: {}", e),
+            })?;
+
+        Ok(output.item.is_some())
+    }
+}
+
+/// Services implementation accepting every request, used until real integrations exist
+#[derive(Clone)]
+pub struct DefaultServices {
+    licenses: Arc<PrescriberLicenseValidator>,
+    interactions: Arc<DrugInteractionChecker>,
+    prescribers: Option<Arc<PrescriberService>>,
+    formulary: Option<Arc<FormularyService>>,
+}
+
+impl DefaultServices {
+    /// Check controlled substance authorization against the prescribers read model
+    pub fn with_prescribers(mut self, prescribers: PrescriberService) -> Self {
+        self.prescribers = Some(Arc::new(prescribers));
+        self
+    }
+
+    /// Only accept the drugs on the formulary of the dispensing pharmacy
+    pub fn with_formulary(mut self, formulary: FormularyService) -> Self {
+        self.formulary = Some(Arc::new(formulary));
+        self
+    }
+}
+
+impl Default for DefaultServices {
+    fn default() -> Self {
+        Self {
+            licenses: Arc::new(PrescriberLicenseValidator::from_env()),
+            interactions: Arc::new(DrugInteractionChecker::from_env()),
+            prescribers: None,
+            formulary: None,
+        }
+    }
+}
+
+#[async_trait]
+impl DispensingServices for DefaultServices {
+    async fn validate_patient_exists(&self, _patient_id: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn check_drug_availability(&self, _drug_id: &str, _quantity: u32) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    async fn validate_prescriber_license(
+        &self,
+        license_number: &str,
+        state: &str,
+    ) -> Result<PrescriberInfo, Error> {
+        self.licenses.validate(license_number, state).await
+    }
+
+    async fn authorize_controlled_prescription(&self, prescriber_id: &str) -> Result<(), Error> {
+        match &self.prescribers {
+            Some(prescribers) => {
+                prescribers
+                    .check_controlled_authorization(prescriber_id)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn check_drug_interactions(
+        &self,
+        drugs: &[DrugItem],
+    ) -> Result<Vec<DrugInteraction>, Error> {
+        self.interactions.check(drugs).await
+    }
+
+    async fn is_drug_on_formulary(&self, pharmacy_id: &str, drug_id: &str) -> Result<bool, Error> {
+        match &self.formulary {
+            Some(formulary) => formulary.is_on_formulary(pharmacy_id, drug_id).await,
+            None => Ok(true),
+        }
+    }
+}
+
+/// Default minimum prescription quality score
+pub const DEFAULT_MIN_QUALITY_SCORE: f32 = 0.7;
+
+/// Rejects prescription analyses below a minimum quality score
+#[derive(Clone, Debug)]
+pub struct QualityCheckService {
+    pub min_score: f32,
+}
+
+impl QualityCheckService {
+    pub fn new(min_score: f32) -> Self {
+        Self { min_score }
+    }
+
+    /// Threshold from `PRESCRIPTION_MIN_QUALITY_SCORE`, defaulting to 0.7
+    pub fn from_env() -> Self {
+        let min_score = env::var("PRESCRIPTION_MIN_QUALITY_SCORE")
+            .ok()
+            .and_then(|score| score.parse().ok())
+            .unwrap_or(DEFAULT_MIN_QUALITY_SCORE);
+        Self::new(min_score)
+    }
+
+    pub fn check(&self, quality_score: f32) -> Result<(), Error> {
+        if quality_score < self.min_score {
+            return Err(Error::Validation {
+                message: "Prescription quality score too low".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Default maximum number of drugs on a dispense
+pub const DEFAULT_MAX_DRUGS_PER_DISPENSE: usize = 20;
+
+/// Default maximum quantity of a drug, catching data entry errors
+pub const DEFAULT_MAX_DRUG_QUANTITY: u32 = 9999;
+
+/// Default number of events after which a dispense only accepts its cancellation
+pub const DEFAULT_MAX_EVENTS_BEFORE_COMPACTION: usize = 1000;
+
+/// Limits of a single dispense
+///
+/// The drugs are stored with the rest of the dispense in one DynamoDB item (view and snapshot),
+/// next to the analysis data, and items are capped at 400 KB. Larger orders are split into
+/// several dispenses (`SplitDispense`).
+#[derive(Clone, Debug)]
+pub struct DispenseConfig {
+    pub max_drugs_per_dispense: usize,
+    pub max_drug_quantity: u32,
+    /// Bounds the replay of a dispense, which loads all its events since the last snapshot
+    pub max_events_before_compaction: usize,
+}
+
+impl Default for DispenseConfig {
+    fn default() -> Self {
+        Self {
+            max_drugs_per_dispense: DEFAULT_MAX_DRUGS_PER_DISPENSE,
+            max_drug_quantity: DEFAULT_MAX_DRUG_QUANTITY,
+            max_events_before_compaction: DEFAULT_MAX_EVENTS_BEFORE_COMPACTION,
+        }
+    }
+}
+
+impl DispenseConfig {
+    /// Limits from `MAX_DRUGS_PER_DISPENSE`, `MAX_DRUG_QUANTITY` and
+    /// `MAX_EVENTS_BEFORE_COMPACTION`, defaulting to 20, 9999 and 1000
+    pub fn from_env() -> Self {
+        let max_drugs_per_dispense = env::var("MAX_DRUGS_PER_DISPENSE")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DRUGS_PER_DISPENSE);
+        let max_drug_quantity = env::var("MAX_DRUG_QUANTITY")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DRUG_QUANTITY);
+        let max_events_before_compaction = env::var("MAX_EVENTS_BEFORE_COMPACTION")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .unwrap_or(DEFAULT_MAX_EVENTS_BEFORE_COMPACTION);
+        Self {
+            max_drugs_per_dispense,
+            max_drug_quantity,
+            max_events_before_compaction,
+        }
+    }
+
+    pub fn check_drug_count(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_drugs_per_dispense {
+            return Err(Error::Validation {
+                message: format!(
+                    "Maximum {} drugs per dispense, the dispense would have {}",
+                    self.max_drugs_per_dispense, count
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Event count from which `CompactionWarning` is logged, 90% of the maximum
+    pub fn compaction_warning_events(&self) -> usize {
+        self.max_events_before_compaction * 9 / 10
+    }
+
+    pub fn check_drug_quantity(&self, quantity: u32) -> Result<(), Error> {
+        if quantity == 0 {
+            return Err(Error::Validation {
+                message: "Drug quantity must be at least 1".to_string(),
+            });
+        }
+        if quantity > self.max_drug_quantity {
+            return Err(Error::Validation {
+                message: format!(
+                    "Drug quantity exceeds maximum of {}",
+                    self.max_drug_quantity
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Default hours before an unfinished dispense is cancelled
+pub const DEFAULT_DISPENSE_EXPIRY_HOURS: i64 = 72;
+
+/// Schedules the automatic cancellation of dispenses with EventBridge Scheduler
+///
+/// Each dispense has one one-time schedule, `dispense-expiry-{id}`, invoking the
+/// dispense-expiry Lambda with `{"dispense_id": ...}`. Fired schedules delete themselves.
+#[derive(Clone, Debug)]
+pub struct SchedulerService {
+    client: aws_sdk_scheduler::Client,
+    group_name: String,
+    target_arn: String,
+    role_arn: String,
+    pub expiry_hours: i64,
+}
+
+impl SchedulerService {
+    pub fn new(
+        client: aws_sdk_scheduler::Client,
+        group_name: &str,
+        target_arn: &str,
+        role_arn: &str,
+        expiry_hours: i64,
+    ) -> Self {
+        Self {
+            client,
+            group_name: group_name.to_string(),
+            target_arn: target_arn.to_string(),
+            role_arn: role_arn.to_string(),
+            expiry_hours,
+        }
+    }
+
+    /// Configured by `DISPENSE_EXPIRY_TARGET_ARN` (the expiry Lambda) and
+    /// `DISPENSE_EXPIRY_ROLE_ARN` (assumed by the scheduler to invoke it), `None` when either
+    /// is missing. `DISPENSE_EXPIRY_HOURS` defaults to 72, `DISPENSE_EXPIRY_SCHEDULE_GROUP` to
+    /// `default`.
+    pub fn from_env(client: aws_sdk_scheduler::Client) -> Option<Self> {
+        let target_arn = env::var("DISPENSE_EXPIRY_TARGET_ARN")
+            .ok()
+            .filter(|arn| !arn.is_empty())?;
+        let role_arn = env::var("DISPENSE_EXPIRY_ROLE_ARN")
+            .ok()
+            .filter(|arn| !arn.is_empty())?;
+        let group_name =
+            env::var("DISPENSE_EXPIRY_SCHEDULE_GROUP").unwrap_or("default".to_string());
+        let expiry_hours = env::var("DISPENSE_EXPIRY_HOURS")
+            .ok()
+            .and_then(|hours| hours.parse().ok())
+            .unwrap_or(DEFAULT_DISPENSE_EXPIRY_HOURS);
+
+        Some(Self::new(
+            client,
+            &group_name,
+            &target_arn,
+            &role_arn,
+            expiry_hours,
+        ))
+    }
+
+    pub fn schedule_name(dispense_id: &str) -> String {
+        format!("dispense-expiry-{}", dispense_id)
+    }
+
+    /// Expiry deadline of a dispense started or re-uploaded at `from`
+    pub fn expires_at(&self, from: DateTime<Utc>) -> DateTime<Utc> {
+        from + Duration::hours(self.expiry_hours)
+    }
+
+    /// Schedule the cancellation of a dispense at `at`, returning the schedule name
+    ///
+    /// An existing schedule of the dispense, left by an earlier attempt of the same command
+    /// (see `RetryingCqrsFramework`), is moved to `at`.
+    pub async fn schedule_expiry(
+        &self,
+        dispense_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<String, Error> {
+        let name = Self::schedule_name(dispense_id);
+        let input = serde_json::json!({ "dispense_id": dispense_id }).to_string();
+
+        let flexible_time_window = FlexibleTimeWindow::builder()
+            .mode(FlexibleTimeWindowMode::Off)
+            .build()
+            .map_err(scheduler_error)?;
+        let target = Target::builder()
+            .arn(&self.target_arn)
+            .role_arn(&self.role_arn)
+            .input(input)
+            .build()
+            .map_err(scheduler_error)?;
+
+        let schedule_expression = format!("at({})", at.format("%Y-%m-%dT%H:%M:%S"));
+
+        let result = self
+            .client
+            .create_schedule()
+            .name(&name)
+            .group_name(&self.group_name)
+            .schedule_expression(&schedule_expression)
+            .schedule_expression_timezone("UTC")
+            .flexible_time_window(flexible_time_window.clone())
+            .target(target.clone())
+            .action_after_completion(ActionAfterCompletion::Delete)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => {}
+            Err(err) => match err.into_service_error() {
+                err if err.is_conflict_exception() => {
+                    self.client
+                        .update_schedule()
+                        .name(&name)
+                        .group_name(&self.group_name)
+                        .schedule_expression(schedule_expression)
+                        .schedule_expression_timezone("UTC")
+                        .flexible_time_window(flexible_time_window)
+                        .target(target)
+                        .action_after_completion(ActionAfterCompletion::Delete)
+                        .send()
+                        .await
+                        .map_err(scheduler_error)?;
+                }
+                err => return Err(scheduler_error(err)),
+            },
+        }
+
+        Ok(name)
+    }
+
+    /// Delete a pending expiry, already fired or deleted schedules are ignored
+    pub async fn cancel_expiry(&self, schedule_name: &str) -> Result<(), Error> {
+        let result = self
+            .client
+            .delete_schedule()
+            .name(schedule_name)
+            .group_name(&self.group_name)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.into_service_error() {
+                err if err.is_resource_not_found_exception() => Ok(()),
+                err => Err(scheduler_error(err)),
+            },
+        }
+    }
+}
+
+fn scheduler_error(err: impl std::fmt::Display) -> Error {
+    Error::ExternalService {
+        service: "EventBridge Scheduler".to_string(),
+        message: format!("Expiry scheduler unavailable: {}", err),
+    }
+}
+
+/// Services injected into the `Dispense` aggregate
+#[derive(Clone)]
+pub struct Services {
+    pub dispensing: Arc<dyn DispensingServices>,
+    pub quality: QualityCheckService,
+    pub config: DispenseConfig,
+    /// Automatic expiry, disabled when `None`
+    pub scheduler: Option<SchedulerService>,
+}
+
+impl Services {
+    pub fn new(dispensing: impl DispensingServices + 'static) -> Self {
+        Self {
+            dispensing: Arc::new(dispensing),
+            quality: QualityCheckService::from_env(),
+            config: DispenseConfig::from_env(),
+            scheduler: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: DispenseConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: SchedulerService) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+}
+
+impl Default for Services {
+    fn default() -> Self {
+        Self::new(DefaultServices::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn license_validation_is_cached_per_state() {
+        let validator = PrescriberLicenseValidator::new(None);
+
+        let california = validator.validate("CA123456", "CA").await.unwrap();
+        let nevada = validator.validate("CA123456", "NV").await.unwrap();
+
+        assert_eq!(california.state, "CA");
+        assert_eq!(nevada.state, "NV");
+    }
+
+    #[tokio::test]
+    async fn unreachable_prescriber_validation_is_external_service_error() {
+        let validator = PrescriberLicenseValidator::new(Some("http://127.0.0.1:9".to_string()));
+
+        let result = validator.validate("CA123456", "CA").await;
+
+        assert!(matches!(result, Err(Error::ExternalService { .. })));
+    }
+
+    #[tokio::test]
+    async fn unreachable_interaction_check_is_external_service_error() {
+        let checker = DrugInteractionChecker::new(Some("http://127.0.0.1:9".to_string()));
+
+        let result = checker
+            .check(&[DrugItem::test_item(1), DrugItem::test_item(2)])
+            .await;
+
+        assert!(matches!(result, Err(Error::ExternalService { .. })));
+    }
+}
@@ -0,0 +1,168 @@
+use super::{Dispense, AGGREGATE_TYPE};
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Snapshot item attributes written by `dynamo-es`
+const KEY: &str = "AggregateTypeAndId";
+const AGGREGATE_ID: &str = "AggregateId";
+const CURRENT_SNAPSHOT: &str = "CurrentSnapshot";
+const PAYLOAD: &str = "Payload";
+
+/// Summary of a stored aggregate snapshot
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SnapshotInfo {
+    pub aggregate_id: String,
+    pub snapshot_version: u64,
+    /// Time of the last event folded into the snapshot, when the payload is readable
+    pub created_at: Option<DateTime<Utc>>,
+    pub size_bytes: usize,
+    /// Whether the payload deserializes as the current `Dispense` shape
+    pub is_valid: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SnapshotStats {
+    pub total_count: usize,
+    pub average_size_bytes: usize,
+    pub oldest_snapshot_age_seconds: Option<i64>,
+}
+
+/// Read-only access to the snapshots table for debugging, plus forced deletion
+pub struct SnapshotInspector {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl SnapshotInspector {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// List all snapshots, or only the one of `aggregate_id`
+    pub async fn list(
+        &self,
+        aggregate_id: Option<&str>,
+    ) -> Result<Vec<SnapshotInfo>, PersistenceError> {
+        match aggregate_id {
+            Some(aggregate_id) => {
+                let output = self
+                    .client
+                    .get_item()
+                    .table_name(&self.table)
+                    .key(KEY, AttributeValue::S(snapshot_key(aggregate_id)))
+                    .send()
+                    .await
+                    .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+                Ok(output
+                    .item
+                    .as_ref()
+                    .map(snapshot_info)
+                    .into_iter()
+                    .collect())
+            }
+            None => {
+                let mut snapshots = Vec::new();
+                let mut start_key = None;
+
+                loop {
+                    let output = self
+                        .client
+                        .scan()
+                        .table_name(&self.table)
+                        .set_exclusive_start_key(start_key)
+                        .send()
+                        .await
+                        .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+                    snapshots.extend(output.items().iter().map(snapshot_info));
+
+                    start_key = output.last_evaluated_key;
+                    if start_key.is_none() {
+                        return Ok(snapshots);
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn stats(&self) -> Result<SnapshotStats, PersistenceError> {
+        let snapshots = self.list(None).await?;
+
+        let total_count = snapshots.len();
+        let average_size_bytes = match total_count {
+            0 => 0,
+            count => snapshots.iter().map(|s| s.size_bytes).sum::<usize>() / count,
+        };
+        let oldest_snapshot_age_seconds = snapshots
+            .iter()
+            .filter_map(|s| s.created_at)
+            .min()
+            .map(|oldest| (Utc::now() - oldest).num_seconds());
+
+        Ok(SnapshotStats {
+            total_count,
+            average_size_bytes,
+            oldest_snapshot_age_seconds,
+        })
+    }
+
+    /// Delete a snapshot so the next load replays the aggregate from its events
+    pub async fn delete(&self, aggregate_id: &str) -> Result<(), PersistenceError> {
+        self.client
+            .delete_item()
+            .table_name(&self.table)
+            .key(KEY, AttributeValue::S(snapshot_key(aggregate_id)))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
+
+fn snapshot_key(aggregate_id: &str) -> String {
+    format!("{}:{}", AGGREGATE_TYPE, aggregate_id)
+}
+
+fn snapshot_info(item: &HashMap<String, AttributeValue>) -> SnapshotInfo {
+    let aggregate_id = item
+        .get(AGGREGATE_ID)
+        .and_then(|v| v.as_s().ok())
+        .cloned()
+        .or_else(|| {
+            item.get(KEY)
+                .and_then(|v| v.as_s().ok())
+                .and_then(|key| key.split_once(':'))
+                .map(|(_, id)| id.to_string())
+        })
+        .unwrap_or_default();
+
+    let snapshot_version = item
+        .get(CURRENT_SNAPSHOT)
+        .and_then(|v| v.as_n().ok())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_default();
+
+    let payload = item
+        .get(PAYLOAD)
+        .and_then(|v| v.as_b().ok())
+        .map(|b| b.as_ref())
+        .unwrap_or_default();
+
+    let dispense = serde_json::from_slice::<Dispense>(payload).ok();
+
+    SnapshotInfo {
+        aggregate_id,
+        snapshot_version,
+        created_at: dispense.as_ref().map(|d| d.updated_at),
+        size_bytes: payload.len(),
+        is_valid: dispense.is_some(),
+    }
+}
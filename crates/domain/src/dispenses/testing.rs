@@ -0,0 +1,217 @@
+use cqrs_es::{Aggregate, DomainEvent};
+
+use super::{Command, Dispense, Event, Services};
+use crate::errors::Error;
+
+/// Events emitted by a command, or the error rejecting it
+pub type CommandResult = Result<Vec<Event>, Error>;
+
+/// Given-When-Then scenarios of the dispense aggregate
+///
+/// `given` builds the dispense from past events (or `given_dispense` from a fixture such as
+/// `Dispense::test_dispense`), `when` handles a command on a blocking runtime and the `then`
+/// assertions panic on an unexpected outcome:
+///
+/// ```ignore
+/// DispenseTestHarness::given(vec![])
+///     .when(Command::CompleteDispense)
+///     .then_error(Error::NotFound {
+///         entity: AGGREGATE_TYPE.to_string(),
+///     });
+/// ```
+pub struct DispenseTestHarness {
+    dispense: Dispense,
+    services: Services,
+}
+
+/// Outcome of `DispenseTestHarness::when`
+pub struct CommandOutcome {
+    result: CommandResult,
+}
+
+impl DispenseTestHarness {
+    /// Default dispense with the events applied, in order
+    pub fn given(events: Vec<Event>) -> Self {
+        let mut dispense = Dispense::default();
+        for event in events {
+            dispense.apply(event);
+        }
+        Self::given_dispense(dispense)
+    }
+
+    pub fn given_dispense(dispense: Dispense) -> Self {
+        Self {
+            dispense,
+            services: Services::default(),
+        }
+    }
+
+    /// Services the commands are handled with, `MockServices` in most scenarios
+    pub fn with_services(mut self, services: Services) -> Self {
+        self.services = services;
+        self
+    }
+
+    pub fn dispense(&self) -> &Dispense {
+        &self.dispense
+    }
+
+    pub fn when(&self, command: Command) -> CommandOutcome {
+        CommandOutcome {
+            result: self.handle(command),
+        }
+    }
+
+    /// Handle the command and apply its events, for multi-step scenarios
+    ///
+    /// Panics when the command is rejected.
+    pub fn when_and_apply(mut self, command: Command) -> Self {
+        let command_type = format!("{:?}", command);
+        match self.handle(command) {
+            Ok(events) => {
+                for event in events {
+                    self.dispense.apply(event);
+                }
+                self
+            }
+            Err(e) => panic!("{} rejected: {}", command_type, e),
+        }
+    }
+
+    fn handle(&self, command: Command) -> CommandResult {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("test runtime")
+            .block_on(self.dispense.handle(command, &self.services))
+    }
+}
+
+impl CommandOutcome {
+    pub fn result(&self) -> &CommandResult {
+        &self.result
+    }
+
+    pub fn then_events(&self, expected: Vec<Event>) {
+        match &self.result {
+            Ok(events) => assert_eq!(events, &expected),
+            Err(e) => panic!("expected {:?}, command rejected: {}", expected, e),
+        }
+    }
+
+    /// Events compared by `event_type`, for events stamped with the current time
+    pub fn then_event_types(&self, expected: &[&str]) {
+        match &self.result {
+            Ok(events) => {
+                let event_types: Vec<String> = events.iter().map(|e| e.event_type()).collect();
+                assert_eq!(event_types, expected);
+            }
+            Err(e) => panic!("expected {:?}, command rejected: {}", expected, e),
+        }
+    }
+
+    /// Errors hold boxed sources, so they are compared by message
+    pub fn then_error(&self, expected: Error) {
+        match &self.result {
+            Ok(events) => panic!("expected error {}, got events {:?}", expected, events),
+            Err(e) => assert_eq!(e.to_string(), expected.to_string()),
+        }
+    }
+}
+
+/// Copies of `value` with one field changed each, by JSON pointer, to test `PartialEq` field by
+/// field
+///
+/// Every field of the JSON form but the `type` tags is changed, nested ones included, to the
+/// first candidate deserializing: numbers are incremented, booleans negated, lists lose their
+/// last item, strings and `null` take another text, timestamp or enum variant. Unit variants
+/// have no copy. Panics on a field no candidate changes.
+#[cfg(test)]
+pub fn field_changes<T>(value: &T) -> Vec<(String, T)>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let json = serde_json::to_value(value).expect("serializable value");
+    let mut pointers = Vec::new();
+    field_pointers(&json, String::new(), &mut pointers);
+
+    pointers
+        .into_iter()
+        .map(|pointer| {
+            let current = json.pointer(&pointer).expect("field of the value");
+            let changed = candidates(current)
+                .into_iter()
+                .filter(|candidate| candidate != current)
+                .find_map(|candidate| {
+                    let mut changed = json.clone();
+                    *changed.pointer_mut(&pointer)? = candidate;
+                    serde_json::from_value(changed).ok()
+                })
+                .unwrap_or_else(|| panic!("no change of {} deserializes", pointer));
+            (pointer, changed)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn field_pointers(json: &serde_json::Value, pointer: String, pointers: &mut Vec<String>) {
+    use serde_json::Value;
+
+    match json {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter().filter(|(name, _)| *name != "type") {
+                let name = name.replace('~', "~0").replace('/', "~1");
+                field_pointers(field, format!("{}/{}", pointer, name), pointers);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                field_pointers(item, format!("{}/{}", pointer, i), pointers);
+            }
+            pointers.push(pointer);
+        }
+        _ if !pointer.is_empty() => pointers.push(pointer),
+        _ => {}
+    }
+}
+
+/// Values replacing a string or `null`, for the timestamps and enums among them
+#[cfg(test)]
+const OTHER_VALUES: [&str; 9] = [
+    "2025-06-01T00:00:00Z",
+    "pending",
+    "ready",
+    "routine",
+    "urgent",
+    "minor",
+    "major",
+    "added",
+    "removed",
+];
+
+#[cfg(test)]
+fn candidates(json: &serde_json::Value) -> Vec<serde_json::Value> {
+    use serde_json::{json, Value};
+
+    let other_values = OTHER_VALUES.iter().map(|value| json!(value));
+    match json {
+        Value::Bool(value) => vec![json!(!value)],
+        Value::Number(number) => match (number.as_u64(), number.as_i64(), number.as_f64()) {
+            (Some(number), _, _) => vec![json!(number + 1)],
+            (_, Some(number), _) => vec![json!(number + 1)],
+            (_, _, number) => vec![json!(number.unwrap_or_default() + 0.5)],
+        },
+        Value::Array(items) => match items.split_last() {
+            Some((_, rest)) => vec![Value::Array(rest.to_vec())],
+            None => vec![],
+        },
+        Value::String(text) => std::iter::once(json!(format!("{}-changed", text)))
+            .chain(other_values)
+            .collect(),
+        Value::Null => [json!("changed"), json!(1), json!(true)]
+            .into_iter()
+            .chain(other_values)
+            .collect(),
+        Value::Object(_) => vec![],
+    }
+}
@@ -0,0 +1,207 @@
+use super::{Dispense, DispenseStatus, Event};
+use crate::EventMetadata;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{operation::query::builders::QueryFluentBuilder, types::AttributeValue};
+use cqrs_es::{persist::PersistenceError, EventEnvelope};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+/// Pharmacy used when the command metadata does not carry one
+pub const DEFAULT_PHARMACY_ID: &str = "default";
+
+const SORT_KEY: &str = "created_at_dispense_id";
+
+/// GSI keyed by `external_reference_id` and `pharmacy_id`
+const EXTERNAL_REFERENCE_INDEX: &str = "external_reference_index";
+
+/// Time-ordered index entry, partitioned by pharmacy
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DispenseTimelineView {
+    pub pharmacy_id: String,
+    pub created_at_iso: String,
+    pub dispense_id: String,
+    pub status: DispenseStatus,
+    pub patient_name: Option<String>,
+    /// EHR reference from the command metadata, absent from the item (and GSI) when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_reference_id: Option<String>,
+}
+
+impl DispenseTimelineView {
+    /// Sort key: `created_at_iso#dispense_id`
+    pub fn sort_key(&self) -> String {
+        format!("{}#{}", self.created_at_iso, self.dispense_id)
+    }
+}
+
+/// Maintains the timeline index table on `DispenseStarted`
+pub struct TimelineIndexQuery {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl TimelineIndexQuery {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// List dispenses of a pharmacy created between `from` and `to` (inclusive, ISO-8601 prefixes)
+    pub async fn list(
+        &self,
+        pharmacy_id: &str,
+        from: &str,
+        to: &str,
+        limit: i32,
+    ) -> Result<Vec<DispenseTimelineView>, PersistenceError> {
+        let output = self
+            .range_query(pharmacy_id, from, to)
+            .limit(limit)
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        serde_dynamo::from_items(output.items.unwrap_or_default())
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    /// Dispense of a pharmacy created for an EHR reference, if any
+    pub async fn find_by_external_reference(
+        &self,
+        pharmacy_id: &str,
+        external_reference_id: &str,
+    ) -> Result<Option<DispenseTimelineView>, PersistenceError> {
+        let output = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .index_name(EXTERNAL_REFERENCE_INDEX)
+            .key_condition_expression(
+                "external_reference_id = :external_reference_id AND pharmacy_id = :pharmacy_id",
+            )
+            .expression_attribute_values(
+                ":external_reference_id",
+                AttributeValue::S(external_reference_id.to_string()),
+            )
+            .expression_attribute_values(":pharmacy_id", AttributeValue::S(pharmacy_id.to_string()))
+            .limit(1)
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        output
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .next()
+            .map(serde_dynamo::from_item)
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    /// List every dispense of a pharmacy created between `from` and `to`, following all pages
+    pub async fn list_all(
+        &self,
+        pharmacy_id: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<DispenseTimelineView>, PersistenceError> {
+        let mut entries = Vec::new();
+        self.collect_pages(pharmacy_id, from, to, None, &mut entries)
+            .await?;
+        Ok(entries)
+    }
+
+    fn collect_pages<'a>(
+        &'a self,
+        pharmacy_id: &'a str,
+        from: &'a str,
+        to: &'a str,
+        start_key: Option<HashMap<String, AttributeValue>>,
+        entries: &'a mut Vec<DispenseTimelineView>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), PersistenceError>> + Send + 'a>> {
+        Box::pin(async move {
+            let output = self
+                .range_query(pharmacy_id, from, to)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+            let page: Vec<DispenseTimelineView> =
+                serde_dynamo::from_items(output.items.unwrap_or_default())
+                    .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))?;
+            entries.extend(page);
+
+            match output.last_evaluated_key {
+                Some(key) => {
+                    self.collect_pages(pharmacy_id, from, to, Some(key), entries)
+                        .await
+                }
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn range_query(&self, pharmacy_id: &str, from: &str, to: &str) -> QueryFluentBuilder {
+        self.client
+            .query()
+            .table_name(&self.table)
+            .key_condition_expression("pharmacy_id = :pharmacy_id AND #sk BETWEEN :from AND :to")
+            .expression_attribute_names("#sk", SORT_KEY)
+            .expression_attribute_values(":pharmacy_id", AttributeValue::S(pharmacy_id.to_string()))
+            .expression_attribute_values(":from", AttributeValue::S(from.to_string()))
+            // `~` sorts after every character of an ISO timestamp, so `to` covers the whole day
+            .expression_attribute_values(":to", AttributeValue::S(format!("{}~", to)))
+            .scan_index_forward(true)
+    }
+
+    async fn update(&self, events: &[EventEnvelope<Dispense>]) -> Result<(), PersistenceError> {
+        for event in events {
+            if let Event::DispenseStarted {
+                id,
+                created_at,
+                status,
+                ..
+            } = &event.payload
+            {
+                let metadata = EventMetadata::from(&event.metadata);
+                let view = DispenseTimelineView {
+                    pharmacy_id: metadata
+                        .pharmacy_id
+                        .unwrap_or(DEFAULT_PHARMACY_ID.to_string()),
+                    created_at_iso: created_at.to_rfc3339(),
+                    dispense_id: id.clone(),
+                    status: status.clone(),
+                    patient_name: None,
+                    external_reference_id: metadata.external_reference_id,
+                };
+
+                let mut item: HashMap<String, AttributeValue> = serde_dynamo::to_item(&view)
+                    .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+                item.insert(SORT_KEY.to_string(), AttributeValue::S(view.sort_key()));
+
+                self.client
+                    .put_item()
+                    .table_name(&self.table)
+                    .set_item(Some(item))
+                    .send()
+                    .await
+                    .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl cqrs_es::Query<Dispense> for TimelineIndexQuery {
+    async fn dispatch(&self, dispense_id: &str, events: &[EventEnvelope<Dispense>]) {
+        if let Err(err) = self.update(events).await {
+            eprintln!("TimelineIndexQuery error for {}: {}", dispense_id, err);
+        }
+    }
+}
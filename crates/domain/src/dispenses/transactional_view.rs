@@ -0,0 +1,109 @@
+use super::{Dispense, View};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, Put, TransactWriteItem, Update},
+};
+use cqrs_es::persist::{PersistenceError, ViewContext, ViewRepository};
+use dynamo_es::DynamoViewRepository;
+
+/// Dispenses view repository writing each update as a conditional transaction on `ViewVersion`
+///
+/// A view written concurrently (another Kinesis shard, a retried batch) fails with
+/// `PersistenceError::OptimisticLockError` instead of being overwritten. Existing items are
+/// updated in place, keeping the attributes added next to the payload (`ttl_at`).
+pub struct TransactionalViewRepository {
+    inner: DynamoViewRepository<View, Dispense>,
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl TransactionalViewRepository {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            inner: DynamoViewRepository::new(table, client.clone()),
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    fn write(
+        &self,
+        payload: Blob,
+        context: &ViewContext,
+    ) -> Result<TransactWriteItem, PersistenceError> {
+        let view_id = AttributeValue::S(context.view_instance_id.clone());
+        let version = AttributeValue::N((context.version + 1).to_string());
+
+        let item = if context.version == 0 {
+            let put = Put::builder()
+                .table_name(&self.table)
+                .item("ViewId", view_id)
+                .item("ViewVersion", version)
+                .item("Payload", AttributeValue::B(payload))
+                .condition_expression("attribute_not_exists(ViewId)")
+                .build()
+                .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+            TransactWriteItem::builder().put(put).build()
+        } else {
+            let update = Update::builder()
+                .table_name(&self.table)
+                .key("ViewId", view_id)
+                .update_expression("SET Payload = :payload, ViewVersion = :version")
+                .condition_expression("ViewVersion = :current_version")
+                .expression_attribute_values(":payload", AttributeValue::B(payload))
+                .expression_attribute_values(":version", version)
+                .expression_attribute_values(
+                    ":current_version",
+                    AttributeValue::N(context.version.to_string()),
+                )
+                .build()
+                .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+            TransactWriteItem::builder().update(update).build()
+        };
+
+        Ok(item)
+    }
+}
+
+#[async_trait]
+impl ViewRepository<View, Dispense> for TransactionalViewRepository {
+    async fn load(&self, view_id: &str) -> Result<Option<View>, PersistenceError> {
+        self.inner.load(view_id).await
+    }
+
+    async fn load_with_context(
+        &self,
+        view_id: &str,
+    ) -> Result<Option<(View, ViewContext)>, PersistenceError> {
+        self.inner.load_with_context(view_id).await
+    }
+
+    async fn update_view(
+        &self,
+        mut view: View,
+        context: ViewContext,
+    ) -> Result<(), PersistenceError> {
+        view.version = (context.version + 1) as u64;
+        let payload =
+            serde_json::to_vec(&view).map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        let result = self
+            .client
+            .transact_write_items()
+            .transact_items(self.write(Blob::new(payload), &context)?)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.into_service_error() {
+                // The only cancellation reason of a single conditional write
+                err if err.is_transaction_canceled_exception() => {
+                    Err(PersistenceError::OptimisticLockError)
+                }
+                err => Err(PersistenceError::UnknownError(Box::new(err))),
+            },
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use cqrs_es::persist::{EventUpcaster, SemanticVersionEventUpcaster};
+use serde_json::Value;
+
+use super::events::PRESCRIPTION_ANALYZED_VERSION;
+
+/// Upcasters applied when loading dispense events
+pub fn all() -> Vec<Box<dyn EventUpcaster>> {
+    vec![Box::new(SemanticVersionEventUpcaster::new(
+        "Dispense:PrescriptionAnalyzed",
+        PRESCRIPTION_ANALYZED_VERSION,
+        Box::new(prescription_analyzed_v1_1),
+    ))]
+}
+
+/// Events analyzed before versioning count as the first analysis by an unknown model.
+/// Events analyzed before quality checks passed them implicitly.
+fn prescription_analyzed_v1_1(mut payload: Value) -> Value {
+    if let Value::Object(fields) = &mut payload {
+        fields.entry("analysis_version").or_insert(Value::from(1));
+        fields.entry("model_id").or_insert(Value::from("unknown"));
+        fields.entry("quality_score").or_insert(Value::from(1.0));
+    }
+    payload
+}
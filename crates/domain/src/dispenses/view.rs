@@ -1,63 +1,431 @@
-use super::{Dispense, AGGREGATE_TYPE};
+use super::{
+    inputs::DispenseId, Dispense, DispenseData, DispenseEventStore, DispenseStatus, Event,
+    Priority, AGGREGATE_TYPE,
+};
+use crate::EventMetadata;
 use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Duration, Utc};
 use cqrs_es::{
     persist::{PersistenceError, ViewContext, ViewRepository},
-    Aggregate, EventEnvelope, View as CqrsView,
+    EventEnvelope, EventStore, View as CqrsView,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::HashMap, env, ops::Deref, sync::Arc};
+
+/// Days a dispense may stay `Pending` before DynamoDB TTL expires its view
+const DEFAULT_PENDING_TTL_DAYS: i64 = 7;
+
+/// Hours an open dispense may go without an update before it is overdue
+const DEFAULT_SLA_HOURS: i64 = 24;
+
+/// Retries of a view update that lost an optimistic lock to a concurrent one
+const MAX_UPDATE_RETRIES: u32 = 3;
+
+/// Backoff before the first retry, doubled on each following one
+const UPDATE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
 
+/// Events applied between two view writes, so a long dispatch (e.g. a replay) saves its progress
+const UPDATE_BATCH_SIZE: usize = 100;
+
+/// Dispense view, its dispense data serialized at the top level and read through `Deref`
+/// (`view.status`)
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(from = "StoredView")]
 pub struct View {
     pub aggregate_type: String,
     pub command_id: String,
+    #[serde(flatten)]
+    pub dispense_data: DispenseData,
+    /// Unix timestamp at which DynamoDB TTL removes a stale pending dispense
+    pub ttl_at: Option<u64>,
+    /// Incremented on every write, see `TransactionalViewRepository`
+    #[serde(default)]
+    pub version: u64,
+    /// Sequence number of the last event applied, sent as `ETag` by the API
+    #[serde(default)]
+    pub aggregate_version: usize,
+    /// Derived from the events rather than the dispense, hence the `_computed` key; persisted in
+    /// DynamoDB with the view on every event, and refreshed by `GET /dispenses/:id`
+    #[serde(rename = "_computed", default)]
+    pub computed_fields: ComputedFields,
+}
+
+/// Analytics derived from the dispense events
+///
+/// `days_since_started` and `is_overdue` depend on the time they were computed at, see
+/// `View::refresh_computed_fields`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ComputedFields {
+    pub days_since_started: u64,
+    /// From `created_at` to the last `PrescriptionAnalyzed`
+    pub time_to_analyze_seconds: Option<u64>,
+    /// From `created_at` to `DispenseCompleted`
+    pub time_to_complete_seconds: Option<u64>,
+    pub drug_count: usize,
+    pub total_quantity: u32,
+    /// Neither completed nor cancelled, and not updated for `DISPENSE_SLA_HOURS` (24 by default)
+    pub is_overdue: bool,
+    /// See `DispenseData::completion_percentage`
+    #[serde(default)]
+    pub completion_percentage: u8,
+}
+
+impl Deref for View {
+    type Target = DispenseData;
+
+    fn deref(&self) -> &DispenseData {
+        &self.dispense_data
+    }
+}
+
+/// View payload as stored, flat or with the dispense nested as before
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredView {
+    Nested {
+        aggregate_type: String,
+        command_id: String,
+        dispense: DispenseData,
+        ttl_at: Option<u64>,
+        #[serde(default)]
+        version: u64,
+        #[serde(default)]
+        aggregate_version: usize,
+        #[serde(rename = "_computed", default)]
+        computed_fields: ComputedFields,
+    },
+    Flat {
+        aggregate_type: String,
+        command_id: String,
+        #[serde(flatten)]
+        dispense_data: DispenseData,
+        ttl_at: Option<u64>,
+        #[serde(default)]
+        version: u64,
+        #[serde(default)]
+        aggregate_version: usize,
+        #[serde(rename = "_computed", default)]
+        computed_fields: ComputedFields,
+    },
+}
+
+impl From<StoredView> for View {
+    fn from(stored: StoredView) -> Self {
+        match stored {
+            StoredView::Nested {
+                aggregate_type,
+                command_id,
+                dispense,
+                ttl_at,
+                version,
+                aggregate_version,
+                computed_fields,
+            } => Self {
+                aggregate_type,
+                command_id,
+                dispense_data: dispense,
+                ttl_at,
+                version,
+                aggregate_version,
+                computed_fields,
+            },
+            StoredView::Flat {
+                aggregate_type,
+                command_id,
+                dispense_data,
+                ttl_at,
+                version,
+                aggregate_version,
+                computed_fields,
+            } => Self {
+                aggregate_type,
+                command_id,
+                dispense_data,
+                ttl_at,
+                version,
+                aggregate_version,
+                computed_fields,
+            },
+        }
+    }
+}
+
+/// Dispense as listed by `GET /dispenses`, without drugs, notes or analysis data
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DispenseSummary {
     pub id: String,
-    pub dispense: Dispense,
+    pub status: DispenseStatus,
+    pub patient_name: Option<String>,
+    pub drug_count: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub priority: Priority,
+}
+
+/// Page of dispense summaries
+#[derive(Debug, Serialize)]
+pub struct DispenseSummaryEnvelope {
+    pub items: Vec<DispenseSummary>,
+    /// Dispense ID to pass as `cursor` for the next page, absent on the last one
+    pub next_cursor: Option<String>,
+}
+
+impl View {
+    pub fn to_summary(&self) -> DispenseSummary {
+        DispenseSummary {
+            id: self.id.to_string(),
+            status: self.status.clone(),
+            patient_name: self.patient_name.clone(),
+            drug_count: self.drugs.len(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            priority: self.priority.clone(),
+        }
+    }
+
+    /// Recompute the fields derived from the dispense state as of `now`
+    pub fn refresh_computed_fields(&mut self, now: DateTime<Utc>) {
+        let dispense = &self.dispense_data;
+        let computed = &mut self.computed_fields;
+
+        computed.days_since_started = (now - dispense.created_at).num_days().max(0) as u64;
+        computed.drug_count = dispense.drugs.len();
+        computed.total_quantity = dispense
+            .drugs
+            .iter()
+            .fold(0u32, |total, drug| total.saturating_add(drug.quantity));
+        computed.completion_percentage = dispense.completion_percentage();
+
+        let open = !matches!(
+            dispense.status,
+            DispenseStatus::Complete | DispenseStatus::Cancelled
+        );
+        computed.is_overdue = open && now > dispense.updated_at + Duration::hours(sla_hours());
+    }
+}
+
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> u64 {
+    (to - from).num_seconds().max(0) as u64
 }
 
 impl CqrsView<Dispense> for View {
     fn update(&mut self, event: &EventEnvelope<Dispense>) {
-        self.id.clone_from(&event.aggregate_id);
         self.aggregate_type = AGGREGATE_TYPE.to_string();
-        self.command_id = event
-            .metadata
-            .get("command_id")
-            .unwrap_or(&"".to_string())
-            .to_string();
-        self.dispense.apply(event.payload.clone());
+        self.command_id = EventMetadata::from(&event.metadata)
+            .command_id
+            .unwrap_or_default();
+        self.dispense_data.apply(event.payload.clone());
+        // From the envelope, so the view stays keyed and versioned like the event log even when
+        // it missed events
+        self.dispense_data.id = DispenseId::new(event.aggregate_id.as_str());
+        self.aggregate_version = event.sequence;
+
+        if let Event::DispenseStarted { created_at, .. } = &event.payload {
+            let expires_at = *created_at + Duration::days(pending_ttl_days());
+            self.ttl_at = Some(expires_at.timestamp() as u64);
+        }
+        if self.status != DispenseStatus::Pending {
+            self.ttl_at = None;
+        }
+
+        match &event.payload {
+            Event::PrescriptionAnalyzed { updated_at, .. } => {
+                self.computed_fields.time_to_analyze_seconds =
+                    Some(seconds_between(self.created_at, *updated_at));
+            }
+            Event::DispenseCompleted { updated_at, .. } => {
+                self.computed_fields.time_to_complete_seconds =
+                    Some(seconds_between(self.created_at, *updated_at));
+            }
+            _ => {}
+        }
+        self.refresh_computed_fields(Utc::now());
     }
 }
 
+fn pending_ttl_days() -> i64 {
+    env::var("PENDING_TTL_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_PENDING_TTL_DAYS)
+}
+
+fn sla_hours() -> i64 {
+    env::var("DISPENSE_SLA_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(DEFAULT_SLA_HOURS)
+}
+
+/// Table holding the views, used to mirror `ttl_at` as a top-level attribute
+struct TtlTable {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
 pub struct Query {
     repo: Arc<Box<dyn ViewRepository<View, Dispense>>>,
+    ttl_table: Option<TtlTable>,
+    event_store: Option<Arc<DispenseEventStore>>,
 }
 
 impl Query {
     pub fn new(repo: Arc<Box<dyn ViewRepository<View, Dispense>>>) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            ttl_table: None,
+            event_store: None,
+        }
     }
 
+    /// Write `ttl_at` to the view item so DynamoDB TTL can expire it
+    pub fn with_ttl(mut self, client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        self.ttl_table = Some(TtlTable {
+            client,
+            table: table.to_string(),
+        });
+        self
+    }
+
+    /// Rebuild from the event log a view missing its earlier events, see `earlier_events`
+    pub fn with_event_store(mut self, event_store: DispenseEventStore) -> Self {
+        self.event_store = Some(Arc::new(event_store));
+        self
+    }
+
+    /// Apply the events `UPDATE_BATCH_SIZE` at a time, saving the view after each batch
+    ///
+    /// A Lambda timing out mid-dispatch keeps the batches written so far, each one incrementing
+    /// the view `version`.
     async fn update(
         &self,
         dispense_id: &str,
         events: &[EventEnvelope<Dispense>],
+    ) -> Result<(), PersistenceError> {
+        for batch in events.chunks(UPDATE_BATCH_SIZE) {
+            self.update_batch(dispense_id, batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Reload and reapply the events while a concurrent update holds the view version
+    async fn update_batch(
+        &self,
+        dispense_id: &str,
+        events: &[EventEnvelope<Dispense>],
+    ) -> Result<(), PersistenceError> {
+        let mut retries = 0;
+        loop {
+            match self.try_update(dispense_id, events).await {
+                Err(PersistenceError::OptimisticLockError) if retries < MAX_UPDATE_RETRIES => {
+                    tokio::time::sleep(UPDATE_RETRY_DELAY * 2u32.pow(retries)).await;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn try_update(
+        &self,
+        dispense_id: &str,
+        events: &[EventEnvelope<Dispense>],
     ) -> Result<(), PersistenceError> {
         let (mut view, view_context) = match self.repo.load_with_context(dispense_id).await? {
             None => {
+                let mut view = View::default();
+                for event in self.earlier_events(dispense_id, events).await? {
+                    view.update(&event);
+                }
                 let view_context = ViewContext::new(dispense_id.to_string(), 0);
-                (Default::default(), view_context)
+                (view, view_context)
             }
             Some((view, context)) => (view, context),
         };
 
-        for event in events {
+        // Events the view already holds, dispatched again by a replay over existing views or
+        // resumed past its last checkpoint, would be applied twice (e.g. merged drugs)
+        let loaded_ttl_at = view.ttl_at;
+        let applied_version = view.aggregate_version;
+        let new_events: Vec<&EventEnvelope<Dispense>> = events
+            .iter()
+            .filter(|event| event.sequence > applied_version)
+            .collect();
+        if new_events.is_empty() {
+            return Ok(());
+        }
+        for event in new_events {
             view.update(event);
         }
 
-        self.repo.update_view(view, view_context).await
+        let ttl_at = view.ttl_at;
+        self.repo.update_view(view, view_context).await?;
+        // Only written when it changed, most events leaving it as is
+        if ttl_at == loaded_ttl_at {
+            return Ok(());
+        }
+        self.update_ttl(dispense_id, ttl_at).await
+    }
+
+    /// Events before `events` when the view is missing: DynamoDB TTL removes the views of stale
+    /// pending dispenses, which would otherwise be rebuilt from their later events only
+    async fn earlier_events(
+        &self,
+        dispense_id: &str,
+        events: &[EventEnvelope<Dispense>],
+    ) -> Result<Vec<EventEnvelope<Dispense>>, PersistenceError> {
+        let (Some(event_store), Some(first)) = (&self.event_store, events.first()) else {
+            return Ok(vec![]);
+        };
+        if first.sequence <= 1 {
+            return Ok(vec![]);
+        }
+
+        let history = event_store
+            .load_events(dispense_id)
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(history
+            .into_iter()
+            .filter(|event| event.sequence < first.sequence)
+            .collect())
+    }
+
+    async fn update_ttl(
+        &self,
+        dispense_id: &str,
+        ttl_at: Option<u64>,
+    ) -> Result<(), PersistenceError> {
+        let Some(ttl_table) = &self.ttl_table else {
+            return Ok(());
+        };
+
+        let request = ttl_table
+            .client
+            .update_item()
+            .table_name(&ttl_table.table)
+            .key("ViewId", AttributeValue::S(dispense_id.to_string()));
+
+        let request = match ttl_at {
+            Some(ttl_at) => request
+                .update_expression("SET ttl_at = :ttl_at")
+                .expression_attribute_values(":ttl_at", AttributeValue::N(ttl_at.to_string())),
+            None => request.update_expression("REMOVE ttl_at"),
+        };
+
+        request
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
     }
 }
 
+// TODO: remove when cqrs_es declares its traits with native async fn, see `impl Aggregate for
+// Dispense`
 #[async_trait]
 impl cqrs_es::Query<Dispense> for Query {
     async fn dispatch(&self, dispense_id: &str, events: &[EventEnvelope<Dispense>]) {
@@ -66,3 +434,368 @@ impl cqrs_es::Query<Dispense> for Query {
         }
     }
 }
+
+/// Page of views in table order
+pub struct ViewPage {
+    pub views: Vec<View>,
+    /// Dispense ID to pass as `cursor` for the next page, absent on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Pages through the dispenses view table
+pub struct ViewScanner {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl ViewScanner {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// Views after the `cursor` dispense, deleted dispenses left out (so a page may hold fewer
+    /// than `limit` views)
+    pub async fn list(
+        &self,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<ViewPage, PersistenceError> {
+        let start_key = cursor.map(|dispense_id| {
+            HashMap::from([(
+                "ViewId".to_string(),
+                AttributeValue::S(dispense_id.to_string()),
+            )])
+        });
+
+        let output = self
+            .client
+            .scan()
+            .table_name(&self.table)
+            .set_exclusive_start_key(start_key)
+            .limit(limit)
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        let mut views = Vec::new();
+        for item in output.items() {
+            let Some(AttributeValue::B(payload)) = item.get("Payload") else {
+                continue;
+            };
+            let view: View = serde_json::from_slice(payload.as_ref())
+                .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))?;
+            if !view.deleted {
+                views.push(view);
+            }
+        }
+        let next_cursor = output
+            .last_evaluated_key()
+            .and_then(|key| key.get("ViewId"))
+            .and_then(|view_id| view_id.as_s().ok())
+            .cloned();
+
+        Ok(ViewPage { views, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use cqrs_es::Query as _;
+
+    use super::*;
+    use crate::dispenses::{
+        aggregate::{
+            Address, DrugInteraction, FulfillmentMethod, InteractionSeverity, PrescriptionRecord,
+            StatusTransition,
+        },
+        testing::field_changes,
+    };
+
+    /// In-memory views, recording the `aggregate_version` of each written view
+    #[derive(Clone, Default)]
+    struct RecordingViewRepository {
+        views: Arc<Mutex<HashMap<String, (View, i64)>>>,
+        written_versions: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl ViewRepository<View, Dispense> for RecordingViewRepository {
+        async fn load(&self, view_id: &str) -> Result<Option<View>, PersistenceError> {
+            Ok(self.load_with_context(view_id).await?.map(|(view, _)| view))
+        }
+
+        async fn load_with_context(
+            &self,
+            view_id: &str,
+        ) -> Result<Option<(View, ViewContext)>, PersistenceError> {
+            Ok(self
+                .views
+                .lock()
+                .unwrap()
+                .get(view_id)
+                .map(|(view, version)| {
+                    (
+                        view.clone(),
+                        ViewContext::new(view_id.to_string(), *version),
+                    )
+                }))
+        }
+
+        async fn update_view(
+            &self,
+            mut view: View,
+            context: ViewContext,
+        ) -> Result<(), PersistenceError> {
+            view.version = (context.version + 1) as u64;
+            self.written_versions
+                .lock()
+                .unwrap()
+                .push(view.aggregate_version);
+            self.views
+                .lock()
+                .unwrap()
+                .insert(context.view_instance_id, (view, context.version + 1));
+            Ok(())
+        }
+    }
+
+    fn envelope(sequence: usize, payload: Event) -> EventEnvelope<Dispense> {
+        EventEnvelope {
+            aggregate_id: "test-dispense".to_string(),
+            sequence,
+            payload,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn dispense_started(created_at: DateTime<Utc>) -> Event {
+        Event::DispenseStarted {
+            id: "test-dispense".to_string(),
+            created_at,
+            status: DispenseStatus::Pending,
+            original_dispense_id: None,
+            pharmacy_branch_id: None,
+            priority: Priority::default(),
+            notes: None,
+            expires_at: None,
+            actor_id: None,
+        }
+    }
+
+    #[test]
+    fn ttl_set_on_creation_and_cleared_on_upload() {
+        let created_at = Utc::now();
+        let mut view = View::default();
+
+        view.update(&envelope(1, dispense_started(created_at)));
+        assert_eq!(
+            view.ttl_at,
+            Some((created_at + Duration::days(DEFAULT_PENDING_TTL_DAYS)).timestamp() as u64)
+        );
+
+        view.update(&envelope(
+            2,
+            Event::PrescriptionUploaded {
+                id: "test-dispense".to_string(),
+                prescription_id: "test-prescription".to_string(),
+                url: "s3://prescriptions/test-dispense/prescription.jpg".to_string(),
+                updated_at: Utc::now(),
+                expires_at: None,
+                file_size_bytes: None,
+                actor_id: None,
+            },
+        ));
+        assert_eq!(view.ttl_at, None);
+    }
+
+    #[test]
+    fn computed_fields_follow_the_events() {
+        let created_at = Utc::now() - Duration::days(3);
+        let analyzed_at = created_at + Duration::days(1);
+        let completed_at = created_at + Duration::days(2);
+        let mut view = View::default();
+
+        view.update(&envelope(1, dispense_started(created_at)));
+        assert_eq!(view.computed_fields.time_to_analyze_seconds, None);
+        assert_eq!(view.computed_fields.time_to_complete_seconds, None);
+        assert!(view.computed_fields.is_overdue);
+
+        view.update(&envelope(
+            2,
+            Event::PrescriptionAnalyzed {
+                id: "test-dispense".to_string(),
+                analysis_data: r#"{"confidence_score":0.95}"#.to_string(),
+                analysis_version: 1,
+                model_id: "test-model".to_string(),
+                quality_score: 0.95,
+                prescription_page_count: Some(1),
+                updated_at: analyzed_at,
+                actor_id: None,
+            },
+        ));
+        assert_eq!(view.computed_fields.time_to_analyze_seconds, Some(86_400));
+        assert_eq!(view.computed_fields.time_to_complete_seconds, None);
+        // Not updated for two days
+        assert!(view.computed_fields.is_overdue);
+
+        view.update(&envelope(
+            3,
+            Event::DispenseCompleted {
+                id: "test-dispense".to_string(),
+                updated_at: completed_at,
+                actor_id: None,
+            },
+        ));
+        assert_eq!(view.computed_fields.time_to_analyze_seconds, Some(86_400));
+        assert_eq!(view.computed_fields.time_to_complete_seconds, Some(172_800));
+        assert!(!view.computed_fields.is_overdue);
+    }
+
+    #[tokio::test]
+    async fn dispatch_saves_the_view_every_batch() {
+        let repo = RecordingViewRepository::default();
+        let query = Query::new(Arc::new(Box::new(repo.clone())));
+        let created_at = Utc::now();
+        let mut events = vec![envelope(1, dispense_started(created_at))];
+        events.extend((2..=500).map(|sequence| {
+            envelope(
+                sequence,
+                Event::PrescriptionThumbnailSet {
+                    id: "test-dispense".to_string(),
+                    thumbnail_url: format!("s3://prescriptions/test-dispense/{}.jpg", sequence),
+                    updated_at: created_at,
+                    actor_id: None,
+                },
+            )
+        }));
+
+        query.dispatch("test-dispense", &events).await;
+
+        assert_eq!(
+            *repo.written_versions.lock().unwrap(),
+            [100, 200, 300, 400, 500]
+        );
+        let view = repo.load("test-dispense").await.unwrap().unwrap();
+        assert_eq!(view.version, 5);
+        assert_eq!(view.aggregate_version, 500);
+        assert_eq!(view.id, "test-dispense");
+        assert_eq!(
+            view.thumbnail_url.as_deref(),
+            Some("s3://prescriptions/test-dispense/500.jpg")
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_skips_events_already_applied() {
+        let repo = RecordingViewRepository::default();
+        let query = Query::new(Arc::new(Box::new(repo.clone())));
+        let created_at = Utc::now();
+        let thumbnail_set = |sequence: usize| {
+            envelope(
+                sequence,
+                Event::PrescriptionThumbnailSet {
+                    id: "test-dispense".to_string(),
+                    thumbnail_url: format!("s3://prescriptions/test-dispense/{}.jpg", sequence),
+                    updated_at: created_at,
+                    actor_id: None,
+                },
+            )
+        };
+        let events: Vec<_> = std::iter::once(envelope(1, dispense_started(created_at)))
+            .chain((2..=4).map(thumbnail_set))
+            .collect();
+
+        query.dispatch("test-dispense", &events[..3]).await;
+        // A replay over the view, then one resumed from an earlier checkpoint
+        query.dispatch("test-dispense", &events[..3]).await;
+        query.dispatch("test-dispense", &events[1..]).await;
+
+        assert_eq!(*repo.written_versions.lock().unwrap(), [3, 4]);
+        let view = repo.load("test-dispense").await.unwrap().unwrap();
+        assert_eq!(view.aggregate_version, 4);
+        assert_eq!(
+            view.thumbnail_url.as_deref(),
+            Some("s3://prescriptions/test-dispense/4.jpg")
+        );
+    }
+
+    #[test]
+    fn view_json_round_trip() {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut dispense = Dispense::test_dispense(DispenseStatus::Ready);
+        dispense.created_at = at;
+        dispense.updated_at = at;
+        let mut view = View {
+            aggregate_type: AGGREGATE_TYPE.to_string(),
+            command_id: "test-dispense".to_string(),
+            dispense_data: dispense.data,
+            ttl_at: None,
+            version: 2,
+            aggregate_version: 5,
+            computed_fields: ComputedFields::default(),
+        };
+        view.refresh_computed_fields(at);
+        let json = serde_json::to_string(&view).unwrap();
+
+        assert_eq!(view.clone(), view);
+        assert_eq!(serde_json::from_str::<View>(&json).unwrap(), view);
+        insta::assert_json_snapshot!(view);
+    }
+
+    #[test]
+    fn view_field_change_is_not_equal() {
+        let at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut dispense_data = Dispense::test_dispense(DispenseStatus::Ready).data;
+        dispense_data.drug_interaction_warnings = vec![DrugInteraction {
+            drug_a: "drug-1".to_string(),
+            drug_b: "drug-2".to_string(),
+            severity: InteractionSeverity::Major,
+            description: "Increased bleeding risk".to_string(),
+        }];
+        let drugs = dispense_data.drugs.clone();
+        dispense_data.drug_ledger.record(&[], &drugs, at);
+        dispense_data.fulfillment_method = Some(FulfillmentMethod::MailOrder {
+            address: Address {
+                street: "1 Main St".to_string(),
+                city: "Springfield".to_string(),
+                state: "CA".to_string(),
+                zip: "90001".to_string(),
+                country: "US".to_string(),
+            },
+            tracking_number: None,
+        });
+        dispense_data
+            .prescription_history
+            .push_back(PrescriptionRecord {
+                prescription_id: "test-prescription".to_string(),
+                url: "s3://bucket/test-prescription.jpg".to_string(),
+                uploaded_at: at,
+                replaced_at: None,
+            });
+        dispense_data.status_history.push_back(StatusTransition {
+            from: DispenseStatus::Pending,
+            to: DispenseStatus::Ready,
+            at,
+            triggered_by_event: "DrugsAdded".to_string(),
+        });
+        let mut view = View {
+            aggregate_type: AGGREGATE_TYPE.to_string(),
+            command_id: "test-dispense".to_string(),
+            dispense_data,
+            ttl_at: Some(at.timestamp() as u64),
+            version: 2,
+            aggregate_version: 5,
+            computed_fields: ComputedFields::default(),
+        };
+        view.refresh_computed_fields(at);
+
+        for (field, changed) in field_changes(&view) {
+            assert_ne!(changed, view, "{}", field);
+        }
+    }
+}
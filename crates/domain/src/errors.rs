@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,10 +7,13 @@ pub enum Error {
     #[error("Entity not found: {entity}")]
     NotFound { entity: String },
 
-    #[error("Uniqueness conflict: {field}")]
+    #[error("Duplicate value for {field}")]
     Uniqueness { field: String },
 
-    #[error("Forbidden action")]
+    #[error("Authentication required")]
+    Unauthorized,
+
+    #[error("This action is not allowed")]
     Forbidden,
 
     #[error("Invalid state transition from {from} to {to}")]
@@ -16,4 +21,235 @@ pub enum Error {
 
     #[error("Validation error: {message}")]
     Validation { message: String },
+
+    #[error("Too many requests, retry after {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
+
+    /// Unexpected failure, with the error that caused it when there is one
+    #[error("Internal error: {message}")]
+    Internal {
+        message: String,
+        #[source]
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+
+    #[error("Cannot parse {context}: {source}")]
+    ParseError {
+        context: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// Failed call to an AWS or third-party service
+    #[error("{service} error: {message}")]
+    ExternalService { service: String, message: String },
+}
+
+impl Error {
+    /// Stable machine-readable code for API responses
+    pub fn display_code(&self) -> &'static str {
+        match self {
+            Error::NotFound { .. } => "not_found",
+            Error::Uniqueness { .. } => "uniqueness_conflict",
+            Error::Unauthorized => "unauthorized",
+            Error::Forbidden => "forbidden",
+            Error::InvalidStateTransition { .. } => "invalid_state_transition",
+            Error::Validation { .. } => "validation_error",
+            Error::RateLimited { .. } => "rate_limited",
+            Error::Internal { .. } => "internal_error",
+            Error::ParseError { .. } => "parse_error",
+            Error::ExternalService { .. } => "external_service_error",
+        }
+    }
+
+    /// HTTP status of API responses carrying the error
+    pub fn http_status_code(&self) -> u16 {
+        match self {
+            Error::NotFound { .. } => 404,
+            Error::Uniqueness { .. } => 409,
+            Error::Unauthorized => 401,
+            Error::Forbidden => 403,
+            Error::InvalidStateTransition { .. } | Error::Validation { .. } => 422,
+            Error::RateLimited { .. } => 429,
+            Error::Internal { .. } | Error::ParseError { .. } => 500,
+            Error::ExternalService { .. } => 502,
+        }
+    }
+
+    /// Whether the error is a 5xx, on the service rather than the caller
+    pub fn is_server_error(&self) -> bool {
+        self.http_status_code() >= 500
+    }
+
+    /// Message of API responses: server errors get a generic one, their own message can name
+    /// tables and resources and is only logged
+    pub fn client_message(&self) -> String {
+        match self {
+            Error::Internal { .. } | Error::ParseError { .. } => {
+                "Internal server error".to_string()
+            }
+            Error::ExternalService { .. } => "External service error".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Structured fields of the error, for clients not parsing the message
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            Error::InvalidStateTransition { from, to } => Some(json!({ "from": from, "to": to })),
+            Error::RateLimited { retry_after_secs } => {
+                Some(json!({ "retry_after_secs": retry_after_secs }))
+            }
+            Error::ExternalService { service, .. } => Some(json!({ "service": service })),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::ParseError {
+            context: "JSON".to_string(),
+            source,
+        }
+    }
+}
+
+impl From<aws_sdk_dynamodb::Error> for Error {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        Error::ExternalService {
+            service: "DynamoDB".to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Serialized as its `ErrorBody`
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorBody::from(self).serialize(serializer)
+    }
+}
+
+/// JSON body of API error responses
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ErrorBody {
+    pub error_code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl From<&Error> for ErrorBody {
+    fn from(error: &Error) -> Self {
+        Self {
+            error_code: error.display_code().to_string(),
+            message: error.client_message(),
+            details: error.details(),
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+mod http {
+    use axum::{
+        http::{header, HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+        Json,
+    };
+
+    use super::{Error, ErrorBody};
+
+    impl Error {
+        pub fn status_code(&self) -> StatusCode {
+            StatusCode::from_u16(self.http_status_code())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    impl From<Error> for (StatusCode, Json<ErrorBody>) {
+        fn from(error: Error) -> Self {
+            (error.status_code(), Json(ErrorBody::from(&error)))
+        }
+    }
+
+    impl IntoResponse for Error {
+        fn into_response(self) -> Response {
+            if self.is_server_error() {
+                tracing::error!("{}", self);
+            }
+            let retry_after = match &self {
+                Error::RateLimited { retry_after_secs } => Some(*retry_after_secs),
+                _ => None,
+            };
+
+            let mut response = <(StatusCode, Json<ErrorBody>)>::from(self).into_response();
+            if let Some(retry_after) = retry_after {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_errors() -> Vec<Error> {
+        vec![
+            Error::NotFound {
+                entity: "Dispense".to_string(),
+            },
+            Error::Uniqueness {
+                field: "prescription_id".to_string(),
+            },
+            Error::Unauthorized,
+            Error::Forbidden,
+            Error::InvalidStateTransition {
+                from: "Pending".to_string(),
+                to: "Complete".to_string(),
+            },
+            Error::Validation {
+                message: "Patient name is required".to_string(),
+            },
+            Error::RateLimited {
+                retry_after_secs: 30,
+            },
+            Error::Internal {
+                message: "dispense_events table not found".to_string(),
+                source: Some(Box::new(std::io::Error::other("connection reset"))),
+            },
+            Error::ParseError {
+                context: "dispense_events item".to_string(),
+                source: serde_json::from_str::<Value>("{").unwrap_err(),
+            },
+            Error::ExternalService {
+                service: "DynamoDB".to_string(),
+                message: "arn:aws:dynamodb:eu-west-1:123456789012:table/dispenses".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn json_of_each_variant_is_stable() {
+        for error in all_errors() {
+            insta::assert_json_snapshot!(error.display_code(), error);
+        }
+    }
+
+    #[test]
+    fn server_errors_keep_their_message_out_of_the_body() {
+        for error in all_errors() {
+            let body = ErrorBody::from(&error);
+
+            if error.is_server_error() {
+                assert_ne!(body.message, error.to_string());
+            } else {
+                assert_eq!(body.message, error.to_string());
+            }
+        }
+    }
 }
@@ -1,8 +1,19 @@
 use derive_new::new;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::errors::Error;
+
+/// First byte of gzipped Kinesis data, plain JSON events starting with `{` (`0x7b`)
+pub const GZIP_MAGIC: u8 = 0x1f;
 
 /// Domain events formatted consistently for cross-team sharing
-#[derive(Clone, Debug, Serialize, Deserialize, new)]
+///
+/// Sent as JSON between the publisher, Kinesis and the projectors. Unknown fields are ignored
+/// (no `deny_unknown_fields`) so a producer can add a field before every consumer is redeployed.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, new)]
 pub struct DomainEvent {
     /// The Aggregate ID
     pub id: String,
@@ -25,3 +36,250 @@ pub struct DomainEvent {
     /// The event metadata (JSON)
     pub metadata: String,
 }
+
+impl DomainEvent {
+    /// Known keys of the metadata JSON, missing when the metadata is not a JSON object
+    pub fn parsed_metadata(&self) -> EventMetadata {
+        let metadata: HashMap<String, String> = match serde_json::from_str::<Value>(&self.metadata)
+        {
+            Ok(Value::Object(metadata)) => metadata
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    Value::String(value) => Some((key, value)),
+                    _ => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        EventMetadata::from(&metadata)
+    }
+
+    /// Gzipped JSON of the event, starting with `GZIP_MAGIC` (the gzip header)
+    pub fn to_compressed_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish().map_err(|e| Error::Internal {
+            message: "Cannot compress event".to_string(),
+            source: Some(Box::new(e)),
+        })
+    }
+
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(serde_json::from_reader(GzDecoder::new(bytes))?)
+    }
+
+    /// Event of Kinesis record data, gzipped or plain JSON as told by its first byte
+    pub fn from_kinesis_data(data: &[u8]) -> Result<Self, Error> {
+        match data.first() {
+            Some(&GZIP_MAGIC) => Self::from_compressed_bytes(data),
+            _ => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
+
+/// Command metadata keys set by the Lambdas, see `execute_with_metadata`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EventMetadata {
+    pub command_id: Option<String>,
+    /// Lambda request that handled the command
+    pub request_id: Option<String>,
+    /// Shared by the events of one workflow
+    pub correlation_id: Option<String>,
+    /// Command or event that led to this event
+    pub causation_id: Option<String>,
+    /// Caller, e.g. the JWT subject
+    pub actor_id: Option<String>,
+    pub pharmacy_id: Option<String>,
+    /// EHR system reference of a started dispense
+    pub external_reference_id: Option<String>,
+}
+
+impl From<&HashMap<String, String>> for EventMetadata {
+    fn from(metadata: &HashMap<String, String>) -> Self {
+        let get = |key: &str| metadata.get(key).cloned();
+
+        Self {
+            command_id: get("command_id"),
+            request_id: get("request_id"),
+            correlation_id: get("correlation_id"),
+            causation_id: get("causation_id"),
+            actor_id: get("actor_id"),
+            pharmacy_id: get("pharmacy_id"),
+            external_reference_id: get("external_reference_id"),
+        }
+    }
+}
+
+impl EventMetadata {
+    /// Metadata map with the keys that are set
+    pub fn to_map(&self) -> HashMap<String, String> {
+        [
+            ("command_id", &self.command_id),
+            ("request_id", &self.request_id),
+            ("correlation_id", &self.correlation_id),
+            ("causation_id", &self.causation_id),
+            ("actor_id", &self.actor_id),
+            ("pharmacy_id", &self.pharmacy_id),
+            ("external_reference_id", &self.external_reference_id),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain_event() -> DomainEvent {
+        DomainEvent::new(
+            "01HZX3Q4N5V6W7X8Y9Z0ABCDEF".to_string(),
+            "Dispense".to_string(),
+            2,
+            "Dispense:PrescriptionUploaded".to_string(),
+            "1.0".to_string(),
+            r#"{"type":"PrescriptionUploaded","id":"01HZX3Q4N5V6W7X8Y9Z0ABCDEF"}"#.to_string(),
+            r#"{"command_id":"01HZX3Q4N5V6W7X8Y9Z0COMMND"}"#.to_string(),
+        )
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let event = domain_event();
+
+        let json = serde_json::to_string(&event).unwrap();
+
+        assert_eq!(serde_json::from_str::<DomainEvent>(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn kinesis_record_data() {
+        let data = br#"{
+            "id": "01HZX3Q4N5V6W7X8Y9Z0ABCDEF",
+            "entity": "Dispense",
+            "sequence": 1,
+            "event_type": "Dispense:Started",
+            "event_version": "1.0",
+            "payload": "{\"type\":\"DispenseStarted\"}",
+            "metadata": "{\"pharmacy_id\":\"pharmacy-1\"}"
+        }"#;
+
+        let event = DomainEvent::from_kinesis_data(data).unwrap();
+
+        assert_eq!(event.id, "01HZX3Q4N5V6W7X8Y9Z0ABCDEF");
+        assert_eq!(event.entity, "Dispense");
+        assert_eq!(event.sequence, 1);
+        assert_eq!(event.event_type, "Dispense:Started");
+        assert_eq!(event.event_version, "1.0");
+        assert_eq!(event.payload, r#"{"type":"DispenseStarted"}"#);
+        assert_eq!(
+            event.parsed_metadata().pharmacy_id.as_deref(),
+            Some("pharmacy-1")
+        );
+    }
+
+    #[test]
+    fn missing_metadata_keys_are_none() {
+        let mut event = domain_event();
+        event.metadata = "{}".to_string();
+
+        assert_eq!(event.parsed_metadata(), EventMetadata::default());
+
+        event.metadata = "not json".to_string();
+
+        assert_eq!(event.parsed_metadata(), EventMetadata::default());
+    }
+
+    #[test]
+    fn missing_field_is_an_error() {
+        let result = serde_json::from_str::<DomainEvent>(r#"{"id": "01HZX3Q4N5V6W7X8Y9Z0ABCDEF"}"#);
+
+        assert!(result.is_err());
+    }
+
+    // A producer may add a field before the consumers are redeployed, see `DomainEvent`
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let mut json = serde_json::to_value(domain_event()).unwrap();
+        json["partition_key"] = Value::from("01HZX3Q4N5V6W7X8Y9Z0ABCDEF");
+
+        assert_eq!(
+            serde_json::from_value::<DomainEvent>(json).unwrap(),
+            domain_event()
+        );
+    }
+
+    #[test]
+    fn compressed_kinesis_record_data() {
+        let event = domain_event();
+
+        let data = event.to_compressed_bytes().unwrap();
+
+        assert_eq!(data[0], GZIP_MAGIC);
+        assert_eq!(DomainEvent::from_kinesis_data(&data).unwrap(), event);
+    }
+
+    /// `PrescriptionAnalyzed` event of a multi-page prescription, its `analysis_data` a few KB
+    fn analyzed_event() -> DomainEvent {
+        let lines: Vec<Value> = (1..=40)
+            .map(|i| {
+                let text = format!(
+                    "Amoxicillin {}mg capsule, take 1 capsule by mouth 3 times daily",
+                    i * 25
+                );
+                serde_json::json!({
+                    "page": (i - 1) / 10 + 1,
+                    "text": text,
+                    "confidence": 0.9 + (i % 10) as f64 / 100.0,
+                    "bounding_box": {
+                        "left": 0.1,
+                        "top": i as f64 / 50.0,
+                        "width": 0.8,
+                        "height": 0.02,
+                    },
+                })
+            })
+            .collect();
+        let analysis_data = serde_json::json!({
+            "confidence_score": 0.95,
+            "page_count": 4,
+            "lines": lines,
+        });
+        let payload = serde_json::json!({
+            "type": "PrescriptionAnalyzed",
+            "id": "01HZX3Q4N5V6W7X8Y9Z0ABCDEF",
+            "analysis_data": analysis_data.to_string(),
+            "analysis_version": 1,
+            "model_id": "anthropic.claude-3-sonnet",
+            "quality_score": 0.95,
+            "prescription_page_count": 4,
+            "updated_at": "2024-01-01T00:00:00Z",
+            "actor_id": null,
+        });
+
+        DomainEvent {
+            event_type: "Dispense:PrescriptionAnalyzed".to_string(),
+            payload: payload.to_string(),
+            ..domain_event()
+        }
+    }
+
+    #[test]
+    fn compression_reduces_analysis_payload_by_60_percent() {
+        let event = analyzed_event();
+        let json = serde_json::to_vec(&event).unwrap();
+        assert!(json.len() > 4 * 1024, "{} bytes", json.len());
+
+        let data = event.to_compressed_bytes().unwrap();
+
+        assert!(
+            data.len() * 100 <= json.len() * 40,
+            "{} bytes compressed from {}",
+            data.len(),
+            json.len()
+        );
+        assert_eq!(DomainEvent::from_kinesis_data(&data).unwrap(), event);
+    }
+}
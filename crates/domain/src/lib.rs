@@ -3,11 +3,32 @@
 /// Dispense aggregate
 pub mod dispenses;
 
+/// Prescriber aggregate
+pub mod prescribers;
+
+/// Event replay checkpoints
+pub mod replay;
+
+/// Event log backups to S3
+pub mod backups;
+
+/// Table names configuration (Secrets Manager or environment)
+pub mod config;
+
 /// Domain errors
 pub mod errors;
 
 /// Domain events wrapper
 pub mod event;
 
-pub use errors::Error;
-pub use event::DomainEvent;
+/// OpenTelemetry metrics
+pub mod metrics;
+
+/// In-memory cache of view repositories
+pub mod view_cache;
+
+/// `#[serde(with = "...")]` formats of timestamps stored in DynamoDB
+pub mod serde_utils;
+
+pub use errors::{Error, ErrorBody};
+pub use event::{DomainEvent, EventMetadata, GZIP_MAGIC};
@@ -0,0 +1,43 @@
+use opentelemetry::{global, metrics::Histogram, KeyValue};
+use std::{sync::OnceLock, time::Duration};
+
+/// Called with the command type and outcome of every handled command
+pub type CommandObserver = fn(&'static str, &'static str);
+
+static COMMAND_OBSERVER: OnceLock<CommandObserver> = OnceLock::new();
+
+/// Register an extra sink for command outcomes (e.g. Prometheus counters), first one wins
+pub fn set_command_observer(observer: CommandObserver) {
+    let _ = COMMAND_OBSERVER.set(observer);
+}
+
+/// Recorded through the global meter provider, a no-op until one is installed
+fn command_handling_duration() -> &'static Histogram<f64> {
+    static HISTOGRAM: OnceLock<Histogram<f64>> = OnceLock::new();
+    HISTOGRAM.get_or_init(|| {
+        global::meter("domain")
+            .f64_histogram("command_handling_duration_ms")
+            .with_description("Aggregate command handling latency")
+            .with_unit("ms")
+            .init()
+    })
+}
+
+/// `outcome` is `success`, `validation_error` or `other_error`
+pub fn record_command_duration(
+    command_type: &'static str,
+    outcome: &'static str,
+    elapsed: Duration,
+) {
+    command_handling_duration().record(
+        elapsed.as_secs_f64() * 1000.0,
+        &[
+            KeyValue::new("command_type", command_type),
+            KeyValue::new("outcome", outcome),
+        ],
+    );
+
+    if let Some(observer) = COMMAND_OBSERVER.get() {
+        observer(command_type, outcome);
+    }
+}
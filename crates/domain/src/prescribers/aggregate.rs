@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use cqrs_es::Aggregate;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+
+use super::{Command, Event};
+
+/// Prescriber aggregate
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Prescriber {
+    pub prescriber_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    pub name: String,
+    pub license_number: String,
+    pub state: String,
+    pub npi: Option<String>,
+
+    // Controlled substances
+    pub dea_number: Option<String>,
+    pub can_prescribe_controlled: bool,
+
+    pub is_active: bool,
+}
+
+pub const AGGREGATE_TYPE: &str = "Prescriber";
+
+#[derive(Clone, Default)]
+pub struct Services {}
+
+#[async_trait]
+impl Aggregate for Prescriber {
+    type Command = Command;
+    type Event = Event;
+    type Error = Error;
+    type Services = Services;
+
+    fn aggregate_type() -> String {
+        AGGREGATE_TYPE.to_string()
+    }
+
+    async fn handle(
+        &self,
+        command: Self::Command,
+        _services: &Self::Services,
+    ) -> Result<Vec<Self::Event>, Self::Error> {
+        match command {
+            Command::RegisterPrescriber {
+                prescriber_id,
+                name,
+                license_number,
+                state,
+                npi,
+            } => {
+                self.validate_new()?;
+
+                Ok(vec![Event::PrescriberRegistered {
+                    id: prescriber_id,
+                    name,
+                    license_number,
+                    state,
+                    npi,
+                    created_at: Utc::now(),
+                }])
+            }
+
+            Command::UpdatePrescriberInfo {
+                name,
+                license_number,
+                state,
+                npi,
+            } => {
+                self.validate_existing()?;
+
+                Ok(vec![Event::PrescriberInfoUpdated {
+                    id: self.prescriber_id.clone(),
+                    name,
+                    license_number,
+                    state,
+                    npi,
+                    updated_at: Utc::now(),
+                }])
+            }
+
+            Command::GrantControlledSubstanceAuth { dea_number } => {
+                self.validate_existing()?;
+                validate_dea_number(&dea_number)?;
+
+                Ok(vec![Event::ControlledSubstanceAuthGranted {
+                    id: self.prescriber_id.clone(),
+                    dea_number,
+                    updated_at: Utc::now(),
+                }])
+            }
+
+            Command::RevokeControlledSubstanceAuth => {
+                self.validate_existing()?;
+
+                Ok(vec![Event::ControlledSubstanceAuthRevoked {
+                    id: self.prescriber_id.clone(),
+                    updated_at: Utc::now(),
+                }])
+            }
+
+            Command::DeactivatePrescriber => {
+                self.validate_existing()?;
+
+                Ok(vec![Event::PrescriberDeactivated {
+                    id: self.prescriber_id.clone(),
+                    updated_at: Utc::now(),
+                }])
+            }
+        }
+    }
+
+    fn apply(&mut self, event: Self::Event) {
+        match event {
+            Event::PrescriberRegistered {
+                id,
+                name,
+                license_number,
+                state,
+                npi,
+                created_at,
+            } => {
+                self.prescriber_id = id;
+                self.name = name;
+                self.license_number = license_number;
+                self.state = state;
+                self.npi = npi;
+                self.created_at = created_at;
+                self.updated_at = created_at;
+                self.is_active = true;
+            }
+
+            Event::PrescriberInfoUpdated {
+                name,
+                license_number,
+                state,
+                npi,
+                updated_at,
+                ..
+            } => {
+                self.name = name;
+                self.license_number = license_number;
+                self.state = state;
+                self.npi = npi;
+                self.updated_at = updated_at;
+            }
+
+            Event::ControlledSubstanceAuthGranted {
+                dea_number,
+                updated_at,
+                ..
+            } => {
+                self.dea_number = Some(dea_number);
+                self.can_prescribe_controlled = true;
+                self.updated_at = updated_at;
+            }
+
+            Event::ControlledSubstanceAuthRevoked { updated_at, .. } => {
+                self.can_prescribe_controlled = false;
+                self.updated_at = updated_at;
+            }
+
+            Event::PrescriberDeactivated { updated_at, .. } => {
+                self.is_active = false;
+                self.can_prescribe_controlled = false;
+                self.updated_at = updated_at;
+            }
+        }
+    }
+}
+
+impl Prescriber {
+    fn validate_new(&self) -> Result<(), Error> {
+        if !self.prescriber_id.is_empty() {
+            return Err(Error::Uniqueness {
+                field: "prescriber_id".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_existing(&self) -> Result<(), Error> {
+        if self.prescriber_id.is_empty() {
+            return Err(Error::NotFound {
+                entity: AGGREGATE_TYPE.to_string(),
+            });
+        }
+        if !self.is_active {
+            return Err(Error::Forbidden);
+        }
+        Ok(())
+    }
+}
+
+/// DEA numbers are two uppercase letters followed by seven digits
+fn validate_dea_number(dea_number: &str) -> Result<(), Error> {
+    let bytes = dea_number.as_bytes();
+    let valid = bytes.len() == 9
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..].iter().all(u8::is_ascii_digit);
+
+    if !valid {
+        return Err(Error::Validation {
+            message: format!("Invalid DEA number: {}", dea_number),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Command {
+    /// Register a new prescriber
+    RegisterPrescriber {
+        prescriber_id: String,
+        name: String,
+        license_number: String,
+        state: String,
+        npi: Option<String>,
+    },
+
+    /// Update prescriber details
+    UpdatePrescriberInfo {
+        name: String,
+        license_number: String,
+        state: String,
+        npi: Option<String>,
+    },
+
+    /// Authorize prescribing controlled substances under a DEA number
+    GrantControlledSubstanceAuth { dea_number: String },
+
+    /// Withdraw controlled substance authorization
+    RevokeControlledSubstanceAuth,
+
+    /// Deactivate the prescriber
+    DeactivatePrescriber,
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+use cqrs_es::{
+    persist::{PersistedEventStore, ViewRepository},
+    CqrsFramework,
+};
+use dynamo_es::{DynamoEventRepository, DynamoViewRepository};
+use super::{Prescriber, Query, Services, View};
+use crate::config::Config;
+
+pub fn init(
+    client: aws_sdk_dynamodb::Client,
+    repo: Arc<Box<dyn ViewRepository<View, Prescriber>>>,
+) -> Arc<CqrsFramework<Prescriber, PersistedEventStore<DynamoEventRepository, Prescriber>>> {
+    let config = Config::current();
+
+    let store: PersistedEventStore<DynamoEventRepository, Prescriber> =
+        PersistedEventStore::new_snapshot_store(
+            DynamoEventRepository::new(client)
+                .with_tables(&config.event_log_table, &config.snapshots_table),
+            5,
+        );
+
+    let query = Box::new(Query::new(repo));
+
+    Arc::new(CqrsFramework::new(store, vec![query], Services::default()))
+}
+
+pub fn init_repo(
+    client: aws_sdk_dynamodb::Client,
+) -> Arc<Box<dyn ViewRepository<View, Prescriber>>> {
+    let view_table = Config::current().prescribers_view_table;
+
+    Arc::new(Box::new(DynamoViewRepository::new(&view_table, client)))
+}
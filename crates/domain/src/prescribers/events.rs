@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use cqrs_es::DomainEvent;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type")]
+pub enum Event {
+    PrescriberRegistered {
+        id: String,
+        name: String,
+        license_number: String,
+        state: String,
+        npi: Option<String>,
+        created_at: DateTime<Utc>,
+    },
+
+    PrescriberInfoUpdated {
+        id: String,
+        name: String,
+        license_number: String,
+        state: String,
+        npi: Option<String>,
+        updated_at: DateTime<Utc>,
+    },
+
+    ControlledSubstanceAuthGranted {
+        id: String,
+        dea_number: String,
+        updated_at: DateTime<Utc>,
+    },
+
+    ControlledSubstanceAuthRevoked {
+        id: String,
+        updated_at: DateTime<Utc>,
+    },
+
+    PrescriberDeactivated {
+        id: String,
+        updated_at: DateTime<Utc>,
+    },
+}
+
+impl DomainEvent for Event {
+    fn event_type(&self) -> String {
+        match self {
+            Event::PrescriberRegistered { .. } => "Prescriber:Registered".to_string(),
+            Event::PrescriberInfoUpdated { .. } => "Prescriber:InfoUpdated".to_string(),
+            Event::ControlledSubstanceAuthGranted { .. } => {
+                "Prescriber:ControlledSubstanceAuthGranted".to_string()
+            }
+            Event::ControlledSubstanceAuthRevoked { .. } => {
+                "Prescriber:ControlledSubstanceAuthRevoked".to_string()
+            }
+            Event::PrescriberDeactivated { .. } => "Prescriber:Deactivated".to_string(),
+        }
+    }
+
+    fn event_version(&self) -> String {
+        "1.0".to_string()
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegisterPrescriberInput {
+    pub name: String,
+    pub license_number: String,
+    pub state: String,
+    pub npi: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UpdatePrescriberInfoInput {
+    pub name: String,
+    pub license_number: String,
+    pub state: String,
+    pub npi: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrantControlledSubstanceAuthInput {
+    pub dea_number: String,
+}
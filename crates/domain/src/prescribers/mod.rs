@@ -0,0 +1,26 @@
+/// Prescriber aggregate
+pub mod aggregate;
+
+/// Commands
+pub mod commands;
+
+/// Events
+pub mod events;
+
+/// Input DTOs
+pub mod inputs;
+
+/// View (read model)
+pub mod view;
+
+/// Services used by other aggregates
+pub mod services;
+
+/// CQRS setup
+pub mod cqrs;
+
+pub use aggregate::{Prescriber, Services, AGGREGATE_TYPE};
+pub use commands::Command;
+pub use events::Event;
+pub use services::PrescriberService;
+pub use view::{Query, View};
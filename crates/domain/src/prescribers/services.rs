@@ -0,0 +1,35 @@
+use super::{Prescriber, View, AGGREGATE_TYPE};
+use cqrs_es::persist::ViewRepository;
+use std::sync::Arc;
+
+use crate::errors::Error;
+
+/// Prescriber checks used while handling dispense commands
+pub struct PrescriberService {
+    repo: Arc<Box<dyn ViewRepository<View, Prescriber>>>,
+}
+
+impl PrescriberService {
+    pub fn new(repo: Arc<Box<dyn ViewRepository<View, Prescriber>>>) -> Self {
+        Self { repo }
+    }
+
+    /// Ensure the prescriber is active and authorized for controlled substances
+    pub async fn check_controlled_authorization(&self, prescriber_id: &str) -> Result<(), Error> {
+        let view = self
+            .repo
+            .load(prescriber_id)
+            .await
+            .map_err(|e| Error::Validation {
+                message: format!("Prescriber lookup failed: {}", e),
+            })?
+            .ok_or(Error::NotFound {
+                entity: AGGREGATE_TYPE.to_string(),
+            })?;
+
+        if !view.prescriber.is_active || !view.prescriber.can_prescribe_controlled {
+            return Err(Error::Forbidden);
+        }
+        Ok(())
+    }
+}
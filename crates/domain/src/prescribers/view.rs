@@ -0,0 +1,67 @@
+use super::{Prescriber, AGGREGATE_TYPE};
+use crate::EventMetadata;
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{PersistenceError, ViewContext, ViewRepository},
+    Aggregate, EventEnvelope, View as CqrsView,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct View {
+    pub aggregate_type: String,
+    pub command_id: String,
+    pub id: String,
+    pub prescriber: Prescriber,
+}
+
+impl CqrsView<Prescriber> for View {
+    fn update(&mut self, event: &EventEnvelope<Prescriber>) {
+        self.id.clone_from(&event.aggregate_id);
+        self.aggregate_type = AGGREGATE_TYPE.to_string();
+        self.command_id = EventMetadata::from(&event.metadata)
+            .command_id
+            .unwrap_or_default();
+        self.prescriber.apply(event.payload.clone());
+    }
+}
+
+pub struct Query {
+    repo: Arc<Box<dyn ViewRepository<View, Prescriber>>>,
+}
+
+impl Query {
+    pub fn new(repo: Arc<Box<dyn ViewRepository<View, Prescriber>>>) -> Self {
+        Self { repo }
+    }
+
+    async fn update(
+        &self,
+        prescriber_id: &str,
+        events: &[EventEnvelope<Prescriber>],
+    ) -> Result<(), PersistenceError> {
+        let (mut view, view_context) = match self.repo.load_with_context(prescriber_id).await? {
+            None => {
+                let view_context = ViewContext::new(prescriber_id.to_string(), 0);
+                (Default::default(), view_context)
+            }
+            Some((view, context)) => (view, context),
+        };
+
+        for event in events {
+            view.update(event);
+        }
+
+        self.repo.update_view(view, view_context).await
+    }
+}
+
+#[async_trait]
+impl cqrs_es::Query<Prescriber> for Query {
+    async fn dispatch(&self, prescriber_id: &str, events: &[EventEnvelope<Prescriber>]) {
+        if let Err(err) = self.update(prescriber_id, events).await {
+            eprintln!("PrescriberQuery error for {}: {}", prescriber_id, err);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use cqrs_es::persist::PersistenceError;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Events processed between two checkpoint writes
+pub const CHECKPOINT_INTERVAL: usize = 1000;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of an event replay, used to resume across Lambda executions
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ReplayCheckpoint {
+    pub replay_id: String,
+    pub aggregate_type: String,
+    /// Position of the last replayed event, `None` before the first one
+    pub last_processed_sequence: Option<usize>,
+    pub last_processed_aggregate_id: Option<String>,
+    pub events_processed: usize,
+    pub status: ReplayStatus,
+    pub started_at: DateTime<Utc>,
+    pub checkpoint_at: DateTime<Utc>,
+}
+
+impl ReplayCheckpoint {
+    pub fn new(replay_id: &str, aggregate_type: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            replay_id: replay_id.to_string(),
+            aggregate_type: aggregate_type.to_string(),
+            last_processed_sequence: None,
+            last_processed_aggregate_id: None,
+            events_processed: 0,
+            status: ReplayStatus::Running,
+            started_at: now,
+            checkpoint_at: now,
+        }
+    }
+}
+
+pub struct ReplayCheckpointStore {
+    client: aws_sdk_dynamodb::Client,
+    table: String,
+}
+
+impl ReplayCheckpointStore {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+        }
+    }
+
+    /// Table of the loaded `Config`
+    pub fn from_env(client: aws_sdk_dynamodb::Client) -> Self {
+        Self::new(client, &Config::current().replay_checkpoints_table)
+    }
+
+    pub async fn get(&self, replay_id: &str) -> Result<Option<ReplayCheckpoint>, PersistenceError> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("replay_id", AttributeValue::S(replay_id.to_string()))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        output
+            .item
+            .map(serde_dynamo::from_item)
+            .transpose()
+            .map_err(|e| PersistenceError::DeserializationError(Box::new(e)))
+    }
+
+    /// Write the checkpoint, stamping `checkpoint_at`
+    pub async fn save(&self, checkpoint: &mut ReplayCheckpoint) -> Result<(), PersistenceError> {
+        checkpoint.checkpoint_at = Utc::now();
+        let item = serde_dynamo::to_item(&*checkpoint)
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(|e| PersistenceError::UnknownError(Box::new(e)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+/// ISO 8601 in UTC with nanosecond precision, e.g. `2024-05-01T12:00:00.000000000Z`
+///
+/// The fixed width keeps string comparisons (condition and key expressions) chronological,
+/// which chrono's default format, trimming trailing zeros, does not.
+pub mod dynamo_datetime {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn format(datetime: &DateTime<Utc>) -> String {
+        datetime.to_rfc3339_opts(SecondsFormat::Nanos, true)
+    }
+
+    pub fn serialize<S: Serializer>(
+        datetime: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(datetime))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        DateTime::parse_from_rfc3339(&value)
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .map_err(D::Error::custom)
+    }
+
+    /// Optional timestamp, `null` when `None`; with `#[serde(default)]` an absent field is `None`
+    pub mod option {
+        use chrono::{DateTime, Utc};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            datetime: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match datetime {
+                Some(datetime) => super::serialize(datetime, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            #[derive(Deserialize)]
+            struct Timestamp(#[serde(with = "super")] DateTime<Utc>);
+
+            let timestamp = Option::<Timestamp>::deserialize(deserializer)?;
+            Ok(timestamp.map(|Timestamp(datetime)| datetime))
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch, for compact numeric sort keys
+pub mod dynamo_timestamp_millis {
+    use chrono::{DateTime, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        datetime: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(datetime.timestamp_millis())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let millis = i64::deserialize(deserializer)?;
+
+        DateTime::from_timestamp_millis(millis)
+            .ok_or_else(|| D::Error::custom(format!("Timestamp out of range: {}", millis)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Record {
+        #[serde(with = "dynamo_datetime")]
+        at: DateTime<Utc>,
+        #[serde(with = "dynamo_timestamp_millis")]
+        sort_key: DateTime<Utc>,
+        #[serde(default, with = "dynamo_datetime::option")]
+        expires_at: Option<DateTime<Utc>>,
+    }
+
+    fn at(datetime: &str) -> DateTime<Utc> {
+        datetime.parse().unwrap()
+    }
+
+    #[test]
+    fn record_json_round_trip() {
+        let record = Record {
+            at: at("2024-05-01T12:00:00.123456789Z"),
+            sort_key: at("2024-05-01T12:00:00.123Z"),
+            expires_at: Some(at("2024-05-02T12:00:00Z")),
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "at": "2024-05-01T12:00:00.123456789Z",
+                "sort_key": 1714564800123i64,
+                "expires_at": "2024-05-02T12:00:00.000000000Z",
+            })
+        );
+        assert_eq!(serde_json::from_value::<Record>(json).unwrap(), record);
+    }
+
+    #[test]
+    fn record_dynamo_round_trip() {
+        let record = Record {
+            at: at("2024-05-01T12:00:00Z"),
+            sort_key: at("2024-05-01T12:00:00Z"),
+            expires_at: None,
+        };
+
+        let item: HashMap<String, AttributeValue> = serde_dynamo::to_item(&record).unwrap();
+
+        assert_eq!(
+            item["at"],
+            AttributeValue::S("2024-05-01T12:00:00.000000000Z".to_string())
+        );
+        assert_eq!(
+            item["sort_key"],
+            AttributeValue::N("1714564800000".to_string())
+        );
+        assert_eq!(item["expires_at"], AttributeValue::Null(true));
+        assert_eq!(serde_dynamo::from_item::<_, Record>(item).unwrap(), record);
+    }
+
+    #[test]
+    fn none_round_trips_as_null() {
+        let record = Record {
+            at: at("2024-05-01T12:00:00Z"),
+            sort_key: at("2024-05-01T12:00:00Z"),
+            expires_at: None,
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(json["expires_at"], serde_json::Value::Null);
+        assert_eq!(serde_json::from_value::<Record>(json).unwrap(), record);
+    }
+
+    #[test]
+    fn absent_optional_timestamp_is_none() {
+        let record: Record = serde_json::from_value(json!({
+            "at": "2024-05-01T12:00:00.000000000Z",
+            "sort_key": 1714564800000i64,
+        }))
+        .unwrap();
+
+        assert_eq!(record.expires_at, None);
+    }
+
+    #[test]
+    fn absent_timestamp_is_rejected() {
+        let result = serde_json::from_value::<Record>(json!({ "sort_key": 1714564800000i64 }));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn invalid_timestamp_is_rejected() {
+        let result = serde_json::from_value::<Record>(json!({
+            "at": "yesterday",
+            "sort_key": 1714564800000i64,
+        }));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn formatted_timestamps_sort_chronologically() {
+        // chrono's default format writes these `12:00:00Z` and `12:00:00.500Z`, which sort the
+        // other way round as strings
+        let earlier = dynamo_datetime::format(&at("2024-05-01T12:00:00Z"));
+        let later = dynamo_datetime::format(&at("2024-05-01T12:00:00.5Z"));
+
+        assert!(earlier < later);
+    }
+}
@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use cqrs_es::{
+    persist::{PersistenceError, ViewContext, ViewRepository},
+    Aggregate, View,
+};
+use lru::LruCache;
+use std::{env, marker::PhantomData, num::NonZeroUsize, sync::Mutex};
+
+/// Views kept by default
+pub const DEFAULT_VIEW_CACHE_SIZE: usize = 100;
+
+/// View repository keeping the last loaded views in memory, across warm Lambda invocations
+///
+/// Entries are invalidated by `update_view`, so views written through this repository (the
+/// queries of the same Lambda) are never served stale. Views written by other Lambda instances
+/// are, until evicted: only use it where that is acceptable. `load_with_context` always reads
+/// the inner repository, keeping optimistic locking on the stored version.
+pub struct CachingViewRepository<V, A> {
+    inner: Box<dyn ViewRepository<V, A>>,
+    cache: Mutex<LruCache<String, V>>,
+    _aggregate: PhantomData<A>,
+}
+
+impl<V, A> CachingViewRepository<V, A>
+where
+    V: View<A> + Clone,
+    A: Aggregate,
+{
+    pub fn new(inner: Box<dyn ViewRepository<V, A>>, size: usize) -> Self {
+        let size = NonZeroUsize::new(size).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(size)),
+            _aggregate: PhantomData,
+        }
+    }
+
+    /// Wraps `inner` when `VIEW_CACHE_ENABLED` is `true`, keeping `VIEW_CACHE_SIZE` views
+    /// (100 by default)
+    pub fn from_env(inner: Box<dyn ViewRepository<V, A>>) -> Box<dyn ViewRepository<V, A>>
+    where
+        V: 'static,
+        A: 'static,
+    {
+        if !cache_enabled() {
+            return inner;
+        }
+
+        let size = env::var("VIEW_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_VIEW_CACHE_SIZE);
+        Box::new(Self::new(inner, size))
+    }
+
+    fn cached(&self, view_id: &str) -> Option<V> {
+        self.cache.lock().ok()?.get(view_id).cloned()
+    }
+
+    fn insert(&self, view_id: &str, view: &V) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.put(view_id.to_string(), view.clone());
+        }
+    }
+
+    fn invalidate(&self, view_id: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.pop(view_id);
+        }
+    }
+}
+
+fn cache_enabled() -> bool {
+    env::var("VIEW_CACHE_ENABLED")
+        .map(|enabled| enabled == "true")
+        .unwrap_or(false)
+}
+
+#[async_trait]
+impl<V, A> ViewRepository<V, A> for CachingViewRepository<V, A>
+where
+    V: View<A> + Clone,
+    A: Aggregate,
+{
+    async fn load(&self, view_id: &str) -> Result<Option<V>, PersistenceError> {
+        if let Some(view) = self.cached(view_id) {
+            return Ok(Some(view));
+        }
+
+        let view = self.inner.load(view_id).await?;
+        if let Some(view) = &view {
+            self.insert(view_id, view);
+        }
+        Ok(view)
+    }
+
+    async fn load_with_context(
+        &self,
+        view_id: &str,
+    ) -> Result<Option<(V, ViewContext)>, PersistenceError> {
+        self.inner.load_with_context(view_id).await
+    }
+
+    async fn update_view(&self, view: V, context: ViewContext) -> Result<(), PersistenceError> {
+        // Invalidated whatever the outcome, a failed write may follow a concurrent one
+        self.invalidate(&context.view_instance_id);
+        self.inner.update_view(view, context).await
+    }
+}
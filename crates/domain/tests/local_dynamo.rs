@@ -0,0 +1,121 @@
+//! Dispenses against DynamoDB Local, ignored by default:
+//!
+//! ```bash
+//! docker run -p 8000:8000 amazon/dynamodb-local
+//! DYNAMODB_ENDPOINT_URL=http://localhost:8000 cargo test -p domain --test local_dynamo -- --ignored
+//! ```
+
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType,
+};
+use domain::{
+    config::Config,
+    dispenses::{self, Command, DispenseStatus, Priority},
+};
+use ulid::Ulid;
+
+/// `None` skips the tests, which then pass without DynamoDB Local (e.g. `--include-ignored` in CI)
+fn endpoint_url() -> Option<String> {
+    let endpoint_url = std::env::var("DYNAMODB_ENDPOINT_URL").ok();
+    if endpoint_url.is_none() {
+        eprintln!("DYNAMODB_ENDPOINT_URL is not set, skipped");
+    }
+    endpoint_url
+}
+
+/// Creates a table with the keys of `infra/modules/dispensary/dynamodb.tf`, kept when it exists
+async fn create_table(
+    client: &aws_sdk_dynamodb::Client,
+    table: &str,
+    keys: &[(&str, ScalarAttributeType, KeyType)],
+) {
+    let mut request = client
+        .create_table()
+        .table_name(table)
+        .billing_mode(BillingMode::PayPerRequest);
+    for (name, attribute_type, key_type) in keys {
+        request = request
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(*name)
+                    .attribute_type(attribute_type.clone())
+                    .build()
+                    .unwrap(),
+            )
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(*name)
+                    .key_type(key_type.clone())
+                    .build()
+                    .unwrap(),
+            );
+    }
+
+    if let Err(e) = request.send().await {
+        let exists = e
+            .as_service_error()
+            .is_some_and(|e| e.is_resource_in_use_exception());
+        assert!(exists, "Cannot create table {}: {:?}", table, e);
+    }
+}
+
+async fn create_tables(client: &aws_sdk_dynamodb::Client) {
+    let config = Config::current();
+    create_table(
+        client,
+        &config.event_log_table,
+        &[
+            ("AggregateTypeAndId", ScalarAttributeType::S, KeyType::Hash),
+            (
+                "AggregateIdSequence",
+                ScalarAttributeType::N,
+                KeyType::Range,
+            ),
+        ],
+    )
+    .await;
+    create_table(
+        client,
+        &config.snapshots_table,
+        &[("AggregateTypeAndId", ScalarAttributeType::S, KeyType::Hash)],
+    )
+    .await;
+    create_table(
+        client,
+        &config.view_table,
+        &[("ViewId", ScalarAttributeType::S, KeyType::Hash)],
+    )
+    .await;
+}
+
+#[tokio::test]
+#[ignore = "needs DynamoDB Local at DYNAMODB_ENDPOINT_URL"]
+async fn started_dispense_is_stored_and_viewed() {
+    let Some(endpoint_url) = endpoint_url() else {
+        return;
+    };
+    create_tables(&dispenses::cqrs::local_client(&endpoint_url)).await;
+    let (cqrs, repo) = dispenses::cqrs::init_local(&endpoint_url);
+    let id = Ulid::new().to_string();
+
+    cqrs.execute(
+        &id,
+        Command::StartDispense {
+            id: id.clone(),
+            original_dispense_id: None,
+            pharmacy_branch_id: Some("pharmacy-1".to_string()),
+            priority: Priority::Routine,
+            notes: None,
+        },
+    )
+    .await
+    .unwrap();
+    cqrs.execute(&id, Command::CancelDispense { reason: None })
+        .await
+        .unwrap();
+
+    let view = repo.load(&id).await.unwrap().expect("dispense view");
+    assert_eq!(view.id, id);
+    assert_eq!(view.status, DispenseStatus::Cancelled);
+    assert_eq!(view.aggregate_version, 2);
+}
@@ -0,0 +1,6 @@
+//! HL7 v2.x messages for legacy pharmacy integrations
+
+/// RDS^O01 (Pharmacy Dispense) messages
+pub mod rds;
+
+pub use rds::{dispense_to_rds_o01, parse_rds_o01, ParseError, RdsDrug, RdsMessage};
@@ -0,0 +1,379 @@
+use chrono::Utc;
+use domain::dispenses::{DispenseStatus, View};
+use std::fmt::{self, Write};
+use thiserror::Error;
+
+const SEGMENT_SEPARATOR: char = '\r';
+const FIELD_SEPARATOR: char = '|';
+const COMPONENT_SEPARATOR: char = '^';
+const ENCODING_CHARACTERS: &str = "^~\\&";
+
+const SENDING_APPLICATION: &str = "DISPENSARY";
+const SENDING_FACILITY: &str = "PHARMACY";
+const ASSIGNING_AUTHORITY: &str = "DISPENSARY";
+const HL7_VERSION: &str = "2.4";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Missing segment: {0}")]
+    MissingSegment(String),
+
+    #[error("Unexpected message type: {0}")]
+    UnexpectedMessageType(String),
+
+    #[error("Invalid field {segment}-{field}")]
+    InvalidField { segment: String, field: usize },
+}
+
+/// Dispensed drug parsed from an RXD segment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RdsDrug {
+    pub drug_id: String,
+    pub name: String,
+    pub quantity: u32,
+}
+
+/// Fields carried by an RDS^O01 message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RdsMessage {
+    pub message_control_id: String,
+    pub dispense_id: String,
+    pub order_status: String,
+    pub patient_id: Option<String>,
+    pub patient_name: Option<String>,
+    pub drugs: Vec<RdsDrug>,
+}
+
+/// Generate an HL7 v2.4 RDS^O01 message (MSH, PID, PV1, ORC, RXD, RXR) for a dispense
+pub fn dispense_to_rds_o01(view: &View) -> String {
+    let mut msg = String::new();
+    write_rds_o01(&mut msg, view).expect("writing to a String cannot fail");
+    msg
+}
+
+fn write_rds_o01(msg: &mut String, view: &View) -> fmt::Result {
+    let dispense = &view.dispense_data;
+    let now = Utc::now();
+    let dispensed_at = dispense.updated_at.format(TIMESTAMP_FORMAT);
+
+    // MSH-1 is the field separator itself, so the segment starts with the encoding characters
+    write!(
+        msg,
+        "MSH|{}|{}|{}|||{}||RDS^O01|{}|P|{}{}",
+        ENCODING_CHARACTERS,
+        SENDING_APPLICATION,
+        SENDING_FACILITY,
+        now.format(TIMESTAMP_FORMAT),
+        now.timestamp_millis(),
+        HL7_VERSION,
+        SEGMENT_SEPARATOR,
+    )?;
+
+    write!(
+        msg,
+        "PID|1||{}||{}{}",
+        dispense
+            .patient_id
+            .as_deref()
+            .map(|id| format!("{}^^^{}", escape(id), ASSIGNING_AUTHORITY))
+            .unwrap_or_default(),
+        dispense
+            .patient_name
+            .as_deref()
+            .map(escape)
+            .unwrap_or_default(),
+        SEGMENT_SEPARATOR,
+    )?;
+
+    write!(msg, "PV1|1|O{}", SEGMENT_SEPARATOR)?;
+
+    write!(
+        msg,
+        "ORC|RE|{}|||{}{}",
+        escape(&view.id),
+        order_status(&dispense.status),
+        SEGMENT_SEPARATOR,
+    )?;
+
+    for drug in &dispense.drugs {
+        write!(
+            msg,
+            "RXD|1|{}^{}^{}|{}|{}{}",
+            escape(&drug.drug_id),
+            escape(&drug.name),
+            ASSIGNING_AUTHORITY,
+            dispensed_at,
+            drug.quantity,
+            SEGMENT_SEPARATOR,
+        )?;
+        write!(msg, "RXR|PO{}", SEGMENT_SEPARATOR)?;
+    }
+
+    Ok(())
+}
+
+/// Parse an RDS^O01 message generated by `dispense_to_rds_o01`
+pub fn parse_rds_o01(msg: &str) -> Result<RdsMessage, ParseError> {
+    let segments = msg
+        .split(SEGMENT_SEPARATOR)
+        .map(|segment| segment.trim_start_matches('\n'))
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.split(FIELD_SEPARATOR).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let find = |name: &str| {
+        segments
+            .iter()
+            .find(|fields| fields[0] == name)
+            .ok_or(ParseError::MissingSegment(name.to_string()))
+    };
+
+    // MSH fields are shifted by one because MSH-1 is the separator
+    let msh = find("MSH")?;
+    let message_type = field(msh, "MSH", 8)?;
+    if message_type != "RDS^O01" {
+        return Err(ParseError::UnexpectedMessageType(message_type.to_string()));
+    }
+    let message_control_id = unescape(field(msh, "MSH", 9)?);
+
+    let pid = find("PID")?;
+    let patient_id = optional(pid, 3)
+        .and_then(|id| id.split(COMPONENT_SEPARATOR).next())
+        .filter(|id| !id.is_empty())
+        .map(unescape);
+    let patient_name = optional(pid, 5).map(unescape);
+
+    let orc = find("ORC")?;
+    let dispense_id = unescape(field(orc, "ORC", 2)?);
+    let order_status = field(orc, "ORC", 5)?.to_string();
+
+    let drugs = segments
+        .iter()
+        .filter(|fields| fields[0] == "RXD")
+        .map(|rxd| {
+            let mut components = field(rxd, "RXD", 2)?.split(COMPONENT_SEPARATOR);
+            let drug_id = components.next().map(unescape).unwrap_or_default();
+            let name = components.next().map(unescape).unwrap_or_default();
+            let quantity = field(rxd, "RXD", 4)?
+                .parse()
+                .map_err(|_| ParseError::InvalidField {
+                    segment: "RXD".to_string(),
+                    field: 4,
+                })?;
+
+            Ok(RdsDrug {
+                drug_id,
+                name,
+                quantity,
+            })
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+
+    Ok(RdsMessage {
+        message_control_id,
+        dispense_id,
+        order_status,
+        patient_id,
+        patient_name,
+        drugs,
+    })
+}
+
+/// HL7 order status (ORC-5) for a dispense status
+fn order_status(status: &DispenseStatus) -> &'static str {
+    match status {
+        DispenseStatus::Pending | DispenseStatus::Analyzing | DispenseStatus::Ready => "IP",
+        DispenseStatus::Complete => "CM",
+        DispenseStatus::Cancelled => "CA",
+    }
+}
+
+fn field<'a>(fields: &[&'a str], segment: &str, index: usize) -> Result<&'a str, ParseError> {
+    fields
+        .get(index)
+        .copied()
+        .filter(|value| !value.is_empty())
+        .ok_or(ParseError::InvalidField {
+            segment: segment.to_string(),
+            field: index,
+        })
+}
+
+fn optional<'a>(fields: &[&'a str], index: usize) -> Option<&'a str> {
+    fields.get(index).copied().filter(|value| !value.is_empty())
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\E\\"),
+            '|' => escaped.push_str("\\F\\"),
+            '^' => escaped.push_str("\\S\\"),
+            '&' => escaped.push_str("\\T\\"),
+            '~' => escaped.push_str("\\R\\"),
+            '\r' | '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('\\') {
+        unescaped.push_str(&rest[..start]);
+        let sequence = &rest[start..];
+        let replacement = match sequence.get(..3) {
+            Some("\\E\\") => Some('\\'),
+            Some("\\F\\") => Some('|'),
+            Some("\\S\\") => Some('^'),
+            Some("\\T\\") => Some('&'),
+            Some("\\R\\") => Some('~'),
+            _ => None,
+        };
+        match replacement {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &sequence[3..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &sequence[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::dispenses::{
+        aggregate::DrugItem,
+        inputs::{DispenseId, DrugId, PatientId},
+        DispenseData,
+    };
+
+    use super::*;
+
+    fn drug(drug_id: &str, name: &str, quantity: u32) -> DrugItem {
+        DrugItem {
+            drug_id: DrugId::new(drug_id),
+            name: name.to_string(),
+            quantity,
+            controlled: false,
+        }
+    }
+
+    fn completed_view() -> View {
+        View {
+            dispense_data: DispenseData {
+                id: DispenseId::new("test-dispense"),
+                status: DispenseStatus::Complete,
+                patient_id: Some(PatientId::new("test-patient")),
+                patient_name: Some("Doe^Jane".to_string()),
+                drugs: vec![
+                    drug("drug-1", "Amoxicillin 500mg", 21),
+                    drug("drug-2", "Ibuprofen|200mg", 30),
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn segments(msg: &str) -> Vec<&str> {
+        msg.split(SEGMENT_SEPARATOR)
+            .filter(|segment| !segment.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_keeps_dispense_fields() {
+        let msg = dispense_to_rds_o01(&completed_view());
+
+        let parsed = parse_rds_o01(&msg).unwrap();
+
+        assert!(!parsed.message_control_id.is_empty());
+        assert_eq!(parsed.dispense_id, "test-dispense");
+        assert_eq!(parsed.order_status, "CM");
+        assert_eq!(parsed.patient_id.as_deref(), Some("test-patient"));
+        assert_eq!(parsed.patient_name.as_deref(), Some("Doe^Jane"));
+        assert_eq!(
+            parsed.drugs,
+            vec![
+                RdsDrug {
+                    drug_id: "drug-1".to_string(),
+                    name: "Amoxicillin 500mg".to_string(),
+                    quantity: 21,
+                },
+                RdsDrug {
+                    drug_id: "drug-2".to_string(),
+                    name: "Ibuprofen|200mg".to_string(),
+                    quantity: 30,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn message_has_segments_in_order() {
+        let msg = dispense_to_rds_o01(&completed_view());
+
+        let names: Vec<&str> = segments(&msg).iter().map(|segment| &segment[..3]).collect();
+
+        assert_eq!(
+            names,
+            ["MSH", "PID", "PV1", "ORC", "RXD", "RXR", "RXD", "RXR"]
+        );
+    }
+
+    #[test]
+    fn segments_carry_header_and_route() {
+        let msg = dispense_to_rds_o01(&completed_view());
+        let segments = segments(&msg);
+
+        let msh: Vec<&str> = segments[0].split(FIELD_SEPARATOR).collect();
+        assert_eq!(msh[1], ENCODING_CHARACTERS);
+        assert_eq!(msh[2], SENDING_APPLICATION);
+        assert_eq!(msh[3], SENDING_FACILITY);
+        assert_eq!(msh[8], "RDS^O01");
+        assert_eq!(msh[10], "P");
+        assert_eq!(msh[11], HL7_VERSION);
+        assert_eq!(
+            segments[1],
+            "PID|1||test-patient^^^DISPENSARY||Doe\\S\\Jane"
+        );
+        assert_eq!(segments[3], "ORC|RE|test-dispense|||CM");
+        assert!(segments[6].starts_with("RXD|1|drug-2^Ibuprofen\\F\\200mg^DISPENSARY|"));
+        assert_eq!(segments[5], "RXR|PO");
+        assert_eq!(segments[7], "RXR|PO");
+    }
+
+    #[test]
+    fn dispense_without_patient_round_trips() {
+        let mut view = completed_view();
+        view.dispense_data.patient_id = None;
+        view.dispense_data.patient_name = None;
+        view.dispense_data.status = DispenseStatus::Cancelled;
+
+        let parsed = parse_rds_o01(&dispense_to_rds_o01(&view)).unwrap();
+
+        assert_eq!(parsed.patient_id, None);
+        assert_eq!(parsed.patient_name, None);
+        assert_eq!(parsed.order_status, "CA");
+    }
+
+    #[test]
+    fn other_message_type_is_rejected() {
+        let msg = dispense_to_rds_o01(&completed_view()).replace("RDS^O01", "ADT^A01");
+
+        assert_eq!(
+            parse_rds_o01(&msg),
+            Err(ParseError::UnexpectedMessageType("ADT^A01".to_string()))
+        );
+    }
+}
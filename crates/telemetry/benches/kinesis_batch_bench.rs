@@ -0,0 +1,77 @@
+use aws_lambda_events::kinesis::KinesisEventRecord;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lambda_runtime::Context;
+use serde_json::json;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use telemetry::{kinesis_batch::process_kinesis_batch, DeadlineAwareProcessor};
+
+const BATCH_SIZE: usize = 100;
+
+/// Latency of a single-item DynamoDB write
+const RECORD_LATENCY: Duration = Duration::from_millis(5);
+
+fn records() -> Vec<KinesisEventRecord> {
+    (0..BATCH_SIZE)
+        .map(|i| {
+            serde_json::from_value(json!({
+                "awsRegion": "us-east-1",
+                "eventID": format!("shardId-000000000000:{:056}", i),
+                "eventName": "aws:kinesis:record",
+                "eventSource": "aws:kinesis",
+                "eventSourceARN": "arn:aws:kinesis:us-east-1:000000000000:stream/dispensary-events",
+                "eventVersion": "1.0",
+                "invokeIdentityArn": "arn:aws:iam::000000000000:role/lambda",
+                "kinesis": {
+                    "approximateArrivalTimestamp": 1_700_000_000.0,
+                    "data": "e30=",
+                    "kinesisSchemaVersion": "1.0",
+                    "partitionKey": format!("dispense-{}", i),
+                    "sequenceNumber": format!("{:056}", i),
+                }
+            }))
+            .expect("Kinesis record fixture")
+        })
+        .collect()
+}
+
+/// Invocation with a deadline far enough to never be reached
+fn deadline() -> DeadlineAwareProcessor {
+    let mut context = Context::default();
+    let deadline = SystemTime::now() + Duration::from_secs(3600);
+    context.deadline = deadline.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    DeadlineAwareProcessor::new(context)
+}
+
+async fn handle_record(_record: &KinesisEventRecord) -> Result<(), String> {
+    tokio::time::sleep(RECORD_LATENCY).await;
+    Ok(())
+}
+
+fn bench_process_kinesis_batch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let records = records();
+    let deadline = deadline();
+
+    let mut group = c.benchmark_group("kinesis_batch");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.sample_size(10);
+    // Concurrency 1 is the former sequential loop
+    for concurrency in [1, 10, 50] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                b.to_async(&runtime).iter(|| async {
+                    black_box(
+                        process_kinesis_batch(&records, &deadline, concurrency, handle_record)
+                            .await,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_process_kinesis_batch);
+criterion_main!(benches);
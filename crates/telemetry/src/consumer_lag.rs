@@ -0,0 +1,100 @@
+use aws_lambda_events::kinesis::KinesisEventRecord;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::env;
+
+/// Lag above which records are reported, in milliseconds
+pub const DEFAULT_MAX_LAG_MS: i64 = 60_000;
+
+/// CloudWatch namespace of the consumer lag metrics
+const METRICS_NAMESPACE: &str = "Dispensary";
+
+/// Reports how far behind the stream a projector is
+///
+/// Metrics are written to stdout in the CloudWatch Embedded Metric Format, so CloudWatch Logs
+/// extracts them without a `PutMetricData` call per batch:
+/// - `KinesisConsumerLagMs`, the lag of the oldest record of the batch
+/// - `ConsumerLagAlert`, 1 when that lag exceeds `KINESIS_MAX_LAG_MS`
+pub struct ConsumerLagMonitor {
+    function_name: String,
+    max_lag_ms: i64,
+}
+
+impl ConsumerLagMonitor {
+    pub fn new(function_name: &str, max_lag_ms: i64) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            max_lag_ms,
+        }
+    }
+
+    /// `KINESIS_MAX_LAG_MS` threshold (60000 by default), dimensioned by the Lambda function name
+    pub fn from_env() -> Self {
+        let function_name = env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or("unknown".to_string());
+        let max_lag_ms = env::var("KINESIS_MAX_LAG_MS")
+            .ok()
+            .and_then(|lag| lag.parse().ok())
+            .unwrap_or(DEFAULT_MAX_LAG_MS);
+        Self::new(&function_name, max_lag_ms)
+    }
+
+    /// Time since the record reached the stream
+    pub fn record_lag_ms(record: &KinesisEventRecord, now: DateTime<Utc>) -> i64 {
+        (now - record.kinesis.approximate_arrival_timestamp.0).num_milliseconds()
+    }
+
+    /// Warn about the records lagging beyond the threshold and emit the batch metrics
+    pub fn observe(&self, records: &[KinesisEventRecord]) {
+        let now = Utc::now();
+
+        let mut max_lag_ms = None;
+        for record in records {
+            let lag_ms = Self::record_lag_ms(record, now);
+            if lag_ms > self.max_lag_ms {
+                tracing::warn!(
+                    lag_ms = %lag_ms,
+                    sequence_number = ?record.kinesis.sequence_number,
+                    "Kinesis record behind the stream"
+                );
+            }
+            max_lag_ms = max_lag_ms.max(Some(lag_ms));
+        }
+
+        // Reported by the event source mapping, when the environment provides it
+        if let Some(iterator_age_ms) = iterator_age_ms() {
+            if iterator_age_ms > self.max_lag_ms {
+                tracing::warn!(iterator_age_ms = %iterator_age_ms, "Kinesis iterator behind");
+            }
+            max_lag_ms = max_lag_ms.max(Some(iterator_age_ms));
+        }
+
+        if let Some(lag_ms) = max_lag_ms {
+            println!("{}", self.metrics(lag_ms, now));
+        }
+    }
+
+    fn metrics(&self, lag_ms: i64, now: DateTime<Utc>) -> serde_json::Value {
+        json!({
+            "_aws": {
+                "Timestamp": now.timestamp_millis(),
+                "CloudWatchMetrics": [{
+                    "Namespace": METRICS_NAMESPACE,
+                    "Dimensions": [["LambdaFunction"]],
+                    "Metrics": [
+                        { "Name": "KinesisConsumerLagMs", "Unit": "Milliseconds" },
+                        { "Name": "ConsumerLagAlert", "Unit": "Count" }
+                    ]
+                }]
+            },
+            "LambdaFunction": self.function_name,
+            "KinesisConsumerLagMs": lag_ms,
+            "ConsumerLagAlert": i64::from(lag_ms > self.max_lag_ms),
+        })
+    }
+}
+
+fn iterator_age_ms() -> Option<i64> {
+    env::var("ITERATOR_AGE_MS")
+        .ok()
+        .and_then(|age| age.parse().ok())
+}
@@ -0,0 +1,163 @@
+use aws_lambda_events::{encodings::Base64Data, kinesis::KinesisEventRecord};
+use aws_sdk_s3::primitives::ByteStream;
+use chrono::{DateTime, NaiveDate, Utc};
+use domain::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env;
+
+/// CloudWatch namespace of the dead letter metric
+const METRICS_NAMESPACE: &str = "Dispensary";
+
+/// Kinesis record a projector could not parse, kept in S3 instead of being retried until the
+/// stream retention drops it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub sequence_number: String,
+    pub error_message: String,
+    pub failed_at: DateTime<Utc>,
+    pub lambda_request_id: String,
+    /// Record data as received, base64 encoded
+    pub data: Base64Data,
+}
+
+impl DeadLetter {
+    pub fn new(
+        record: &KinesisEventRecord,
+        error_message: String,
+        lambda_request_id: &str,
+    ) -> Self {
+        Self {
+            sequence_number: record.kinesis.sequence_number.clone().unwrap_or_default(),
+            error_message,
+            failed_at: Utc::now(),
+            lambda_request_id: lambda_request_id.to_string(),
+            data: record.kinesis.data.clone(),
+        }
+    }
+
+    /// `{year}/{month}/{day}/{sequence_number}.json`
+    pub fn key(&self) -> String {
+        format!(
+            "{}/{}.json",
+            self.failed_at.format("%Y/%m/%d"),
+            self.sequence_number
+        )
+    }
+}
+
+/// Dead letters of the projectors, in the `DEAD_LETTERS_BUCKET` bucket
+pub struct DeadLetterStore {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl DeadLetterStore {
+    pub fn new(client: aws_sdk_s3::Client, bucket: &str) -> Self {
+        Self {
+            client,
+            bucket: bucket.to_string(),
+        }
+    }
+
+    /// `DEAD_LETTERS_BUCKET`, `dispensary-dead-letters` by default
+    pub fn from_env(client: aws_sdk_s3::Client) -> Self {
+        let bucket =
+            env::var("DEAD_LETTERS_BUCKET").unwrap_or("dispensary-dead-letters".to_string());
+        Self::new(client, &bucket)
+    }
+
+    /// Store the dead letter, returning its key
+    pub async fn put(&self, dead_letter: &DeadLetter) -> Result<String, Error> {
+        let key = dead_letter.key();
+        let body = serde_json::to_vec(dead_letter)?;
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .content_type("application/json")
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(key)
+    }
+
+    /// Keys of the dead letters of a day
+    pub async fn list(&self, date: NaiveDate) -> Result<Vec<String>, Error> {
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(format!("{}/", date.format("%Y/%m/%d")))
+            .into_paginator()
+            .send();
+
+        let mut keys = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(s3_error)?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|object| object.key().map(str::to_string)),
+            );
+        }
+        Ok(keys)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<DeadLetter>, Error> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(s3_error(e)),
+        };
+
+        let body = output.body.collect().await.map_err(s3_error)?;
+        Ok(Some(serde_json::from_slice(&body.into_bytes())?))
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(s3_error)?;
+        Ok(())
+    }
+}
+
+fn s3_error(error: impl std::error::Error) -> Error {
+    Error::ExternalService {
+        service: "S3".to_string(),
+        message: error.to_string(),
+    }
+}
+
+/// Write the `DeadLetterEvent` metric to stdout, in the CloudWatch Embedded Metric Format
+pub fn emit_dead_letter_metric() {
+    let function_name = env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or("unknown".to_string());
+    let metrics = json!({
+        "_aws": {
+            "Timestamp": Utc::now().timestamp_millis(),
+            "CloudWatchMetrics": [{
+                "Namespace": METRICS_NAMESPACE,
+                "Dimensions": [["LambdaFunction"]],
+                "Metrics": [{ "Name": "DeadLetterEvent", "Unit": "Count" }]
+            }]
+        },
+        "LambdaFunction": function_name,
+        "DeadLetterEvent": 1,
+    });
+    println!("{}", metrics);
+}
@@ -0,0 +1,24 @@
+use lambda_runtime::Context;
+use std::time::{Duration, SystemTime};
+
+/// Time kept before the deadline to report the records left unprocessed
+pub const DEADLINE_MARGIN: Duration = Duration::from_secs(5);
+
+/// Stops batch processing before the Lambda times out (which would retry the whole batch)
+pub struct DeadlineAwareProcessor {
+    context: Context,
+}
+
+impl DeadlineAwareProcessor {
+    pub fn new(context: Context) -> Self {
+        Self { context }
+    }
+
+    /// More than `DEADLINE_MARGIN` left before the invocation deadline
+    pub fn has_time_remaining(&self) -> bool {
+        self.context
+            .deadline()
+            .duration_since(SystemTime::now())
+            .is_ok_and(|remaining| remaining >= DEADLINE_MARGIN)
+    }
+}
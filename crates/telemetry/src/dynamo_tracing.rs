@@ -0,0 +1,227 @@
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            context::{
+                BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+                FinalizerInterceptorContextRef,
+            },
+            Intercept,
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::{ConfigBag, Storable, StoreReplace};
+use chrono::Utc;
+use serde_json::json;
+use std::{env, time::Instant};
+
+/// Duration above which a DynamoDB call is reported, in milliseconds
+pub const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 500;
+
+/// CloudWatch namespace of the slow query metric
+const METRICS_NAMESPACE: &str = "Dispensary";
+
+/// DynamoDB client timing every operation, see `SlowQueryInterceptor`
+///
+/// Covers the event store and view repositories, which are built on the client they are given.
+/// `DYNAMODB_ENDPOINT_URL` points it at a local DynamoDB (e.g. `http://localhost:8000`).
+pub fn traced_dynamodb_client(config: &aws_config::SdkConfig) -> aws_sdk_dynamodb::Client {
+    let mut builder = aws_sdk_dynamodb::config::Builder::from(config)
+        .interceptor(SlowQueryInterceptor::from_env());
+    if let Some(endpoint_url) = env::var("DYNAMODB_ENDPOINT_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+    {
+        builder = builder.endpoint_url(endpoint_url);
+    }
+
+    aws_sdk_dynamodb::Client::from_conf(builder.build())
+}
+
+/// Warns about DynamoDB operations slower than `DYNAMO_SLOW_QUERY_THRESHOLD_MS`, usually a sign
+/// of exhausted capacity before requests start timing out
+///
+/// Each slow operation is logged with its table and duration, and counted in the
+/// `SlowDynamoQuery` metric (CloudWatch Embedded Metric Format, by operation). Durations include
+/// the SDK retries.
+#[derive(Debug)]
+pub struct SlowQueryInterceptor {
+    function_name: String,
+    threshold_ms: u64,
+}
+
+#[derive(Debug)]
+struct OperationStart(Instant);
+
+impl Storable for OperationStart {
+    type Storer = StoreReplace<Self>;
+}
+
+#[derive(Debug)]
+struct TableName(String);
+
+impl Storable for TableName {
+    type Storer = StoreReplace<Self>;
+}
+
+impl SlowQueryInterceptor {
+    pub fn new(function_name: &str, threshold_ms: u64) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            threshold_ms,
+        }
+    }
+
+    /// `DYNAMO_SLOW_QUERY_THRESHOLD_MS` threshold (500 by default), dimensioned by the Lambda
+    /// function name
+    pub fn from_env() -> Self {
+        let function_name = env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or("unknown".to_string());
+        let threshold_ms = env::var("DYNAMO_SLOW_QUERY_THRESHOLD_MS")
+            .ok()
+            .and_then(|threshold| threshold.parse().ok())
+            .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD_MS);
+        Self::new(&function_name, threshold_ms)
+    }
+
+    /// Warn and emit the metric when the operation exceeded the threshold, returning whether it did
+    pub fn observe(&self, operation: &str, table: &str, duration_ms: u64) -> bool {
+        if duration_ms <= self.threshold_ms {
+            return false;
+        }
+
+        tracing::warn!(
+            operation = %operation,
+            table = %table,
+            duration_ms = %duration_ms,
+            "Slow DynamoDB query"
+        );
+        println!("{}", self.metrics(operation, table, duration_ms));
+        true
+    }
+
+    fn metrics(&self, operation: &str, table: &str, duration_ms: u64) -> serde_json::Value {
+        json!({
+            "_aws": {
+                "Timestamp": Utc::now().timestamp_millis(),
+                "CloudWatchMetrics": [{
+                    "Namespace": METRICS_NAMESPACE,
+                    "Dimensions": [["LambdaFunction", "Operation"]],
+                    "Metrics": [
+                        { "Name": "SlowDynamoQuery", "Unit": "Count" },
+                        { "Name": "SlowDynamoQueryDurationMs", "Unit": "Milliseconds" }
+                    ]
+                }]
+            },
+            "LambdaFunction": self.function_name,
+            "Operation": operation,
+            "TableName": table,
+            "SlowDynamoQuery": 1,
+            "SlowDynamoQueryDurationMs": duration_ms,
+        })
+    }
+}
+
+impl Intercept for SlowQueryInterceptor {
+    fn name(&self) -> &'static str {
+        "SlowQueryInterceptor"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        cfg.interceptor_state()
+            .store_put(OperationStart(Instant::now()));
+        Ok(())
+    }
+
+    // The input is type-erased, the table is read from the serialized request instead
+    fn read_before_transmit(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let table = context
+            .request()
+            .body()
+            .bytes()
+            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .and_then(|body| body.get("TableName")?.as_str().map(str::to_string));
+        if let Some(table) = table {
+            cfg.interceptor_state().store_put(TableName(table));
+        }
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        _context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(OperationStart(start)) = cfg.load::<OperationStart>() else {
+            return Ok(());
+        };
+        let operation = cfg.load::<Metadata>().map_or("unknown", Metadata::name);
+        // Batch and transaction requests span several tables
+        let table = cfg
+            .load::<TableName>()
+            .map_or("multiple", |TableName(table)| table.as_str());
+
+        self.observe(operation, table, start.elapsed().as_millis() as u64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::*;
+    use crate::testing::CapturedEvents;
+
+    #[test]
+    fn operation_over_the_threshold_is_warned_about() {
+        let interceptor = SlowQueryInterceptor::new("test-function", 500);
+        let captured = CapturedEvents::start();
+
+        assert!(interceptor.observe("Query", "dispenses", 600));
+
+        let events = captured.events();
+        let [warning] = events.as_slice() else {
+            panic!("expected one warning, got {:?}", events);
+        };
+        assert_eq!(warning.level, Level::WARN);
+        assert_eq!(warning.fields["message"], "Slow DynamoDB query");
+        assert_eq!(warning.fields["operation"], "Query");
+        assert_eq!(warning.fields["table"], "dispenses");
+        assert_eq!(warning.fields["duration_ms"], "600");
+    }
+
+    #[test]
+    fn operation_at_the_threshold_is_not_warned_about() {
+        let interceptor = SlowQueryInterceptor::new("test-function", 500);
+        let captured = CapturedEvents::start();
+
+        assert!(!interceptor.observe("Query", "dispenses", 500));
+
+        assert!(captured.events().is_empty());
+    }
+
+    #[test]
+    fn slow_query_metric_is_dimensioned_by_function_and_operation() {
+        let interceptor = SlowQueryInterceptor::new("test-function", 500);
+
+        let metrics = interceptor.metrics("Query", "dispenses", 600);
+
+        assert_eq!(metrics["LambdaFunction"], "test-function");
+        assert_eq!(metrics["Operation"], "Query");
+        assert_eq!(metrics["TableName"], "dispenses");
+        assert_eq!(metrics["SlowDynamoQuery"], 1);
+        assert_eq!(metrics["SlowDynamoQueryDurationMs"], 600);
+    }
+}
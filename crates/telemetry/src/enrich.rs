@@ -0,0 +1,156 @@
+use chrono::Utc;
+use domain::DomainEvent;
+use serde_json::{Map, Value};
+
+/// Lambda execution that processed an event
+#[derive(Clone, Debug, Default)]
+pub struct LambdaContext {
+    pub function_version: String,
+    pub request_id: String,
+    /// Kinesis shard, when already known (Kinesis assigns it on put)
+    pub kinesis_shard_id: Option<String>,
+    /// DynamoDB stream sequence number of the triggering record
+    pub stream_sequence_number: Option<String>,
+}
+
+impl LambdaContext {
+    pub fn new(context: &lambda_runtime::Context) -> Self {
+        Self {
+            function_version: context.env_config.version.clone(),
+            request_id: context.request_id.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_stream_sequence_number(mut self, sequence_number: impl Into<String>) -> Self {
+        self.stream_sequence_number = Some(sequence_number.into());
+        self
+    }
+}
+
+/// Adds publisher processing context to `DomainEvent.metadata`
+pub struct MetadataEnricher;
+
+impl MetadataEnricher {
+    /// Merge the processing context into the event metadata JSON object, logged at debug level
+    /// with the event.
+    ///
+    /// Metadata that is not a JSON object is replaced.
+    pub fn enrich(mut event: DomainEvent, context: &LambdaContext) -> DomainEvent {
+        let mut metadata = match serde_json::from_str::<Value>(&event.metadata) {
+            Ok(Value::Object(metadata)) => metadata,
+            _ => Map::new(),
+        };
+
+        metadata.insert(
+            "publisher_lambda_version".to_string(),
+            Value::String(context.function_version.clone()),
+        );
+        metadata.insert(
+            "publisher_request_id".to_string(),
+            Value::String(context.request_id.clone()),
+        );
+        metadata.insert(
+            "kinesis_shard_id".to_string(),
+            context
+                .kinesis_shard_id
+                .clone()
+                .map_or(Value::Null, Value::String),
+        );
+        metadata.insert(
+            "dynamodb_stream_sequence".to_string(),
+            context
+                .stream_sequence_number
+                .clone()
+                .map_or(Value::Null, Value::String),
+        );
+        metadata.insert(
+            "processed_at".to_string(),
+            Value::String(Utc::now().to_rfc3339()),
+        );
+
+        tracing::debug!(
+            publisher_lambda_version = %context.function_version,
+            publisher_request_id = %context.request_id,
+            kinesis_shard_id = ?context.kinesis_shard_id,
+            dynamodb_stream_sequence = ?context.stream_sequence_number,
+            "Enriched the metadata of {} {}",
+            event.event_type,
+            event.id
+        );
+        event.metadata = Value::Object(metadata).to_string();
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::*;
+    use crate::testing::CapturedEvents;
+
+    fn event(metadata: &str) -> DomainEvent {
+        DomainEvent {
+            id: "test-dispense".to_string(),
+            entity: "Dispense".to_string(),
+            sequence: 1,
+            event_type: "Dispense:Started".to_string(),
+            event_version: "1.0".to_string(),
+            payload: "{}".to_string(),
+            metadata: metadata.to_string(),
+        }
+    }
+
+    fn context() -> LambdaContext {
+        LambdaContext {
+            function_version: "7".to_string(),
+            request_id: "request-1".to_string(),
+            kinesis_shard_id: None,
+            stream_sequence_number: None,
+        }
+        .with_stream_sequence_number("4200")
+    }
+
+    #[test]
+    fn processing_context_is_added_to_the_metadata() {
+        let event = MetadataEnricher::enrich(event(r#"{"command_id":"command-1"}"#), &context());
+
+        let metadata: Value = serde_json::from_str(&event.metadata).unwrap();
+        assert_eq!(metadata["command_id"], "command-1");
+        assert_eq!(metadata["publisher_lambda_version"], "7");
+        assert_eq!(metadata["publisher_request_id"], "request-1");
+        assert_eq!(metadata["kinesis_shard_id"], Value::Null);
+        assert_eq!(metadata["dynamodb_stream_sequence"], "4200");
+        assert!(metadata["processed_at"].is_string());
+    }
+
+    #[test]
+    fn metadata_that_is_not_an_object_is_replaced() {
+        let event = MetadataEnricher::enrich(event("not json"), &context());
+
+        let metadata: Value = serde_json::from_str(&event.metadata).unwrap();
+        assert_eq!(metadata["publisher_request_id"], "request-1");
+    }
+
+    #[test]
+    fn processing_context_is_logged() {
+        let captured = CapturedEvents::start();
+
+        MetadataEnricher::enrich(event("{}"), &context());
+
+        let events = captured.events();
+        let [logged] = events.as_slice() else {
+            panic!("expected one event, got {:?}", events);
+        };
+        assert_eq!(logged.level, Level::DEBUG);
+        assert_eq!(logged.fields["publisher_lambda_version"], "7");
+        assert_eq!(logged.fields["publisher_request_id"], "request-1");
+        assert_eq!(logged.fields["kinesis_shard_id"], "None");
+        assert_eq!(logged.fields["dynamodb_stream_sequence"], "Some(\"4200\")");
+        assert_eq!(
+            logged.fields["message"],
+            "Enriched the metadata of Dispense:Started test-dispense"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use aws_lambda_events::{kinesis::KinesisEventRecord, streams::KinesisBatchItemFailure};
+use futures::{stream, StreamExt};
+use std::{collections::HashMap, env, fmt::Display, future::Future};
+
+use crate::DeadlineAwareProcessor;
+
+const DEFAULT_CONCURRENCY: usize = 10;
+
+/// Keeps DynamoDB writes of a batch under the table's burst capacity
+const MAX_CONCURRENCY: usize = 50;
+
+/// `KINESIS_CONCURRENCY`, the number of partition keys processed at once (10 by default, at
+/// most 50)
+pub fn kinesis_concurrency() -> usize {
+    env::var("KINESIS_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .clamp(1, MAX_CONCURRENCY)
+}
+
+/// Process the records `concurrency` partition keys at a time, returning those to retry
+///
+/// Records sharing a partition key (the events of one aggregate) are handled one after the
+/// other in batch order, those of different keys concurrently. A failure holds back the later
+/// records of its key, reported as failures with it so they are retried in order, without
+/// stopping the other keys. Records not started before the deadline are reported as failures,
+/// so the next invocation resumes from them.
+pub async fn process_kinesis_batch<'a, F, Fut, E>(
+    records: &'a [KinesisEventRecord],
+    deadline: &DeadlineAwareProcessor,
+    concurrency: usize,
+    handle_record: F,
+) -> Vec<KinesisBatchItemFailure>
+where
+    F: Fn(&'a KinesisEventRecord) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let handle_record = &handle_record;
+    let failures: Vec<Vec<KinesisBatchItemFailure>> = stream::iter(partitions(records))
+        .map(|partition| process_partition(partition, deadline, handle_record))
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    failures.into_iter().flatten().collect()
+}
+
+/// Records grouped by partition key, in batch order
+fn partitions(records: &[KinesisEventRecord]) -> Vec<Vec<&KinesisEventRecord>> {
+    let mut partitions: Vec<Vec<&KinesisEventRecord>> = Vec::new();
+    let mut partition_of_key = HashMap::new();
+    for record in records {
+        let partition = *partition_of_key
+            .entry(record.kinesis.partition_key.as_deref())
+            .or_insert_with(|| {
+                partitions.push(Vec::new());
+                partitions.len() - 1
+            });
+        partitions[partition].push(record);
+    }
+    partitions
+}
+
+async fn process_partition<'a, F, Fut, E>(
+    records: Vec<&'a KinesisEventRecord>,
+    deadline: &DeadlineAwareProcessor,
+    handle_record: &F,
+) -> Vec<KinesisBatchItemFailure>
+where
+    F: Fn(&'a KinesisEventRecord) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let mut failures = Vec::new();
+    for record in records {
+        let sequence = record.kinesis.sequence_number.clone();
+
+        if !failures.is_empty() {
+            tracing::warn!("{:?} held back behind a failed record of its key", sequence);
+        } else if !deadline.has_time_remaining() {
+            tracing::warn!("Deadline near, {:?} left for the next invocation", sequence);
+        } else {
+            match handle_record(record).await {
+                Ok(()) => continue,
+                Err(e) => tracing::error!("Failed to process: {}", e),
+            }
+        }
+        failures.push(KinesisBatchItemFailure {
+            item_identifier: sequence,
+        });
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_runtime::Context;
+    use serde_json::json;
+    use std::{
+        sync::Mutex,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    fn record(partition_key: &str, sequence: usize) -> KinesisEventRecord {
+        serde_json::from_value(json!({
+            "eventID": format!("shardId-000000000000:{:056}", sequence),
+            "eventName": "aws:kinesis:record",
+            "eventSource": "aws:kinesis",
+            "eventVersion": "1.0",
+            "kinesis": {
+                "approximateArrivalTimestamp": 1_700_000_000.0,
+                "data": "e30=",
+                "kinesisSchemaVersion": "1.0",
+                "partitionKey": partition_key,
+                "sequenceNumber": format!("{:056}", sequence),
+            }
+        }))
+        .expect("Kinesis record fixture")
+    }
+
+    fn deadline() -> DeadlineAwareProcessor {
+        let mut context = Context::default();
+        let deadline = SystemTime::now() + Duration::from_secs(3600);
+        context.deadline = deadline.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        DeadlineAwareProcessor::new(context)
+    }
+
+    fn sequence(record: &KinesisEventRecord) -> usize {
+        record
+            .kinesis
+            .sequence_number
+            .as_deref()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn records_of_a_key_are_handled_in_order() {
+        let records: Vec<_> = (0..12)
+            .map(|sequence| record(&format!("dispense-{}", sequence % 3), sequence))
+            .collect();
+        let handled = Mutex::new(Vec::new());
+
+        let failures = process_kinesis_batch(&records, &deadline(), 3, |record| {
+            let handled = &handled;
+            async move {
+                // Later records of the batch finish first
+                let delay = 12 - sequence(record) as u64;
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                handled.lock().unwrap().push(sequence(record));
+                Ok::<(), String>(())
+            }
+        })
+        .await;
+
+        assert!(failures.is_empty());
+        let handled = handled.into_inner().unwrap();
+        assert_eq!(handled.len(), 12);
+        for key in 0..3 {
+            let of_key: Vec<usize> = handled.iter().copied().filter(|s| s % 3 == key).collect();
+            assert!(of_key.is_sorted(), "{:?}", of_key);
+        }
+    }
+
+    #[tokio::test]
+    async fn failure_holds_back_the_later_records_of_its_key() {
+        let records = vec![
+            record("dispense-1", 1),
+            record("dispense-2", 2),
+            record("dispense-1", 3),
+            record("dispense-2", 4),
+        ];
+
+        let failures = process_kinesis_batch(&records, &deadline(), 2, |record| async move {
+            if sequence(record) == 1 {
+                return Err("conflict");
+            }
+            Ok(())
+        })
+        .await;
+
+        let mut failed: Vec<usize> = failures
+            .iter()
+            .map(|failure| failure.item_identifier.as_deref().unwrap().parse().unwrap())
+            .collect();
+        failed.sort();
+        assert_eq!(failed, [1, 3]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::env;
+use thiserror::Error;
+
+/// Expected shape, shown in configuration errors
+const CONSUMER_ARN_FORMAT: &str =
+    "arn:aws:kinesis:<region>:<account-id>:stream/<stream-name>/consumer/<consumer-name>:<timestamp>";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConsumerArnError {
+    #[error(
+        "KINESIS_CONSUMER_ARN is not a Kinesis ARN: {arn} (expected {})",
+        CONSUMER_ARN_FORMAT
+    )]
+    NotKinesis { arn: String },
+
+    #[error("KINESIS_CONSUMER_ARN is missing the region or account id: {arn}")]
+    MissingAccount { arn: String },
+
+    #[error(
+        "KINESIS_CONSUMER_ARN is a stream ARN, register a consumer with \
+         `aws kinesis register-stream-consumer` and use its ConsumerARN: {arn}"
+    )]
+    StreamArn { arn: String },
+
+    #[error(
+        "KINESIS_CONSUMER_ARN has an invalid resource: {arn} (expected {})",
+        CONSUMER_ARN_FORMAT
+    )]
+    InvalidResource { arn: String },
+}
+
+/// Registered Kinesis enhanced fan-out consumer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KinesisConsumer {
+    pub arn: String,
+    pub region: String,
+    pub account_id: String,
+    pub stream_name: String,
+    pub consumer_name: String,
+}
+
+impl KinesisConsumer {
+    /// Consumer from `KINESIS_CONSUMER_ARN`, `None` when standard polling is used
+    pub fn from_env() -> Result<Option<Self>, ConsumerArnError> {
+        match env::var("KINESIS_CONSUMER_ARN") {
+            Ok(arn) if !arn.is_empty() => Self::parse(&arn).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse(arn: &str) -> Result<Self, ConsumerArnError> {
+        let parts: Vec<&str> = arn.splitn(6, ':').collect();
+        let [prefix, partition, service, region, account_id, resource] = parts[..] else {
+            return Err(ConsumerArnError::NotKinesis {
+                arn: arn.to_string(),
+            });
+        };
+        if prefix != "arn" || !partition.starts_with("aws") || service != "kinesis" {
+            return Err(ConsumerArnError::NotKinesis {
+                arn: arn.to_string(),
+            });
+        }
+        if region.is_empty() || account_id.is_empty() {
+            return Err(ConsumerArnError::MissingAccount {
+                arn: arn.to_string(),
+            });
+        }
+
+        // stream/<stream-name>/consumer/<consumer-name>:<timestamp>
+        let invalid = || ConsumerArnError::InvalidResource {
+            arn: arn.to_string(),
+        };
+        let segments: Vec<&str> = resource.split('/').collect();
+        match segments[..] {
+            ["stream", stream_name] if !stream_name.is_empty() => {
+                Err(ConsumerArnError::StreamArn {
+                    arn: arn.to_string(),
+                })
+            }
+            ["stream", stream_name, "consumer", consumer] if !stream_name.is_empty() => {
+                match consumer.split_once(':') {
+                    Some((consumer_name, timestamp))
+                        if !consumer_name.is_empty() && timestamp.parse::<u64>().is_ok() =>
+                    {
+                        Ok(Self {
+                            arn: arn.to_string(),
+                            region: region.to_string(),
+                            account_id: account_id.to_string(),
+                            stream_name: stream_name.to_string(),
+                            consumer_name: consumer_name.to_string(),
+                        })
+                    }
+                    _ => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Telemetry utilities shared by the Lambdas
+
+/// Kinesis consumer lag metrics
+pub mod consumer_lag;
+
+/// S3 storage of the Kinesis records projectors cannot parse
+pub mod dead_letters;
+
+/// Lambda deadline checks for batch processing
+pub mod deadline;
+
+/// DynamoDB client with slow query detection
+pub mod dynamo_tracing;
+
+/// Processing context added to published event metadata
+pub mod enrich;
+
+/// Kinesis enhanced fan-out consumer configuration
+pub mod kinesis_consumer;
+
+/// Concurrent processing of Kinesis batches
+pub mod kinesis_batch;
+
+/// Tracing subscriber and OpenTelemetry export
+pub mod setup;
+
+/// Tracing events captured by tests
+#[cfg(test)]
+mod testing;
+
+pub use consumer_lag::ConsumerLagMonitor;
+pub use dead_letters::{emit_dead_letter_metric, DeadLetter, DeadLetterStore};
+pub use deadline::DeadlineAwareProcessor;
+pub use dynamo_tracing::{traced_dynamodb_client, SlowQueryInterceptor};
+pub use enrich::{LambdaContext, MetadataEnricher};
+pub use kinesis_batch::{kinesis_concurrency, process_kinesis_batch};
+pub use kinesis_consumer::{ConsumerArnError, KinesisConsumer};
+pub use setup::{setup_lambda_tracing, TracingConfig};
@@ -0,0 +1,174 @@
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use std::env;
+use tracing::Level;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Install the global subscriber of a Lambda
+///
+/// - `RUST_LOG` filters events, falling back to `level` (`INFO` when `None`)
+/// - `LOG_FORMAT=json` switches to JSON lines, pretty output otherwise
+/// - `OTLP_ENDPOINT` exports spans to an OpenTelemetry collector under `service_name`
+pub fn setup_lambda_tracing(service_name: &str, level: Option<Level>) {
+    let config = TracingConfig::from_env(level);
+    let filter = EnvFilter::new(&config.filter);
+
+    // CloudWatch already timestamps every log line
+    let fmt_layer = if config.json {
+        fmt::layer()
+            .json()
+            .with_target(false)
+            .without_time()
+            .boxed()
+    } else {
+        fmt::layer()
+            .pretty()
+            .with_target(false)
+            .without_time()
+            .boxed()
+    };
+
+    let otel_layer = match config.otlp_endpoint {
+        Some(endpoint) => match otlp_tracer(service_name, &endpoint) {
+            Ok(tracer) => Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+            Err(e) => {
+                eprintln!("OTLP exporter to {} disabled: {}", endpoint, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .with(filter)
+        .init();
+}
+
+/// Subscriber settings of `setup_lambda_tracing`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracingConfig {
+    /// `EnvFilter` directives
+    pub filter: String,
+    /// JSON lines rather than pretty output
+    pub json: bool,
+    pub otlp_endpoint: Option<String>,
+}
+
+impl TracingConfig {
+    pub fn from_env(level: Option<Level>) -> Self {
+        Self::from_vars(level, |name| env::var(name).ok())
+    }
+
+    /// Settings from the variables `var` returns: an unset, empty or invalid `RUST_LOG` falls
+    /// back to `level`, and an empty `OTLP_ENDPOINT` disables the export
+    pub fn from_vars(level: Option<Level>, var: impl Fn(&str) -> Option<String>) -> Self {
+        let filter = var("RUST_LOG")
+            .filter(|filter| !filter.is_empty() && EnvFilter::try_new(filter).is_ok())
+            .unwrap_or_else(|| level.unwrap_or(Level::INFO).to_string());
+
+        Self {
+            filter,
+            json: var("LOG_FORMAT").is_some_and(|format| format.eq_ignore_ascii_case("json")),
+            otlp_endpoint: var("OTLP_ENDPOINT").filter(|endpoint| !endpoint.is_empty()),
+        }
+    }
+}
+
+fn otlp_tracer(
+    service_name: &str,
+    endpoint: &str,
+) -> Result<trace::Tracer, opentelemetry::trace::TraceError> {
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::Config::default().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", service_name.to_string()),
+        ])))
+        .install_batch(runtime::Tokio)?;
+
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    Ok(provider.tracer(service_name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn config(level: Option<Level>, vars: &[(&str, &str)]) -> TracingConfig {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        TracingConfig::from_vars(level, |name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn defaults_to_pretty_info_logs_without_export() {
+        assert_eq!(
+            config(None, &[]),
+            TracingConfig {
+                filter: "INFO".to_string(),
+                json: false,
+                otlp_endpoint: None,
+            }
+        );
+    }
+
+    #[test]
+    fn level_is_the_fallback_filter() {
+        assert_eq!(config(Some(Level::DEBUG), &[]).filter, "DEBUG");
+    }
+
+    #[test]
+    fn variables_override_the_defaults() {
+        let config = config(
+            Some(Level::DEBUG),
+            &[
+                ("RUST_LOG", "api=trace,warn"),
+                ("LOG_FORMAT", "JSON"),
+                ("OTLP_ENDPOINT", "http://collector:4317"),
+            ],
+        );
+
+        assert_eq!(
+            config,
+            TracingConfig {
+                filter: "api=trace,warn".to_string(),
+                json: true,
+                otlp_endpoint: Some("http://collector:4317".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_the_defaults() {
+        let config = config(
+            Some(Level::WARN),
+            &[
+                ("RUST_LOG", "api=loud"),
+                ("LOG_FORMAT", "xml"),
+                ("OTLP_ENDPOINT", ""),
+            ],
+        );
+
+        assert_eq!(
+            config,
+            TracingConfig {
+                filter: "WARN".to_string(),
+                json: false,
+                otlp_endpoint: None,
+            }
+        );
+    }
+
+    #[test]
+    fn empty_filter_falls_back_to_the_level() {
+        assert_eq!(config(None, &[("RUST_LOG", "")]).filter, "INFO");
+    }
+}
@@ -0,0 +1,9 @@
+#![no_main]
+
+use domain::DomainEvent;
+use libfuzzer_sys::fuzz_target;
+
+// Kinesis record data consumed by the projectors
+fuzz_target!(|data: &str| {
+    let _ = serde_json::from_str::<DomainEvent>(data);
+});
@@ -0,0 +1,85 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use domain::DomainEvent;
+use libfuzzer_sys::fuzz_target;
+use publisher::EventLogRecord;
+use serde_dynamo::{AttributeValue, Item};
+use std::collections::HashMap;
+
+/// Attribute names written by `dynamo-es`, so the fuzzer reaches the record fields
+#[derive(Arbitrary, Debug)]
+enum Field {
+    AggregateTypeAndId,
+    EventType,
+    AggregateId,
+    AggregateType,
+    Metadata,
+    Payload,
+    EventVersion,
+    AggregateIdSequence,
+}
+
+impl Field {
+    fn name(&self) -> &'static str {
+        match self {
+            Field::AggregateTypeAndId => "AggregateTypeAndId",
+            Field::EventType => "EventType",
+            Field::AggregateId => "AggregateId",
+            Field::AggregateType => "AggregateType",
+            Field::Metadata => "Metadata",
+            Field::Payload => "Payload",
+            Field::EventVersion => "EventVersion",
+            Field::AggregateIdSequence => "AggregateIdSequence",
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Value {
+    N(String),
+    S(String),
+    Bool(bool),
+    B(Vec<u8>),
+    Null(bool),
+    Ss(Vec<String>),
+    Ns(Vec<String>),
+    Bs(Vec<Vec<u8>>),
+}
+
+impl From<Value> for AttributeValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::N(n) => AttributeValue::N(n),
+            Value::S(s) => AttributeValue::S(s),
+            Value::Bool(b) => AttributeValue::Bool(b),
+            Value::B(b) => AttributeValue::B(b),
+            Value::Null(n) => AttributeValue::Null(n),
+            Value::Ss(ss) => AttributeValue::Ss(ss),
+            Value::Ns(ns) => AttributeValue::Ns(ns),
+            Value::Bs(bs) => AttributeValue::Bs(bs),
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    known: Vec<(Field, Value)>,
+    extra: Vec<(String, Value)>,
+}
+
+// DynamoDB stream images consumed by the publisher
+fuzz_target!(|input: Input| {
+    let mut attributes: HashMap<String, AttributeValue> = HashMap::new();
+
+    for (field, value) in input.known {
+        attributes.insert(field.name().to_string(), value.into());
+    }
+    for (name, value) in input.extra {
+        attributes.insert(name, value.into());
+    }
+
+    if let Ok(record) = serde_dynamo::from_item::<_, EventLogRecord>(Item::from(attributes)) {
+        let _ = DomainEvent::try_from(record);
+    }
+});
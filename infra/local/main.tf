@@ -13,6 +13,7 @@ provider "aws" {
     cloudwatch     = "http://localhost:4566"
     cloudwatchlogs = "http://localhost:4566"
     dynamodb       = "http://localhost:4566"
+    eventbridge    = "http://localhost:4566"
     iam            = "http://localhost:4566"
     kinesis        = "http://localhost:4566"
     lambda         = "http://localhost:4566"
@@ -0,0 +1,44 @@
+locals {
+  kinesis_projectors = {
+    projector_views    = aws_lambda_function.projector_views.function_name
+    projector_analyzer = aws_lambda_function.projector_analyzer.function_name
+  }
+}
+
+# Oldest record of a batch older than the threshold, from the metrics the projectors log
+resource "aws_cloudwatch_metric_alarm" "consumer_lag" {
+  for_each = local.kinesis_projectors
+
+  alarm_name          = "${each.value}-ConsumerLagAlert"
+  alarm_description   = "${each.value} is more than ${var.kinesis_max_lag_ms} ms behind the event stream"
+  namespace           = "Dispensary"
+  metric_name         = "KinesisConsumerLagMs"
+  dimensions          = { LambdaFunction = each.value }
+  statistic           = "Maximum"
+  period              = 60
+  evaluation_periods  = 3
+  threshold           = var.kinesis_max_lag_ms
+  comparison_operator = "GreaterThanThreshold"
+  treat_missing_data  = "notBreaching"
+
+  tags = local.common_tags
+}
+
+# Same threshold on the iterator age reported by the Kinesis event source mappings
+resource "aws_cloudwatch_metric_alarm" "iterator_age" {
+  for_each = local.kinesis_projectors
+
+  alarm_name          = "${each.value}-IteratorAge"
+  alarm_description   = "${each.value} event source mapping iterator is more than ${var.kinesis_max_lag_ms} ms old"
+  namespace           = "AWS/Lambda"
+  metric_name         = "IteratorAge"
+  dimensions          = { FunctionName = each.value }
+  statistic           = "Maximum"
+  period              = 60
+  evaluation_periods  = 3
+  threshold           = var.kinesis_max_lag_ms
+  comparison_operator = "GreaterThanThreshold"
+  treat_missing_data  = "notBreaching"
+
+  tags = local.common_tags
+}
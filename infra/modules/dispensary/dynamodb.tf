@@ -1,10 +1,10 @@
 # Event Log Table
 resource "aws_dynamodb_table" "event_log" {
-  name           = "${local.prefix}-event-log"
-  billing_mode   = "PAY_PER_REQUEST"
-  hash_key       = "AggregateTypeAndId"
-  range_key      = "AggregateIdSequence"
-  stream_enabled = true
+  name             = "${local.prefix}-event-log"
+  billing_mode     = "PAY_PER_REQUEST"
+  hash_key         = "AggregateTypeAndId"
+  range_key        = "AggregateIdSequence"
+  stream_enabled   = true
   stream_view_type = "NEW_IMAGE"
 
   attribute {
@@ -45,5 +45,205 @@ resource "aws_dynamodb_table" "dispenses_view" {
     type = "S"
   }
 
+  # Stale pending dispenses expire after PENDING_TTL_DAYS
+  ttl {
+    attribute_name = "ttl_at"
+    enabled        = true
+  }
+
+  tags = local.common_tags
+}
+
+# Prescribers View Table
+resource "aws_dynamodb_table" "prescribers_view" {
+  name         = "${local.prefix}-prescribers-view"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "ViewId"
+
+  attribute {
+    name = "ViewId"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Dispenses Timeline Index Table (per-pharmacy, sorted by creation date)
+resource "aws_dynamodb_table" "timeline_index" {
+  name         = "${local.prefix}-timeline-index"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "pharmacy_id"
+  range_key    = "created_at_dispense_id"
+
+  attribute {
+    name = "pharmacy_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "created_at_dispense_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "external_reference_id"
+    type = "S"
+  }
+
+  # Deduplicates dispenses created from the same EHR reference within a pharmacy
+  global_secondary_index {
+    name            = "external_reference_index"
+    hash_key        = "external_reference_id"
+    range_key       = "pharmacy_id"
+    projection_type = "ALL"
+  }
+
+  tags = local.common_tags
+}
+
+resource "aws_dynamodb_table" "patient_history" {
+  name         = "${local.prefix}-patient-history"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "patient_id"
+  range_key    = "dispense_id"
+
+  attribute {
+    name = "patient_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "dispense_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Patient Last Dispense Table (written by the views projector)
+resource "aws_dynamodb_table" "patient_last_dispense" {
+  name         = "${local.prefix}-patient-last-dispense"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "patient_id"
+
+  attribute {
+    name = "patient_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Export Locks Table (short-lived locks released by TTL)
+resource "aws_dynamodb_table" "export_locks" {
+  name         = "${local.prefix}-export-locks"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "lock_id"
+
+  attribute {
+    name = "lock_id"
+    type = "S"
+  }
+
+  ttl {
+    attribute_name = "expires_at"
+    enabled        = true
+  }
+
+  tags = local.common_tags
+}
+
+# Migrations Table (event store migration progress)
+resource "aws_dynamodb_table" "migrations" {
+  name         = "${local.prefix}-migrations"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "migration_id"
+
+  attribute {
+    name = "migration_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Analysis Jobs Table (prescription re-analysis status)
+resource "aws_dynamodb_table" "analysis_jobs" {
+  name         = "${local.prefix}-analysis-jobs"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "job_id"
+
+  attribute {
+    name = "job_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Formulary Table (drugs each pharmacy may dispense)
+resource "aws_dynamodb_table" "formulary" {
+  name         = "${local.prefix}-formulary"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "pharmacy_id"
+  range_key    = "drug_id"
+
+  attribute {
+    name = "pharmacy_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "drug_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# OCR Cache Table (prescription analyses by file, removed after 7 days)
+resource "aws_dynamodb_table" "ocr_cache" {
+  name         = "${local.prefix}-ocr-cache"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "prescription_url"
+
+  attribute {
+    name = "prescription_url"
+    type = "S"
+  }
+
+  ttl {
+    attribute_name = "ttl"
+    enabled        = true
+  }
+
+  tags = local.common_tags
+}
+
+# Replay Checkpoints Table (event replay progress across Lambda executions)
+resource "aws_dynamodb_table" "replay_checkpoints" {
+  name         = "${local.prefix}-replay-checkpoints"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "replay_id"
+
+  attribute {
+    name = "replay_id"
+    type = "S"
+  }
+
+  tags = local.common_tags
+}
+
+# Backup Jobs Table (state of each event log export to S3)
+resource "aws_dynamodb_table" "backup_jobs" {
+  name         = "${local.prefix}-backup-jobs"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "backup_id"
+
+  attribute {
+    name = "backup_id"
+    type = "S"
+  }
+
   tags = local.common_tags
 }
@@ -0,0 +1,35 @@
+# Event bus for rule-based routing of domain events to downstream services
+resource "aws_cloudwatch_event_bus" "domain_events" {
+  name = "${local.prefix}-events"
+
+  tags = local.common_tags
+}
+
+# Daily event log backup
+resource "aws_cloudwatch_event_rule" "event_backup_schedule" {
+  name                = "${local.prefix}-event-backup"
+  description         = "Export the event log to S3 every day"
+  schedule_expression = "cron(0 3 * * ? *)"
+
+  tags = local.common_tags
+}
+
+resource "aws_cloudwatch_event_target" "event_backup" {
+  rule = aws_cloudwatch_event_rule.event_backup_schedule.name
+  arn  = aws_lambda_function.event_backup.arn
+}
+
+resource "aws_lambda_permission" "eventbridge_invoke_backup" {
+  statement_id  = "AllowEventBridgeInvoke"
+  action        = "lambda:InvokeFunction"
+  function_name = aws_lambda_function.event_backup.function_name
+  principal     = "events.amazonaws.com"
+  source_arn    = aws_cloudwatch_event_rule.event_backup_schedule.arn
+}
+
+# One-time schedules cancelling dispenses left unfinished
+resource "aws_scheduler_schedule_group" "dispense_expiry" {
+  name = "${local.prefix}-dispense-expiry"
+
+  tags = local.common_tags
+}
@@ -16,6 +16,38 @@ resource "aws_iam_role" "lambda_exec" {
   tags = local.common_tags
 }
 
+# Assumed by EventBridge Scheduler to run the dispense expiry schedules
+resource "aws_iam_role" "dispense_expiry_scheduler" {
+  name = "${local.prefix}-dispense-expiry-scheduler"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [{
+      Action = "sts:AssumeRole"
+      Effect = "Allow"
+      Principal = {
+        Service = "scheduler.amazonaws.com"
+      }
+    }]
+  })
+
+  tags = local.common_tags
+}
+
+resource "aws_iam_role_policy" "dispense_expiry_scheduler" {
+  name = "${local.prefix}-dispense-expiry-scheduler"
+  role = aws_iam_role.dispense_expiry_scheduler.id
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [{
+      Effect   = "Allow"
+      Action   = ["lambda:InvokeFunction"]
+      Resource = aws_lambda_function.dispense_expiry.arn
+    }]
+  })
+}
+
 # Lambda basic execution policy
 resource "aws_iam_role_policy_attachment" "lambda_basic" {
   role       = aws_iam_role.lambda_exec.name
@@ -40,6 +72,8 @@ resource "aws_iam_role_policy" "lambda_policy" {
           "dynamodb:UpdateItem",
           "dynamodb:DeleteItem",
           "dynamodb:BatchWriteItem",
+          "dynamodb:TransactWriteItems",
+          "dynamodb:ConditionCheckItem",
           "dynamodb:GetRecords",
           "dynamodb:GetShardIterator",
           "dynamodb:DescribeStream",
@@ -49,7 +83,19 @@ resource "aws_iam_role_policy" "lambda_policy" {
           aws_dynamodb_table.event_log.arn,
           "${aws_dynamodb_table.event_log.arn}/*",
           aws_dynamodb_table.event_snapshots.arn,
-          aws_dynamodb_table.dispenses_view.arn
+          aws_dynamodb_table.dispenses_view.arn,
+          aws_dynamodb_table.prescribers_view.arn,
+          aws_dynamodb_table.timeline_index.arn,
+          "${aws_dynamodb_table.timeline_index.arn}/index/*",
+          aws_dynamodb_table.patient_history.arn,
+          aws_dynamodb_table.patient_last_dispense.arn,
+          aws_dynamodb_table.export_locks.arn,
+          aws_dynamodb_table.migrations.arn,
+          aws_dynamodb_table.analysis_jobs.arn,
+          aws_dynamodb_table.replay_checkpoints.arn,
+          aws_dynamodb_table.backup_jobs.arn,
+          aws_dynamodb_table.formulary.arn,
+          aws_dynamodb_table.ocr_cache.arn
         ]
       },
       {
@@ -60,33 +106,76 @@ resource "aws_iam_role_policy" "lambda_policy" {
           "kinesis:GetRecords",
           "kinesis:GetShardIterator",
           "kinesis:DescribeStream",
-          "kinesis:ListShards"
+          "kinesis:ListShards",
+          "kinesis:DescribeStreamConsumer",
+          "kinesis:SubscribeToShard"
+        ]
+        Resource = [
+          aws_kinesis_stream.event_stream.arn,
+          "${aws_kinesis_stream.event_stream.arn}/consumer/*"
         ]
-        Resource = aws_kinesis_stream.event_stream.arn
       },
       {
         Effect = "Allow"
         Action = [
           "s3:GetObject",
           "s3:PutObject",
-          "s3:ListBucket"
+          "s3:ListBucket",
+          "s3:DeleteObject",
+          "s3:AbortMultipartUpload"
         ]
         Resource = [
           aws_s3_bucket.prescriptions.arn,
-          "${aws_s3_bucket.prescriptions.arn}/*"
+          "${aws_s3_bucket.prescriptions.arn}/*",
+          aws_s3_bucket.event_backups.arn,
+          "${aws_s3_bucket.event_backups.arn}/*",
+          aws_s3_bucket.dead_letters.arn,
+          "${aws_s3_bucket.dead_letters.arn}/*"
+        ]
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["secretsmanager:GetSecretValue"]
+        Resource = aws_secretsmanager_secret.config.arn
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["events:PutEvents"]
+        Resource = aws_cloudwatch_event_bus.domain_events.arn
+      },
+      {
+        # The event replay hands over to a new execution before timing out
+        Effect   = "Allow"
+        Action   = ["lambda:InvokeFunction"]
+        Resource = aws_lambda_function.event_replay.arn
+      },
+      {
+        # Dispense expiry schedules, created on start and moved on prescription upload
+        Effect = "Allow"
+        Action = [
+          "scheduler:CreateSchedule",
+          "scheduler:DeleteSchedule"
         ]
+        Resource = "arn:aws:scheduler:*:*:schedule/${aws_scheduler_schedule_group.dispense_expiry.name}/*"
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["iam:PassRole"]
+        Resource = aws_iam_role.dispense_expiry_scheduler.arn
       },
       {
         Effect = "Allow"
         Action = [
           "sqs:SendMessage",
           "sqs:ReceiveMessage",
-          "sqs:DeleteMessage"
+          "sqs:DeleteMessage",
+          "sqs:GetQueueAttributes"
         ]
         Resource = [
           aws_sqs_queue.publisher_dlq.arn,
           aws_sqs_queue.projector_views_dlq.arn,
-          aws_sqs_queue.projector_analyzer_dlq.arn
+          aws_sqs_queue.projector_analyzer_dlq.arn,
+          aws_sqs_queue.analysis_jobs.arn
         ]
       }
     ]
@@ -11,10 +11,27 @@ resource "aws_lambda_function" "api" {
 
   environment {
     variables = {
-      DYNAMODB_EVENT_LOG_TABLE       = aws_dynamodb_table.event_log.name
-      DYNAMODB_EVENT_SNAPSHOTS_TABLE = aws_dynamodb_table.event_snapshots.name
-      DYNAMODB_DISPENSES_VIEW_TABLE  = aws_dynamodb_table.dispenses_view.name
+      SECRETS_ARN                    = aws_secretsmanager_secret.config.arn
+      PENDING_TTL_DAYS               = "7"
+      DISPENSE_SLA_HOURS             = "24"
+      PRESCRIPTION_MIN_QUALITY_SCORE = "0.7"
+      MAX_DRUGS_PER_DISPENSE         = "20"
+      MAX_DRUG_QUANTITY              = "9999"
+      MAX_EVENTS_BEFORE_COMPACTION   = "1000"
+      CQRS_RETRY_ENABLED             = "true"
+      PRESCRIBER_VALIDATION_API_URL  = var.prescriber_validation_api_url
+      DRUG_INTERACTION_CHECK_ENABLED = var.drug_interaction_api_url != "" ? "true" : "false"
+      DRUG_INTERACTION_API_URL       = var.drug_interaction_api_url
+      FORMULARY_VALIDATION_ENABLED   = tostring(var.formulary_validation_enabled)
+      ANALYSIS_QUEUE_URL             = aws_sqs_queue.analysis_jobs.url
+      PROMETHEUS_ENDPOINT_ENABLED    = var.environment == "local" ? "true" : "false"
       PRESCRIPTIONS_BUCKET           = aws_s3_bucket.prescriptions.id
+      DEAD_LETTERS_BUCKET            = aws_s3_bucket.dead_letters.id
+      EVENT_STREAM_NAME              = aws_kinesis_stream.event_stream.name
+      DISPENSE_EXPIRY_TARGET_ARN     = aws_lambda_function.dispense_expiry.arn
+      DISPENSE_EXPIRY_ROLE_ARN       = aws_iam_role.dispense_expiry_scheduler.arn
+      DISPENSE_EXPIRY_SCHEDULE_GROUP = aws_scheduler_schedule_group.dispense_expiry.name
+      DISPENSE_EXPIRY_HOURS          = tostring(var.dispense_expiry_hours)
       RUST_LOG                       = "info"
     }
   }
@@ -35,8 +52,12 @@ resource "aws_lambda_function" "publisher" {
 
   environment {
     variables = {
-      EVENT_STREAM_NAME = aws_kinesis_stream.event_stream.name
-      RUST_LOG          = "info"
+      EVENT_STREAM_NAME              = aws_kinesis_stream.event_stream.name
+      EVENTBRIDGE_BUS_NAME           = aws_cloudwatch_event_bus.domain_events.name
+      EVENT_BRIDGE_ENABLED           = "true"
+      KINESIS_PARTITION_KEY_STRATEGY = "aggregate_id"
+      KINESIS_COMPRESSION_ENABLED    = "false"
+      RUST_LOG                       = "info"
     }
   }
 
@@ -45,12 +66,13 @@ resource "aws_lambda_function" "publisher" {
 
 # Event source mapping: DynamoDB Stream -> Publisher Lambda
 resource "aws_lambda_event_source_mapping" "dynamodb_to_publisher" {
-  event_source_arn                   = aws_dynamodb_table.event_log.stream_arn
-  function_name                      = aws_lambda_function.publisher.arn
-  starting_position                  = "LATEST"
-  maximum_retry_attempts             = 3
-  bisect_batch_on_function_error     = true
-  maximum_record_age_in_seconds      = 604800
+  event_source_arn               = aws_dynamodb_table.event_log.stream_arn
+  function_name                  = aws_lambda_function.publisher.arn
+  enabled                        = !var.event_pipe_enabled
+  starting_position              = "LATEST"
+  maximum_retry_attempts         = 3
+  bisect_batch_on_function_error = true
+  maximum_record_age_in_seconds  = 604800
 
   destination_config {
     on_failure {
@@ -72,7 +94,11 @@ resource "aws_lambda_function" "projector_views" {
 
   environment {
     variables = {
-      RUST_LOG = "info"
+      SECRETS_ARN          = aws_secretsmanager_secret.config.arn
+      KINESIS_CONSUMER_ARN = var.kinesis_views_consumer_arn
+      KINESIS_MAX_LAG_MS   = tostring(var.kinesis_max_lag_ms)
+      DEAD_LETTERS_BUCKET  = aws_s3_bucket.dead_letters.id
+      RUST_LOG             = "info"
     }
   }
 
@@ -80,8 +106,9 @@ resource "aws_lambda_function" "projector_views" {
 }
 
 # Event source mapping: Kinesis -> Projector Views Lambda
+# Push-based delivery through the consumer when one is registered
 resource "aws_lambda_event_source_mapping" "kinesis_to_views" {
-  event_source_arn        = aws_kinesis_stream.event_stream.arn
+  event_source_arn        = var.kinesis_views_consumer_arn != "" ? var.kinesis_views_consumer_arn : aws_kinesis_stream.event_stream.arn
   function_name           = aws_lambda_function.projector_views.arn
   starting_position       = "LATEST"
   batch_size              = 10
@@ -108,10 +135,27 @@ resource "aws_lambda_function" "projector_analyzer" {
 
   environment {
     variables = {
-      DYNAMODB_EVENT_LOG_TABLE       = aws_dynamodb_table.event_log.name
-      DYNAMODB_EVENT_SNAPSHOTS_TABLE = aws_dynamodb_table.event_snapshots.name
-      DYNAMODB_DISPENSES_VIEW_TABLE  = aws_dynamodb_table.dispenses_view.name
+      SECRETS_ARN                    = aws_secretsmanager_secret.config.arn
+      PENDING_TTL_DAYS               = "7"
+      DISPENSE_SLA_HOURS             = "24"
+      PRESCRIPTION_MIN_QUALITY_SCORE = "0.7"
+      MAX_DRUGS_PER_DISPENSE         = "20"
+      MAX_DRUG_QUANTITY              = "9999"
+      MAX_EVENTS_BEFORE_COMPACTION   = "1000"
+      CQRS_RETRY_ENABLED             = "true"
+      PRESCRIBER_VALIDATION_API_URL  = var.prescriber_validation_api_url
+      DRUG_INTERACTION_CHECK_ENABLED = var.drug_interaction_api_url != "" ? "true" : "false"
+      DRUG_INTERACTION_API_URL       = var.drug_interaction_api_url
+      FORMULARY_VALIDATION_ENABLED   = tostring(var.formulary_validation_enabled)
       PRESCRIPTIONS_BUCKET           = aws_s3_bucket.prescriptions.id
+      ANALYSIS_MODEL_ID              = var.analysis_model_id
+      OCR_CACHE_ENABLED              = tostring(var.ocr_cache_enabled)
+      KINESIS_CONSUMER_ARN           = var.kinesis_analyzer_consumer_arn
+      KINESIS_MAX_LAG_MS             = tostring(var.kinesis_max_lag_ms)
+      DISPENSE_EXPIRY_TARGET_ARN     = aws_lambda_function.dispense_expiry.arn
+      DISPENSE_EXPIRY_ROLE_ARN       = aws_iam_role.dispense_expiry_scheduler.arn
+      DISPENSE_EXPIRY_SCHEDULE_GROUP = aws_scheduler_schedule_group.dispense_expiry.name
+      DISPENSE_EXPIRY_HOURS          = tostring(var.dispense_expiry_hours)
       RUST_LOG                       = "info"
     }
   }
@@ -120,8 +164,9 @@ resource "aws_lambda_function" "projector_analyzer" {
 }
 
 # Event source mapping: Kinesis -> Projector Analyzer Lambda
+# Push-based delivery through the consumer when one is registered
 resource "aws_lambda_event_source_mapping" "kinesis_to_analyzer" {
-  event_source_arn        = aws_kinesis_stream.event_stream.arn
+  event_source_arn        = var.kinesis_analyzer_consumer_arn != "" ? var.kinesis_analyzer_consumer_arn : aws_kinesis_stream.event_stream.arn
   function_name           = aws_lambda_function.projector_analyzer.arn
   starting_position       = "LATEST"
   batch_size              = 1
@@ -134,3 +179,125 @@ resource "aws_lambda_event_source_mapping" "kinesis_to_analyzer" {
     }
   }
 }
+
+# Event source mapping: SQS re-analysis jobs -> Projector Analyzer Lambda
+resource "aws_lambda_event_source_mapping" "analysis_jobs_to_analyzer" {
+  event_source_arn        = aws_sqs_queue.analysis_jobs.arn
+  function_name           = aws_lambda_function.projector_analyzer.arn
+  batch_size              = 1
+  function_response_types = ["ReportBatchItemFailures"]
+}
+
+# Event Store Migration Lambda (invoked manually)
+resource "aws_lambda_function" "event_store_migration" {
+  filename         = "../../target/lambda/event-store-migration/bootstrap.zip"
+  function_name    = "${local.prefix}-event-store-migration"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 900
+  source_code_hash = filebase64sha256("../../target/lambda/event-store-migration/bootstrap.zip")
+
+  environment {
+    variables = {
+      SECRETS_ARN = aws_secretsmanager_secret.config.arn
+      RUST_LOG    = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
+
+# Event Replay Lambda (invoked manually, re-invokes itself until done)
+resource "aws_lambda_function" "event_replay" {
+  filename         = "../../target/lambda/event-replay/bootstrap.zip"
+  function_name    = "${local.prefix}-event-replay"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 900
+  source_code_hash = filebase64sha256("../../target/lambda/event-replay/bootstrap.zip")
+
+  environment {
+    variables = {
+      SECRETS_ARN        = aws_secretsmanager_secret.config.arn
+      PENDING_TTL_DAYS   = "7"
+      DISPENSE_SLA_HOURS = "24"
+      RUST_LOG           = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
+
+# Event Backup Lambda (daily EventBridge schedule)
+resource "aws_lambda_function" "event_backup" {
+  filename         = "../../target/lambda/event-backup/bootstrap.zip"
+  function_name    = "${local.prefix}-event-backup"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 900
+  memory_size      = 512
+  source_code_hash = filebase64sha256("../../target/lambda/event-backup/bootstrap.zip")
+
+  environment {
+    variables = {
+      SECRETS_ARN      = aws_secretsmanager_secret.config.arn
+      S3_BACKUP_BUCKET = aws_s3_bucket.event_backups.id
+      RUST_LOG         = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
+
+# Event Restore Lambda (invoked manually with a backup key)
+resource "aws_lambda_function" "event_restore" {
+  filename         = "../../target/lambda/event-restore/bootstrap.zip"
+  function_name    = "${local.prefix}-event-restore"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 900
+  memory_size      = 1024
+  source_code_hash = filebase64sha256("../../target/lambda/event-restore/bootstrap.zip")
+
+  environment {
+    variables = {
+      SECRETS_ARN      = aws_secretsmanager_secret.config.arn
+      S3_BACKUP_BUCKET = aws_s3_bucket.event_backups.id
+      RUST_LOG         = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
+
+# Dispense Expiry Lambda (invoked by the one-time dispense expiry schedules)
+resource "aws_lambda_function" "dispense_expiry" {
+  filename         = "../../target/lambda/dispense-expiry/bootstrap.zip"
+  function_name    = "${local.prefix}-dispense-expiry"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 30
+  source_code_hash = filebase64sha256("../../target/lambda/dispense-expiry/bootstrap.zip")
+
+  environment {
+    variables = {
+      SECRETS_ARN        = aws_secretsmanager_secret.config.arn
+      PENDING_TTL_DAYS   = "7"
+      DISPENSE_SLA_HOURS = "24"
+      CQRS_RETRY_ENABLED = "true"
+      RUST_LOG           = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
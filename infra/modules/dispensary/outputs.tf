@@ -13,17 +13,33 @@ output "event_stream" {
   description = "Kinesis event stream name"
 }
 
+output "event_bus" {
+  value       = aws_cloudwatch_event_bus.domain_events.name
+  description = "EventBridge bus name"
+}
+
 output "prescriptions_bucket" {
   value       = aws_s3_bucket.prescriptions.id
   description = "Prescriptions S3 bucket name"
 }
 
+output "event_backups_bucket" {
+  value       = aws_s3_bucket.event_backups.id
+  description = "Event log backups S3 bucket name"
+}
+
 output "lambda_functions" {
   value = {
-    api                = aws_lambda_function.api.function_name
-    publisher          = aws_lambda_function.publisher.function_name
-    projector_views    = aws_lambda_function.projector_views.function_name
-    projector_analyzer = aws_lambda_function.projector_analyzer.function_name
+    api                   = aws_lambda_function.api.function_name
+    publisher             = aws_lambda_function.publisher.function_name
+    projector_views       = aws_lambda_function.projector_views.function_name
+    projector_analyzer    = aws_lambda_function.projector_analyzer.function_name
+    event_store_migration = aws_lambda_function.event_store_migration.function_name
+    event_replay          = aws_lambda_function.event_replay.function_name
+    event_backup          = aws_lambda_function.event_backup.function_name
+    event_restore         = aws_lambda_function.event_restore.function_name
+    dispense_expiry       = aws_lambda_function.dispense_expiry.function_name
+    pipes_enricher        = one(aws_lambda_function.pipes_enricher[*].function_name)
   }
   description = "Lambda function names"
 }
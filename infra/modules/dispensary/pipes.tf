@@ -0,0 +1,125 @@
+# EventBridge Pipe: event log stream -> enricher Lambda -> Kinesis, replacing the publisher
+# Lambda when `event_pipe_enabled` is set (without the EventBridge fan-out)
+resource "aws_lambda_function" "pipes_enricher" {
+  count = var.event_pipe_enabled ? 1 : 0
+
+  filename         = "../../target/lambda/pipes-enricher/bootstrap.zip"
+  function_name    = "${local.prefix}-pipes-enricher"
+  role             = aws_iam_role.lambda_exec.arn
+  handler          = "bootstrap"
+  runtime          = "provided.al2023"
+  architectures    = [var.lambda_architecture]
+  timeout          = 30
+  source_code_hash = filebase64sha256("../../target/lambda/pipes-enricher/bootstrap.zip")
+
+  environment {
+    variables = {
+      RUST_LOG = "info"
+    }
+  }
+
+  tags = local.common_tags
+}
+
+resource "aws_iam_role" "event_pipe" {
+  count = var.event_pipe_enabled ? 1 : 0
+
+  name = "${local.prefix}-event-pipe"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [{
+      Action = "sts:AssumeRole"
+      Effect = "Allow"
+      Principal = {
+        Service = "pipes.amazonaws.com"
+      }
+    }]
+  })
+
+  tags = local.common_tags
+}
+
+resource "aws_iam_role_policy" "event_pipe" {
+  count = var.event_pipe_enabled ? 1 : 0
+
+  name = "${local.prefix}-event-pipe"
+  role = aws_iam_role.event_pipe[0].id
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Effect = "Allow"
+        Action = [
+          "dynamodb:GetRecords",
+          "dynamodb:GetShardIterator",
+          "dynamodb:DescribeStream",
+          "dynamodb:ListStreams"
+        ]
+        Resource = aws_dynamodb_table.event_log.stream_arn
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["lambda:InvokeFunction"]
+        Resource = aws_lambda_function.pipes_enricher[0].arn
+      },
+      {
+        Effect = "Allow"
+        Action = [
+          "kinesis:PutRecord",
+          "kinesis:PutRecords"
+        ]
+        Resource = aws_kinesis_stream.event_stream.arn
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["sqs:SendMessage"]
+        Resource = aws_sqs_queue.publisher_dlq.arn
+      }
+    ]
+  })
+}
+
+resource "aws_pipes_pipe" "event_log_to_kinesis" {
+  count = var.event_pipe_enabled ? 1 : 0
+
+  name     = "${local.prefix}-event-log-to-kinesis"
+  role_arn = aws_iam_role.event_pipe[0].arn
+
+  source     = aws_dynamodb_table.event_log.stream_arn
+  enrichment = aws_lambda_function.pipes_enricher[0].arn
+  target     = aws_kinesis_stream.event_stream.arn
+
+  source_parameters {
+    dynamodb_stream_parameters {
+      starting_position                  = "LATEST"
+      batch_size                         = 100
+      maximum_retry_attempts             = 3
+      maximum_record_age_in_seconds      = 604800
+      on_partial_batch_item_failure      = "AUTOMATIC_BISECT"
+      maximum_batching_window_in_seconds = 0
+
+      dead_letter_config {
+        arn = aws_sqs_queue.publisher_dlq.arn
+      }
+    }
+
+    filter_criteria {
+      filter {
+        pattern = jsonencode({ eventName = ["INSERT"] })
+      }
+    }
+  }
+
+  target_parameters {
+    kinesis_stream_parameters {
+      # Aggregate ID of the enriched `DomainEvent`, as the publisher `aggregate_id` strategy
+      partition_key = "$.id"
+    }
+  }
+
+  tags = local.common_tags
+
+  depends_on = [aws_iam_role_policy.event_pipe]
+}
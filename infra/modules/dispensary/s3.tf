@@ -65,3 +65,37 @@ resource "aws_lambda_permission" "s3_invoke_analyzer" {
   principal     = "s3.amazonaws.com"
   source_arn    = aws_s3_bucket.prescriptions.arn
 }
+
+# Daily event log backups (gzipped JSON Lines)
+resource "aws_s3_bucket" "event_backups" {
+  bucket = "${local.prefix}-event-backups"
+
+  tags = local.common_tags
+}
+
+resource "aws_s3_bucket_server_side_encryption_configuration" "event_backups" {
+  bucket = aws_s3_bucket.event_backups.id
+
+  rule {
+    apply_server_side_encryption_by_default {
+      sse_algorithm = "AES256"
+    }
+  }
+}
+
+# Kinesis records the projectors could not parse, by day
+resource "aws_s3_bucket" "dead_letters" {
+  bucket = "${local.prefix}-dead-letters"
+
+  tags = local.common_tags
+}
+
+resource "aws_s3_bucket_server_side_encryption_configuration" "dead_letters" {
+  bucket = aws_s3_bucket.dead_letters.id
+
+  rule {
+    apply_server_side_encryption_by_default {
+      sse_algorithm = "AES256"
+    }
+  }
+}
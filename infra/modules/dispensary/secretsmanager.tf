@@ -0,0 +1,27 @@
+# Table names read by the Lambdas at startup (kept out of their environment)
+resource "aws_secretsmanager_secret" "config" {
+  name        = "dispensary/${var.environment}/config"
+  description = "DynamoDB table names of the dispensary Lambdas"
+
+  tags = local.common_tags
+}
+
+resource "aws_secretsmanager_secret_version" "config" {
+  secret_id = aws_secretsmanager_secret.config.id
+  secret_string = jsonencode({
+    event_log_table             = aws_dynamodb_table.event_log.name
+    snapshots_table             = aws_dynamodb_table.event_snapshots.name
+    view_table                  = aws_dynamodb_table.dispenses_view.name
+    prescribers_view_table      = aws_dynamodb_table.prescribers_view.name
+    timeline_index_table        = aws_dynamodb_table.timeline_index.name
+    patient_history_table       = aws_dynamodb_table.patient_history.name
+    patient_last_dispense_table = aws_dynamodb_table.patient_last_dispense.name
+    export_locks_table          = aws_dynamodb_table.export_locks.name
+    analysis_jobs_table         = aws_dynamodb_table.analysis_jobs.name
+    replay_checkpoints_table    = aws_dynamodb_table.replay_checkpoints.name
+    backup_jobs_table           = aws_dynamodb_table.backup_jobs.name
+    migrations_table            = aws_dynamodb_table.migrations.name
+    formulary_table             = aws_dynamodb_table.formulary.name
+    ocr_cache_table             = aws_dynamodb_table.ocr_cache.name
+  })
+}
@@ -14,3 +14,17 @@ resource "aws_sqs_queue" "projector_analyzer_dlq" {
   name = "${local.prefix}-projector-analyzer-dlq"
   tags = local.common_tags
 }
+
+# Prescription re-analysis jobs (API -> Projector Analyzer)
+resource "aws_sqs_queue" "analysis_jobs" {
+  name = "${local.prefix}-analysis-jobs"
+  # Must exceed the analyzer timeout
+  visibility_timeout_seconds = 360
+
+  redrive_policy = jsonencode({
+    deadLetterTargetArn = aws_sqs_queue.projector_analyzer_dlq.arn
+    maxReceiveCount     = 3
+  })
+
+  tags = local.common_tags
+}
@@ -21,6 +21,36 @@ variable "lambda_architecture" {
   default     = "x86_64"
 }
 
+variable "prescriber_validation_api_url" {
+  type        = string
+  description = "State license database endpoint for prescriber validation (format check only when empty)"
+  default     = ""
+}
+
+variable "drug_interaction_api_url" {
+  type        = string
+  description = "Drug interaction API endpoint (checks disabled when empty)"
+  default     = ""
+}
+
+variable "formulary_validation_enabled" {
+  type        = bool
+  description = "Reject drugs missing from the pharmacy formulary table"
+  default     = false
+}
+
+variable "analysis_model_id" {
+  type        = string
+  description = "Model ID recorded on prescription analyses"
+  default     = "anthropic.claude-3-sonnet-20240229-v1:0"
+}
+
+variable "ocr_cache_enabled" {
+  type        = bool
+  description = "Reuse prescription analyses of the same file for 7 days"
+  default     = true
+}
+
 locals {
   prefix = "dispensary-${var.environment}"
 
@@ -30,3 +60,33 @@ locals {
     ProvisionedBy = "terraform"
   }
 }
+
+variable "kinesis_views_consumer_arn" {
+  type        = string
+  description = "Enhanced fan-out consumer for the views projector (standard polling when empty)"
+  default     = ""
+}
+
+variable "kinesis_analyzer_consumer_arn" {
+  type        = string
+  description = "Enhanced fan-out consumer for the analyzer projector (standard polling when empty)"
+  default     = ""
+}
+
+variable "dispense_expiry_hours" {
+  type        = number
+  description = "Hours before an unfinished dispense is cancelled, extended by each prescription upload"
+  default     = 72
+}
+
+variable "kinesis_max_lag_ms" {
+  type        = number
+  description = "Kinesis record age (ms) above which the projectors raise a consumer lag alarm"
+  default     = 60000
+}
+
+variable "event_pipe_enabled" {
+  type        = bool
+  description = "Publish the event log to Kinesis with an EventBridge Pipe instead of the publisher Lambda"
+  default     = false
+}
@@ -0,0 +1,140 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use lambda_http::request::RequestContext;
+use std::{collections::HashMap, convert::Infallible};
+use ulid::Ulid;
+
+use crate::versioning::RequestId;
+
+/// Caller of a request, the JWT subject when authenticated through API Gateway
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Actor {
+    pub id: Option<String>,
+    /// Set by `versioning::check_version` on the routes of a single dispense
+    pub request_id: Option<String>,
+}
+
+impl Actor {
+    pub fn from_context(context: Option<&RequestContext>) -> Self {
+        let id = match context {
+            Some(RequestContext::ApiGatewayV2(context)) => context
+                .authorizer
+                .as_ref()
+                .and_then(|authorizer| authorizer.jwt.as_ref())
+                .and_then(|jwt| jwt.claims.get("sub").cloned()),
+            _ => None,
+        };
+
+        Self {
+            id,
+            request_id: None,
+        }
+    }
+
+    /// Caller recorded by pharmacist acknowledgments, `anonymous` when unauthenticated
+    pub fn name(&self) -> String {
+        self.id.clone().unwrap_or("anonymous".to_string())
+    }
+
+    /// Metadata of a command sent by the caller: a new `command_id`, the caller as `actor_id`
+    /// (stamped on the events by `DispenseEventStore`) when authenticated, and the `request_id`
+    pub fn command_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert("command_id".to_string(), Ulid::new().to_string());
+        if let Some(actor_id) = &self.id {
+            metadata.insert("actor_id".to_string(), actor_id.clone());
+        }
+        if let Some(request_id) = &self.request_id {
+            metadata.insert("request_id".to_string(), request_id.clone());
+        }
+        metadata
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let mut actor = Self::from_context(parts.extensions.get::<RequestContext>());
+        actor.request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .map(|RequestId(id)| id.clone());
+
+        Ok(actor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lambda_http::aws_lambda_events::apigw::{
+        ApiGatewayRequestAuthorizer, ApiGatewayRequestAuthorizerJwtDescription,
+        ApiGatewayV2httpRequestContext,
+    };
+
+    use super::*;
+
+    fn authenticated(sub: &str) -> RequestContext {
+        RequestContext::ApiGatewayV2(ApiGatewayV2httpRequestContext {
+            authorizer: Some(ApiGatewayRequestAuthorizer {
+                jwt: Some(ApiGatewayRequestAuthorizerJwtDescription {
+                    claims: HashMap::from([("sub".to_string(), sub.to_string())]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn command_metadata_carries_authenticated_caller() {
+        let actor = Actor::from_context(Some(&authenticated("user-1")));
+
+        let metadata = actor.command_metadata();
+
+        assert_eq!(metadata.get("actor_id").map(String::as_str), Some("user-1"));
+        assert!(metadata.contains_key("command_id"));
+        assert_eq!(actor.name(), "user-1");
+    }
+
+    #[test]
+    fn command_metadata_of_anonymous_caller_has_no_actor() {
+        let actor = Actor::from_context(None);
+
+        let metadata = actor.command_metadata();
+
+        assert!(!metadata.contains_key("actor_id"));
+        assert!(metadata.contains_key("command_id"));
+        assert_eq!(actor.name(), "anonymous");
+    }
+
+    #[test]
+    fn each_command_gets_its_own_id() {
+        let actor = Actor {
+            id: Some("user-1".to_string()),
+            request_id: None,
+        };
+
+        assert_ne!(
+            actor.command_metadata()["command_id"],
+            actor.command_metadata()["command_id"]
+        );
+    }
+
+    #[test]
+    fn command_metadata_carries_request_id() {
+        let actor = Actor {
+            id: None,
+            request_id: Some("request-1".to_string()),
+        };
+
+        assert_eq!(
+            actor
+                .command_metadata()
+                .get("request_id")
+                .map(String::as_str),
+            Some("request-1")
+        );
+    }
+}
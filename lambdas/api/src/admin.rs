@@ -0,0 +1,209 @@
+use aws_sdk_kinesis::primitives::Blob;
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::NaiveDate;
+use domain::{
+    dispenses::{self, DispenseConfig},
+    DomainEvent,
+};
+use lambda_http::{request::RequestContext, RequestExt};
+use serde::Deserialize;
+
+use crate::{errors::ApiError, AppState};
+
+const ADMIN_ROLE: &str = "admin";
+
+/// Admin routes, all guarded by `require_admin`
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/admin/snapshots", get(list_snapshots))
+        .route("/admin/snapshots/stats", get(snapshot_stats))
+        .route("/admin/snapshots/:aggregate_id", delete(delete_snapshot))
+        .route("/admin/analysis-jobs/:job_id", get(get_analysis_job))
+        .route("/admin/dispenses/:id/event-count", get(get_event_count))
+        .route(
+            "/admin/replays/:replay_id/progress",
+            get(get_replay_progress),
+        )
+        .route("/admin/backups", get(list_backups))
+        .route("/admin/schemas/inputs", get(list_input_schemas))
+        .route("/admin/dead-letters", get(list_dead_letters))
+        .route(
+            "/admin/dead-letters/:key/reprocess",
+            post(reprocess_dead_letter),
+        )
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+// Reject unauthenticated callers with 401, and those whose API Gateway JWT claims do not carry
+// the admin role with 403
+async fn require_admin(State(_state): State<AppState>, request: Request, next: Next) -> Response {
+    let jwt = match request.request_context_ref() {
+        Some(RequestContext::ApiGatewayV2(context)) => context
+            .authorizer
+            .as_ref()
+            .and_then(|authorizer| authorizer.jwt.as_ref()),
+        _ => None,
+    };
+    let Some(jwt) = jwt else {
+        return domain::Error::Unauthorized.into_response();
+    };
+
+    let is_admin = jwt.claims.get("role").map(String::as_str) == Some(ADMIN_ROLE)
+        || jwt.claims.get("cognito:groups").is_some_and(|groups| {
+            groups
+                .split(['[', ']', ',', ' '])
+                .any(|group| group == ADMIN_ROLE)
+        });
+    if !is_admin {
+        return domain::Error::Forbidden.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapshotParams {
+    aggregate_id: Option<String>,
+}
+
+// List snapshots, optionally for a single aggregate
+async fn list_snapshots(
+    Query(params): Query<SnapshotParams>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let snapshots = state
+        .snapshot_inspector
+        .list(params.aggregate_id.as_deref())
+        .await?;
+
+    Ok(Json(snapshots))
+}
+
+// Snapshot count, average size and oldest age
+async fn snapshot_stats(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let stats = state.snapshot_inspector.stats().await?;
+
+    Ok(Json(stats))
+}
+
+// Status of a prescription re-analysis job
+async fn get_analysis_job(
+    Path(job_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = state
+        .analysis_jobs
+        .get(&job_id)
+        .await?
+        .ok_or(ApiError::not_found("Analysis job"))?;
+
+    Ok(Json(job))
+}
+
+// Events of a dispense, against the count past which only its cancellation is accepted
+async fn get_event_count(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let config = DispenseConfig::from_env();
+    Ok(Json(serde_json::json!({
+        "dispense_id": id,
+        "event_count": view.aggregate_version,
+        "max_events_before_compaction": config.max_events_before_compaction,
+        "compaction_warning_events": config.compaction_warning_events(),
+    })))
+}
+
+// Latest checkpoint of an event replay
+async fn get_replay_progress(
+    Path(replay_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let checkpoint = state
+        .replay_checkpoints
+        .get(&replay_id)
+        .await?
+        .ok_or(ApiError::not_found("Replay"))?;
+
+    Ok(Json(checkpoint))
+}
+
+// Event log backups, most recent first
+async fn list_backups(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let backups = state.backup_jobs.list().await?;
+
+    Ok(Json(backups))
+}
+
+// Request body schemas loaded at startup, by route
+async fn list_input_schemas(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.input_schemas.list())
+}
+
+#[derive(Debug, Deserialize)]
+struct DeadLetterParams {
+    date: NaiveDate,
+}
+
+// Keys of the records the projectors could not parse on a day (`?date=2024-01-15`)
+async fn list_dead_letters(
+    Query(params): Query<DeadLetterParams>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let keys = state.dead_letters.list(params.date).await?;
+
+    Ok(Json(keys))
+}
+
+// Put a dead letter back on the event stream once it parses (e.g. after a consumer fix), the key
+// URL-encoded (`2024%2F01%2F15%2F{sequence_number}.json`)
+async fn reprocess_dead_letter(
+    Path(key): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let dead_letter = state
+        .dead_letters
+        .get(&key)
+        .await?
+        .ok_or(ApiError::not_found("Dead letter"))?;
+
+    let event = DomainEvent::from_kinesis_data(&dead_letter.data)
+        .map_err(|e| ApiError::bad_request(format!("Dead letter still cannot be parsed: {}", e)))?;
+
+    let stream_name = std::env::var("EVENT_STREAM_NAME").map_err(ApiError::internal)?;
+    state
+        .kinesis_client
+        .put_record()
+        .stream_name(stream_name)
+        .partition_key(&event.id)
+        .data(Blob::new(dead_letter.data.0))
+        .send()
+        .await
+        .map_err(ApiError::internal)?;
+    state.dead_letters.delete(&key).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Delete a snapshot so the next load replays from scratch
+async fn delete_snapshot(
+    Path(aggregate_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    state.snapshot_inspector.delete(&aggregate_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
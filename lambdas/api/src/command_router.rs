@@ -0,0 +1,395 @@
+use axum::{
+    body::Bytes,
+    extract::{MatchedPath, Path, State},
+    http::{Method, StatusCode},
+    response::IntoResponse,
+};
+use domain::dispenses::{inputs, Command};
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+
+use crate::{actor::Actor, errors::ApiError, AppState};
+
+/// Builds the command of a route from its JSON body
+type CommandBuilder = fn(&Bytes) -> Result<Command, ApiError>;
+
+struct CommandRoute {
+    build: CommandBuilder,
+    /// Response body once the command is executed
+    message: &'static str,
+}
+
+static ROUTES: Lazy<HashMap<(Method, &'static str), CommandRoute>> = Lazy::new(|| {
+    HashMap::from([
+        route("/dispenses/:id/patient", add_patient, "Patient added"),
+        route(
+            "/dispenses/:id/prescriber",
+            set_prescriber,
+            "Prescriber set",
+        ),
+        route("/dispenses/:id/drugs", add_drugs, "Drugs added"),
+        route(
+            "/dispenses/:id/fulfillment",
+            set_fulfillment_method,
+            "Fulfillment method set",
+        ),
+        route(
+            "/dispenses/:id/shipment",
+            record_shipment,
+            "Shipment recorded",
+        ),
+        route(
+            "/dispenses/:id/delivery",
+            record_delivery,
+            "Delivery recorded",
+        ),
+        route(
+            "/dispenses/:id/complete",
+            complete_dispense,
+            "Dispense completed",
+        ),
+    ])
+});
+
+fn route(
+    path: &'static str,
+    build: CommandBuilder,
+    message: &'static str,
+) -> ((Method, &'static str), CommandRoute) {
+    ((Method::POST, path), CommandRoute { build, message })
+}
+
+/// Dispense commands built straight from the request, for the routes executing a single command
+/// on `/dispenses/:id` without reading anything else
+pub struct CommandRouter;
+
+impl CommandRouter {
+    /// Command of the matched route and its response message
+    ///
+    /// Unknown routes are 404s and malformed bodies 400s.
+    pub fn route(
+        method: &Method,
+        path: &str,
+        body: &Bytes,
+    ) -> Result<(Command, &'static str), ApiError> {
+        let route = ROUTES.get(&(method.clone(), path)).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "route_not_found",
+                format!("No command for {} {}", method, path),
+            )
+        })?;
+
+        Ok(((route.build)(body)?, route.message))
+    }
+}
+
+// Execute the command of a `CommandRouter` route
+pub async fn execute_command(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    actor: Actor,
+    method: Method,
+    path: MatchedPath,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let (command, message) = CommandRouter::route(&method, path.as_str(), &body)?;
+
+    state
+        .dispenses_cqrs
+        .execute_with_metadata(&id, command, actor.command_metadata())
+        .await?;
+
+    Ok((StatusCode::OK, message))
+}
+
+fn input<T: DeserializeOwned>(body: &Bytes) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::bad_request(format!("Invalid body: {}", e)))
+}
+
+fn add_patient(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::AddPatientInput = input(body)?;
+    Ok(Command::AddPatient {
+        patient_id: input.patient_id.into(),
+        name: input.name,
+    })
+}
+
+fn set_prescriber(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::SetPrescriberInput = input(body)?;
+    Ok(Command::SetPrescriber {
+        prescriber_id: input.prescriber_id.map(Into::into),
+        license_number: input.license_number,
+        state: input.state,
+    })
+}
+
+fn add_drugs(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::AddDrugsInput = input(body)?;
+    Ok(Command::AddDrugs { drugs: input.drugs })
+}
+
+fn set_fulfillment_method(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::SetFulfillmentMethodInput = input(body)?;
+    Ok(Command::SetFulfillmentMethod {
+        method: input.method,
+    })
+}
+
+fn record_shipment(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::RecordShipmentInput = input(body)?;
+    Ok(Command::RecordShipment {
+        tracking_number: input.tracking_number,
+        shipped_at: input.shipped_at,
+        carrier: input.carrier,
+    })
+}
+
+fn record_delivery(body: &Bytes) -> Result<Command, ApiError> {
+    let input: inputs::RecordDeliveryInput = input(body)?;
+    Ok(Command::RecordDelivery {
+        delivered_at: input.delivered_at,
+    })
+}
+
+fn complete_dispense(_body: &Bytes) -> Result<Command, ApiError> {
+    Ok(Command::CompleteDispense)
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::dispenses::{aggregate::DrugItem, Address, FulfillmentMethod};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn route(path: &str, body: Value) -> Result<(Command, &'static str), ApiError> {
+        CommandRouter::route(&Method::POST, path, &Bytes::from(body.to_string()))
+    }
+
+    fn assert_status(result: Result<(Command, &'static str), ApiError>, expected: StatusCode) {
+        match result {
+            Err(ApiError::Other { status, .. }) => assert_eq!(status, expected),
+            other => panic!("Expected a {} error, got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn add_patient() {
+        let result = route(
+            "/dispenses/:id/patient",
+            json!({ "patient_id": "patient-1", "name": "Test Patient" }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::AddPatient {
+                    patient_id: "patient-1".to_string(),
+                    name: "Test Patient".to_string(),
+                },
+                "Patient added"
+            )
+        );
+    }
+
+    #[test]
+    fn add_patient_rejects_empty_patient_id() {
+        let result = route(
+            "/dispenses/:id/patient",
+            json!({ "patient_id": " ", "name": "Test Patient" }),
+        );
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn set_prescriber() {
+        let result = route(
+            "/dispenses/:id/prescriber",
+            json!({ "license_number": "CA123456", "state": "CA" }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::SetPrescriber {
+                    prescriber_id: None,
+                    license_number: "CA123456".to_string(),
+                    state: "CA".to_string(),
+                },
+                "Prescriber set"
+            )
+        );
+    }
+
+    #[test]
+    fn set_prescriber_requires_license_number() {
+        let result = route("/dispenses/:id/prescriber", json!({ "state": "CA" }));
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn add_drugs() {
+        let result = route(
+            "/dispenses/:id/drugs",
+            json!({ "drugs": [{ "drug_id": "drug-1", "name": "Drug 1", "quantity": 1 }] }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::AddDrugs {
+                    drugs: vec![DrugItem::builder()
+                        .drug_id("drug-1")
+                        .name("Drug 1")
+                        .quantity(1)
+                        .build()
+                        .unwrap()],
+                },
+                "Drugs added"
+            )
+        );
+    }
+
+    #[test]
+    fn add_drugs_rejects_negative_quantity() {
+        let result = route(
+            "/dispenses/:id/drugs",
+            json!({ "drugs": [{ "drug_id": "drug-1", "name": "Drug 1", "quantity": -1 }] }),
+        );
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn set_fulfillment_method() {
+        let address = json!({
+            "street": "1 Main St",
+            "city": "Springfield",
+            "state": "CA",
+            "zip": "90001",
+            "country": "US",
+        });
+        let result = route(
+            "/dispenses/:id/fulfillment",
+            json!({ "method": { "type": "MailOrder", "address": address, "tracking_number": null } }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::SetFulfillmentMethod {
+                    method: FulfillmentMethod::MailOrder {
+                        address: Address {
+                            street: "1 Main St".to_string(),
+                            city: "Springfield".to_string(),
+                            state: "CA".to_string(),
+                            zip: "90001".to_string(),
+                            country: "US".to_string(),
+                        },
+                        tracking_number: None,
+                    },
+                },
+                "Fulfillment method set"
+            )
+        );
+    }
+
+    #[test]
+    fn set_fulfillment_method_rejects_unknown_method() {
+        let result = route(
+            "/dispenses/:id/fulfillment",
+            json!({ "method": { "type": "Drone" } }),
+        );
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn record_shipment() {
+        let result = route(
+            "/dispenses/:id/shipment",
+            json!({
+                "tracking_number": "1Z999",
+                "shipped_at": "2024-01-01T00:00:00Z",
+                "carrier": "UPS",
+            }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::RecordShipment {
+                    tracking_number: "1Z999".to_string(),
+                    shipped_at: "2024-01-01T00:00:00Z".parse().unwrap(),
+                    carrier: "UPS".to_string(),
+                },
+                "Shipment recorded"
+            )
+        );
+    }
+
+    #[test]
+    fn record_shipment_rejects_invalid_date() {
+        let result = route(
+            "/dispenses/:id/shipment",
+            json!({ "tracking_number": "1Z999", "shipped_at": "yesterday", "carrier": "UPS" }),
+        );
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn record_delivery() {
+        let result = route(
+            "/dispenses/:id/delivery",
+            json!({ "delivered_at": "2024-01-02T00:00:00Z" }),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            (
+                Command::RecordDelivery {
+                    delivered_at: "2024-01-02T00:00:00Z".parse().unwrap(),
+                },
+                "Delivery recorded"
+            )
+        );
+    }
+
+    #[test]
+    fn record_delivery_rejects_invalid_json() {
+        let result = CommandRouter::route(
+            &Method::POST,
+            "/dispenses/:id/delivery",
+            &Bytes::from_static(b"{\"delivered_at\":"),
+        );
+
+        assert_status(result, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn complete_dispense_ignores_body() {
+        let result = CommandRouter::route(&Method::POST, "/dispenses/:id/complete", &Bytes::new());
+
+        assert_eq!(
+            result.unwrap(),
+            (Command::CompleteDispense, "Dispense completed")
+        );
+    }
+
+    #[test]
+    fn unknown_route_is_not_found() {
+        assert_status(
+            route("/dispenses/:id/unknown", json!({})),
+            StatusCode::NOT_FOUND,
+        );
+        assert_status(
+            CommandRouter::route(&Method::GET, "/dispenses/:id/patient", &Bytes::new()),
+            StatusCode::NOT_FOUND,
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use cqrs_es::{persist::PersistenceError, AggregateError};
+use domain::ErrorBody;
+use serde_json::Value;
+
+/// Handler error, rendered as an `ErrorBody` JSON response
+///
+/// Domain errors keep their own status (see `domain::Error::status_code`); failures of the
+/// event store or of AWS calls are 500s.
+#[derive(Debug)]
+pub enum ApiError {
+    Domain(domain::Error),
+    Other { status: StatusCode, body: ErrorBody },
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, error_code: &str, message: impl Into<String>) -> Self {
+        Self::Other {
+            status,
+            body: ErrorBody {
+                error_code: error_code.to_string(),
+                message: message.into(),
+                details: None,
+            },
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    /// The error is logged, not returned: AWS and event store errors name tables and resources
+    pub fn internal(error: impl ToString) -> Self {
+        tracing::error!("Internal error: {}", error.to_string());
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Internal server error",
+        )
+    }
+
+    pub fn not_found(entity: &str) -> Self {
+        Self::Domain(domain::Error::NotFound {
+            entity: entity.to_string(),
+        })
+    }
+
+    /// Add fields to the `details` of the body, keeping the status and code
+    pub fn with_details(self, details: Value) -> Self {
+        let (status, mut body) = match self {
+            ApiError::Domain(error) => (error.status_code(), ErrorBody::from(&error)),
+            ApiError::Other { status, body } => (status, body),
+        };
+        body.details = match (body.details, details) {
+            (Some(Value::Object(mut existing)), Value::Object(added)) => {
+                existing.extend(added);
+                Some(Value::Object(existing))
+            }
+            (_, details) => Some(details),
+        };
+
+        Self::Other { status, body }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::Domain(error) => error.into_response(),
+            ApiError::Other { status, body } => (status, Json(body)).into_response(),
+        }
+    }
+}
+
+impl From<domain::Error> for ApiError {
+    fn from(error: domain::Error) -> Self {
+        Self::Domain(error)
+    }
+}
+
+impl From<AggregateError<domain::Error>> for ApiError {
+    fn from(error: AggregateError<domain::Error>) -> Self {
+        match error {
+            AggregateError::UserError(error) => Self::Domain(error),
+            AggregateError::AggregateConflict => Self::new(
+                StatusCode::CONFLICT,
+                "concurrency_conflict",
+                error.to_string(),
+            ),
+            error => Self::internal(error),
+        }
+    }
+}
+
+impl From<PersistenceError> for ApiError {
+    fn from(error: PersistenceError) -> Self {
+        match error {
+            PersistenceError::OptimisticLockError => Self::new(
+                StatusCode::CONFLICT,
+                "concurrency_conflict",
+                error.to_string(),
+            ),
+            error => Self::internal(error),
+        }
+    }
+}
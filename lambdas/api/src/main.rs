@@ -1,60 +1,244 @@
 use aws_config::BehaviorVersion;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use domain::dispenses::{self, Dispense};
-use std::{collections::HashMap, sync::Arc};
+use cqrs_es::DomainEvent;
+use domain::{
+    config::ConfigLoader,
+    dispenses::{
+        self,
+        analysis_jobs::{AnalysisJob, AnalysisJobMessage, AnalysisJobStatus},
+        Dispense,
+    },
+    prescribers::{self, Prescriber},
+};
+use serde::Deserialize;
+use std::sync::Arc;
 use ulid::Ulid;
 
+use actor::Actor;
+use command_router::execute_command;
+use errors::ApiError;
+
+mod actor;
+mod admin;
+mod command_router;
+mod errors;
+mod metrics;
+mod prescriber_routes;
+mod presigned_urls;
+mod schemas;
+mod shutdown;
+mod versioning;
+
 #[derive(Clone)]
 struct AppState {
     dispenses_repo: Arc<Box<dyn cqrs_es::persist::ViewRepository<dispenses::View, Dispense>>>,
-    dispenses_cqrs: Arc<
+    dispenses_cqrs: Arc<dispenses::cqrs::DispenseCqrs>,
+    dispenses_dry_run: Arc<dispenses::cqrs::DispenseDryRun>,
+    dispenses_events: Arc<dispenses::DispenseEventStore>,
+    dispenses_scanner: Arc<dispenses::ViewScanner>,
+    dispenses_timeline: Arc<dispenses::TimelineIndexQuery>,
+    patient_history: Arc<dispenses::PatientHistoryQuery>,
+    patient_last_dispense: Arc<dispenses::PatientLastDispenseStore>,
+    dispenses_export_lock: Arc<dispenses::export::ExportLock>,
+    snapshot_inspector: Arc<dispenses::snapshots::SnapshotInspector>,
+    analysis_jobs: Arc<dispenses::analysis_jobs::AnalysisJobStore>,
+    replay_checkpoints: Arc<domain::replay::ReplayCheckpointStore>,
+    backup_jobs: Arc<domain::backups::BackupJobStore>,
+    prescribers_repo: Arc<Box<dyn cqrs_es::persist::ViewRepository<prescribers::View, Prescriber>>>,
+    prescribers_cqrs: Arc<
         cqrs_es::CqrsFramework<
-            Dispense,
-            cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Dispense>,
+            Prescriber,
+            cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Prescriber>,
         >,
     >,
     s3_client: aws_sdk_s3::Client,
+    presigned_urls: presigned_urls::PresignedUrlCache,
+    sqs_client: aws_sdk_sqs::Client,
+    kinesis_client: aws_sdk_kinesis::Client,
+    dead_letters: Arc<telemetry::DeadLetterStore>,
+    input_schemas: Arc<schemas::InputSchemas>,
+    in_flight: Arc<tokio::sync::Semaphore>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), lambda_http::Error> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .without_time()
-        .init();
+    telemetry::setup_lambda_tracing("api", None);
 
     let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
-    let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+    // Table names read by the `cqrs::init` functions
+    ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
     let s3_client = aws_sdk_s3::Client::new(&config);
+    let sqs_client = aws_sdk_sqs::Client::new(&config);
+    let kinesis_client = aws_sdk_kinesis::Client::new(&config);
+    let dead_letters = Arc::new(telemetry::DeadLetterStore::from_env(s3_client.clone()));
+    let expiry_scheduler =
+        dispenses::SchedulerService::from_env(aws_sdk_scheduler::Client::new(&config));
+
+    let dispenses_repo = dispenses::cqrs::init_cached_repo(dynamodb_client.clone());
+    let dispenses_timeline = dispenses::cqrs::init_timeline(dynamodb_client.clone());
+    let dispenses_dry_run = dispenses::cqrs::init_dry_run(dynamodb_client.clone());
+    let dispenses_events = dispenses::cqrs::init_event_store(dynamodb_client.clone());
+    let dispenses_scanner = dispenses::cqrs::init_view_scanner(dynamodb_client.clone());
+    let patient_history = dispenses::cqrs::init_patient_history(dynamodb_client.clone());
+    let patient_last_dispense =
+        dispenses::cqrs::init_patient_last_dispense(dynamodb_client.clone());
+    let dispenses_export_lock = dispenses::cqrs::init_export_lock(dynamodb_client.clone());
+    let snapshot_inspector = dispenses::cqrs::init_snapshot_inspector(dynamodb_client.clone());
+    let analysis_jobs = dispenses::cqrs::init_analysis_jobs(dynamodb_client.clone());
+    let replay_checkpoints = Arc::new(domain::replay::ReplayCheckpointStore::from_env(
+        dynamodb_client.clone(),
+    ));
+    let backup_jobs = Arc::new(domain::backups::BackupJobStore::from_env(
+        dynamodb_client.clone(),
+    ));
+    let prometheus_enabled = metrics::endpoint_enabled();
+    let mut dispenses_cqrs = dispenses::cqrs::DispenseCqrsBuilder::new(dynamodb_client.clone())
+        .with_view_query(dispenses_repo.clone())
+        .with_timeline_query()
+        .with_patient_history_query(dispenses_repo.clone())
+        .with_scheduler(expiry_scheduler);
+    if prometheus_enabled {
+        metrics::install();
+        dispenses_cqrs = dispenses_cqrs.with_query(metrics::EventCounter);
+    }
+    let dispenses_cqrs = dispenses_cqrs.build();
+    let prescribers_repo = prescribers::cqrs::init_repo(dynamodb_client.clone());
+    let prescribers_cqrs = prescribers::cqrs::init(dynamodb_client, prescribers_repo.clone());
+
+    let input_schemas = Arc::new(schemas::InputSchemas::from_env());
 
-    let dispenses_repo = dispenses::cqrs::init_repo(dynamodb_client.clone());
-    let dispenses_cqrs = dispenses::cqrs::init(dynamodb_client, dispenses_repo.clone());
+    let max_concurrent_requests = shutdown::max_concurrent_requests();
+    let in_flight = Arc::new(tokio::sync::Semaphore::new(
+        max_concurrent_requests as usize,
+    ));
+    shutdown::spawn_sigterm_handler(in_flight.clone(), max_concurrent_requests)?;
 
     let state = AppState {
         dispenses_repo,
         dispenses_cqrs,
+        dispenses_dry_run,
+        dispenses_events,
+        dispenses_scanner,
+        dispenses_timeline,
+        patient_history,
+        patient_last_dispense,
+        dispenses_export_lock,
+        snapshot_inspector,
+        analysis_jobs,
+        replay_checkpoints,
+        backup_jobs,
+        prescribers_repo,
+        prescribers_cqrs,
         s3_client,
+        presigned_urls: presigned_urls::PresignedUrlCache::from_env(),
+        sqs_client,
+        kinesis_client,
+        dead_letters,
+        input_schemas,
+        in_flight,
     };
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/dispenses", post(create_dispense).get(list_dispenses))
-        .route("/dispenses/:id", get(get_dispense).delete(cancel_dispense))
+        .route("/dispenses/timeline", get(list_dispenses_timeline))
+        .route("/dispenses/export", get(export_dispenses))
+        .route(
+            "/dispenses/:id",
+            get(get_dispense)
+                .patch(update_dispense)
+                .delete(cancel_dispense),
+        )
+        .route("/dispenses/:id/fhir", get(get_dispense_fhir))
+        .route("/dispenses/:id/hl7", get(get_dispense_hl7))
         .route(
             "/dispenses/:id/prescription/upload-url",
             post(get_upload_url),
         )
-        .route("/dispenses/:id/patient", post(add_patient))
-        .route("/dispenses/:id/drugs", post(add_drugs))
-        .route("/dispenses/:id/complete", post(complete_dispense))
+        .route(
+            "/dispenses/:id/prescription/download-url",
+            get(get_download_url),
+        )
+        .route(
+            "/dispenses/:id/prescription/metadata",
+            get(get_prescription_metadata),
+        )
+        .route(
+            "/dispenses/:id/prescription/history",
+            get(get_prescription_history),
+        )
+        .route(
+            "/dispenses/:id/prescription/analysis",
+            get(get_prescription_analysis),
+        )
+        .route(
+            "/dispenses/:id/prescription/thumbnail",
+            get(get_prescription_thumbnail),
+        )
+        .route(
+            "/dispenses/:id/prescription/reanalyze",
+            post(reanalyze_prescription),
+        )
+        .route(
+            "/dispenses/:id/prescription/approve-multi-page",
+            post(approve_multi_page_prescription),
+        )
+        .route("/dispenses/:id/review", post(review_dispense))
+        .route("/dispenses/:id/patient", post(execute_command))
+        .route("/dispenses/:id/prescriber", post(execute_command))
+        .route("/dispenses/:id/drugs", post(execute_command))
+        .route("/dispenses/:id/drugs/ledger", get(get_drug_ledger))
+        .route("/dispenses/:id/split", post(split_dispense))
+        .route("/dispenses/:id/merge", post(merge_dispense))
+        .route("/dispenses/:id/fulfillment", post(execute_command))
+        .route("/dispenses/:id/shipment", post(execute_command))
+        .route("/dispenses/:id/delivery", post(execute_command))
+        .route("/dispenses/:id/complete", post(execute_command))
+        .route("/dispenses/:id/validate", post(validate_command))
+        .route("/dispenses/:id/readiness", get(get_readiness))
+        .route("/dispenses/:id/progress", get(get_progress))
+        .route(
+            "/patients/:patient_id/dispenses",
+            get(list_patient_dispenses),
+        )
+        .route(
+            "/patients/:patient_id/last-dispense",
+            get(get_patient_last_dispense),
+        )
+        .merge(prescriber_routes::routes())
+        .merge(admin::routes(state.clone()));
+
+    // Local development only, production Lambdas set PROMETHEUS_ENDPOINT_ENABLED=false
+    if prometheus_enabled {
+        app = app
+            .route("/metrics", get(metrics::render))
+            .layer(axum::middleware::from_fn(metrics::track_http));
+    }
+
+    let app = app
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            versioning::check_version,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            schemas::validate_body,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            shutdown::track_in_flight,
+        ))
         .with_state(state);
 
     let app = tower::ServiceBuilder::new()
@@ -65,67 +249,309 @@ async fn main() -> Result<(), lambda_http::Error> {
     Ok(())
 }
 
-// Create dispense
+// Create dispense (the body is optional, an empty one starts a routine dispense)
 async fn create_dispense(
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let command_id = Ulid::new().to_string();
-    let aggregate_id = Ulid::new().to_string();
+    actor: Actor,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let input: dispenses::inputs::StartDispenseInput = if body.is_empty() {
+        Default::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ApiError::bad_request(e.to_string()))?
+    };
 
-    let mut metadata = HashMap::new();
-    metadata.insert("command_id".to_string(), command_id);
+    let pharmacy_id = input
+        .pharmacy_branch_id
+        .unwrap_or(dispenses::timeline::DEFAULT_PHARMACY_ID.to_string());
 
-    let command = dispenses::Command::StartDispense {
-        id: aggregate_id.clone(),
-    };
+    let mut metadata = actor.command_metadata();
 
-    state
-        .dispenses_cqrs
-        .execute_with_metadata(&aggregate_id, command, metadata)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // The EHR reference lives in the metadata, deduplicated per pharmacy through the timeline index
+    if let Some(external_reference_id) = input.external_reference_id {
+        let existing = state
+            .dispenses_timeline
+            .find_by_external_reference(&pharmacy_id, &external_reference_id)
+            .await?;
+        if let Some(existing) = existing {
+            return Err(ApiError::conflict(format!(
+                "Dispense {} already exists for external reference {}",
+                existing.dispense_id, external_reference_id
+            )));
+        }
+        metadata.insert("external_reference_id".to_string(), external_reference_id);
+    }
+
+    let aggregate_id = dispenses::cqrs::create_dispense_with_id(
+        &state.dispenses_cqrs,
+        &pharmacy_id,
+        input.priority,
+        input.notes,
+        metadata,
+    )
+    .await?;
 
     let view = state
         .dispenses_repo
         .load(&aggregate_id)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Not found".to_string()))?;
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
 
-    Ok((StatusCode::CREATED, Json(view)))
+    Ok((
+        StatusCode::CREATED,
+        [(header::ETAG, versioning::etag(view.aggregate_version))],
+        Json(view),
+    ))
 }
 
 // Get dispense
 async fn get_dispense(
     Path(id): Path<String>,
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
+    let mut view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+    view.refresh_computed_fields(chrono::Utc::now());
+
+    Ok((
+        [(header::ETAG, versioning::etag(view.aggregate_version))],
+        Json(view),
+    ))
+}
+
+// Get dispense as a FHIR R4 MedicationDispense resource
+async fn get_dispense_fhir(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
     let view = state
         .dispenses_repo
         .load(&id)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Not found".to_string()))?;
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let resource = dispenses::fhir::to_medication_dispense(&view);
+    let body = serde_json::to_string(&resource).map_err(ApiError::internal)?;
 
-    Ok(Json(view))
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/fhir+json"),
+            ),
+            (header::ETAG, versioning::etag(view.aggregate_version)),
+        ],
+        body,
+    ))
 }
 
-// List dispenses (simplified - in production use pagination)
+// Get dispense as an HL7 v2.4 RDS^O01 message
+async fn get_dispense_hl7(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            ),
+            (header::ETAG, versioning::etag(view.aggregate_version)),
+        ],
+        hl7::dispense_to_rds_o01(&view),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    limit: Option<i32>,
+    cursor: Option<String>,
+    /// Full views instead of summaries
+    #[serde(default)]
+    detail: bool,
+}
+
+// List dispenses a page at a time, as summaries unless `detail=true`
 async fn list_dispenses(
-    State(_state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // TODO: Implement proper listing with DynamoDB scan/query
-    Ok(Json(
-        serde_json::json!({ "message": "List not implemented yet" }),
+    Query(params): Query<ListParams>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    let page = state
+        .dispenses_scanner
+        .list(limit, params.cursor.as_deref())
+        .await?;
+
+    if params.detail {
+        return Ok(Json(serde_json::json!({
+            "items": page.views,
+            "next_cursor": page.next_cursor,
+        }))
+        .into_response());
+    }
+
+    Ok(Json(dispenses::DispenseSummaryEnvelope {
+        items: page.views.iter().map(dispenses::View::to_summary).collect(),
+        next_cursor: page.next_cursor,
+    })
+    .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineParams {
+    from: String,
+    to: String,
+    limit: Option<i32>,
+    pharmacy_id: Option<String>,
+}
+
+// List dispenses by creation date using the timeline index
+async fn list_dispenses_timeline(
+    Query(params): Query<TimelineParams>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pharmacy_id = params
+        .pharmacy_id
+        .unwrap_or(dispenses::timeline::DEFAULT_PHARMACY_ID.to_string());
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    let entries = state
+        .dispenses_timeline
+        .list(&pharmacy_id, &params.from, &params.to, limit)
+        .await?;
+
+    Ok(Json(entries))
+}
+
+#[derive(Debug, Deserialize)]
+struct PatientHistoryParams {
+    limit: Option<i32>,
+    cursor: Option<String>,
+}
+
+// List the dispenses of a patient, a page at a time
+async fn list_patient_dispenses(
+    Path(patient_id): Path<String>,
+    Query(params): Query<PatientHistoryParams>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+
+    let history = state
+        .patient_history
+        .list(&patient_id, limit, params.cursor.as_deref())
+        .await?;
+
+    Ok(Json(history))
+}
+
+// Most recent dispense of a patient, as projected from the event stream
+async fn get_patient_last_dispense(
+    Path(patient_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let last_dispense = state
+        .patient_last_dispense
+        .get(&patient_id)
+        .await?
+        .ok_or(ApiError::not_found("Patient last dispense"))?;
+
+    Ok(Json(last_dispense))
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportParams {
+    from: String,
+    to: String,
+    format: Option<String>,
+    pharmacy_id: Option<String>,
+}
+
+// Export dispenses created in a date range as CSV
+async fn export_dispenses(
+    Query(params): Query<ExportParams>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(format) = params.format.as_deref() {
+        if format != "csv" {
+            return Err(ApiError::bad_request(format!(
+                "Unsupported export format: {}",
+                format
+            )));
+        }
+    }
+
+    let pharmacy_id = params
+        .pharmacy_id
+        .unwrap_or(dispenses::timeline::DEFAULT_PHARMACY_ID.to_string());
+    let lock_id = format!("export#{}", pharmacy_id);
+
+    let acquired = state.dispenses_export_lock.acquire(&lock_id).await?;
+    if !acquired {
+        // Another export holds the lock until it finishes or expires
+        return Err(domain::Error::RateLimited {
+            retry_after_secs: dispenses::export::EXPORT_LOCK_TTL_SECONDS as u64,
+        }
+        .into());
+    }
+
+    let result = load_export_views(&state, &pharmacy_id, &params.from, &params.to).await;
+
+    state.dispenses_export_lock.release(&lock_id).await?;
+
+    let views = result?;
+    let csv = dispenses::export::CsvExporter::new(&pharmacy_id).export(&views);
+    let disposition = format!(
+        "attachment; filename=\"dispenses-{}.csv\"",
+        chrono::Utc::now().format("%Y-%m-%d")
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        csv,
     ))
 }
 
+async fn load_export_views(
+    state: &AppState,
+    pharmacy_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<Vec<dispenses::View>, ApiError> {
+    let entries = state
+        .dispenses_timeline
+        .list_all(pharmacy_id, from, to)
+        .await?;
+
+    let mut views = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if let Some(view) = state.dispenses_repo.load(&entry.dispense_id).await? {
+            views.push(view);
+        }
+    }
+
+    Ok(views)
+}
+
 // Get S3 presigned URL for upload
 async fn get_upload_url(
     Path(id): Path<String>,
     State(state): State<AppState>,
     Json(input): Json<dispenses::inputs::UploadPrescriptionInput>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let bucket =
         std::env::var("PRESCRIPTIONS_BUCKET").unwrap_or("dispensary-prescriptions".to_string());
 
@@ -145,7 +571,7 @@ async fn get_upload_url(
             .unwrap(),
         )
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::internal)?;
 
     Ok(Json(serde_json::json!({
         "upload_url": presigned.uri(),
@@ -154,83 +580,719 @@ async fn get_upload_url(
     })))
 }
 
-// Add patient
-async fn add_patient(
+// Get a presigned URL downloading the prescription, shared by requests within 10 minutes
+async fn get_download_url(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let url = view
+        .dispense_data
+        .prescription_url
+        .ok_or(ApiError::not_found("Prescription"))?;
+    let (bucket, key) = url
+        .strip_prefix("s3://")
+        .and_then(|location| location.split_once('/'))
+        .ok_or(ApiError::internal("Invalid prescription URL"))?;
+
+    let download_url = state
+        .presigned_urls
+        .download_url(&state.s3_client, bucket, key)
+        .await?;
+
+    Ok(Json(serde_json::json!({ "download_url": download_url })))
+}
+
+// Get the prescription file details, for billing by size
+async fn get_prescription_metadata(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let (Some(prescription_id), Some(url)) = (&view.prescription_id, &view.prescription_url) else {
+        return Err(ApiError::not_found("Prescription"));
+    };
+
+    Ok(Json(serde_json::json!({
+        "prescription_id": prescription_id,
+        "file_size_bytes": view.prescription_file_size_bytes,
+        "content_type": prescription_content_type(url),
+        "uploaded_at": view.prescription_uploaded_at,
+    })))
+}
+
+// Prescriptions uploaded for the dispense, oldest first
+async fn get_prescription_history(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok(Json(view.dispense_data.prescription_history))
+}
+
+// Drug quantity changes of the dispense, oldest first
+async fn get_drug_ledger(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok(Json(view.dispense_data.drug_ledger))
+}
+
+// Content type of the prescription file, from the extensions the analyzer is notified of
+fn prescription_content_type(url: &str) -> Option<&'static str> {
+    let (_, extension) = url.rsplit_once('.')?;
+    match extension.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "pdf" => Some("application/pdf"),
+        _ => None,
+    }
+}
+
+// Get the prescription analysis as structured JSON
+async fn get_prescription_analysis(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let analysis = dispenses::analysis::parse_analysis(&view)
+        .ok_or(ApiError::not_found("Prescription analysis"))?
+        .map_err(|e| domain::Error::Validation {
+            message: format!("Invalid analysis data: {}", e),
+        })?;
+
+    Ok(Json(analysis))
+}
+
+// Get the prescription preview
+async fn get_prescription_thumbnail(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    let thumbnail_url = view
+        .dispense_data
+        .thumbnail_url
+        .ok_or(ApiError::not_found("Prescription thumbnail"))?;
+
+    Ok(Json(serde_json::json!({ "thumbnail_url": thumbnail_url })))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReanalyzeInput {
+    model_id: Option<String>,
+    #[serde(default)]
+    force: bool,
+}
+
+// Queue a new analysis of the prescription, optionally with another model
+async fn reanalyze_prescription(
     Path(id): Path<String>,
     State(state): State<AppState>,
-    Json(input): Json<dispenses::inputs::AddPatientInput>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let mut metadata = HashMap::new();
-    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+    actor: Actor,
+    Json(input): Json<ReanalyzeInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    if view.prescription_url.is_none() {
+        return Err(ApiError::bad_request("No prescription uploaded"));
+    }
+    if view.prescription_analyzed && !input.force {
+        return Err(ApiError::conflict("Prescription already analyzed"));
+    }
 
-    let command = dispenses::Command::AddPatient {
-        patient_id: input.patient_id,
-        name: input.name,
+    if input.force {
+        state
+            .dispenses_cqrs
+            .execute_with_metadata(
+                &id,
+                dispenses::Command::ResetAnalysis,
+                actor.command_metadata(),
+            )
+            .await?;
+    }
+
+    let message = AnalysisJobMessage {
+        job_id: Ulid::new().to_string(),
+        dispense_id: id,
+        model_id: input.model_id,
+        triggered_by: actor.name(),
+        triggered_at: chrono::Utc::now(),
     };
 
     state
-        .dispenses_cqrs
-        .execute_with_metadata(&id, command, metadata)
+        .analysis_jobs
+        .create(&AnalysisJob::queued(&message))
+        .await?;
+
+    let queue_url = std::env::var("ANALYSIS_QUEUE_URL")
+        .map_err(|_| ApiError::internal("ANALYSIS_QUEUE_URL not set"))?;
+    let body = serde_json::to_string(&message).map_err(ApiError::internal)?;
+
+    // The reset is already committed: the job is marked failed and, the prescription no longer
+    // being analyzed, the client retries this request without `force`
+    if let Err(e) = state
+        .sqs_client
+        .send_message()
+        .queue_url(queue_url)
+        .message_body(body)
+        .send()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    {
+        tracing::error!("Analysis job {} not queued: {}", message.job_id, e);
+        if let Err(e) = state
+            .analysis_jobs
+            .set_status(
+                &message.job_id,
+                AnalysisJobStatus::Failed,
+                Some("Analysis queue unavailable".to_string()),
+            )
+            .await
+        {
+            tracing::error!("Analysis job {} not marked failed: {}", message.job_id, e);
+        }
 
-    Ok((StatusCode::OK, "Patient added"))
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "analysis_not_queued",
+            "Analysis could not be queued, retry the request",
+        )
+        .with_details(serde_json::json!({ "job_id": message.job_id })));
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "job_id": message.job_id,
+            "status": "queued",
+        })),
+    ))
 }
 
-// Add drugs
-async fn add_drugs(
+// Pharmacist acknowledgment of a multi-page prescription
+async fn approve_multi_page_prescription(
     Path(id): Path<String>,
     State(state): State<AppState>,
-    Json(input): Json<dispenses::inputs::AddDrugsInput>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let mut metadata = HashMap::new();
-    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let command = dispenses::Command::ApproveMultiPagePrescription {
+        approved_by: actor.name(),
+    };
 
-    let command = dispenses::Command::AddDrugs { drugs: input.drugs };
+    state
+        .dispenses_cqrs
+        .execute_with_metadata(&id, command, actor.command_metadata())
+        .await?;
+
+    Ok((StatusCode::OK, "Multi-page prescription approved"))
+}
+
+// Pharmacist review of a flagged prescription (the body is optional)
+async fn review_dispense(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    actor: Actor,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let input: dispenses::inputs::PharmacistReviewInput = if body.is_empty() {
+        Default::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ApiError::bad_request(e.to_string()))?
+    };
+
+    let command = dispenses::Command::MarkPharmacistReviewed {
+        reviewed_by: actor.name(),
+        notes: input.notes,
+    };
 
     state
         .dispenses_cqrs
-        .execute_with_metadata(&id, command, metadata)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .execute_with_metadata(&id, command, actor.command_metadata())
+        .await?;
 
-    Ok((StatusCode::OK, "Drugs added"))
+    Ok((StatusCode::OK, "Pharmacist review recorded"))
 }
 
-// Complete dispense
-async fn complete_dispense(
+// Move drugs to a new dispense carrying the same patient and prescription
+async fn split_dispense(
     Path(id): Path<String>,
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let mut metadata = HashMap::new();
-    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+    actor: Actor,
+    Json(input): Json<dispenses::inputs::SplitDispenseInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let original = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?
+        .dispense_data;
+    let new_dispense_id = Ulid::new().to_string();
 
-    let command = dispenses::Command::CompleteDispense;
+    let split = dispenses::Command::SplitDispense {
+        new_dispense_id: new_dispense_id.clone(),
+        drugs_to_split: input
+            .drugs_to_split
+            .iter()
+            .map(|drug_id| drug_id.to_string())
+            .collect(),
+    };
+
+    // Rebuild the new dispense from the original's state before the split
+    let pharmacy_id = original
+        .pharmacy_branch_id
+        .clone()
+        .unwrap_or(dispenses::timeline::DEFAULT_PHARMACY_ID.to_string());
+    let mut commands = vec![dispenses::Command::StartDispense {
+        id: new_dispense_id.clone(),
+        original_dispense_id: Some(id.clone()),
+        pharmacy_branch_id: original.pharmacy_branch_id,
+        priority: original.priority,
+        notes: original.notes,
+    }];
+    if let (Some(prescription_id), Some(url)) =
+        (original.prescription_id, original.prescription_url)
+    {
+        commands.push(dispenses::Command::UploadPrescription {
+            prescription_id,
+            url,
+            file_size_bytes: original.prescription_file_size_bytes,
+        });
+    }
+    if let Some(thumbnail_url) = original.thumbnail_url {
+        commands.push(dispenses::Command::SetPrescriptionThumbnail { thumbnail_url });
+    }
+    if let (Some(analysis_data), Some(model_id)) = (original.analysis_data, original.analysis_model)
+    {
+        commands.push(dispenses::Command::AnalyzePrescription {
+            analysis_data,
+            model_id,
+            page_count: original.prescription_page_count,
+        });
+    }
+    if let (Some(patient_id), Some(name)) = (original.patient_id, original.patient_name) {
+        commands.push(dispenses::Command::AddPatient {
+            patient_id: patient_id.into(),
+            name,
+        });
+    }
+    if let (Some(license_number), Some(prescriber_state)) = (
+        original.prescriber_license_number,
+        original.prescriber_state,
+    ) {
+        commands.push(dispenses::Command::SetPrescriber {
+            prescriber_id: original.prescriber_id,
+            license_number,
+            state: prescriber_state,
+        });
+    }
+    commands.push(dispenses::Command::AddDrugs {
+        drugs: original
+            .drugs
+            .into_iter()
+            .filter(|drug| input.drugs_to_split.contains(&drug.drug_id))
+            .collect(),
+    });
+
+    // Both dispenses are validated before anything is committed
+    dry_run(&state, &id, split.clone()).await?;
+    state
+        .dispenses_dry_run
+        .handle_all(&new_dispense_id, commands.clone())
+        .await?;
+
+    // The new dispense is built before the split is recorded, so the original keeps its drugs
+    // until the copy exists. Like any new dispense it gets its own expiry, cancelled with it
+    // when the split fails.
+    let committed = async {
+        for command in commands {
+            let mut metadata = actor.command_metadata();
+            metadata.insert("pharmacy_id".to_string(), pharmacy_id.clone());
+
+            state
+                .dispenses_cqrs
+                .execute_with_metadata(&new_dispense_id, command, metadata)
+                .await?;
+        }
+
+        state
+            .dispenses_cqrs
+            .execute_with_metadata(&id, split, actor.command_metadata())
+            .await
+    }
+    .await;
+
+    if let Err(e) = committed {
+        let command = dispenses::Command::CancelDispense {
+            reason: Some(format!("split of {} failed", id)),
+        };
+        if let Err(cancel_error) = state
+            .dispenses_cqrs
+            .execute_with_metadata(&new_dispense_id, command, actor.command_metadata())
+            .await
+        {
+            tracing::warn!(
+                "Partial split dispense {} not cancelled: {}",
+                new_dispense_id,
+                cancel_error
+            );
+        }
+        return Err(e.into());
+    }
+
+    let view = state
+        .dispenses_repo
+        .load(&new_dispense_id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(header::ETAG, versioning::etag(view.aggregate_version))],
+        Json(view),
+    ))
+}
+
+// Move the drugs of another dispense for the same patient into this one
+async fn merge_dispense(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    actor: Actor,
+    Json(input): Json<dispenses::inputs::MergeDispenseInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?
+        .dispense_data;
+    let source = state
+        .dispenses_repo
+        .load(&input.source_dispense_id)
+        .await?
+        .ok_or(ApiError::not_found("Source dispense"))?
+        .dispense_data;
+
+    if target.patient_id.is_none() || target.patient_id != source.patient_id {
+        return Err(ApiError::conflict("Dispenses belong to different patients"));
+    }
+    if !matches!(
+        source.status,
+        dispenses::DispenseStatus::Pending | dispenses::DispenseStatus::Ready
+    ) {
+        return Err(ApiError::conflict(
+            "Source dispense is no longer pending or ready",
+        ));
+    }
+
+    let merge = dispenses::Command::MergeDispense {
+        source_dispense_id: input.source_dispense_id.to_string(),
+        merged_drugs: source.drugs,
+    };
+    let cancel = dispenses::Command::CancelDispense {
+        reason: Some(format!("merged into {}", id)),
+    };
+
+    // Both commands are validated before either is committed, so a rejected merge leaves the
+    // source open and a rejected cancellation does not duplicate its drugs
+    dry_run(&state, &id, merge.clone()).await?;
+    dry_run(&state, &input.source_dispense_id, cancel.clone()).await?;
 
     state
         .dispenses_cqrs
-        .execute_with_metadata(&id, command, metadata)
+        .execute_with_metadata(&id, merge, actor.command_metadata())
+        .await?;
+
+    state
+        .dispenses_cqrs
+        .execute_with_metadata(&input.source_dispense_id, cancel, actor.command_metadata())
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| {
+            tracing::error!(
+                "Dispense {} merged into {} but not cancelled: {}",
+                input.source_dispense_id,
+                id,
+                e
+            );
+            ApiError::from(e).with_details(serde_json::json!({
+                "merged": true,
+                "source_dispense_id": input.source_dispense_id,
+            }))
+        })?;
+
+    Ok((StatusCode::OK, "Dispenses merged"))
+}
+
+// Check a command against the stored dispense without committing it
+async fn dry_run(
+    state: &AppState,
+    id: &str,
+    command: dispenses::Command,
+) -> Result<Vec<dispenses::Event>, ApiError> {
+    Ok(state.dispenses_dry_run.handle(id, command).await?)
+}
+
+// Commands of a JSON merge patch (RFC 7396) of a dispense, in `patient`, `drugs`, `prescriber`
+// order. Nothing can be removed, so `null` members are rejected like unknown ones.
+fn merge_patch_commands(patch: serde_json::Value) -> Result<Vec<dispenses::Command>, ApiError> {
+    let serde_json::Value::Object(mut fields) = patch else {
+        return Err(ApiError::bad_request("Merge patch must be a JSON object"));
+    };
+
+    let mut commands = Vec::new();
+    if let Some(patient) = fields.remove("patient") {
+        let input: dispenses::inputs::AddPatientInput = serde_json::from_value(patient)
+            .map_err(|e| ApiError::bad_request(format!("Invalid patient: {}", e)))?;
+        commands.push(dispenses::Command::AddPatient {
+            patient_id: input.patient_id.into(),
+            name: input.name,
+        });
+    }
+    if let Some(drugs) = fields.remove("drugs") {
+        let drugs = serde_json::from_value(drugs)
+            .map_err(|e| ApiError::bad_request(format!("Invalid drugs: {}", e)))?;
+        commands.push(dispenses::Command::AddDrugs { drugs });
+    }
+    if let Some(prescriber) = fields.remove("prescriber") {
+        let input: dispenses::inputs::SetPrescriberInput = serde_json::from_value(prescriber)
+            .map_err(|e| ApiError::bad_request(format!("Invalid prescriber: {}", e)))?;
+        commands.push(dispenses::Command::SetPrescriber {
+            prescriber_id: input.prescriber_id.map(Into::into),
+            license_number: input.license_number,
+            state: input.state,
+        });
+    }
+
+    if let Some(field) = fields.keys().next() {
+        return Err(ApiError::bad_request(format!(
+            "Unsupported field: {}",
+            field
+        )));
+    }
+    if commands.is_empty() {
+        return Err(ApiError::bad_request("Empty merge patch"));
+    }
+
+    Ok(commands)
+}
+
+// Add the patient, drugs and prescriber present in a merge patch, one command each
+async fn update_dispense(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    actor: Actor,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    let commands = merge_patch_commands(patch)?;
+
+    // Commands are not transactional: on failure, report those already applied
+    let mut succeeded = Vec::new();
+    for command in commands {
+        let command_type = command.command_type();
+
+        state
+            .dispenses_cqrs
+            .execute_with_metadata(&id, command, actor.command_metadata())
+            .await
+            .map_err(|e| {
+                ApiError::from(e).with_details(serde_json::json!({
+                    "failed_command": command_type,
+                    "succeeded_commands": succeeded,
+                }))
+            })?;
+        succeeded.push(command_type);
+    }
+
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok((
+        [(header::ETAG, versioning::etag(view.aggregate_version))],
+        Json(view),
+    ))
+}
+
+// Whether the dispense can be completed, and what is blocking it
+async fn get_readiness(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
 
-    Ok((StatusCode::OK, "Dispense completed"))
+    Ok(Json(serde_json::json!({
+        "ready": view.is_complete_ready(),
+        "blockers": view.completion_blockers(),
+    })))
+}
+
+// Workflow progress, for progress bars
+async fn get_progress(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .dispenses_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(dispenses::AGGREGATE_TYPE))?;
+
+    Ok(Json(serde_json::json!({
+        "percentage": view.completion_percentage(),
+        "status": view.status,
+        "next_steps": view.next_steps(),
+    })))
+}
+
+// Dry run of a command: the events it would emit, or why it would fail
+async fn validate_command(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(input): Json<dispenses::inputs::ValidateCommandInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let command: dispenses::Command = serde_json::from_value(input.command)
+        .map_err(|e| ApiError::bad_request(format!("Invalid command: {}", e)))?;
+
+    let result = match state.dispenses_dry_run.handle(&id, command).await {
+        Ok(events) => serde_json::json!({
+            "valid": true,
+            "would_emit": events.iter().map(|event| event.event_type()).collect::<Vec<_>>(),
+            "errors": Vec::<String>::new(),
+        }),
+        Err(cqrs_es::AggregateError::UserError(e)) => serde_json::json!({
+            "valid": false,
+            "would_emit": Vec::<String>::new(),
+            "errors": vec![e.to_string()],
+        }),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(Json(result))
 }
 
 // Cancel dispense
 async fn cancel_dispense(
     Path(id): Path<String>,
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let mut metadata = HashMap::new();
-    metadata.insert("command_id".to_string(), Ulid::new().to_string());
-
-    let command = dispenses::Command::CancelDispense;
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let command = dispenses::Command::CancelDispense { reason: None };
 
     state
         .dispenses_cqrs
-        .execute_with_metadata(&id, command, metadata)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .execute_with_metadata(&id, command, actor.command_metadata())
+        .await?;
 
     Ok((StatusCode::OK, "Dispense cancelled"))
 }
+
+#[cfg(test)]
+mod tests {
+    use domain::dispenses::aggregate::DrugItem;
+    use serde_json::json;
+
+    use super::*;
+
+    fn drug() -> DrugItem {
+        DrugItem::builder()
+            .drug_id("drug-1")
+            .name("Drug 1")
+            .quantity(1)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_patch_of_every_field_is_applied_in_order() {
+        let commands = merge_patch_commands(json!({
+            "prescriber": { "license_number": "CA123456", "state": "CA" },
+            "drugs": [{ "drug_id": "drug-1", "name": "Drug 1", "quantity": 1 }],
+            "patient": { "patient_id": "patient-1", "name": "Test Patient" },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            commands,
+            [
+                dispenses::Command::AddPatient {
+                    patient_id: "patient-1".to_string(),
+                    name: "Test Patient".to_string(),
+                },
+                dispenses::Command::AddDrugs {
+                    drugs: vec![drug()],
+                },
+                dispenses::Command::SetPrescriber {
+                    prescriber_id: None,
+                    license_number: "CA123456".to_string(),
+                    state: "CA".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_merge_patch_sends_its_fields_only() {
+        let commands = merge_patch_commands(json!({
+            "drugs": [{ "drug_id": "drug-1", "name": "Drug 1", "quantity": 1 }],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            commands,
+            [dispenses::Command::AddDrugs {
+                drugs: vec![drug()],
+            }]
+        );
+    }
+
+    #[test]
+    fn empty_merge_patch_is_rejected() {
+        match merge_patch_commands(json!({})) {
+            Err(ApiError::Other { status, body }) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                assert_eq!(body.message, "Empty merge patch");
+            }
+            other => panic!("Expected a bad request, got {:?}", other),
+        }
+    }
+}
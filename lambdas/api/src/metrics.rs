@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use cqrs_es::{DomainEvent, EventEnvelope};
+use domain::dispenses::Dispense;
+use once_cell::sync::Lazy;
+use prometheus::{
+    core::Collector, histogram_opts, opts, Encoder, HistogramVec, IntCounterVec, Registry,
+    TextEncoder, TEXT_FORMAT,
+};
+
+use crate::errors::ApiError;
+
+/// Registry rendered by `GET /metrics`
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+static DISPENSE_COMMANDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            opts!("dispense_commands_total", "Dispense commands handled"),
+            &["command", "outcome"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static DISPENSE_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            opts!("dispense_events_emitted_total", "Dispense events committed"),
+            &["event_type"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            opts!("http_requests_total", "HTTP requests served"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            histogram_opts!("http_request_duration_seconds", "HTTP request latency"),
+            &["route"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("metric registered once");
+    collector
+}
+
+/// `PROMETHEUS_ENDPOINT_ENABLED`, on unless set to `false` (production Lambdas)
+pub fn endpoint_enabled() -> bool {
+    std::env::var("PROMETHEUS_ENDPOINT_ENABLED").map_or(true, |enabled| enabled != "false")
+}
+
+/// Count dispense commands handled by the aggregate
+pub fn install() {
+    domain::metrics::set_command_observer(|command, outcome| {
+        DISPENSE_COMMANDS
+            .with_label_values(&[command, outcome])
+            .inc();
+    });
+}
+
+/// Counts committed dispense events by type
+pub struct EventCounter;
+
+#[async_trait]
+impl cqrs_es::Query<Dispense> for EventCounter {
+    async fn dispatch(&self, _dispense_id: &str, events: &[EventEnvelope<Dispense>]) {
+        for event in events {
+            DISPENSE_EVENTS
+                .with_label_values(&[&event.payload.event_type()])
+                .inc();
+        }
+    }
+}
+
+// Time and count every request by its route template
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or("unmatched".to_string());
+
+    let timer = HTTP_REQUEST_DURATION
+        .with_label_values(&[&route])
+        .start_timer();
+    let response = next.run(request).await;
+    timer.observe_duration();
+
+    HTTP_REQUESTS
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+// Prometheus text exposition of the registry
+pub async fn render() -> Result<impl IntoResponse, ApiError> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .map_err(ApiError::internal)?;
+
+    Ok(([(header::CONTENT_TYPE, TEXT_FORMAT)], buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    /// Metric names and label names of the text format
+    fn is_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    }
+
+    /// `name{label="value",...} value`, without timestamp
+    fn assert_sample(line: &str) {
+        let (series, value) = line.rsplit_once(' ').expect("sample value");
+        assert!(value.parse::<f64>().is_ok(), "value of {}", line);
+        let name = match series.split_once('{') {
+            Some((name, labels)) => {
+                let labels = labels.strip_suffix('}').expect("closed labels");
+                for label in labels.split(',') {
+                    let (label, value) = label.split_once('=').expect("label value");
+                    assert!(is_name(label), "label of {}", line);
+                    assert!(value.starts_with('"') && value.ends_with('"'), "{}", line);
+                }
+                name
+            }
+            None => series,
+        };
+        assert!(is_name(name), "name of {}", line);
+    }
+
+    #[tokio::test]
+    async fn exposition_is_prometheus_text_format() {
+        DISPENSE_COMMANDS
+            .with_label_values(&["CompleteDispense", "success"])
+            .inc();
+        DISPENSE_EVENTS
+            .with_label_values(&["Dispense:Completed"])
+            .inc_by(2);
+        HTTP_REQUESTS
+            .with_label_values(&["POST", "/dispenses/:id/complete", "200"])
+            .inc();
+        HTTP_REQUEST_DURATION
+            .with_label_values(&["/dispenses/:id/complete"])
+            .observe(0.2);
+
+        let response = render().await.unwrap().into_response();
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], TEXT_FORMAT);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        for expected in [
+            "# TYPE dispense_commands_total counter",
+            "# TYPE dispense_events_emitted_total counter",
+            "# TYPE http_requests_total counter",
+            "# TYPE http_request_duration_seconds histogram",
+            r#"dispense_commands_total{command="CompleteDispense",outcome="success"} 1"#,
+            r#"dispense_events_emitted_total{event_type="Dispense:Completed"} 2"#,
+            r#"http_requests_total{method="POST",route="/dispenses/:id/complete",status="200"} 1"#,
+            r#"http_request_duration_seconds_bucket{route="/dispenses/:id/complete",le="0.25"} 1"#,
+            r#"http_request_duration_seconds_bucket{route="/dispenses/:id/complete",le="+Inf"} 1"#,
+            r#"http_request_duration_seconds_count{route="/dispenses/:id/complete"} 1"#,
+        ] {
+            assert!(
+                text.lines().any(|line| line == expected),
+                "{} in\n{}",
+                expected,
+                text
+            );
+        }
+        for line in text.lines() {
+            match line.strip_prefix("# ") {
+                Some(comment) => assert!(
+                    comment.starts_with("HELP ") || comment.starts_with("TYPE "),
+                    "{}",
+                    line
+                ),
+                None => assert_sample(line),
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use domain::prescribers;
+use std::collections::HashMap;
+use ulid::Ulid;
+
+use crate::{errors::ApiError, AppState};
+
+/// Prescriber registry routes
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/prescribers", post(register_prescriber))
+        .route(
+            "/prescribers/:id",
+            get(get_prescriber)
+                .put(update_prescriber)
+                .delete(deactivate_prescriber),
+        )
+        .route(
+            "/prescribers/:id/controlled-substance-auth",
+            post(grant_controlled_substance_auth).delete(revoke_controlled_substance_auth),
+        )
+}
+
+async fn execute(
+    state: &AppState,
+    id: &str,
+    command: prescribers::Command,
+) -> Result<(), ApiError> {
+    let mut metadata = HashMap::new();
+    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+
+    state
+        .prescribers_cqrs
+        .execute_with_metadata(id, command, metadata)
+        .await?;
+
+    Ok(())
+}
+
+// Register prescriber
+async fn register_prescriber(
+    State(state): State<AppState>,
+    Json(input): Json<prescribers::inputs::RegisterPrescriberInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let aggregate_id = Ulid::new().to_string();
+
+    let command = prescribers::Command::RegisterPrescriber {
+        prescriber_id: aggregate_id.clone(),
+        name: input.name,
+        license_number: input.license_number,
+        state: input.state,
+        npi: input.npi,
+    };
+
+    execute(&state, &aggregate_id, command).await?;
+
+    let view = state
+        .prescribers_repo
+        .load(&aggregate_id)
+        .await?
+        .ok_or(ApiError::not_found(prescribers::AGGREGATE_TYPE))?;
+
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+// Get prescriber
+async fn get_prescriber(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .prescribers_repo
+        .load(&id)
+        .await?
+        .ok_or(ApiError::not_found(prescribers::AGGREGATE_TYPE))?;
+
+    Ok(Json(view))
+}
+
+// Update prescriber details
+async fn update_prescriber(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(input): Json<prescribers::inputs::UpdatePrescriberInfoInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let command = prescribers::Command::UpdatePrescriberInfo {
+        name: input.name,
+        license_number: input.license_number,
+        state: input.state,
+        npi: input.npi,
+    };
+
+    execute(&state, &id, command).await?;
+
+    Ok((StatusCode::OK, "Prescriber updated"))
+}
+
+// Grant controlled substance authorization
+async fn grant_controlled_substance_auth(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(input): Json<prescribers::inputs::GrantControlledSubstanceAuthInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let command = prescribers::Command::GrantControlledSubstanceAuth {
+        dea_number: input.dea_number,
+    };
+
+    execute(&state, &id, command).await?;
+
+    Ok((StatusCode::OK, "Controlled substance authorization granted"))
+}
+
+// Revoke controlled substance authorization
+async fn revoke_controlled_substance_auth(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    execute(
+        &state,
+        &id,
+        prescribers::Command::RevokeControlledSubstanceAuth,
+    )
+    .await?;
+
+    Ok((StatusCode::OK, "Controlled substance authorization revoked"))
+}
+
+// Deactivate prescriber
+async fn deactivate_prescriber(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    execute(&state, &id, prescribers::Command::DeactivatePrescriber).await?;
+
+    Ok((StatusCode::OK, "Prescriber deactivated"))
+}
@@ -0,0 +1,147 @@
+use lru::LruCache;
+use std::{
+    env,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::errors::ApiError;
+
+/// URLs kept by default
+pub const DEFAULT_PRESIGNED_URL_CACHE_SIZE: usize = 1000;
+
+/// Validity of a presigned download URL
+const URL_EXPIRY: Duration = Duration::from_secs(15 * 60);
+
+/// How long a URL is reused, leaving it at least 5 minutes of validity once returned
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Presigned download URLs by `{bucket}/{key}`, with the time they were generated
+///
+/// Kept per Lambda instance, so concurrent requests for the same prescription share a URL.
+#[derive(Clone)]
+pub struct PresignedUrlCache {
+    cache: Arc<Mutex<LruCache<String, (String, Instant)>>>,
+}
+
+impl PresignedUrlCache {
+    pub fn new(size: usize) -> Self {
+        let size = NonZeroUsize::new(size).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            cache: Arc::new(Mutex::new(LruCache::new(size))),
+        }
+    }
+
+    /// `PRESIGNED_URL_CACHE_SIZE` URLs (1000 by default)
+    pub fn from_env() -> Self {
+        let size = env::var("PRESIGNED_URL_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_PRESIGNED_URL_CACHE_SIZE);
+        Self::new(size)
+    }
+
+    /// Presigned GET URL of the object, generated again once older than 10 minutes
+    pub async fn download_url(
+        &self,
+        s3_client: &aws_sdk_s3::Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<String, ApiError> {
+        let cache_key = format!("{}/{}", bucket, key);
+        if let Some(url) = self.cached(&cache_key) {
+            return Ok(url);
+        }
+
+        let presigned = s3_client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(
+                aws_sdk_s3::presigning::PresigningConfig::expires_in(URL_EXPIRY)
+                    .map_err(ApiError::internal)?,
+            )
+            .await
+            .map_err(ApiError::internal)?;
+        let url = presigned.uri().to_string();
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.put(cache_key, (url.clone(), Instant::now()));
+        }
+        Ok(url)
+    }
+
+    fn cached(&self, cache_key: &str) -> Option<String> {
+        self.cached_at(cache_key, Instant::now())
+    }
+
+    /// URL generated less than `CACHE_TTL` before `now`, older ones being dropped
+    fn cached_at(&self, cache_key: &str, now: Instant) -> Option<String> {
+        let mut cache = self.cache.lock().ok()?;
+        match cache.get(cache_key) {
+            Some((url, generated_at)) if now.duration_since(*generated_at) < CACHE_TTL => {
+                Some(url.clone())
+            }
+            Some(_) => {
+                cache.pop(cache_key);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+
+    /// Presigns offline, with static credentials
+    fn s3_client() -> aws_sdk_s3::Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        aws_sdk_s3::Client::from_conf(config)
+    }
+
+    #[tokio::test]
+    async fn second_call_returns_the_cached_url() {
+        let cache = PresignedUrlCache::new(10);
+        let s3_client = s3_client();
+
+        let first = cache
+            .download_url(&s3_client, "bucket", "prescriptions/d-1/prescription.jpg")
+            .await
+            .unwrap();
+        // Replaced, so a URL generated again would differ
+        cache.cache.lock().unwrap().put(
+            "bucket/prescriptions/d-1/prescription.jpg".to_string(),
+            ("https://cached".to_string(), Instant::now()),
+        );
+        let second = cache
+            .download_url(&s3_client, "bucket", "prescriptions/d-1/prescription.jpg")
+            .await
+            .unwrap();
+
+        assert!(first.contains("X-Amz-Signature"));
+        assert_eq!(second, "https://cached");
+    }
+
+    #[tokio::test]
+    async fn expired_url_is_dropped() {
+        let cache = PresignedUrlCache::new(10);
+        let cache_key = "bucket/prescriptions/d-1/prescription.jpg";
+        cache
+            .download_url(&s3_client(), "bucket", "prescriptions/d-1/prescription.jpg")
+            .await
+            .unwrap();
+        let now = Instant::now();
+
+        assert!(cache.cached_at(cache_key, now).is_some());
+        assert!(cache.cached_at(cache_key, now + CACHE_TTL).is_none());
+        assert!(cache.cache.lock().unwrap().peek(cache_key).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonschema::JSONSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::HashMap, path::Path};
+
+use crate::AppState;
+
+const DEFAULT_SCHEMAS_DIR: &str = "schemas";
+
+/// `"METHOD /route/:param"` to schema file, next to the schemas
+const ROUTE_MAP_FILE: &str = "routes.json";
+
+/// Larger bodies are rejected before validation
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// JSON Schemas of the request bodies, read at startup so they change without a rebuild
+#[derive(Default)]
+pub struct InputSchemas {
+    routes: HashMap<String, RouteSchema>,
+}
+
+struct RouteSchema {
+    file: String,
+    schema: JSONSchema,
+}
+
+/// Route and the schema file validating its body
+#[derive(Debug, Serialize)]
+pub struct InputSchemaEntry {
+    pub route: String,
+    pub schema: String,
+}
+
+/// Schema violation, `instance_path` pointing into the body (e.g. `/drugs/0/quantity`)
+#[derive(Debug, Serialize)]
+pub struct SchemaViolation {
+    pub instance_path: String,
+    pub message: String,
+}
+
+impl InputSchemas {
+    /// Schemas of `SCHEMAS_DIR` (`schemas` by default), none when the directory is unusable
+    pub fn from_env() -> Self {
+        let dir = std::env::var("SCHEMAS_DIR").unwrap_or(DEFAULT_SCHEMAS_DIR.to_string());
+
+        match Self::load(Path::new(&dir)) {
+            Ok(schemas) => schemas,
+            Err(e) => {
+                tracing::warn!("Request body schemas disabled: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let route_map: HashMap<String, String> =
+            serde_json::from_value(read_json(&dir.join(ROUTE_MAP_FILE))?)
+                .map_err(|e| format!("Invalid {}: {}", ROUTE_MAP_FILE, e))?;
+
+        let mut routes = HashMap::new();
+        for (route, file) in route_map {
+            let schema = read_json(&dir.join(&file))?;
+            let schema = JSONSchema::compile(&schema)
+                .map_err(|e| format!("Invalid schema {}: {}", file, e))?;
+            routes.insert(route, RouteSchema { file, schema });
+        }
+
+        Ok(Self { routes })
+    }
+
+    pub fn list(&self) -> Vec<InputSchemaEntry> {
+        let mut entries: Vec<InputSchemaEntry> = self
+            .routes
+            .iter()
+            .map(|(route, route_schema)| InputSchemaEntry {
+                route: route.clone(),
+                schema: route_schema.file.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.route.cmp(&b.route));
+        entries
+    }
+
+    /// Violations of the route schema, empty for routes without one
+    pub fn validate(&self, route: &str, body: &Value) -> Vec<SchemaViolation> {
+        let Some(route_schema) = self.routes.get(route) else {
+            return Vec::new();
+        };
+
+        match route_schema.schema.validate(body) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|error| SchemaViolation {
+                    instance_path: error.instance_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn covers(&self, route: &str) -> bool {
+        self.routes.contains_key(route)
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&content).map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))
+}
+
+// Reject bodies violating the route schema with 422, before the handler deserializes them
+pub async fn validate_body(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(path) => format!("{} {}", request.method(), path.as_str()),
+        None => return next.run(request).await,
+    };
+    if !state.input_schemas.covers(&route) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response(),
+    };
+
+    // Empty or malformed bodies are left to the handler
+    if let Ok(body) = serde_json::from_slice::<Value>(&bytes) {
+        let violations = state.input_schemas.validate(&route, &body);
+        if !violations.is_empty() {
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(violations)).into_response();
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
@@ -0,0 +1,71 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::Semaphore,
+};
+
+use crate::AppState;
+
+const DEFAULT_MAX_CONCURRENT_REQUESTS: u32 = 10;
+
+/// Time given to in-flight requests once SIGTERM is received
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// `MAX_CONCURRENT_REQUESTS`, the number of permits of the in-flight semaphore
+pub fn max_concurrent_requests() -> u32 {
+    std::env::var("MAX_CONCURRENT_REQUESTS")
+        .ok()
+        .and_then(|max| max.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS)
+}
+
+// Hold a permit for the duration of the request, refused once shutdown has started
+pub async fn track_in_flight(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Ok(_permit) = state.in_flight.acquire().await else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Shutting down").into_response();
+    };
+
+    next.run(request).await
+}
+
+/// On SIGTERM, wait for every permit (in-flight requests) up to the grace period, then exit
+pub fn spawn_sigterm_handler(in_flight: Arc<Semaphore>, permits: u32) -> std::io::Result<()> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+
+    tokio::spawn(async move {
+        sigterm.recv().await;
+        let started = Instant::now();
+        tracing::info!("SIGTERM received, draining in-flight requests");
+
+        // Queued behind this, new requests wait until `close` turns them away
+        let drained = tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, in_flight.acquire_many(permits))
+            .await
+            .is_ok();
+        in_flight.close();
+
+        if drained {
+            tracing::info!("Shutdown after {:?}", started.elapsed());
+        } else {
+            tracing::warn!(
+                "Shutdown after {:?} with requests still in flight",
+                started.elapsed()
+            );
+        }
+        std::process::exit(0);
+    });
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use axum::{
+    extract::{MatchedPath, Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use ulid::Ulid;
+
+use crate::{errors::ApiError, AppState};
+
+/// Routes of a single dispense, versioned by the `aggregate_version` of its view
+const DISPENSE_ROUTE_PREFIX: &str = "/dispenses/:id";
+
+/// Strong entity tag of a dispense version
+pub fn etag(version: usize) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("valid ETag")
+}
+
+/// Id of the request, sent as `request_id` command metadata to trace its events
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+// Optimistic concurrency on the routes of a single dispense: an `If-Match` version other than the
+// event store's is rejected with 412 before the handler runs. Once the handler ran its command is
+// committed, even over events of another request since the check, and reported as such: a 412
+// would have the caller retry it and apply it twice.
+//
+// Handlers returning the dispense send the version of the view they loaded as `ETag`; that of
+// the other successful writes is read from the event store once the handler ran.
+pub async fn check_version(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    path: Option<Path<HashMap<String, String>>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let dispense_id = match (matched_path, path) {
+        (Some(matched_path), Some(Path(params)))
+            if matched_path.as_str().starts_with(DISPENSE_ROUTE_PREFIX) =>
+        {
+            params.get("id").cloned()
+        }
+        _ => None,
+    };
+    let Some(dispense_id) = dispense_id else {
+        return next.run(request).await;
+    };
+
+    let request_id = request
+        .extensions()
+        .get::<lambda_http::Context>()
+        .map(|context| context.request_id.clone())
+        .unwrap_or_else(|| Ulid::new().to_string());
+    request.extensions_mut().insert(RequestId(request_id));
+
+    let is_write = !matches!(*request.method(), Method::GET | Method::HEAD);
+    if let Some(expected) = if_match_version(request.headers()) {
+        let checked = match current_version(&state, &dispense_id).await {
+            Ok(current) => precondition(expected, current),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = checked {
+            return e.into_response();
+        }
+    }
+
+    let mut response = next.run(request).await;
+
+    if is_write && response.status().is_success() && !response.headers().contains_key(header::ETAG)
+    {
+        if let Ok(version) = current_version(&state, &dispense_id).await {
+            if version > 0 {
+                response.headers_mut().insert(header::ETAG, etag(version));
+            }
+        }
+    }
+
+    response
+}
+
+// Uncached: the view behind `dispenses_repo` may be served stale from the cache
+async fn current_version(state: &AppState, dispense_id: &str) -> Result<usize, ApiError> {
+    Ok(state.dispenses_events.current_version(dispense_id).await?)
+}
+
+// An unknown dispense (version 0) is left to the handler to report as 404
+fn precondition(expected: usize, current: usize) -> Result<(), ApiError> {
+    if current == 0 || current == expected {
+        Ok(())
+    } else {
+        Err(precondition_failed(expected, current))
+    }
+}
+
+fn precondition_failed(expected: usize, current: usize) -> ApiError {
+    ApiError::new(
+        StatusCode::PRECONDITION_FAILED,
+        "precondition_failed",
+        format!("Dispense is at version {}, not {}", current, expected),
+    )
+    .with_details(serde_json::json!({ "current_version": current }))
+}
+
+// `"3"`, `W/"3"` or `3`; `*` matches any version
+fn if_match_version(headers: &HeaderMap) -> Option<usize> {
+    let value = headers.get(header::IF_MATCH)?.to_str().ok()?.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+
+    value.trim_matches('"').parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_match_accepts_strong_weak_and_bare_versions() {
+        for value in ["\"3\"", "W/\"3\"", "3"] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_MATCH, HeaderValue::from_static(value));
+
+            assert_eq!(if_match_version(&headers), Some(3), "{}", value);
+        }
+    }
+
+    #[test]
+    fn stale_version_is_precondition_failed() {
+        let response = precondition(3, 4).unwrap_err().into_response();
+
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[test]
+    fn current_version_passes_precondition() {
+        assert!(precondition(4, 4).is_ok());
+    }
+
+    #[test]
+    fn unknown_dispense_is_left_to_the_handler() {
+        assert!(precondition(4, 0).is_ok());
+    }
+}
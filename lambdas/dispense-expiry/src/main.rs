@@ -0,0 +1,99 @@
+use aws_config::BehaviorVersion;
+use chrono::Utc;
+use domain::{
+    config::ConfigLoader,
+    dispenses::{self, Dispense, DispenseStatus},
+};
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+use ulid::Ulid;
+
+/// Cancellation reason of expired dispenses
+const EXPIRED_REASON: &str = "expired";
+
+/// Input of the one-time `dispense-expiry-{id}` schedules
+#[derive(Debug, Deserialize)]
+struct ExpiryEvent {
+    dispense_id: String,
+}
+
+struct State {
+    dispenses_repo: Arc<Box<dyn cqrs_es::persist::ViewRepository<dispenses::View, Dispense>>>,
+    dispenses_cqrs: Arc<dispenses::cqrs::DispenseCqrs>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("dispense-expiry", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    // Table names read by the `cqrs::init` functions
+    ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
+
+    let dispenses_repo = dispenses::cqrs::init_repo(dynamodb_client.clone());
+    let dispenses_cqrs = dispenses::cqrs::DispenseCqrsBuilder::new(dynamodb_client)
+        .with_view_query(dispenses_repo.clone())
+        .with_timeline_query()
+        .with_patient_history_query(dispenses_repo.clone())
+        .build();
+
+    let state = State {
+        dispenses_repo,
+        dispenses_cqrs,
+    };
+
+    let state = &state;
+    lambda_runtime::run(service_fn(|event: LambdaEvent<ExpiryEvent>| async move {
+        handle(&event.payload.dispense_id, state).await
+    }))
+    .await
+}
+
+async fn handle(dispense_id: &str, state: &State) -> Result<(), Error> {
+    let Some(view) = state.dispenses_repo.load(dispense_id).await? else {
+        tracing::warn!("Expired dispense {} not found", dispense_id);
+        return Ok(());
+    };
+    let dispense = view.dispense_data;
+
+    if matches!(
+        dispense.status,
+        DispenseStatus::Complete | DispenseStatus::Cancelled
+    ) {
+        tracing::info!(
+            "Dispense {} already {}, not expired",
+            dispense_id,
+            dispense.status
+        );
+        return Ok(());
+    }
+    // A schedule left behind by a failed reschedule fires before the current deadline
+    let expired = dispense
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now());
+    if !expired {
+        tracing::info!("Dispense {} not expired yet", dispense_id);
+        return Ok(());
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+
+    let command = dispenses::Command::CancelDispense {
+        reason: Some(EXPIRED_REASON.to_string()),
+    };
+
+    state
+        .dispenses_cqrs
+        .execute_with_metadata(dispense_id, command, metadata)
+        .await?;
+
+    tracing::info!("Dispense {} expired", dispense_id);
+    Ok(())
+}
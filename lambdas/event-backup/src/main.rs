@@ -0,0 +1,195 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::{
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use chrono::Utc;
+use domain::{
+    backups::{BackupJob, BackupJobStore, BackupStatus},
+    config::ConfigLoader,
+};
+use flate2::{write::GzEncoder, Compression};
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use publisher::EventLogRecord;
+use std::io::Write;
+use ulid::Ulid;
+
+/// Compressed bytes buffered before uploading a part (S3 minimum part size is 5 MiB)
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Items read per scan page
+const PAGE_SIZE: i32 = 500;
+
+struct State {
+    dynamodb_client: aws_sdk_dynamodb::Client,
+    s3_client: aws_sdk_s3::Client,
+    backup_jobs: BackupJobStore,
+    event_log_table: String,
+    backup_bucket: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("event-backup", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    let tables = ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
+
+    let state = State {
+        backup_jobs: BackupJobStore::from_env(dynamodb_client.clone()),
+        dynamodb_client,
+        s3_client: aws_sdk_s3::Client::new(&config),
+        event_log_table: tables.event_log_table,
+        backup_bucket: std::env::var("S3_BACKUP_BUCKET")
+            .unwrap_or("dispensary-event-backups".to_string()),
+    };
+
+    // Triggered by the daily EventBridge schedule, the event itself carries nothing we need
+    lambda_runtime::run(service_fn(|_event: LambdaEvent<serde_json::Value>| async {
+        handle(&state).await
+    }))
+    .await
+}
+
+async fn handle(state: &State) -> Result<BackupJob, Error> {
+    let mut job = BackupJob::new(&Ulid::new().to_string(), &state.backup_bucket, Utc::now());
+    state.backup_jobs.save(&job).await?;
+
+    tracing::info!(
+        "Backup {} to s3://{}/{}",
+        job.backup_id,
+        job.bucket,
+        job.key
+    );
+
+    let upload = state
+        .s3_client
+        .create_multipart_upload()
+        .bucket(&job.bucket)
+        .key(&job.key)
+        .content_type("application/gzip")
+        .send()
+        .await?;
+    let upload_id = upload.upload_id.ok_or("Multipart upload without id")?;
+
+    match export(state, &mut job, &upload_id).await {
+        Ok(size_bytes) => {
+            job.status = BackupStatus::Completed;
+            job.size_bytes = Some(size_bytes);
+            job.completed_at = Some(Utc::now());
+            state.backup_jobs.save(&job).await?;
+            tracing::info!(
+                "Backup {} completed: {} events, {} bytes",
+                job.backup_id,
+                job.events_exported,
+                size_bytes
+            );
+        }
+        Err(e) => {
+            // Drop the uploaded parts, S3 keeps billing for them otherwise
+            let _ = state
+                .s3_client
+                .abort_multipart_upload()
+                .bucket(&job.bucket)
+                .key(&job.key)
+                .upload_id(&upload_id)
+                .send()
+                .await;
+
+            job.status = BackupStatus::Failed;
+            job.error = Some(e.to_string());
+            job.completed_at = Some(Utc::now());
+            state.backup_jobs.save(&job).await?;
+            return Err(e);
+        }
+    }
+
+    Ok(job)
+}
+
+/// Stream the whole event log as gzipped JSON Lines, returns the compressed size
+async fn export(state: &State, job: &mut BackupJob, upload_id: &str) -> Result<u64, Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut parts = Vec::new();
+    let mut size_bytes = 0;
+    let mut start_key = None;
+
+    loop {
+        let output = state
+            .dynamodb_client
+            .scan()
+            .table_name(&state.event_log_table)
+            .limit(PAGE_SIZE)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        for item in output.items.unwrap_or_default() {
+            let record: EventLogRecord = serde_dynamo::from_item(item)?;
+            serde_json::to_writer(&mut encoder, &record)?;
+            encoder.write_all(b"\n")?;
+            job.events_exported += 1;
+        }
+
+        if encoder.get_ref().len() >= PART_SIZE {
+            let chunk = std::mem::take(encoder.get_mut());
+            size_bytes += chunk.len() as u64;
+            parts.push(upload_part(state, job, upload_id, parts.len() as i32 + 1, chunk).await?);
+        }
+
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            break;
+        }
+    }
+
+    // The last part may be smaller than the S3 minimum
+    let chunk = encoder.finish()?;
+    size_bytes += chunk.len() as u64;
+    parts.push(upload_part(state, job, upload_id, parts.len() as i32 + 1, chunk).await?);
+
+    state
+        .s3_client
+        .complete_multipart_upload()
+        .bucket(&job.bucket)
+        .key(&job.key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        )
+        .send()
+        .await?;
+
+    Ok(size_bytes)
+}
+
+async fn upload_part(
+    state: &State,
+    job: &BackupJob,
+    upload_id: &str,
+    part_number: i32,
+    chunk: Vec<u8>,
+) -> Result<CompletedPart, Error> {
+    let output = state
+        .s3_client
+        .upload_part()
+        .bucket(&job.bucket)
+        .key(&job.key)
+        .upload_id(upload_id)
+        .part_number(part_number)
+        .body(ByteStream::from(chunk))
+        .send()
+        .await?;
+
+    Ok(CompletedPart::builder()
+        .set_e_tag(output.e_tag)
+        .part_number(part_number)
+        .build())
+}
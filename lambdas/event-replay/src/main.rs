@@ -0,0 +1,288 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_lambda::{primitives::Blob, types::InvocationType};
+use cqrs_es::{
+    persist::{EventUpcaster, SerializedEvent},
+    Aggregate, EventEnvelope, Query,
+};
+use domain::{
+    config::ConfigLoader,
+    dispenses::{self, Dispense},
+    prescribers::{self, Prescriber},
+    replay::{ReplayCheckpoint, ReplayCheckpointStore, ReplayStatus, CHECKPOINT_INTERVAL},
+};
+use lambda_runtime::{service_fn, Context, Error, LambdaEvent};
+use publisher::EventLogRecord;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+use ulid::Ulid;
+
+/// Remaining time below which the replay checkpoints and hands over to a new execution
+const HANDOVER_MARGIN: Duration = Duration::from_secs(60);
+
+/// Items read per scan page
+const PAGE_SIZE: i32 = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ReplayRequest {
+    /// Resume this replay, a new one starts when absent
+    replay_id: Option<String>,
+    #[serde(default = "default_aggregate_type")]
+    aggregate_type: String,
+}
+
+fn default_aggregate_type() -> String {
+    dispenses::AGGREGATE_TYPE.to_string()
+}
+
+struct State {
+    dynamodb_client: aws_sdk_dynamodb::Client,
+    lambda_client: aws_sdk_lambda::Client,
+    checkpoints: ReplayCheckpointStore,
+    event_log_table: String,
+}
+
+type Key = HashMap<String, AttributeValue>;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("event-replay", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    let tables = ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
+    let lambda_client = aws_sdk_lambda::Client::new(&config);
+
+    let state = State {
+        checkpoints: ReplayCheckpointStore::from_env(dynamodb_client.clone()),
+        dynamodb_client,
+        lambda_client,
+        event_log_table: tables.event_log_table,
+    };
+
+    lambda_runtime::run(service_fn(|event: LambdaEvent<ReplayRequest>| async {
+        handle(event, &state).await
+    }))
+    .await
+}
+
+async fn handle(
+    event: LambdaEvent<ReplayRequest>,
+    state: &State,
+) -> Result<ReplayCheckpoint, Error> {
+    let request = event.payload;
+
+    let mut checkpoint = match &request.replay_id {
+        Some(replay_id) => state
+            .checkpoints
+            .get(replay_id)
+            .await?
+            .ok_or(format!("Unknown replay {}", replay_id))?,
+        None => ReplayCheckpoint::new(&Ulid::new().to_string(), &request.aggregate_type),
+    };
+    if checkpoint.status != ReplayStatus::Running {
+        return Ok(checkpoint);
+    }
+
+    tracing::info!(
+        "Replay {} of {} events, {} already processed",
+        checkpoint.replay_id,
+        checkpoint.aggregate_type,
+        checkpoint.events_processed
+    );
+    state.checkpoints.save(&mut checkpoint).await?;
+
+    let result = match checkpoint.aggregate_type.as_str() {
+        dispenses::AGGREGATE_TYPE => {
+            let client = state.dynamodb_client.clone();
+            let repo = dispenses::cqrs::init_repo(client.clone());
+            let queries = dispenses::cqrs::DispenseCqrsBuilder::new(client)
+                .with_view_query(repo.clone())
+                .with_timeline_query()
+                .with_patient_history_query(repo)
+                .into_queries();
+            replay::<Dispense>(
+                state,
+                &event.context,
+                &mut checkpoint,
+                &queries,
+                &dispenses::upcasters::all(),
+            )
+            .await
+        }
+        prescribers::AGGREGATE_TYPE => {
+            let client = state.dynamodb_client.clone();
+            let queries: Vec<Box<dyn Query<Prescriber>>> = vec![Box::new(prescribers::Query::new(
+                prescribers::cqrs::init_repo(client),
+            ))];
+            replay::<Prescriber>(state, &event.context, &mut checkpoint, &queries, &[]).await
+        }
+        other => Err(format!("Unsupported aggregate type {}", other).into()),
+    };
+
+    match result {
+        Ok(Progress::Completed) => {
+            checkpoint.status = ReplayStatus::Completed;
+            state.checkpoints.save(&mut checkpoint).await?;
+            tracing::info!(
+                "Replay {} completed: {} events",
+                checkpoint.replay_id,
+                checkpoint.events_processed
+            );
+        }
+        Ok(Progress::HandedOver) => {
+            state.checkpoints.save(&mut checkpoint).await?;
+            continue_in_new_execution(state, &event.context, &checkpoint).await?;
+            tracing::info!(
+                "Replay {} handed over after {} events",
+                checkpoint.replay_id,
+                checkpoint.events_processed
+            );
+        }
+        Err(e) => {
+            checkpoint.status = ReplayStatus::Failed;
+            state.checkpoints.save(&mut checkpoint).await?;
+            return Err(e);
+        }
+    }
+
+    Ok(checkpoint)
+}
+
+enum Progress {
+    Completed,
+    /// Stopped before the Lambda deadline, to be resumed from the checkpoint
+    HandedOver,
+}
+
+async fn replay<A: Aggregate>(
+    state: &State,
+    context: &Context,
+    checkpoint: &mut ReplayCheckpoint,
+    queries: &[Box<dyn Query<A>>],
+    upcasters: &[Box<dyn EventUpcaster>],
+) -> Result<Progress, Error> {
+    let mut start_key = resume_key(checkpoint);
+
+    loop {
+        if deadline_near(context) {
+            return Ok(Progress::HandedOver);
+        }
+
+        let output = state
+            .dynamodb_client
+            .scan()
+            .table_name(&state.event_log_table)
+            .filter_expression("AggregateType = :aggregate_type")
+            .expression_attribute_values(
+                ":aggregate_type",
+                AttributeValue::S(checkpoint.aggregate_type.clone()),
+            )
+            .limit(PAGE_SIZE)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        for item in output.items.unwrap_or_default() {
+            let record: EventLogRecord = serde_dynamo::from_item(item)?;
+            let event = to_envelope::<A>(record, upcasters)?;
+
+            for query in queries {
+                query.dispatch(&event.aggregate_id, std::slice::from_ref(&event)).await;
+            }
+
+            checkpoint.last_processed_aggregate_id = Some(event.aggregate_id);
+            checkpoint.last_processed_sequence = Some(event.sequence);
+            checkpoint.events_processed += 1;
+
+            if checkpoint.events_processed.is_multiple_of(CHECKPOINT_INTERVAL) {
+                state.checkpoints.save(checkpoint).await?;
+            }
+            // Items after this one are re-read from the checkpoint by the next execution
+            if deadline_near(context) {
+                return Ok(Progress::HandedOver);
+            }
+        }
+
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            return Ok(Progress::Completed);
+        }
+    }
+}
+
+/// Event log key of the last replayed event, scanning resumes right after it
+fn resume_key(checkpoint: &ReplayCheckpoint) -> Option<Key> {
+    let aggregate_id = checkpoint.last_processed_aggregate_id.as_ref()?;
+    let sequence = checkpoint.last_processed_sequence?;
+
+    Some(HashMap::from([
+        (
+            "AggregateTypeAndId".to_string(),
+            AttributeValue::S(format!("{}:{}", checkpoint.aggregate_type, aggregate_id)),
+        ),
+        (
+            "AggregateIdSequence".to_string(),
+            AttributeValue::N(sequence.to_string()),
+        ),
+    ]))
+}
+
+fn to_envelope<A: Aggregate>(
+    record: EventLogRecord,
+    upcasters: &[Box<dyn EventUpcaster>],
+) -> Result<EventEnvelope<A>, Error> {
+    let mut event = SerializedEvent::new(
+        record.aggregate_id,
+        record.aggregate_id_sequence,
+        record.aggregate_type,
+        record.event_type,
+        record.event_version,
+        serde_json::from_slice(&record.payload)?,
+        serde_json::from_slice(&record.metadata)?,
+    );
+    for upcaster in upcasters {
+        if upcaster.can_upcast(&event.event_type, &event.event_version) {
+            event = upcaster.upcast(event);
+        }
+    }
+
+    Ok(EventEnvelope::try_from(event)?)
+}
+
+fn deadline_near(context: &Context) -> bool {
+    context
+        .deadline()
+        .duration_since(SystemTime::now())
+        .map_or(true, |remaining| remaining < HANDOVER_MARGIN)
+}
+
+/// Invoke this function asynchronously to resume the replay from its checkpoint
+async fn continue_in_new_execution(
+    state: &State,
+    context: &Context,
+    checkpoint: &ReplayCheckpoint,
+) -> Result<(), Error> {
+    let request = ReplayRequest {
+        replay_id: Some(checkpoint.replay_id.clone()),
+        aggregate_type: checkpoint.aggregate_type.clone(),
+    };
+
+    state
+        .lambda_client
+        .invoke()
+        .function_name(&context.env_config.function_name)
+        .invocation_type(InvocationType::Event)
+        .payload(Blob::new(serde_json::to_vec(&request)?))
+        .send()
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::types::{PutRequest, WriteRequest};
+use domain::config::ConfigLoader;
+use flate2::read::GzDecoder;
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use publisher::EventLogRecord;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    time::Duration,
+};
+
+/// Maximum number of items accepted by `BatchWriteItem`
+const BATCH_SIZE: usize = 25;
+
+/// Attempts at writing the items DynamoDB left unprocessed
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Clone, Debug, Deserialize)]
+struct RestoreRequest {
+    /// Backup object key, as listed by `GET /admin/backups`
+    key: String,
+    /// Defaults to `S3_BACKUP_BUCKET`
+    bucket: Option<String>,
+    /// Defaults to `DYNAMODB_EVENT_LOG_TABLE`
+    target_table: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct RestoreResult {
+    key: String,
+    target_table: String,
+    events_restored: usize,
+}
+
+struct State {
+    dynamodb_client: aws_sdk_dynamodb::Client,
+    s3_client: aws_sdk_s3::Client,
+    event_log_table: String,
+    backup_bucket: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("event-restore", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    let tables = ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+
+    let state = State {
+        dynamodb_client: telemetry::traced_dynamodb_client(&config),
+        s3_client: aws_sdk_s3::Client::new(&config),
+        event_log_table: tables.event_log_table,
+        backup_bucket: std::env::var("S3_BACKUP_BUCKET")
+            .unwrap_or("dispensary-event-backups".to_string()),
+    };
+
+    lambda_runtime::run(service_fn(|event: LambdaEvent<RestoreRequest>| async {
+        handle(event.payload, &state).await
+    }))
+    .await
+}
+
+async fn handle(request: RestoreRequest, state: &State) -> Result<RestoreResult, Error> {
+    let bucket = request.bucket.unwrap_or(state.backup_bucket.clone());
+    let target_table = request
+        .target_table
+        .unwrap_or(state.event_log_table.clone());
+
+    tracing::info!(
+        "Restoring s3://{}/{} into {}",
+        bucket,
+        request.key,
+        target_table
+    );
+
+    let object = state
+        .s3_client
+        .get_object()
+        .bucket(&bucket)
+        .key(&request.key)
+        .send()
+        .await?;
+    let data = object.body.collect().await?.into_bytes();
+
+    let mut events_restored = 0;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+    for line in BufReader::new(GzDecoder::new(&data[..])).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let record: EventLogRecord = serde_json::from_str(&line)?;
+        let item = serde_dynamo::to_item(record)?;
+        batch.push(
+            WriteRequest::builder()
+                .put_request(PutRequest::builder().set_item(Some(item)).build()?)
+                .build(),
+        );
+
+        if batch.len() == BATCH_SIZE {
+            write_batch(state, &target_table, std::mem::take(&mut batch)).await?;
+            events_restored += BATCH_SIZE;
+        }
+    }
+
+    if !batch.is_empty() {
+        events_restored += batch.len();
+        write_batch(state, &target_table, batch).await?;
+    }
+
+    tracing::info!("Restored {} events into {}", events_restored, target_table);
+
+    Ok(RestoreResult {
+        key: request.key,
+        target_table,
+        events_restored,
+    })
+}
+
+/// Write a batch, retrying unprocessed items with exponential backoff
+async fn write_batch(state: &State, table: &str, batch: Vec<WriteRequest>) -> Result<(), Error> {
+    let mut pending = HashMap::from([(table.to_string(), batch)]);
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let output = state
+            .dynamodb_client
+            .batch_write_item()
+            .set_request_items(Some(pending))
+            .send()
+            .await?;
+
+        pending = output.unprocessed_items.unwrap_or_default();
+        if pending.values().all(Vec::is_empty) {
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(100 * 2u64.pow(attempt))).await;
+    }
+
+    Err(format!("Items still unprocessed after {} attempts", MAX_ATTEMPTS).into())
+}
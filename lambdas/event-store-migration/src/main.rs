@@ -0,0 +1,261 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::{
+    operation::transact_write_items::TransactWriteItemsError,
+    types::{AttributeValue, TransactWriteItem, Update},
+};
+use domain::config::{Config, ConfigLoader};
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ulid::Ulid;
+
+/// Items per `TransactWriteItems` call (DynamoDB allows up to 100)
+const BATCH_SIZE: usize = 25;
+
+#[derive(Clone, Debug, Deserialize)]
+struct MigrationRequest {
+    old_event_type: String,
+    new_event_type: String,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct MigrationReport {
+    migration_id: String,
+    old_event_type: String,
+    new_event_type: String,
+    dry_run: bool,
+    matched: usize,
+    migrated: usize,
+}
+
+type Key = HashMap<String, AttributeValue>;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("event-store-migration", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    // Table names read by `handle`
+    ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
+
+    lambda_runtime::run(service_fn(|event: LambdaEvent<MigrationRequest>| async {
+        handle(event, &dynamodb_client).await
+    }))
+    .await
+}
+
+async fn handle(
+    event: LambdaEvent<MigrationRequest>,
+    client: &aws_sdk_dynamodb::Client,
+) -> Result<MigrationReport, Error> {
+    let request = event.payload;
+
+    if request.old_event_type.is_empty() || request.new_event_type.is_empty() {
+        return Err("old_event_type and new_event_type are required".into());
+    }
+
+    let Config {
+        event_log_table,
+        migrations_table,
+        ..
+    } = Config::current();
+
+    let mut report = MigrationReport {
+        migration_id: Ulid::new().to_string(),
+        old_event_type: request.old_event_type.clone(),
+        new_event_type: request.new_event_type.clone(),
+        dry_run: request.dry_run,
+        matched: 0,
+        migrated: 0,
+    };
+
+    tracing::info!(
+        "Migration {}: {} -> {} (dry run: {})",
+        report.migration_id,
+        report.old_event_type,
+        report.new_event_type,
+        report.dry_run
+    );
+
+    record_progress(client, &migrations_table, &report, "running").await?;
+
+    let result = migrate(client, &event_log_table, &migrations_table, &mut report).await;
+
+    let status = if result.is_ok() {
+        "completed"
+    } else {
+        "failed"
+    };
+    record_progress(client, &migrations_table, &report, status).await?;
+    result?;
+
+    tracing::info!(
+        "Migration {} {}: {} matched, {} migrated",
+        report.migration_id,
+        status,
+        report.matched,
+        report.migrated
+    );
+
+    Ok(report)
+}
+
+async fn migrate(
+    client: &aws_sdk_dynamodb::Client,
+    event_log_table: &str,
+    migrations_table: &str,
+    report: &mut MigrationReport,
+) -> Result<(), Error> {
+    let mut start_key: Option<Key> = None;
+
+    loop {
+        let output = client
+            .scan()
+            .table_name(event_log_table)
+            .filter_expression("EventType = :old_event_type")
+            .projection_expression("AggregateTypeAndId, AggregateIdSequence")
+            .expression_attribute_values(
+                ":old_event_type",
+                AttributeValue::S(report.old_event_type.clone()),
+            )
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        let keys = output.items.unwrap_or_default();
+        report.matched += keys.len();
+
+        if !report.dry_run {
+            for batch in keys.chunks(BATCH_SIZE) {
+                report.migrated += rename_batch(client, event_log_table, report, batch).await?;
+            }
+            record_progress(client, migrations_table, report, "running").await?;
+        }
+
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Rename the events of `keys`, returning how many were renamed
+///
+/// An event renamed concurrently since the scan fails its condition and cancels the whole
+/// transaction, which is sent again without it.
+async fn rename_batch(
+    client: &aws_sdk_dynamodb::Client,
+    event_log_table: &str,
+    report: &MigrationReport,
+    keys: &[Key],
+) -> Result<usize, Error> {
+    let mut keys = keys.to_vec();
+
+    while !keys.is_empty() {
+        let items = keys
+            .iter()
+            .map(|key| {
+                let update = Update::builder()
+                    .table_name(event_log_table)
+                    .set_key(Some(key.clone()))
+                    .update_expression("SET EventType = :new_event_type")
+                    .condition_expression("EventType = :old_event_type")
+                    .expression_attribute_values(
+                        ":old_event_type",
+                        AttributeValue::S(report.old_event_type.clone()),
+                    )
+                    .expression_attribute_values(
+                        ":new_event_type",
+                        AttributeValue::S(report.new_event_type.clone()),
+                    )
+                    .build()?;
+
+                Ok(TransactWriteItem::builder().update(update).build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let error = match client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await
+        {
+            Ok(_) => return Ok(keys.len()),
+            Err(error) => error,
+        };
+
+        // Reasons are listed in item order, `None` for the items that did not fail
+        let renamed_concurrently: Vec<bool> = match error.as_service_error() {
+            Some(TransactWriteItemsError::TransactionCanceledException(cancellation)) => {
+                cancellation
+                    .cancellation_reasons()
+                    .iter()
+                    .map(|reason| reason.code() == Some("ConditionalCheckFailed"))
+                    .collect()
+            }
+            _ => vec![],
+        };
+        if renamed_concurrently.len() != keys.len() || !renamed_concurrently.contains(&true) {
+            return Err(error.into());
+        }
+
+        tracing::info!(
+            "Migration {}: {} events renamed concurrently, skipped",
+            report.migration_id,
+            renamed_concurrently
+                .iter()
+                .filter(|&&renamed| renamed)
+                .count()
+        );
+        keys = keys
+            .into_iter()
+            .zip(renamed_concurrently)
+            .filter(|(_, renamed)| !renamed)
+            .map(|(key, _)| key)
+            .collect();
+    }
+
+    Ok(0)
+}
+
+async fn record_progress(
+    client: &aws_sdk_dynamodb::Client,
+    migrations_table: &str,
+    report: &MigrationReport,
+    status: &str,
+) -> Result<(), Error> {
+    client
+        .put_item()
+        .table_name(migrations_table)
+        .item(
+            "migration_id",
+            AttributeValue::S(report.migration_id.clone()),
+        )
+        .item(
+            "old_event_type",
+            AttributeValue::S(report.old_event_type.clone()),
+        )
+        .item(
+            "new_event_type",
+            AttributeValue::S(report.new_event_type.clone()),
+        )
+        .item("dry_run", AttributeValue::Bool(report.dry_run))
+        .item("matched", AttributeValue::N(report.matched.to_string()))
+        .item("migrated", AttributeValue::N(report.migrated.to_string()))
+        .item("status", AttributeValue::S(status.to_string()))
+        .item(
+            "updated_at",
+            AttributeValue::S(chrono::Utc::now().to_rfc3339()),
+        )
+        .send()
+        .await?;
+
+    Ok(())
+}
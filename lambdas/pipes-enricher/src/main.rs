@@ -0,0 +1,49 @@
+//! EventBridge Pipes enrichment Lambda
+//!
+//! Turns the event log records of the DynamoDB stream into `DomainEvent` JSON, which the
+//! `event-log-to-kinesis` pipe puts on the Kinesis stream. Unlike the publisher, it makes no
+//! AWS calls.
+
+use aws_lambda_events::dynamodb::EventRecord;
+use domain::DomainEvent;
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use publisher::EventLogRecord;
+use telemetry::{LambdaContext, MetadataEnricher};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    dotenvy::dotenv().ok();
+
+    telemetry::setup_lambda_tracing("pipes-enricher", None);
+
+    lambda_runtime::run(service_fn(handle)).await
+}
+
+/// Domain events of the batch, in stream order: the pipe sends one Kinesis record per element
+///
+/// Any invalid record fails the batch, which the pipe retries and bisects.
+async fn handle(event: LambdaEvent<Vec<EventRecord>>) -> Result<Vec<DomainEvent>, Error> {
+    tracing::info!("Enriching {} DynamoDB records", event.payload.len());
+
+    let context = LambdaContext::new(&event.context);
+
+    // The pipe only forwards inserts, others are skipped should the filter change
+    event
+        .payload
+        .iter()
+        .filter(|record| record.event_name == "INSERT")
+        .map(|record| enrich(record, &context))
+        .collect()
+}
+
+fn enrich(record: &EventRecord, context: &LambdaContext) -> Result<DomainEvent, Error> {
+    let event_log: EventLogRecord = serde_dynamo::from_item(record.change.new_image.clone())?;
+    let domain_event: DomainEvent = event_log.try_into()?;
+
+    let mut context = context.clone();
+    if let Some(sequence_number) = &record.change.sequence_number {
+        context = context.with_stream_sequence_number(sequence_number);
+    }
+
+    Ok(MetadataEnricher::enrich(domain_event, &context))
+}
@@ -2,48 +2,99 @@ use aws_config::BehaviorVersion;
 use aws_lambda_events::{
     event::s3::S3Event,
     kinesis::{KinesisEvent, KinesisEventRecord},
-    streams::{KinesisBatchItemFailure, KinesisEventResponse},
+    sqs::{BatchItemFailure, SqsBatchResponse, SqsEvent, SqsMessage},
+    streams::KinesisEventResponse,
 };
 use domain::{
-    dispenses::{self, Dispense},
+    config::ConfigLoader,
+    dispenses::{
+        self,
+        analysis_jobs::{AnalysisJobMessage, AnalysisJobStatus, AnalysisJobStore},
+        ocr_cache::{OcrCache, OcrCacheEntry, OcrCacheService},
+        Dispense,
+    },
     DomainEvent,
 };
 use lambda_runtime::{service_fn, Error, LambdaEvent};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
+use telemetry::{ConsumerLagMonitor, DeadlineAwareProcessor, KinesisConsumer};
+use tracing::Instrument;
 use ulid::Ulid;
 
+use pages::{AnalysisStep, PrescriptionKey};
+
+mod pages;
+mod thumbnail;
+
+/// Prescription file downloaded from S3
+struct PrescriptionFile {
+    key: String,
+    /// Changes when the file is uploaded again under the same key
+    etag: Option<String>,
+    data: Vec<u8>,
+}
+
+struct State {
+    dispenses_repo: Arc<Box<dyn cqrs_es::persist::ViewRepository<dispenses::View, Dispense>>>,
+    dispenses_cqrs: Arc<dispenses::cqrs::DispenseCqrs>,
+    analysis_jobs: Arc<AnalysisJobStore>,
+    /// `None` unless `OCR_CACHE_ENABLED=true`
+    ocr_cache: Option<OcrCacheService>,
+    s3_client: aws_sdk_s3::Client,
+    /// Enhanced fan-out consumer, `None` with standard polling
+    kinesis_consumer: Option<KinesisConsumer>,
+    lag_monitor: ConsumerLagMonitor,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .without_time()
-        .init();
+    telemetry::setup_lambda_tracing("projector-analyzer", None);
 
     let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
-    let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+    // Table names read by the `cqrs::init` functions
+    ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
     let s3_client = aws_sdk_s3::Client::new(&config);
 
     let dispenses_repo = dispenses::cqrs::init_repo(dynamodb_client.clone());
-    let dispenses_cqrs = dispenses::cqrs::init(dynamodb_client, dispenses_repo);
+    let analysis_jobs = dispenses::cqrs::init_analysis_jobs(dynamodb_client.clone());
+    let ocr_cache = OcrCacheService::from_env(dynamodb_client.clone());
+    let dispenses_cqrs = dispenses::cqrs::DispenseCqrsBuilder::new(dynamodb_client)
+        .with_view_query(dispenses_repo.clone())
+        .with_timeline_query()
+        .with_patient_history_query(dispenses_repo.clone())
+        .with_scheduler(dispenses::SchedulerService::from_env(
+            aws_sdk_scheduler::Client::new(&config),
+        ))
+        .build();
+
+    let kinesis_consumer = KinesisConsumer::from_env()?;
+
+    let state = State {
+        dispenses_repo,
+        dispenses_cqrs,
+        analysis_jobs,
+        ocr_cache,
+        s3_client,
+        kinesis_consumer,
+        lag_monitor: ConsumerLagMonitor::from_env(),
+    };
 
     lambda_runtime::run(service_fn(|event: LambdaEvent<Value>| async {
-        handle_event(event, &dispenses_cqrs, &s3_client).await
+        handle_event(event, &state).await
     }))
     .await
 }
 
-async fn handle_event(
-    event: LambdaEvent<Value>,
-    cqrs: &cqrs_es::CqrsFramework<
-        Dispense,
-        cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Dispense>,
-    >,
-    s3_client: &aws_sdk_s3::Client,
-) -> Result<Value, Error> {
+async fn handle_event(event: LambdaEvent<Value>, state: &State) -> Result<Value, Error> {
+    let cqrs = state.dispenses_cqrs.as_ref();
+    let s3_client = &state.s3_client;
+
     // Detect event type
     if event.payload.get("Records").is_some() {
         if let Some(records) = event.payload.get("Records").and_then(|r| r.as_array()) {
@@ -52,14 +103,31 @@ async fn handle_event(
                 if first_record.get("s3").is_some() {
                     tracing::info!("Detected S3 event");
                     let s3_event: S3Event = serde_json::from_value(event.payload)?;
-                    handle_s3_event(s3_event, cqrs, s3_client).await?;
+                    handle_s3_event(s3_event, state).await?;
                     return Ok(serde_json::json!({"statusCode": 200}));
                 }
+                // Check if it's a re-analysis request from SQS
+                else if first_record.get("eventSource").and_then(|s| s.as_str())
+                    == Some("aws:sqs")
+                {
+                    tracing::info!("Detected SQS event");
+                    let sqs_event: SqsEvent = serde_json::from_value(event.payload)?;
+                    let response = handle_sqs_event(sqs_event, state).await;
+                    return Ok(serde_json::to_value(response)?);
+                }
                 // Check if it's a Kinesis event
                 else if first_record.get("kinesis").is_some() {
                     tracing::info!("Detected Kinesis event");
                     let kinesis_event: KinesisEvent = serde_json::from_value(event.payload)?;
-                    let response = handle_kinesis_event(kinesis_event, cqrs, s3_client).await?;
+                    let consumer_arn = state
+                        .kinesis_consumer
+                        .as_ref()
+                        .map_or("none", |consumer| consumer.arn.as_str());
+                    let deadline = DeadlineAwareProcessor::new(event.context);
+                    state.lag_monitor.observe(&kinesis_event.records);
+                    let response = handle_kinesis_event(kinesis_event, &deadline, cqrs, s3_client)
+                        .instrument(tracing::info_span!("kinesis_batch", consumer_arn))
+                        .await?;
                     return Ok(serde_json::to_value(response)?);
                 }
             }
@@ -70,14 +138,10 @@ async fn handle_event(
     Ok(serde_json::json!({"statusCode": 200}))
 }
 
-async fn handle_s3_event(
-    event: S3Event,
-    cqrs: &cqrs_es::CqrsFramework<
-        Dispense,
-        cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Dispense>,
-    >,
-    s3_client: &aws_sdk_s3::Client,
-) -> Result<(), Error> {
+async fn handle_s3_event(event: S3Event, state: &State) -> Result<(), Error> {
+    let cqrs = state.dispenses_cqrs.as_ref();
+    let s3_client = &state.s3_client;
+
     tracing::info!("Processing {} S3 records", event.records.len());
 
     for record in event.records {
@@ -86,56 +150,97 @@ async fn handle_s3_event(
 
         tracing::info!("New file uploaded: s3://{}/{}", bucket, key);
 
-        // Extract dispense ID from key pattern: prescriptions/{dispense_id}/prescription.jpg
-        let parts: Vec<&str> = key.split('/').collect();
-        if parts.len() >= 2 && parts[0] == "prescriptions" {
-            let dispense_id = parts[1];
-
-            tracing::info!("Analyzing prescription for dispense {}", dispense_id);
+        // Key pattern: prescriptions/{dispense_id}/prescription.jpg, or page-{n}.jpg for each
+        // page of a multi-page prescription
+        if let Some(prescription) = PrescriptionKey::parse(&key) {
+            let dispense_id = prescription.dispense_id;
 
             tracing::info!("Processing prescription for dispense {}", dispense_id);
 
-            // Step 1: Set prescription URL in the aggregate
-            let prescription_id = Ulid::new().to_string();
-            let prescription_url = format!("s3://{}/{}", bucket, key);
+            let view = state
+                .dispenses_repo
+                .load(dispense_id)
+                .await?
+                .ok_or("Dispense not found")?;
+            let step = AnalysisStep::for_status(&view.dispense_data.status);
+            if step == AnalysisStep::Skip {
+                tracing::warn!(
+                    "Ignoring {} of dispense {} in status {:?}",
+                    key,
+                    dispense_id,
+                    view.dispense_data.status
+                );
+                continue;
+            }
+
+            // Step 1: Set prescription URL in the aggregate, or reopen its analysis for a
+            // later page
             let mut metadata = HashMap::new();
             metadata.insert("command_id".to_string(), Ulid::new().to_string());
 
-            let upload_command = dispenses::Command::UploadPrescription {
-                prescription_id,
-                url: prescription_url.clone(),
-            };
+            match step {
+                AnalysisStep::Upload => {
+                    let upload_command = dispenses::Command::UploadPrescription {
+                        prescription_id: Ulid::new().to_string(),
+                        url: format!("s3://{}/{}", bucket, key),
+                        file_size_bytes: record
+                            .s3
+                            .object
+                            .size
+                            .and_then(|size| u64::try_from(size).ok()),
+                    };
+                    cqrs.execute_with_metadata(dispense_id, upload_command, metadata.clone())
+                        .await?;
+
+                    tracing::info!("Prescription URL set for {}", dispense_id);
+                }
+                AnalysisStep::Reanalyze => {
+                    cqrs.execute_with_metadata(
+                        dispense_id,
+                        dispenses::Command::ResetAnalysis,
+                        metadata.clone(),
+                    )
+                    .await?;
+                }
+                AnalysisStep::Analyze | AnalysisStep::Skip => {}
+            }
 
-            cqrs.execute_with_metadata(dispense_id, upload_command, metadata.clone())
-                .await?;
+            // Step 2: Download and analyze the files, every page uploaded so far for a page
+            let pages = download_pages(s3_client, &bucket, &key).await?;
+
+            // A missing preview does not hold the analysis back, it shows the first page
+            let first_page = &pages[0];
+            match upload_thumbnail(
+                s3_client,
+                &bucket,
+                dispense_id,
+                &first_page.key,
+                &first_page.data,
+            )
+            .await
+            {
+                Ok(Some(thumbnail_url)) => {
+                    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+                    let thumbnail_command =
+                        dispenses::Command::SetPrescriptionThumbnail { thumbnail_url };
+                    cqrs.execute_with_metadata(dispense_id, thumbnail_command, metadata.clone())
+                        .await?;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("No thumbnail for dispense {}: {}", dispense_id, e),
+            }
 
-            tracing::info!("Prescription URL set for {}", dispense_id);
-
-            // Step 2: Download and analyze file
-            let file_data = download_from_s3(s3_client, &bucket, &key).await?;
-
-            // TODO: Actual AI analysis
-            // 1. Call Textract for OCR
-            // 2. Call Claude for structured extraction
-            // 3. Validate extracted data
-
-            // Mock analysis result
-            let analysis_data = serde_json::json!({
-                "file_key": key,
-                "file_size": file_data.len(),
-                "patient_name": "John Doe",
-                "medications": [
-                    {"name": "Aspirin", "dosage": "500mg", "quantity": 30},
-                    {"name": "Ibuprofen", "dosage": "200mg", "quantity": 20}
-                ],
-                "analyzed_at": chrono::Utc::now().to_rfc3339()
-            });
+            let model_id = default_model_id();
+            let (analysis_data, page_count) =
+                analyze_pages(&bucket, &pages, &model_id, state.ocr_cache.as_ref()).await;
 
             // Step 3: Store analysis results
             metadata.insert("command_id".to_string(), Ulid::new().to_string());
 
             let analyze_command = dispenses::Command::AnalyzePrescription {
                 analysis_data: serde_json::to_string(&analysis_data)?,
+                model_id,
+                page_count,
             };
 
             cqrs.execute_with_metadata(dispense_id, analyze_command, metadata)
@@ -150,29 +255,206 @@ async fn handle_s3_event(
     Ok(())
 }
 
-async fn handle_kinesis_event(
-    event: KinesisEvent,
-    cqrs: &cqrs_es::CqrsFramework<
-        Dispense,
-        cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Dispense>,
-    >,
-    _s3_client: &aws_sdk_s3::Client,
-) -> Result<KinesisEventResponse, Error> {
-    tracing::info!("Processing {} Kinesis records", event.records.len());
+fn default_model_id() -> String {
+    std::env::var("ANALYSIS_MODEL_ID").unwrap_or("unknown".to_string())
+}
 
-    let mut batch_item_failures = Vec::new();
+fn analyze_prescription(key: &str, file_data: &[u8]) -> Value {
+    // TODO: Actual AI analysis
+    // 1. Call Textract for OCR
+    // 2. Call Claude for structured extraction
+    // 3. Validate extracted data
+
+    // Mock analysis result
+    serde_json::json!({
+        "file_key": key,
+        "file_size": file_data.len(),
+        "patient_name": "John Doe",
+        "medications": [
+            {"name": "Aspirin", "dosage": "500mg", "quantity": 30},
+            {"name": "Ibuprofen", "dosage": "200mg", "quantity": 20}
+        ],
+        "confidence_score": 0.95,
+        "analyzed_at": chrono::Utc::now().to_rfc3339()
+    })
+}
+
+fn prescription_page_count(_file_data: &[u8]) -> Option<u32> {
+    // TODO: Take `DocumentMetadata.Pages` from the Textract response
+    Some(1)
+}
+
+/// Analysis of every file of the prescription, merged, and their total page count
+///
+/// Files analyzed with the same model in the last 7 days, and not uploaded again since, are
+/// taken from the OCR cache, a cache failure only costing a new analysis.
+async fn analyze_pages(
+    bucket: &str,
+    pages: &[PrescriptionFile],
+    model_id: &str,
+    ocr_cache: Option<&impl OcrCache>,
+) -> (Value, Option<u32>) {
+    let mut analyses = Vec::with_capacity(pages.len());
+    for page in pages {
+        let analysis = match ocr_cache {
+            Some(ocr_cache) => {
+                let prescription_url = format!("s3://{}/{}", bucket, page.key);
+                analyze_cached(ocr_cache, &prescription_url, page, model_id).await
+            }
+            None => analyze_prescription(&page.key, &page.data),
+        };
+        analyses.push(analysis);
+    }
+    let page_count = pages
+        .iter()
+        .map(|page| prescription_page_count(&page.data))
+        .sum();
+
+    (pages::merge_analyses(analyses), page_count)
+}
+
+async fn analyze_cached(
+    ocr_cache: &impl OcrCache,
+    prescription_url: &str,
+    file: &PrescriptionFile,
+    model_id: &str,
+) -> Value {
+    match ocr_cache.get(prescription_url).await {
+        Ok(Some(entry)) if entry.is_analysis_of(file.etag.as_deref(), model_id) => {
+            match serde_json::from_str(&entry.extracted_fields) {
+                Ok(analysis) => {
+                    tracing::info!("OCR cache hit for {}", prescription_url);
+                    return analysis;
+                }
+                Err(e) => tracing::warn!("Invalid OCR cache entry for {}: {}", prescription_url, e),
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("OCR cache read failed for {}: {}", prescription_url, e),
+    }
 
-    for record in event.records.iter() {
-        let sequence = record.kinesis.sequence_number.clone();
+    let analysis = analyze_prescription(&file.key, &file.data);
+    let entry = OcrCacheEntry::new(
+        prescription_url,
+        file.etag.clone(),
+        None,
+        analysis.to_string(),
+        model_id,
+        chrono::Utc::now(),
+    );
+    if let Err(e) = ocr_cache.put(&entry).await {
+        tracing::warn!("OCR cache write failed for {}: {}", prescription_url, e);
+    }
+    analysis
+}
 
-        if let Err(e) = handle_kinesis_record(record, cqrs).await {
-            tracing::error!("Failed to process: {}", e);
-            batch_item_failures.push(KinesisBatchItemFailure {
-                item_identifier: sequence,
+async fn handle_sqs_event(event: SqsEvent, state: &State) -> SqsBatchResponse {
+    tracing::info!("Processing {} analysis jobs", event.records.len());
+
+    let mut batch_item_failures = Vec::new();
+
+    for message in event.records.iter() {
+        if let Err(e) = handle_analysis_job(message, state).await {
+            tracing::error!("Failed to process analysis job: {}", e);
+            batch_item_failures.push(BatchItemFailure {
+                item_identifier: message.message_id.clone().unwrap_or_default(),
             });
         }
     }
 
+    SqsBatchResponse {
+        batch_item_failures,
+    }
+}
+
+async fn handle_analysis_job(message: &SqsMessage, state: &State) -> Result<(), Error> {
+    let body = message.body.as_deref().ok_or("Missing message body")?;
+    let job: AnalysisJobMessage = serde_json::from_str(body)?;
+
+    tracing::info!(
+        "Re-analyzing prescription for dispense {} (job {})",
+        job.dispense_id,
+        job.job_id
+    );
+
+    state
+        .analysis_jobs
+        .set_status(&job.job_id, AnalysisJobStatus::Running, None)
+        .await?;
+
+    match reanalyze(&job, state).await {
+        Ok(()) => {
+            state
+                .analysis_jobs
+                .set_status(&job.job_id, AnalysisJobStatus::Completed, None)
+                .await?;
+            Ok(())
+        }
+        Err(e) => {
+            state
+                .analysis_jobs
+                .set_status(&job.job_id, AnalysisJobStatus::Failed, Some(e.to_string()))
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+async fn reanalyze(job: &AnalysisJobMessage, state: &State) -> Result<(), Error> {
+    let view = state
+        .dispenses_repo
+        .load(&job.dispense_id)
+        .await?
+        .ok_or("Dispense not found")?;
+    let url = view
+        .dispense_data
+        .prescription_url
+        .ok_or("Dispense has no prescription")?;
+    let (bucket, key) = url
+        .strip_prefix("s3://")
+        .and_then(|location| location.split_once('/'))
+        .ok_or("Invalid prescription URL")?;
+
+    let pages = download_pages(&state.s3_client, bucket, key).await?;
+    let model_id = job.model_id.clone().unwrap_or_else(default_model_id);
+    let (analysis_data, page_count) =
+        analyze_pages(bucket, &pages, &model_id, state.ocr_cache.as_ref()).await;
+
+    let mut metadata = HashMap::new();
+    metadata.insert("command_id".to_string(), Ulid::new().to_string());
+    metadata.insert("analysis_job_id".to_string(), job.job_id.clone());
+
+    let analyze_command = dispenses::Command::AnalyzePrescription {
+        analysis_data: serde_json::to_string(&analysis_data)?,
+        model_id,
+        page_count,
+    };
+
+    state
+        .dispenses_cqrs
+        .execute_with_metadata(&job.dispense_id, analyze_command, metadata)
+        .await?;
+
+    tracing::info!("Prescription re-analyzed for {}", job.dispense_id);
+    Ok(())
+}
+
+async fn handle_kinesis_event(
+    event: KinesisEvent,
+    deadline: &DeadlineAwareProcessor,
+    cqrs: &dispenses::cqrs::DispenseCqrs,
+    _s3_client: &aws_sdk_s3::Client,
+) -> Result<KinesisEventResponse, Error> {
+    tracing::info!("Processing {} Kinesis records", event.records.len());
+
+    let batch_item_failures = telemetry::process_kinesis_batch(
+        &event.records,
+        deadline,
+        telemetry::kinesis_concurrency(),
+        |record| handle_kinesis_record(record, cqrs),
+    )
+    .await;
+
     Ok(KinesisEventResponse {
         batch_item_failures,
     })
@@ -180,19 +462,20 @@ async fn handle_kinesis_event(
 
 async fn handle_kinesis_record(
     record: &KinesisEventRecord,
-    cqrs: &cqrs_es::CqrsFramework<
-        Dispense,
-        cqrs_es::persist::PersistedEventStore<dynamo_es::DynamoEventRepository, Dispense>,
-    >,
+    _cqrs: &dispenses::cqrs::DispenseCqrs,
 ) -> Result<(), Error> {
-    let data = std::str::from_utf8(&record.kinesis.data)?;
-    let event: DomainEvent = serde_json::from_str(data)?;
+    let event = DomainEvent::from_kinesis_data(&record.kinesis.data)?;
 
     // Only process PrescriptionUploaded events
-    if event.event_type == "Dispense:PrescriptionUploaded" {
+    if let Ok(dispenses::Event::PrescriptionUploaded { id, .. }) =
+        dispenses::Event::try_from(&event)
+    {
+        let metadata = event.parsed_metadata();
         tracing::info!(
+            command_id = ?metadata.command_id,
+            correlation_id = ?metadata.correlation_id,
             "Processing PrescriptionUploaded event for dispense {}",
-            event.id
+            id
         );
         // Additional processing if needed when prescription URL is set via API
     }
@@ -204,7 +487,7 @@ async fn download_from_s3(
     s3_client: &aws_sdk_s3::Client,
     bucket: &str,
     key: &str,
-) -> Result<Vec<u8>, Error> {
+) -> Result<PrescriptionFile, Error> {
     let response = s3_client
         .get_object()
         .bucket(bucket)
@@ -212,6 +495,139 @@ async fn download_from_s3(
         .send()
         .await?;
 
+    let etag = response.e_tag().map(str::to_string);
     let data = response.body.collect().await?;
-    Ok(data.to_vec())
+    Ok(PrescriptionFile {
+        key: key.to_string(),
+        etag,
+        data: data.to_vec(),
+    })
+}
+
+/// Files of the prescription `key` belongs to, by page number: the `page-{n}` files next to a
+/// page, `key` alone otherwise
+async fn download_pages(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<PrescriptionFile>, Error> {
+    let pages_prefix = PrescriptionKey::parse(key)
+        .filter(|prescription| prescription.page.is_some())
+        .map(|prescription| prescription.pages_prefix());
+    let Some(pages_prefix) = pages_prefix else {
+        return Ok(vec![download_from_s3(s3_client, bucket, key).await?]);
+    };
+
+    let output = s3_client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(&pages_prefix)
+        .send()
+        .await?;
+    let mut page_keys: Vec<(u32, String)> = output
+        .contents()
+        .iter()
+        .filter_map(|object| {
+            let key = object.key()?;
+            Some((pages::page_number(key)?, key.to_string()))
+        })
+        .collect();
+    page_keys.sort();
+
+    let mut pages = Vec::new();
+    for (_, page_key) in page_keys {
+        pages.push(download_from_s3(s3_client, bucket, &page_key).await?);
+    }
+    if pages.is_empty() {
+        return Err(format!("No pages found under {}", pages_prefix).into());
+    }
+
+    Ok(pages)
+}
+
+/// Store the JPEG thumbnail of the prescription next to it, returning its URL
+async fn upload_thumbnail(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    dispense_id: &str,
+    key: &str,
+    file_data: &[u8],
+) -> Result<Option<String>, Error> {
+    let Some(jpeg) = thumbnail::render(key, file_data)? else {
+        return Ok(None);
+    };
+    let thumbnail_key = format!("thumbnails/{}/thumb.jpg", dispense_id);
+
+    s3_client
+        .put_object()
+        .bucket(bucket)
+        .key(&thumbnail_key)
+        .content_type("image/jpeg")
+        .body(jpeg.into())
+        .send()
+        .await?;
+
+    Ok(Some(format!("s3://{}/{}", bucket, thumbnail_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::dispenses::ocr_cache::MockOcrCache;
+
+    const PRESCRIPTION_URL: &str = "s3://bucket/prescriptions/test-dispense/prescription.jpg";
+
+    fn file(etag: &str) -> PrescriptionFile {
+        PrescriptionFile {
+            key: "prescriptions/test-dispense/prescription.jpg".to_string(),
+            etag: Some(etag.to_string()),
+            data: vec![0; 16],
+        }
+    }
+
+    fn cached(etag: &str) -> OcrCacheEntry {
+        OcrCacheEntry::new(
+            PRESCRIPTION_URL,
+            Some(etag.to_string()),
+            None,
+            r#"{"patient_name":"Cached Patient"}"#.to_string(),
+            "test-model",
+            chrono::Utc::now(),
+        )
+    }
+
+    #[tokio::test]
+    async fn cache_hit_skips_the_analysis() {
+        let mut ocr_cache = MockOcrCache::new();
+        ocr_cache
+            .expect_get()
+            .returning(|_| Ok(Some(cached("\"v1\""))));
+        ocr_cache.expect_put().never();
+
+        let analysis =
+            analyze_cached(&ocr_cache, PRESCRIPTION_URL, &file("\"v1\""), "test-model").await;
+
+        assert_eq!(
+            analysis,
+            serde_json::json!({"patient_name": "Cached Patient"})
+        );
+    }
+
+    #[tokio::test]
+    async fn file_uploaded_again_is_analyzed_again() {
+        let mut ocr_cache = MockOcrCache::new();
+        ocr_cache
+            .expect_get()
+            .returning(|_| Ok(Some(cached("\"v1\""))));
+        ocr_cache
+            .expect_put()
+            .withf(|entry| entry.etag.as_deref() == Some("\"v2\""))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let analysis =
+            analyze_cached(&ocr_cache, PRESCRIPTION_URL, &file("\"v2\""), "test-model").await;
+
+        assert_eq!(analysis["file_size"], 16);
+    }
 }
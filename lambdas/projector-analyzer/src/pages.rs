@@ -0,0 +1,204 @@
+use domain::dispenses::DispenseStatus;
+use serde_json::{Map, Value};
+
+/// Uploaded prescription file, `prescriptions/{dispense_id}/{file}`
+pub struct PrescriptionKey<'a> {
+    pub dispense_id: &'a str,
+    /// Page of a `page-{n}.{ext}` file, `None` for a single-file prescription
+    pub page: Option<u32>,
+}
+
+impl<'a> PrescriptionKey<'a> {
+    pub fn parse(key: &'a str) -> Option<Self> {
+        let parts: Vec<&str> = key.split('/').collect();
+        if parts.len() < 2 || parts[0] != "prescriptions" {
+            return None;
+        }
+
+        Some(Self {
+            dispense_id: parts[1],
+            page: page_number(key),
+        })
+    }
+
+    /// Key prefix shared by the pages of the prescription
+    pub fn pages_prefix(&self) -> String {
+        format!("prescriptions/{}/page-", self.dispense_id)
+    }
+}
+
+/// How an uploaded file is brought to analysis, from the status of its dispense
+///
+/// Each page of a multi-page prescription fires its own S3 event: the first file uploads the
+/// prescription, later ones analyze all the pages uploaded so far again.
+#[derive(Debug, PartialEq)]
+pub enum AnalysisStep {
+    /// No prescription yet: `UploadPrescription`, then analyze
+    Upload,
+    /// Analysis running: analyze
+    Analyze,
+    /// Analyzed dispense: `ResetAnalysis`, then analyze
+    Reanalyze,
+    /// Finished dispense, the file is ignored
+    Skip,
+}
+
+impl AnalysisStep {
+    pub fn for_status(status: &DispenseStatus) -> Self {
+        match status {
+            DispenseStatus::Pending => Self::Upload,
+            DispenseStatus::Analyzing => Self::Analyze,
+            DispenseStatus::Ready => Self::Reanalyze,
+            DispenseStatus::Complete | DispenseStatus::Cancelled => Self::Skip,
+        }
+    }
+}
+
+/// `n` of a `.../page-{n}.{ext}` key
+pub fn page_number(key: &str) -> Option<u32> {
+    let file_name = key.rsplit('/').next()?.strip_prefix("page-")?;
+    let number = file_name
+        .split_once('.')
+        .map_or(file_name, |(number, _extension)| number);
+    number.parse().ok()
+}
+
+/// Single analysis of the pages, in page order, with their `page_count`
+///
+/// Fields found on one page are kept as is, so the first page setting a field wins, except:
+/// - lists (e.g. `medications`) are concatenated
+/// - `raw_text` is joined, one line per page
+/// - `confidence_score` is the lowest of the pages
+pub fn merge_analyses(pages: Vec<Value>) -> Value {
+    let page_count = pages.len();
+
+    let mut merged = Map::new();
+    for page in pages {
+        let Value::Object(fields) = page else {
+            continue;
+        };
+        for (field, value) in fields {
+            let Some(existing) = merged.get_mut(&field) else {
+                merged.insert(field, value);
+                continue;
+            };
+            match (field.as_str(), existing, value) {
+                (_, Value::Array(values), Value::Array(more)) => values.extend(more),
+                ("raw_text", Value::String(text), Value::String(more)) => {
+                    text.push('\n');
+                    text.push_str(&more);
+                }
+                ("confidence_score", existing, Value::Number(score))
+                    if score.as_f64() < existing.as_f64() =>
+                {
+                    *existing = Value::Number(score)
+                }
+                (_, existing, value) if existing.is_null() => *existing = value,
+                _ => {}
+            }
+        }
+    }
+    merged.insert("page_count".to_string(), page_count.into());
+
+    Value::Object(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::dispenses::{testing::DispenseTestHarness, Command, Dispense};
+    use serde_json::json;
+
+    fn page_analysis(page: u32) -> Value {
+        json!({
+            "file_key": format!("prescriptions/test-dispense/page-{}.jpg", page),
+            "raw_text": format!("Page {}", page),
+            "medications": [{"name": format!("Drug {}", page)}],
+            "confidence_score": 0.9 + f64::from(page) / 100.0,
+        })
+    }
+
+    #[test]
+    fn page_number_of_page_keys() {
+        assert_eq!(page_number("prescriptions/d-1/page-1.jpg"), Some(1));
+        assert_eq!(page_number("prescriptions/d-1/page-12.png"), Some(12));
+        assert_eq!(page_number("prescriptions/d-1/page-3"), Some(3));
+        assert_eq!(page_number("prescriptions/d-1/prescription.jpg"), None);
+        assert_eq!(page_number("prescriptions/d-1/page-one.jpg"), None);
+    }
+
+    #[test]
+    fn parse_prescription_keys() {
+        let prescription = PrescriptionKey::parse("prescriptions/d-1/prescription.jpg").unwrap();
+        assert_eq!(prescription.dispense_id, "d-1");
+        assert_eq!(prescription.page, None);
+
+        let page = PrescriptionKey::parse("prescriptions/d-1/page-2.jpg").unwrap();
+        assert_eq!(page.dispense_id, "d-1");
+        assert_eq!(page.page, Some(2));
+        assert_eq!(page.pages_prefix(), "prescriptions/d-1/page-");
+
+        assert!(PrescriptionKey::parse("thumbnails/d-1/thumb.jpg").is_none());
+        assert!(PrescriptionKey::parse("prescriptions").is_none());
+    }
+
+    #[test]
+    fn merged_analysis_combines_the_pages() {
+        let merged = merge_analyses((1..=3).map(page_analysis).collect());
+
+        assert_eq!(merged["page_count"], 3);
+        assert_eq!(merged["raw_text"], "Page 1\nPage 2\nPage 3");
+        assert_eq!(merged["medications"].as_array().unwrap().len(), 3);
+        assert_eq!(merged["file_key"], "prescriptions/test-dispense/page-1.jpg");
+        assert_eq!(merged["confidence_score"], 0.91);
+    }
+
+    #[test]
+    fn finished_dispenses_skip_the_analysis() {
+        for status in [DispenseStatus::Complete, DispenseStatus::Cancelled] {
+            assert_eq!(AnalysisStep::for_status(&status), AnalysisStep::Skip);
+        }
+    }
+
+    /// Each page fires its own S3 event, handled once the previous one is analyzed
+    #[test]
+    fn three_page_prescription_is_analyzed_with_all_its_pages() {
+        let mut harness =
+            DispenseTestHarness::given_dispense(Dispense::test_dispense(DispenseStatus::Pending));
+
+        for page in 1..=3 {
+            let command = match AnalysisStep::for_status(&harness.dispense().status) {
+                AnalysisStep::Upload => Some(Command::UploadPrescription {
+                    prescription_id: "test-prescription".to_string(),
+                    url: format!("s3://bucket/prescriptions/test-dispense/page-{}.jpg", page),
+                    file_size_bytes: None,
+                }),
+                AnalysisStep::Reanalyze => Some(Command::ResetAnalysis),
+                AnalysisStep::Analyze => None,
+                AnalysisStep::Skip => panic!("page {} skipped", page),
+            };
+            if let Some(command) = command {
+                harness = harness.when_and_apply(command);
+            }
+
+            let analysis = merge_analyses((1..=page).map(page_analysis).collect());
+            harness = harness.when_and_apply(Command::AnalyzePrescription {
+                analysis_data: analysis.to_string(),
+                model_id: "test-model".to_string(),
+                page_count: Some(page),
+            });
+        }
+
+        let dispense = harness.dispense();
+        assert_eq!(dispense.status, DispenseStatus::Ready);
+        assert_eq!(dispense.prescription_page_count, Some(3));
+        assert_eq!(
+            dispense.prescription_url.as_deref(),
+            Some("s3://bucket/prescriptions/test-dispense/page-1.jpg")
+        );
+        let analysis: Value =
+            serde_json::from_str(dispense.analysis_data.as_deref().unwrap()).unwrap();
+        assert_eq!(analysis["page_count"], 3);
+        assert_eq!(analysis["medications"].as_array().unwrap().len(), 3);
+    }
+}
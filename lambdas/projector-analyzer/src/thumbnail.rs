@@ -0,0 +1,44 @@
+use image::{DynamicImage, ImageFormat};
+use lambda_runtime::Error;
+use pdfium_render::prelude::*;
+use std::io::Cursor;
+
+/// Bounding box of the thumbnails, the aspect ratio is preserved
+const THUMBNAIL_SIZE: u32 = 200;
+
+/// JPEG thumbnail of a prescription file, `None` for unsupported file types
+pub fn render(key: &str, file_data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let extension = key
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let image = match extension.as_str() {
+        "jpg" | "jpeg" | "png" => image::load_from_memory(file_data)?,
+        "pdf" => first_pdf_page(file_data)?,
+        _ => return Ok(None),
+    };
+
+    // JPEG has no alpha channel
+    let thumbnail =
+        DynamicImage::ImageRgb8(image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8());
+    let mut jpeg = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut jpeg, ImageFormat::Jpeg)?;
+
+    Ok(Some(jpeg.into_inner()))
+}
+
+/// Needs `libpdfium` next to the bootstrap binary or on the library path
+fn first_pdf_page(file_data: &[u8]) -> Result<DynamicImage, Error> {
+    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
+        .or_else(|_| Pdfium::bind_to_system_library())?;
+    let pdfium = Pdfium::new(bindings);
+
+    let document = pdfium.load_pdf_from_byte_slice(file_data, None)?;
+    let page = document.pages().first()?;
+    // Rendered larger than the thumbnail so downscaling keeps the text legible
+    let bitmap = page
+        .render_with_config(&PdfRenderConfig::new().set_target_width(THUMBNAIL_SIZE as i32 * 2))?;
+
+    Ok(bitmap.as_image())
+}
@@ -1,56 +1,196 @@
+use aws_config::BehaviorVersion;
 use aws_lambda_events::{
     kinesis::{KinesisEvent, KinesisEventRecord},
-    streams::{KinesisBatchItemFailure, KinesisEventResponse},
+    streams::KinesisEventResponse,
+};
+use domain::{
+    config::ConfigLoader,
+    dispenses::{
+        self, cqrs::ReadOnlyDispenseRepo, PatientLastDispenseStore, PatientLastDispenseView,
+    },
+    DomainEvent,
 };
-use domain::DomainEvent;
 use lambda_runtime::{service_fn, Error, LambdaEvent};
+use std::sync::Arc;
+use telemetry::{
+    ConsumerLagMonitor, DeadLetter, DeadLetterStore, DeadlineAwareProcessor, KinesisConsumer,
+};
+use tracing::Instrument;
+
+struct State {
+    dispenses_repo: ReadOnlyDispenseRepo,
+    patient_last_dispense: Arc<PatientLastDispenseStore>,
+    lag_monitor: ConsumerLagMonitor,
+    dead_letters: DeadLetterStore,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenvy::dotenv().ok();
     
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .without_time()
-        .init();
+    telemetry::setup_lambda_tracing("projector-views", None);
+
+    let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    // Table names read by the `cqrs::init` functions
+    ConfigLoader::from_env(aws_sdk_secretsmanager::Client::new(&config))
+        .load()
+        .await?;
+    let dynamodb_client = telemetry::traced_dynamodb_client(&config);
+
+    let consumer = KinesisConsumer::from_env()?;
+    let state = State {
+        dispenses_repo: dispenses::cqrs::init_read_only(dynamodb_client.clone()),
+        patient_last_dispense: dispenses::cqrs::init_patient_last_dispense(dynamodb_client),
+        lag_monitor: ConsumerLagMonitor::from_env(),
+        dead_letters: DeadLetterStore::from_env(aws_sdk_s3::Client::new(&config)),
+    };
 
     lambda_runtime::run(service_fn(|event: LambdaEvent<KinesisEvent>| async {
-        handle(event).await
+        let consumer_arn = consumer
+            .as_ref()
+            .map_or("none", |consumer| consumer.arn.as_str());
+        handle(event, &state)
+            .instrument(tracing::info_span!("kinesis_batch", consumer_arn))
+            .await
     }))
     .await
 }
 
-async fn handle(event: LambdaEvent<KinesisEvent>) -> Result<KinesisEventResponse, Error> {
+async fn handle(
+    event: LambdaEvent<KinesisEvent>,
+    state: &State,
+) -> Result<KinesisEventResponse, Error> {
     tracing::info!("Processing {} Kinesis records", event.payload.records.len());
+    state.lag_monitor.observe(&event.payload.records);
+
+    let request_id = event.context.request_id.clone();
+    let deadline = DeadlineAwareProcessor::new(event.context);
+    let batch_item_failures = telemetry::process_kinesis_batch(
+        &event.payload.records,
+        &deadline,
+        telemetry::kinesis_concurrency(),
+        |record| handle_record(record, state, &request_id),
+    )
+    .await;
+
+    Ok(KinesisEventResponse { batch_item_failures })
+}
 
-    let mut batch_item_failures = Vec::new();
+async fn handle_record(
+    record: &KinesisEventRecord,
+    state: &State,
+    request_id: &str,
+) -> Result<(), Error> {
+    let event = match DomainEvent::from_kinesis_data(&record.kinesis.data) {
+        Ok(event) => event,
+        // Retrying cannot parse it either
+        Err(e) => return dead_letter(record, e, state, request_id).await,
+    };
 
-    for record in event.payload.records.iter() {
-        let sequence = record.kinesis.sequence_number.clone();
-        
-        if let Err(e) = handle_record(record).await {
-            tracing::error!("Failed to process: {}", e);
-            batch_item_failures.push(KinesisBatchItemFailure {
-                item_identifier: sequence,
-            });
+    let metadata = event.parsed_metadata();
+    tracing::info!(
+        command_id = ?metadata.command_id,
+        correlation_id = ?metadata.correlation_id,
+        "Received event: {} for {}",
+        event.event_type,
+        event.id
+    );
+
+    // The dispenses view is updated via CQRS Query automatically, the read models below are
+    // projected from the stream
+    let Ok(event) = dispenses::Event::try_from(&event) else {
+        return Ok(());
+    };
+    match event {
+        dispenses::Event::PatientAdded {
+            id,
+            patient_id,
+            updated_at,
+            ..
+        } => {
+            let Some(view) = state.dispenses_repo.get(&id).await? else {
+                return Ok(());
+            };
+            let last_dispense = PatientLastDispenseView {
+                patient_id,
+                last_dispense_id: id,
+                last_dispense_status: view.dispense_data.status,
+                last_updated: updated_at,
+            };
+            if !state
+                .patient_last_dispense
+                .set_last_dispense(&last_dispense)
+                .await?
+            {
+                tracing::debug!("Newer last dispense kept for {}", last_dispense.patient_id);
+            }
+        }
+        dispenses::Event::DispenseCompleted { id, updated_at, .. } => {
+            update_last_dispense_status(state, id, dispenses::DispenseStatus::Complete, updated_at)
+                .await?;
+        }
+        dispenses::Event::DispenseCancelled { id, updated_at, .. } => {
+            update_last_dispense_status(
+                state,
+                id,
+                dispenses::DispenseStatus::Cancelled,
+                updated_at,
+            )
+            .await?;
         }
+        _ => {}
     }
 
-    Ok(KinesisEventResponse { batch_item_failures })
+    Ok(())
 }
 
-async fn handle_record(record: &KinesisEventRecord) -> Result<(), Error> {
-    let data = std::str::from_utf8(&record.kinesis.data)?;
-    let event: DomainEvent = serde_json::from_str(data)?;
+// Store the unparseable record in S3, consuming it unless the write fails
+async fn dead_letter(
+    record: &KinesisEventRecord,
+    error: domain::Error,
+    state: &State,
+    request_id: &str,
+) -> Result<(), Error> {
+    let dead_letter = DeadLetter::new(record, error.to_string(), request_id);
+    let key = state.dead_letters.put(&dead_letter).await?;
+    telemetry::emit_dead_letter_metric();
 
-    tracing::info!("Received event: {} for {}", event.event_type, event.id);
+    tracing::error!(
+        "Unparseable record {} stored as {}: {}",
+        dead_letter.sequence_number,
+        key,
+        error
+    );
+    Ok(())
+}
 
-    // Views are updated via CQRS Query automatically
-    // This projector could be used for other side effects:
-    // - Notifications
-    // - External system integration
-    // - Analytics
+async fn update_last_dispense_status(
+    state: &State,
+    dispense_id: String,
+    status: dispenses::DispenseStatus,
+    updated_at: chrono::DateTime<chrono::Utc>,
+) -> Result<(), Error> {
+    let Some(view) = state.dispenses_repo.get(&dispense_id).await? else {
+        return Ok(());
+    };
+    // Dispenses without a patient are not tracked
+    let Some(patient_id) = view.dispense_data.patient_id else {
+        return Ok(());
+    };
+
+    let last_dispense = PatientLastDispenseView {
+        patient_id: patient_id.into(),
+        last_dispense_id: dispense_id,
+        last_dispense_status: status,
+        last_updated: updated_at,
+    };
+    if !state
+        .patient_last_dispense
+        .set_last_dispense_status(&last_dispense)
+        .await?
+    {
+        tracing::debug!("Newer last dispense kept for {}", last_dispense.patient_id);
+    }
 
     Ok(())
 }
@@ -0,0 +1,132 @@
+//! Publisher Lambda
+//!
+//! Forwards new event log records from the DynamoDB stream to Kinesis.
+
+use domain::{dispenses, DomainEvent};
+use serde::{Deserialize, Serialize};
+
+/// Event log item as written by `dynamo-es`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventLogRecord {
+    pub aggregate_type_and_id: String,
+    pub event_type: String,
+    pub aggregate_id: String,
+    pub aggregate_type: String,
+    #[serde(with = "serde_bytes")]
+    pub metadata: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+    pub event_version: String,
+    pub aggregate_id_sequence: usize,
+}
+
+/// How Kinesis partition keys are derived from event log records
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PartitionKeyStrategy {
+    /// One key per aggregate type: every dispense event lands on the same shard
+    AggregateType,
+    /// One key per aggregate: spreads load across shards, ordered per aggregate
+    #[default]
+    AggregateId,
+}
+
+impl PartitionKeyStrategy {
+    /// Read `KINESIS_PARTITION_KEY_STRATEGY` (`aggregate_type` or `aggregate_id`)
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("KINESIS_PARTITION_KEY_STRATEGY") {
+            Ok(strategy) => strategy.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Dispense events are keyed by `Event::kinesis_partition_key`, other aggregates by the ID of
+    /// their event log record
+    pub fn partition_key(&self, event: &DomainEvent) -> String {
+        match self {
+            Self::AggregateType => event.entity.clone(),
+            Self::AggregateId => dispenses::Event::try_from(event)
+                .map(|dispense_event| dispense_event.kinesis_partition_key())
+                .unwrap_or_else(|_| event.id.clone()),
+        }
+    }
+}
+
+impl std::str::FromStr for PartitionKeyStrategy {
+    type Err = String;
+
+    fn from_str(strategy: &str) -> Result<Self, Self::Err> {
+        match strategy {
+            "aggregate_type" => Ok(Self::AggregateType),
+            "aggregate_id" => Ok(Self::AggregateId),
+            other => Err(format!("Unknown partition key strategy: {}", other)),
+        }
+    }
+}
+
+impl TryFrom<EventLogRecord> for DomainEvent {
+    type Error = String;
+
+    fn try_from(record: EventLogRecord) -> Result<Self, Self::Error> {
+        let payload = String::from_utf8(record.payload)
+            .map_err(|e| format!("Invalid payload UTF-8: {}", e))?;
+        let metadata = String::from_utf8(record.metadata)
+            .map_err(|e| format!("Invalid metadata UTF-8: {}", e))?;
+
+        Ok(DomainEvent::new(
+            record.aggregate_id,
+            record.aggregate_type,
+            record.aggregate_id_sequence,
+            record.event_type,
+            record.event_version,
+            payload,
+            metadata,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_log_record() -> EventLogRecord {
+        EventLogRecord {
+            aggregate_type_and_id: "Dispense:01HZX3Q4N5V6W7X8Y9Z0ABCDEF".to_string(),
+            event_type: "Dispense:Started".to_string(),
+            aggregate_id: "01HZX3Q4N5V6W7X8Y9Z0ABCDEF".to_string(),
+            aggregate_type: "Dispense".to_string(),
+            metadata: br#"{"command_id":"01HZX3Q4N5V6W7X8Y9Z0COMMND"}"#.to_vec(),
+            payload: br#"{"type":"DispenseStarted"}"#.to_vec(),
+            event_version: "1.0".to_string(),
+            aggregate_id_sequence: 1,
+        }
+    }
+
+    #[test]
+    fn domain_event_from_event_log_record() {
+        let event = DomainEvent::try_from(event_log_record()).unwrap();
+
+        assert_eq!(
+            event,
+            DomainEvent::new(
+                "01HZX3Q4N5V6W7X8Y9Z0ABCDEF".to_string(),
+                "Dispense".to_string(),
+                1,
+                "Dispense:Started".to_string(),
+                "1.0".to_string(),
+                r#"{"type":"DispenseStarted"}"#.to_string(),
+                r#"{"command_id":"01HZX3Q4N5V6W7X8Y9Z0COMMND"}"#.to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn event_log_record_with_invalid_payload() {
+        let mut record = event_log_record();
+        record.payload = vec![0xff, 0xfe];
+
+        let result = DomainEvent::try_from(record);
+
+        assert!(matches!(result, Err(e) if e.starts_with("Invalid payload UTF-8")));
+    }
+}
@@ -5,78 +5,72 @@ use aws_lambda_events::{
 };
 use aws_sdk_kinesis::primitives::Blob;
 use domain::DomainEvent;
+use aws_sdk_eventbridge::types::PutEventsRequestEntry;
 use lambda_runtime::{service_fn, Error, LambdaEvent};
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct EventLogRecord {
-    aggregate_type_and_id: String,
-    event_type: String,
-    aggregate_id: String,
-    aggregate_type: String,
-    #[serde(with = "serde_bytes")]
-    metadata: Vec<u8>,
-    #[serde(with = "serde_bytes")]
-    payload: Vec<u8>,
-    event_version: String,
-    aggregate_id_sequence: usize,
-}
-
-impl TryFrom<EventLogRecord> for DomainEvent {
-    type Error = String;
-
-    fn try_from(record: EventLogRecord) -> Result<Self, Self::Error> {
-        let payload = String::from_utf8(record.payload)
-            .map_err(|e| format!("Invalid payload UTF-8: {}", e))?;
-        let metadata = String::from_utf8(record.metadata)
-            .map_err(|e| format!("Invalid metadata UTF-8: {}", e))?;
-
-        Ok(DomainEvent::new(
-            record.aggregate_id,
-            record.aggregate_type,
-            record.aggregate_id_sequence,
-            record.event_type,
-            record.event_version,
-            payload,
-            metadata,
-        ))
-    }
+use publisher::{EventLogRecord, PartitionKeyStrategy};
+use telemetry::{LambdaContext, MetadataEnricher};
+
+const EVENTBRIDGE_SOURCE: &str = "dispensary.domain";
+
+struct State {
+    kinesis_client: aws_sdk_kinesis::Client,
+    eventbridge_client: aws_sdk_eventbridge::Client,
+    /// Bus for the EventBridge fan-out, `None` when disabled
+    event_bus_name: Option<String>,
+    partition_key_strategy: PartitionKeyStrategy,
+    /// Gzip the Kinesis records, EventBridge still receiving JSON
+    compression_enabled: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenvy::dotenv().ok();
     
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .without_time()
-        .init();
+    telemetry::setup_lambda_tracing("publisher", None);
 
     let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+    // Both clients share the SDK retry configuration
     let kinesis_client = aws_sdk_kinesis::Client::new(&config);
+    let eventbridge_client = aws_sdk_eventbridge::Client::new(&config);
+
+    let eventbridge_enabled = std::env::var("EVENT_BRIDGE_ENABLED")
+        .map(|enabled| enabled != "false")
+        .unwrap_or(true);
+    let event_bus_name = std::env::var("EVENTBRIDGE_BUS_NAME")
+        .ok()
+        .filter(|name| eventbridge_enabled && !name.is_empty());
+
+    let partition_key_strategy = PartitionKeyStrategy::from_env()?;
+    let compression_enabled = std::env::var("KINESIS_COMPRESSION_ENABLED")
+        .map(|enabled| enabled == "true")
+        .unwrap_or(false);
+
+    let state = State {
+        kinesis_client,
+        eventbridge_client,
+        event_bus_name,
+        partition_key_strategy,
+        compression_enabled,
+    };
 
     lambda_runtime::run(service_fn(|event: LambdaEvent<Event>| async {
-        handle(event, &kinesis_client).await
+        handle(event, &state).await
     }))
     .await
 }
 
-async fn handle(
-    event: LambdaEvent<Event>,
-    kinesis_client: &aws_sdk_kinesis::Client,
-) -> Result<DynamoDbEventResponse, Error> {
+async fn handle(event: LambdaEvent<Event>, state: &State) -> Result<DynamoDbEventResponse, Error> {
     tracing::info!("Processing {} DynamoDB records", event.payload.records.len());
 
     let stream_name = std::env::var("EVENT_STREAM_NAME")?;
+    let context = LambdaContext::new(&event.context);
     let mut batch_item_failures = Vec::new();
 
     for record in event.payload.records.iter() {
         if record.event_name == "INSERT" {
             let event_id = record.event_id.clone();
             
-            if let Err(e) = handle_record(record, kinesis_client, &stream_name).await {
+            if let Err(e) = handle_record(record, state, &stream_name, &context).await {
                 tracing::error!("Failed to process {}: {}", event_id, e);
                 batch_item_failures.push(DynamoDbBatchItemFailure {
                     item_identifier: Some(event_id),
@@ -90,28 +84,88 @@ async fn handle(
 
 async fn handle_record(
     record: &EventRecord,
-    kinesis_client: &aws_sdk_kinesis::Client,
+    state: &State,
     stream_name: &str,
+    context: &LambdaContext,
 ) -> Result<(), Error> {
     let item = &record.change.new_image;
     let event_log: EventLogRecord = serde_dynamo::from_item(item.clone())?;
-    let domain_event: DomainEvent = event_log.clone().try_into()?;
+    let domain_event: DomainEvent = event_log.try_into()?;
+
+    let mut context = context.clone();
+    if let Some(sequence_number) = &record.change.sequence_number {
+        context = context.with_stream_sequence_number(sequence_number);
+    }
+    let domain_event = MetadataEnricher::enrich(domain_event, &context);
 
+    let metadata = domain_event.parsed_metadata();
     tracing::info!(
+        command_id = ?metadata.command_id,
+        correlation_id = ?metadata.correlation_id,
         "Publishing {} for {}",
         domain_event.event_type,
         domain_event.id
     );
 
     let data = serde_json::to_string(&domain_event)?;
-
-    kinesis_client
+    let record_data = if state.compression_enabled {
+        domain_event.to_compressed_bytes()?
+    } else {
+        data.clone().into_bytes()
+    };
+
+    state
+        .kinesis_client
         .put_record()
         .stream_name(stream_name)
-        .partition_key(event_log.aggregate_type)
-        .data(Blob::new(data))
+        .partition_key(state.partition_key_strategy.partition_key(&domain_event))
+        .data(Blob::new(record_data))
+        .send()
+        .await?;
+
+    // EventBridge is a best-effort fan-out: failures must not retry the Kinesis publish
+    if let Some(event_bus_name) = &state.event_bus_name {
+        if let Err(e) = publish_to_eventbridge(state, event_bus_name, &domain_event, data).await {
+            tracing::warn!(
+                "Failed to publish {} for {} to EventBridge: {}",
+                domain_event.event_type,
+                domain_event.id,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn publish_to_eventbridge(
+    state: &State,
+    event_bus_name: &str,
+    domain_event: &DomainEvent,
+    detail: String,
+) -> Result<(), Error> {
+    let entry = PutEventsRequestEntry::builder()
+        .event_bus_name(event_bus_name)
+        .source(EVENTBRIDGE_SOURCE)
+        .detail_type(&domain_event.event_type)
+        .detail(detail)
+        .build();
+
+    let output = state
+        .eventbridge_client
+        .put_events()
+        .entries(entry)
         .send()
         .await?;
 
+    if output.failed_entry_count() > 0 {
+        let reason = output
+            .entries()
+            .iter()
+            .find_map(|entry| entry.error_message())
+            .unwrap_or("unknown error");
+        return Err(format!("EventBridge rejected entry: {}", reason).into());
+    }
+
     Ok(())
 }